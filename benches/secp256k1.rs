@@ -0,0 +1,41 @@
+// compare the scalar multiplication of the generic affine FieldEccPoint with the jacobian one of S256Point,
+// and u*G + v*P by two multiplications with the combined one used by verification
+use btcstudy::secp256k1::{PrivateKey, S256Point};
+use btcstudy::util::hash;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use primitive_types::U256;
+
+fn scalar(seed: &str) -> U256 {
+    U256::from_big_endian(&hash::sha256(seed.as_bytes()))
+}
+
+fn scalar_mul(c: &mut Criterion) {
+    let k = scalar("scalar");
+    let mut group = c.benchmark_group("scalar_mul");
+    // the affine path inverts for every addition, a few samples are enough
+    group.sample_size(10);
+    group.bench_function("affine", |b| b.iter(|| S256Point::g().into_inner() * black_box(k)));
+    group.bench_function("jacobian", |b| b.iter(|| S256Point::g() * black_box(k)));
+    group.bench_function("ladder", |b| b.iter(|| S256Point::g().mul_secret(black_box(k))));
+    group.finish();
+}
+
+fn double_mul(c: &mut Criterion) {
+    let (u, v) = (scalar("u"), scalar("v"));
+    let point = S256Point::g() * scalar("point");
+    let mut group = c.benchmark_group("double_mul");
+    group.bench_function("separate", |b| b.iter(|| S256Point::g() * black_box(u) + point.clone() * black_box(v)));
+    group.bench_function("combined", |b| b.iter(|| point.mul_add_g(black_box(u), black_box(v))));
+    group.finish();
+}
+
+fn ecdsa_verify(c: &mut Criterion) {
+    let sk = PrivateKey::new(scalar("secret")).unwrap();
+    let z = scalar("message");
+    let sig = sk.sign_deterministic(z).unwrap();
+    let point = sk.pk_point().clone();
+    c.bench_function("ecdsa_verify", |b| b.iter(|| sig.verify(black_box(z), point.clone())));
+}
+
+criterion_group!(benches, scalar_mul, double_mul, ecdsa_verify);
+criterion_main!(benches);
@@ -0,0 +1,87 @@
+use super::Error;
+
+// BIP380, the position in it is the symbol to be checked
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const CHECKSUM_LEN: usize = 8;
+
+fn polymod(c: u64, val: u64) -> u64 {
+    let c0 = c >> 35;
+    let mut c = ((c & 0x7ffffffff) << 5) ^ val;
+    if c0 & 1 != 0 { c ^= 0xf5dee51989; }
+    if c0 & 2 != 0 { c ^= 0xa9fdca3312; }
+    if c0 & 4 != 0 { c ^= 0x1bab10e32d; }
+    if c0 & 8 != 0 { c ^= 0x3706b1677a; }
+    if c0 & 16 != 0 { c ^= 0x644d626ffd; }
+    c
+}
+
+// 8 chars of checksum of the descriptor without #
+pub fn checksum(desc: &str) -> Result<String, Error> {
+    let mut c = 1u64;
+    let mut cls = 0u64;
+    let mut cls_count = 0;
+    for ch in desc.chars() {
+        let pos = INPUT_CHARSET.find(ch).ok_or(Error::InvalidChar(ch))? as u64;
+        // the low 5 bits of a symbol, then the high bits of every 3 symbols
+        c = polymod(c, pos & 31);
+        cls = cls * 3 + (pos >> 5);
+        cls_count += 1;
+        if cls_count == 3 {
+            c = polymod(c, cls);
+            cls = 0;
+            cls_count = 0;
+        }
+    }
+    if cls_count > 0 {
+        c = polymod(c, cls);
+    }
+    for _ in 0..CHECKSUM_LEN {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+
+    Ok((0..CHECKSUM_LEN).map(|i| CHECKSUM_CHARSET[((c >> (5 * (7 - i))) & 31) as usize] as char).collect())
+}
+
+// desc#checksum
+pub fn add_checksum(desc: &str) -> Result<String, Error> {
+    Ok(format!("{}#{}", desc, checksum(desc)?))
+}
+
+// the descriptor without checksum, the checksum is optional but must be valid if present
+pub fn strip_checksum(s: &str) -> Result<&str, Error> {
+    match s.split_once('#') {
+        Some((desc, sum)) => {
+            if sum.len() != CHECKSUM_LEN || checksum(desc)? != sum {
+                return Err(Error::InvalidChecksum(sum.into()));
+            }
+            Ok(desc)
+        },
+        None => {
+            checksum(s)?;
+            Ok(s)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_checksum, checksum, strip_checksum};
+    use super::super::Error;
+
+    #[test]
+    fn descriptor_checksum() {
+        // BIP380
+        assert_eq!(checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert_eq!(add_checksum("raw(deadbeef)").unwrap(), "raw(deadbeef)#89f8spxm");
+        assert_eq!(strip_checksum("raw(deadbeef)#89f8spxm").unwrap(), "raw(deadbeef)");
+        assert_eq!(strip_checksum("raw(deadbeef)").unwrap(), "raw(deadbeef)");
+
+        assert!(matches!(strip_checksum("raw(deadbeef)#"), Err(Error::InvalidChecksum(_))));
+        assert!(matches!(strip_checksum("raw(deadbeef)#89f8spxmx"), Err(Error::InvalidChecksum(_))));
+        assert!(matches!(strip_checksum("raw(deadbeef)#89f8spxn"), Err(Error::InvalidChecksum(_))));
+        assert!(matches!(strip_checksum("raw(deadbeef)#89f8spxm#89f8spxm"), Err(Error::InvalidChecksum(_))));
+        assert!(matches!(checksum("raw(dead\u{e9}beef)"), Err(Error::InvalidChar('\u{e9}'))));
+    }
+}
@@ -0,0 +1,405 @@
+use crate::miniscript::Tree;
+use crate::script::{taproot, CommandElement, Opcode, Script, ScriptBuilder, MAX_PUBKEYS_PER_MULTISIG, MAX_SCRIPT_ELEMENT_SIZE, MAX_SCRIPT_SIZE, TAPROOT_LEAF_TAPSCRIPT};
+use crate::util::hash::{self, Hash256Value};
+use crate::wallet::address;
+use super::{checksum, DescriptorKey, Error};
+use std::fmt;
+use std::ops::Range;
+
+// more keys are not standard for bare multisig
+const MAX_BARE_MULTISIG_KEYS: usize = 3;
+const MAX_TAP_TREE_DEPTH: usize = 128;
+
+// where a script expression is, which decides the functions and keys allowed
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Context {
+    Top,
+    P2sh,
+    P2wsh, // also the key of wpkh
+    Tap,
+}
+
+// BIP380-386, such as wpkh([d34db33f/84'/0'/0']xpub.../0/*)#checksum
+#[derive(Debug, Clone, PartialEq)]
+pub enum Descriptor {
+    Pk(DescriptorKey),
+    Pkh(DescriptorKey),
+    Wpkh(DescriptorKey),
+    Sh(Box<Descriptor>),
+    Wsh(Box<Descriptor>),
+    Multi { k: usize, keys: Vec<DescriptorKey>, sorted: bool },
+    Tr { internal_key: DescriptorKey, tree: Option<TapTree> },
+    Addr(String),
+    Raw(Vec<u8>),
+}
+
+// {A,B} of tr(), leaves are scripts of tapscript
+#[derive(Debug, Clone, PartialEq)]
+pub enum TapTree {
+    Leaf(Box<Descriptor>),
+    Branch(Box<TapTree>, Box<TapTree>),
+}
+
+impl Descriptor {
+    // the checksum is optional
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let desc = checksum::strip_checksum(s)?;
+        let tree = Tree::parse(desc)?;
+        Self::from_tree(&tree, Context::Top)
+    }
+
+    fn from_tree(tree: &Tree, context: Context) -> Result<Self, Error> {
+        let name = tree.name.as_str();
+        let allowed = match name {
+            "pk" => true,
+            "pkh" | "multi" | "sortedmulti" => context != Context::Tap,
+            "wpkh" | "wsh" => matches!(context, Context::Top | Context::P2sh),
+            "sh" | "tr" | "addr" | "raw" => context == Context::Top,
+            _ => return Err(Error::UnknownFunction(name.into())),
+        };
+        if !allowed {
+            return Err(Error::InvalidContext(name.into()));
+        }
+        let arg_count_valid = match name {
+            "multi" | "sortedmulti" => tree.args.len() >= 2,
+            "tr" => tree.args.len() == 1 || tree.args.len() == 2,
+            _ => tree.args.len() == 1,
+        };
+        if !arg_count_valid {
+            return Err(Error::InvalidArgCount(name.into()));
+        }
+        let arg = &tree.args[0];
+
+        let result = match name {
+            "pk" => Self::Pk(Self::parse_key(arg, context)?),
+            "pkh" => Self::Pkh(Self::parse_key(arg, context)?),
+            "wpkh" => Self::Wpkh(Self::parse_key(arg, Context::P2wsh)?),
+            "sh" => Self::Sh(Box::new(Self::from_tree(arg, Context::P2sh)?)),
+            "wsh" => Self::Wsh(Box::new(Self::from_tree(arg, Context::P2wsh)?)),
+            "multi" | "sortedmulti" => {
+                let k = Self::parse_number(arg)?;
+                let keys = tree.args[1..].iter().map(|arg| Self::parse_key(arg, context)).collect::<Result<Vec<_>, _>>()?;
+                let max = if context == Context::Top { MAX_BARE_MULTISIG_KEYS } else { MAX_PUBKEYS_PER_MULTISIG };
+                if k == 0 || k > keys.len() || keys.len() > max {
+                    return Err(Error::InvalidThreshold(k, keys.len()));
+                }
+                Self::Multi { k, keys, sorted: name == "sortedmulti" }
+            },
+            "tr" => {
+                let internal_key = Self::parse_key(arg, Context::Tap)?;
+                let tree = match tree.args.get(1) {
+                    Some(tree) => Some(TapTree::from_tree(tree, 0)?),
+                    None => None,
+                };
+                Self::Tr { internal_key, tree }
+            },
+            "addr" => {
+                if !arg.is_terminal() {
+                    return Err(Error::InvalidArgCount(name.into()));
+                }
+                address::to_script(&arg.name)?;
+                Self::Addr(arg.name.clone())
+            },
+            "raw" => {
+                let bytes = hex::decode(&arg.name).map_err(|_| Error::InvalidHex(arg.name.clone()))?;
+                if !arg.is_terminal() || bytes.is_empty() {
+                    return Err(Error::InvalidHex(arg.name.clone()));
+                }
+                Script::parse_raw(&bytes)?;
+                Self::Raw(bytes)
+            },
+            _ => unreachable!("checked by allowed"),
+        };
+
+        Ok(result)
+    }
+
+    // segwit only allows compressed keys, and x-only keys are only for taproot
+    fn parse_key(tree: &Tree, context: Context) -> Result<DescriptorKey, Error> {
+        if !tree.is_terminal() {
+            return Err(Error::InvalidKey(tree.name.clone()));
+        }
+        let key = DescriptorKey::parse(&tree.name)?;
+        if key.is_uncompressed() && matches!(context, Context::P2wsh | Context::Tap) {
+            return Err(Error::UncompressedKey(tree.name.clone()));
+        }
+        if key.is_x_only() && context != Context::Tap {
+            return Err(Error::XOnlyKey(tree.name.clone()));
+        }
+        Ok(key)
+    }
+
+    fn parse_number(tree: &Tree) -> Result<usize, Error> {
+        let name = &tree.name;
+        if !tree.is_terminal() || name.is_empty() || !name.chars().all(|c| c.is_ascii_digit()) || (name.starts_with('0') && name.len() > 1) {
+            return Err(Error::InvalidNumber(name.clone()));
+        }
+        name.parse().map_err(|_| Error::InvalidNumber(name.clone()))
+    }
+
+    pub fn is_ranged(&self) -> bool {
+        match self {
+            Self::Pk(key) | Self::Pkh(key) | Self::Wpkh(key) => key.is_ranged(),
+            Self::Sh(inner) | Self::Wsh(inner) => inner.is_ranged(),
+            Self::Multi { keys, .. } => keys.iter().any(|key| key.is_ranged()),
+            Self::Tr { internal_key, tree } => internal_key.is_ranged() || tree.as_ref().is_some_and(|tree| tree.is_ranged()),
+            Self::Addr(_) | Self::Raw(_) => false,
+        }
+    }
+
+    // the index is ignored if not ranged
+    pub fn script_pubkey(&self, index: u32) -> Result<Script, Error> {
+        let script = match self {
+            Self::Pk(key) => Self::pk_script(key.public_key(index)?),
+            Self::Pkh(key) => ScriptBuilder::p2pkh(&hash::hash160(&key.public_key(index)?)),
+            Self::Wpkh(key) => ScriptBuilder::p2wpkh(&hash::hash160(&key.public_key(index)?)),
+            Self::Sh(inner) => {
+                let redeem_script = inner.script_pubkey(index)?;
+                Self::check_size(&redeem_script, MAX_SCRIPT_ELEMENT_SIZE)?;
+                ScriptBuilder::p2sh(&redeem_script)?
+            },
+            Self::Wsh(inner) => {
+                let witness_script = inner.script_pubkey(index)?;
+                Self::check_size(&witness_script, MAX_SCRIPT_SIZE)?;
+                ScriptBuilder::p2wsh(&witness_script)?
+            },
+            Self::Multi { k, keys, sorted } => {
+                let mut keys = keys.iter().map(|key| key.public_key(index)).collect::<Result<Vec<_>, _>>()?;
+                if *sorted {
+                    keys.sort();
+                }
+                let mut cmds = vec![ScriptBuilder::push_num(*k as i64)];
+                cmds.extend(keys.into_iter().map(CommandElement::Data));
+                cmds.push(ScriptBuilder::push_num(cmds.len() as i64 - 1));
+                cmds.push(Opcode::OpCheckmultisig.into());
+                cmds.reverse();
+                Script::new(cmds)
+            },
+            Self::Tr { internal_key, tree } => {
+                let internal_key = internal_key.x_only_public_key(index)?;
+                let merkle_root = match tree {
+                    Some(tree) => Some(tree.hash(index)?),
+                    None => None,
+                };
+                let (output_key, _) = taproot::tap_tweak(&internal_key, merkle_root.as_ref())?;
+                ScriptBuilder::p2tr(&output_key)
+            },
+            Self::Addr(addr) => address::to_script(addr)?.0,
+            Self::Raw(bytes) => Script::parse_raw(bytes)?,
+        };
+
+        Ok(script)
+    }
+
+    pub fn script_pubkeys(&self, range: Range<u32>) -> Result<Vec<Script>, Error> {
+        range.map(|index| self.script_pubkey(index)).collect()
+    }
+
+    // bare pk() and multi() have no address
+    pub fn address(&self, index: u32, test_net: bool) -> Result<String, Error> {
+        Ok(address::from_script(&self.script_pubkey(index)?, test_net)?)
+    }
+
+    pub fn addresses(&self, range: Range<u32>, test_net: bool) -> Result<Vec<String>, Error> {
+        range.map(|index| self.address(index, test_net)).collect()
+    }
+
+    pub fn to_string_with_checksum(&self) -> String {
+        checksum::add_checksum(&self.to_string()).expect("chars of a descriptor are valid")
+    }
+
+    // <key> OP_CHECKSIG
+    fn pk_script(key: Vec<u8>) -> Script {
+        Script::new(vec![Opcode::OpChecksig.into(), CommandElement::Data(key)])
+    }
+
+    fn check_size(script: &Script, max: usize) -> Result<(), Error> {
+        let size = script.raw_serialize()?.len();
+        if size > max {
+            return Err(Error::ScriptTooLarge(size));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pk(key) => write!(f, "pk({})", key),
+            Self::Pkh(key) => write!(f, "pkh({})", key),
+            Self::Wpkh(key) => write!(f, "wpkh({})", key),
+            Self::Sh(inner) => write!(f, "sh({})", inner),
+            Self::Wsh(inner) => write!(f, "wsh({})", inner),
+            Self::Multi { k, keys, sorted } => {
+                write!(f, "{}({}", if *sorted { "sortedmulti" } else { "multi" }, k)?;
+                for key in keys {
+                    write!(f, ",{}", key)?;
+                }
+                write!(f, ")")
+            },
+            Self::Tr { internal_key, tree: None } => write!(f, "tr({})", internal_key),
+            Self::Tr { internal_key, tree: Some(tree) } => write!(f, "tr({},{})", internal_key, tree),
+            Self::Addr(addr) => write!(f, "addr({})", addr),
+            Self::Raw(bytes) => write!(f, "raw({})", hex::encode(bytes)),
+        }
+    }
+}
+
+impl TapTree {
+    fn from_tree(tree: &Tree, depth: usize) -> Result<Self, Error> {
+        if depth > MAX_TAP_TREE_DEPTH {
+            return Err(Error::InvalidTapTree);
+        }
+        if tree.name != "{}" {
+            return Ok(Self::Leaf(Box::new(Descriptor::from_tree(tree, Context::Tap)?)));
+        }
+        if tree.args.len() != 2 {
+            return Err(Error::InvalidTapTree);
+        }
+        let left = Self::from_tree(&tree.args[0], depth + 1)?;
+        let right = Self::from_tree(&tree.args[1], depth + 1)?;
+        Ok(Self::Branch(Box::new(left), Box::new(right)))
+    }
+
+    fn is_ranged(&self) -> bool {
+        match self {
+            Self::Leaf(leaf) => leaf.is_ranged(),
+            Self::Branch(left, right) => left.is_ranged() || right.is_ranged(),
+        }
+    }
+
+    // the script of a leaf, keys are x-only in tapscript
+    pub fn leaf_script(leaf: &Descriptor, index: u32) -> Result<Script, Error> {
+        match leaf {
+            Descriptor::Pk(key) => Ok(Descriptor::pk_script(key.x_only_public_key(index)?.to_vec())),
+            _ => Err(Error::InvalidContext(leaf.to_string())),
+        }
+    }
+
+    // the merkle root of the tree
+    pub fn hash(&self, index: u32) -> Result<Hash256Value, Error> {
+        match self {
+            Self::Leaf(leaf) => {
+                let script = Self::leaf_script(leaf, index)?;
+                Ok(taproot::tap_leaf_hash(TAPROOT_LEAF_TAPSCRIPT, &script.raw_serialize()?))
+            },
+            Self::Branch(left, right) => Ok(taproot::tap_branch_hash(&left.hash(index)?, &right.hash(index)?)),
+        }
+    }
+}
+
+impl fmt::Display for TapTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Leaf(leaf) => write!(f, "{}", leaf),
+            Self::Branch(left, right) => write!(f, "{{{},{}}}", left, right),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wallet::ExtendedPubKey;
+    use super::Descriptor;
+    use super::super::Error;
+
+    fn script_hex(desc: &str) -> String {
+        let script = Descriptor::parse(desc).unwrap().script_pubkey(0).unwrap();
+        hex::encode(script.raw_serialize().unwrap())
+    }
+
+    #[test]
+    fn descriptor_single_key() {
+        assert_eq!(script_hex("pk(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)"), "210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac");
+        assert_eq!(script_hex("pkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)"), "76a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac");
+        assert_eq!(script_hex("wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)"), "00147dd65592d0ab2fe0d0257d571abf032cd9db93dc");
+        assert_eq!(script_hex("sh(wpkh(03fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556))"), "a914cc6ffbc0bf31af759451068f90ba7a0272b6b33287");
+
+        let desc = Descriptor::parse("pkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)").unwrap();
+        assert_eq!(desc.address(0, false).unwrap(), "1cMh228HTCiwS8ZsaakH8A8wze1JR5ZsP");
+    }
+
+    #[test]
+    fn descriptor_multi() {
+        let keys = "022f8bde4d1a07209355b4a7250a5c5128e88b84bddc619ab7cba8d569b240efe4,025cbdf0646e5db4eaa398f365f2ea7a0e3d419b7e0330e39ce92bddedcac4f9bc";
+        let expected = "5121022f8bde4d1a07209355b4a7250a5c5128e88b84bddc619ab7cba8d569b240efe421025cbdf0646e5db4eaa398f365f2ea7a0e3d419b7e0330e39ce92bddedcac4f9bc52ae";
+        assert_eq!(script_hex(&format!("multi(1,{})", keys)), expected);
+        // sortedmulti sorts the keys
+        let reversed = "025cbdf0646e5db4eaa398f365f2ea7a0e3d419b7e0330e39ce92bddedcac4f9bc,022f8bde4d1a07209355b4a7250a5c5128e88b84bddc619ab7cba8d569b240efe4";
+        assert_eq!(script_hex(&format!("sortedmulti(1,{})", reversed)), expected);
+        assert_ne!(script_hex(&format!("multi(1,{})", reversed)), expected);
+
+        // p2wsh of the multisig
+        let desc = Descriptor::parse(&format!("wsh(multi(1,{}))", keys)).unwrap();
+        assert_eq!(desc.address(0, false).unwrap().len(), 62);
+
+        assert!(matches!(Descriptor::parse(&format!("multi(3,{})", keys)), Err(Error::InvalidThreshold(3, 2))));
+        assert!(matches!(Descriptor::parse(&format!("multi(0,{})", keys)), Err(Error::InvalidThreshold(0, 2))));
+        assert!(matches!(Descriptor::parse(&format!("multi(01,{})", keys)), Err(Error::InvalidNumber(_))));
+        // bare multisig is limited to 3 keys
+        assert!(matches!(Descriptor::parse(&format!("multi(1,{},{})", keys, keys)), Err(Error::InvalidThreshold(1, 4))));
+        assert!(Descriptor::parse(&format!("sh(multi(1,{},{}))", keys, keys)).is_ok());
+    }
+
+    #[test]
+    fn descriptor_taproot() {
+        // BIP386
+        let expected = "512077aab6e066f8a7419c5ab714c12c67d25007ed55a43cadcacb4d7a970a093f11";
+        assert_eq!(script_hex("tr(a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd)"), expected);
+        assert_eq!(script_hex("tr(L4rK1yDtCWekvXuE6oXD9jCYfFNV2cWRpVuPLBcCU2z8TrisoyY1)"), expected);
+
+        let s = "tr(a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd,{pk(669b8afcec803a0d323e9a17f3ea8e68e8abe5a278020a929adbec52421adbd0),pk(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)})";
+        let desc = Descriptor::parse(s).unwrap();
+        assert_eq!(desc.to_string(), s);
+        assert_ne!(hex::encode(desc.script_pubkey(0).unwrap().raw_serialize().unwrap()), expected);
+        assert!(desc.address(0, false).unwrap().starts_with("bc1p"));
+
+        assert!(matches!(Descriptor::parse("tr(a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd,{pk(669b8afcec803a0d323e9a17f3ea8e68e8abe5a278020a929adbec52421adbd0)})"), Err(Error::InvalidTapTree)));
+        assert!(matches!(Descriptor::parse("tr(a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd,pkh(669b8afcec803a0d323e9a17f3ea8e68e8abe5a278020a929adbec52421adbd0))"), Err(Error::InvalidContext(_))));
+    }
+
+    #[test]
+    fn descriptor_ranged() {
+        // m/0' of BIP32 test vector 1, m/0'/1 is xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ
+        let xpub = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+        let desc = Descriptor::parse(&format!("wpkh([3442193e/0']{}/*)", xpub)).unwrap();
+        assert!(desc.is_ranged());
+
+        let child = ExtendedPubKey::parse("xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ").unwrap();
+        let single = Descriptor::parse(&format!("wpkh({})", hex::encode(child.public_key.sec_compressed().unwrap()))).unwrap();
+        assert!(!single.is_ranged());
+
+        let scripts: Vec<Vec<u8>> = desc.script_pubkeys(0..2).unwrap().iter().map(|script| script.raw_serialize().unwrap()).collect();
+        assert_ne!(scripts[0], scripts[1]);
+        assert_eq!(scripts[1], single.script_pubkey(0).unwrap().raw_serialize().unwrap());
+        let addresses = desc.addresses(0..2, true).unwrap();
+        assert!(addresses.iter().all(|address| address.starts_with("tb1q")));
+        assert_eq!(addresses[1], single.address(7, true).unwrap());
+    }
+
+    #[test]
+    fn descriptor_parse_and_display() {
+        let s = "sh(wsh(pkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)))";
+        let desc = Descriptor::parse(s).unwrap();
+        assert_eq!(desc.to_string(), s);
+        let with_checksum = desc.to_string_with_checksum();
+        assert_eq!(Descriptor::parse(&with_checksum).unwrap(), desc);
+
+        let desc = Descriptor::parse("addr(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4)").unwrap();
+        assert_eq!(hex::encode(desc.script_pubkey(0).unwrap().raw_serialize().unwrap()), "0014751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(script_hex("raw(deadbeef)#89f8spxm"), "deadbeef");
+
+        assert!(matches!(Descriptor::parse("raw(deadbeef)#89f8spxn"), Err(Error::InvalidChecksum(_))));
+        assert!(matches!(Descriptor::parse("sh(sh(pk(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)))"), Err(Error::InvalidContext(_))));
+        assert!(matches!(Descriptor::parse("wsh(wpkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5))"), Err(Error::InvalidContext(_))));
+        assert!(matches!(Descriptor::parse("wsh(raw(deadbeef))"), Err(Error::InvalidContext(_))));
+        assert!(matches!(Descriptor::parse("combo(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)"), Err(Error::UnknownFunction(_))));
+        assert!(matches!(Descriptor::parse("addr(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5)"), Err(Error::Wallet(_))));
+        // segwit needs compressed keys
+        let uncompressed = "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8";
+        assert!(Descriptor::parse(&format!("pkh({})", uncompressed)).is_ok());
+        assert!(matches!(Descriptor::parse(&format!("wpkh({})", uncompressed)), Err(Error::UncompressedKey(_))));
+        assert!(matches!(Descriptor::parse(&format!("wsh(pk({}))", uncompressed)), Err(Error::UncompressedKey(_))));
+        assert!(matches!(Descriptor::parse("pk(a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd)"), Err(Error::XOnlyKey(_))));
+    }
+}
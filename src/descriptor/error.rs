@@ -0,0 +1,51 @@
+use crate::miniscript::Error as MiniscriptError;
+use crate::script::Error as ScriptError;
+use crate::wallet::Error as WalletError;
+
+#[derive(Debug)]
+pub enum Error {
+    // checksum
+    InvalidChar(char), // not in the character set of descriptors
+    InvalidChecksum(String),
+
+    // expression
+    Expression(MiniscriptError),
+    UnknownFunction(String),
+    InvalidArgCount(String),
+    InvalidContext(String), // the function is not allowed in this place
+    InvalidThreshold(usize, usize), // k of n
+    InvalidNumber(String),
+    InvalidHex(String),
+    InvalidTapTree,
+
+    // key
+    InvalidKey(String),
+    InvalidKeyOrigin(String),
+    UncompressedKey(String), // segwit only allows compressed keys
+    XOnlyKey(String), // x-only keys are only for taproot
+    HardenedFromPublic(String),
+
+    // script
+    ScriptTooLarge(usize),
+
+    Wallet(WalletError),
+    Script(ScriptError),
+}
+
+impl From<MiniscriptError> for Error {
+    fn from(e: MiniscriptError) -> Self {
+        Self::Expression(e)
+    }
+}
+
+impl From<WalletError> for Error {
+    fn from(e: WalletError) -> Self {
+        Self::Wallet(e)
+    }
+}
+
+impl From<ScriptError> for Error {
+    fn from(e: ScriptError) -> Self {
+        Self::Script(e)
+    }
+}
@@ -0,0 +1,261 @@
+use crate::secp256k1::{S256Curve, S256Point};
+use crate::util::base58;
+use crate::wallet::{bip32, ExtendedPrivKey, ExtendedPubKey, HARDENED};
+use primitive_types::U256;
+use super::Error;
+use std::fmt;
+
+const WIF_MAIN: u8 = 0x80;
+const WIF_TEST: u8 = 0xef;
+
+// [d34db33f/44'/0'/0'], where the key comes from
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyOrigin {
+    pub fingerprint: [u8; 4],
+    pub path: Vec<u32>,
+}
+
+// the last step of derivation, /* or /*'
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Wildcard {
+    None,
+    Unhardened,
+    Hardened,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeySource {
+    PublicKey(Vec<u8>), // compressed, uncompressed or x-only
+    PrivateKey { secret: U256, compressed: bool, test_net: bool }, // WIF
+    Xpub { xpub: Box<ExtendedPubKey>, path: Vec<u32>, wildcard: Wildcard },
+    Xprv { xprv: Box<ExtendedPrivKey>, path: Vec<u32>, wildcard: Wildcard },
+}
+
+// KEY expression of BIP380
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescriptorKey {
+    pub origin: Option<KeyOrigin>,
+    pub source: KeySource,
+}
+
+impl DescriptorKey {
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let (origin, key) = match s.strip_prefix('[') {
+            Some(rest) => {
+                let (origin, key) = rest.split_once(']').ok_or_else(|| Error::InvalidKeyOrigin(s.into()))?;
+                (Some(Self::parse_origin(origin)?), key)
+            },
+            None => (None, s),
+        };
+        let source = if !key.is_empty() && key.chars().all(|c| c.is_ascii_hexdigit()) {
+            Self::parse_public_key(key)?
+        } else {
+            Self::parse_private_or_extended(key)?
+        };
+
+        Ok(Self { origin, source })
+    }
+
+    fn parse_origin(origin: &str) -> Result<KeyOrigin, Error> {
+        let invalid = || Error::InvalidKeyOrigin(origin.into());
+        let (fingerprint, path) = match origin.split_once('/') {
+            Some((fingerprint, path)) => (fingerprint, bip32::parse_path(&format!("/{}", path)).map_err(|_| invalid())?),
+            None => (origin, vec![]),
+        };
+        if fingerprint.len() != 8 {
+            return Err(invalid());
+        }
+        let bytes = hex::decode(fingerprint).map_err(|_| invalid())?;
+        let mut fingerprint = [0u8; 4];
+        fingerprint.copy_from_slice(&bytes);
+
+        Ok(KeyOrigin { fingerprint, path })
+    }
+
+    fn parse_public_key(key: &str) -> Result<KeySource, Error> {
+        let invalid = || Error::InvalidKey(key.into());
+        let bytes = hex::decode(key).map_err(|_| invalid())?;
+        let valid = match (bytes.len(), bytes.first()) {
+            (33, Some(2 | 3)) | (65, Some(4)) => S256Point::parse(&bytes).is_ok(),
+            (32, _) => S256Point::lift_x(&bytes).is_ok(),
+            _ => false,
+        };
+        if !valid {
+            return Err(invalid());
+        }
+        Ok(KeySource::PublicKey(bytes))
+    }
+
+    fn parse_private_or_extended(key: &str) -> Result<KeySource, Error> {
+        let invalid = || Error::InvalidKey(key.into());
+        let mut steps = key.split('/');
+        let encoded = steps.next().ok_or_else(invalid)?;
+        let bytes = base58::decode_checksum(encoded).map_err(|_| invalid())?;
+        let steps: Vec<&str> = steps.collect();
+
+        // WIF of 32 bytes, with the suffix 0x01 if compressed
+        if matches!(bytes.first(), Some(&WIF_MAIN) | Some(&WIF_TEST)) && (bytes.len() == 33 || (bytes.len() == 34 && bytes[33] == 1)) {
+            if !steps.is_empty() {
+                return Err(invalid());
+            }
+            let secret = U256::from_big_endian(&bytes[1..33]);
+            if secret.is_zero() || secret >= S256Curve::n() {
+                return Err(invalid());
+            }
+            return Ok(KeySource::PrivateKey { secret, compressed: bytes.len() == 34, test_net: bytes[0] == WIF_TEST });
+        }
+
+        let (path, wildcard) = Self::parse_steps(&steps).map_err(|_| invalid())?;
+        if let Ok(xpub) = ExtendedPubKey::parse(encoded) {
+            if wildcard == Wildcard::Hardened || path.iter().any(|index| index & HARDENED != 0) {
+                return Err(Error::HardenedFromPublic(key.into()));
+            }
+            return Ok(KeySource::Xpub { xpub: Box::new(xpub), path, wildcard });
+        }
+        let xprv = ExtendedPrivKey::parse(encoded).map_err(|_| invalid())?;
+        Ok(KeySource::Xprv { xprv: Box::new(xprv), path, wildcard })
+    }
+
+    // /1/2 and an optional wildcard at the end
+    fn parse_steps(steps: &[&str]) -> Result<(Vec<u32>, Wildcard), Error> {
+        let (wildcard, steps) = match steps.split_last() {
+            Some((&"*", rest)) => (Wildcard::Unhardened, rest),
+            Some((&"*'", rest)) | Some((&"*h", rest)) => (Wildcard::Hardened, rest),
+            _ => (Wildcard::None, steps),
+        };
+        let path = steps.iter().map(|step| bip32::parse_child_number(step)).collect::<Result<Vec<_>, _>>()?;
+        Ok((path, wildcard))
+    }
+
+    // there are different keys for different indexes
+    pub fn is_ranged(&self) -> bool {
+        match &self.source {
+            KeySource::Xpub { wildcard, .. } | KeySource::Xprv { wildcard, .. } => *wildcard != Wildcard::None,
+            _ => false,
+        }
+    }
+
+    pub fn is_uncompressed(&self) -> bool {
+        match &self.source {
+            KeySource::PublicKey(bytes) => bytes.len() == 65,
+            KeySource::PrivateKey { compressed, .. } => !compressed,
+            _ => false,
+        }
+    }
+
+    pub fn is_x_only(&self) -> bool {
+        matches!(&self.source, KeySource::PublicKey(bytes) if bytes.len() == 32)
+    }
+
+    // the serialized public key at the index, which is ignored if not ranged
+    pub fn public_key(&self, index: u32) -> Result<Vec<u8>, Error> {
+        let child = |path: &[u32], wildcard: &Wildcard| -> Vec<u32> {
+            let mut path = path.to_vec();
+            match wildcard {
+                Wildcard::None => {},
+                Wildcard::Unhardened => path.push(index),
+                Wildcard::Hardened => path.push(index | HARDENED),
+            }
+            path
+        };
+        let point = match &self.source {
+            KeySource::PublicKey(bytes) => return Ok(bytes.clone()),
+            KeySource::PrivateKey { secret, compressed, .. } => {
+                let point = S256Point::g().mul_secret(*secret);
+                let sec = if *compressed { point.sec_compressed() } else { point.sec_uncompressed() };
+                return sec.ok_or_else(|| Error::InvalidKey(self.to_string()));
+            },
+            KeySource::Xpub { xpub, path, wildcard } => xpub.derive_path(&child(path, wildcard))?.public_key,
+            KeySource::Xprv { xprv, path, wildcard } => xprv.derive_path(&child(path, wildcard))?.public_key(),
+        };
+        point.sec_compressed().ok_or_else(|| Error::InvalidKey(self.to_string()))
+    }
+
+    // 32 bytes of x for taproot
+    pub fn x_only_public_key(&self, index: u32) -> Result<[u8; 32], Error> {
+        let key = self.public_key(index)?;
+        let x = match key.len() {
+            32 => &key[..],
+            33 => &key[1..],
+            _ => return Err(Error::UncompressedKey(self.to_string())),
+        };
+        let mut result = [0u8; 32];
+        result.copy_from_slice(x);
+        Ok(result)
+    }
+}
+
+impl fmt::Display for DescriptorKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(origin) = &self.origin {
+            write!(f, "[{}{}]", hex::encode(origin.fingerprint), bip32::format_path(&origin.path))?;
+        }
+        let wildcard = |wildcard: &Wildcard| match wildcard {
+            Wildcard::None => "",
+            Wildcard::Unhardened => "/*",
+            Wildcard::Hardened => "/*'",
+        };
+        match &self.source {
+            KeySource::PublicKey(bytes) => write!(f, "{}", hex::encode(bytes)),
+            KeySource::PrivateKey { secret, compressed, test_net } => {
+                let mut bytes = vec![if *test_net { WIF_TEST } else { WIF_MAIN }];
+                let mut secret_bytes = [0u8; 32];
+                secret.to_big_endian(&mut secret_bytes);
+                bytes.extend_from_slice(&secret_bytes);
+                if *compressed {
+                    bytes.push(1);
+                }
+                write!(f, "{}", base58::encode_bytes_checksum(&bytes))
+            },
+            KeySource::Xpub { xpub, path, wildcard: w } => write!(f, "{}{}{}", xpub, bip32::format_path(path), wildcard(w)),
+            KeySource::Xprv { xprv, path, wildcard: w } => write!(f, "{}{}{}", xprv, bip32::format_path(path), wildcard(w)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wallet::HARDENED;
+    use super::{DescriptorKey, KeySource, Wildcard};
+    use super::super::Error;
+
+    const XPUB: &str = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+
+    #[test]
+    fn descriptor_key_parse() {
+        let s = format!("[d34db33f/44'/0'/0']{}/1/*", XPUB);
+        let key = DescriptorKey::parse(&s).unwrap();
+        let origin = key.origin.as_ref().unwrap();
+        assert_eq!(origin.fingerprint, [0xd3, 0x4d, 0xb3, 0x3f]);
+        assert_eq!(origin.path, vec![44 | HARDENED, HARDENED, HARDENED]);
+        assert!(matches!(&key.source, KeySource::Xpub { path, wildcard: Wildcard::Unhardened, .. } if *path == vec![1]));
+        assert!(key.is_ranged());
+        assert_eq!(key.to_string(), s);
+        // h is the same as '
+        assert_eq!(DescriptorKey::parse(&format!("[d34db33f/44h/0h/0h]{}/1/*", XPUB)).unwrap(), key);
+
+        let wif = "L4rK1yDtCWekvXuE6oXD9jCYfFNV2cWRpVuPLBcCU2z8TrisoyY1";
+        let key = DescriptorKey::parse(wif).unwrap();
+        assert!(matches!(key.source, KeySource::PrivateKey { compressed: true, test_net: false, .. }));
+        assert_eq!(key.to_string(), wif);
+        assert_eq!(hex::encode(key.public_key(0).unwrap()), "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd");
+
+        let key = DescriptorKey::parse("03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd").unwrap();
+        assert_eq!(hex::encode(key.x_only_public_key(0).unwrap()), "a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd");
+        assert!(!key.is_ranged());
+    }
+
+    #[test]
+    fn descriptor_key_parse_failed() {
+        assert!(matches!(DescriptorKey::parse(&format!("{}/1'/*", XPUB)), Err(Error::HardenedFromPublic(_))));
+        assert!(matches!(DescriptorKey::parse(&format!("{}/*'", XPUB)), Err(Error::HardenedFromPublic(_))));
+        assert!(matches!(DescriptorKey::parse(&format!("[d34db33]{}", XPUB)), Err(Error::InvalidKeyOrigin(_))));
+        assert!(matches!(DescriptorKey::parse(&format!("[d34db33f/x]{}", XPUB)), Err(Error::InvalidKeyOrigin(_))));
+        assert!(matches!(DescriptorKey::parse(&format!("[d34db33f{}", XPUB)), Err(Error::InvalidKeyOrigin(_))));
+        assert!(matches!(DescriptorKey::parse(&format!("{}/1/*/2", XPUB)), Err(Error::InvalidKey(_))));
+        // not on the curve
+        assert!(matches!(DescriptorKey::parse("020000000000000000000000000000000000000000000000000000000000000007"), Err(Error::InvalidKey(_))));
+        assert!(matches!(DescriptorKey::parse("04a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd"), Err(Error::InvalidKey(_))));
+        assert!(matches!(DescriptorKey::parse("L4rK1yDtCWekvXuE6oXD9jCYfFNV2cWRpVuPLBcCU2z8TrisoyY1/0"), Err(Error::InvalidKey(_))));
+    }
+}
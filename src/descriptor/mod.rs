@@ -0,0 +1,9 @@
+pub use error::Error;
+pub use checksum::{checksum, add_checksum};
+pub use key::{DescriptorKey, KeyOrigin, KeySource, Wildcard};
+pub use descriptor::{Descriptor, TapTree};
+
+mod error;
+mod checksum;
+mod key;
+mod descriptor;
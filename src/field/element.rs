@@ -3,6 +3,7 @@ use std::convert::TryFrom;
 use std::ops::{Add, Sub, Mul, Div};
 use std::fmt::{self, Display, LowerHex};
 use primitive_types::{U256, U512};
+use zeroize::Zeroize;
 use super::Prime;
 
 // creator
@@ -84,6 +85,12 @@ impl FieldElement {
     }
 }
 
+impl Zeroize for FieldElement {
+    fn zeroize(&mut self) {
+        self.num.0.zeroize();
+    }
+}
+
 impl Display for FieldElement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.num)
@@ -4,6 +4,8 @@ pub mod field;
 pub mod field_ecc;
 pub mod secp256k1;
 pub mod script;
+pub mod miniscript;
+pub mod descriptor;
 pub mod transaction;
 pub mod util;
 pub mod wallet;
@@ -0,0 +1,32 @@
+use crate::script::Error as ScriptError;
+
+#[derive(Debug)]
+pub enum Error {
+    // expression
+    UnexpectedEnd,
+    UnexpectedChar(usize, char), // position and the char
+    EmptyName,
+
+    // fragment
+    UnknownFragment(String),
+    UnknownWrapper(char),
+    InvalidArgCount(String),
+    InvalidKey(String),
+    InvalidHash(String),
+    InvalidNumber(String),
+    InvalidThreshold(usize, usize), // k of n
+
+    // type check
+    TypeCheck(String), // the fragment whose sub-expressions have wrong types
+
+    // satisfaction
+    CouldNotSatisfy,
+
+    Script(ScriptError),
+}
+
+impl From<ScriptError> for Error {
+    fn from(e: ScriptError) -> Self {
+        Self::Script(e)
+    }
+}
@@ -0,0 +1,105 @@
+use super::Error;
+
+// name(arg,arg,...), the common syntax of miniscript and descriptors
+// {arg,arg} of taproot script trees is parsed as a tree named "{}"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tree {
+    pub name: String,
+    pub args: Vec<Tree>,
+}
+
+impl Tree {
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let chars: Vec<char> = s.chars().collect();
+        let (tree, used) = Self::parse_at(&chars, 0)?;
+        if used != chars.len() {
+            return Err(Error::UnexpectedChar(used, chars[used]));
+        }
+        Ok(tree)
+    }
+
+    // @return (tree, position after the tree)
+    fn parse_at(chars: &[char], start: usize) -> Result<(Self, usize), Error> {
+        if chars.get(start) == Some(&'{') {
+            let (args, next) = Self::parse_args(chars, start, '}')?;
+            return Ok((Self { name: "{}".into(), args }, next));
+        }
+        let mut pos = start;
+        while pos < chars.len() && !matches!(chars[pos], '(' | ')' | ',' | '{' | '}') {
+            pos += 1;
+        }
+        let name: String = chars[start..pos].iter().collect();
+        let mut args = Vec::new();
+        if pos < chars.len() && chars[pos] == '(' {
+            let (parsed, next) = Self::parse_args(chars, pos, ')')?;
+            args = parsed;
+            pos = next;
+        }
+        if name.is_empty() && args.is_empty() {
+            return Err(Error::EmptyName);
+        }
+
+        Ok((Self { name, args }, pos))
+    }
+
+    // @param open: position of the opening bracket
+    // @return (args, position after the closing bracket)
+    fn parse_args(chars: &[char], open: usize, close: char) -> Result<(Vec<Self>, usize), Error> {
+        let mut args = Vec::new();
+        let mut pos = open;
+        loop {
+            let (arg, next) = Self::parse_at(chars, pos + 1)?;
+            args.push(arg);
+            match chars.get(next) {
+                Some(',') => pos = next,
+                Some(c) if *c == close => return Ok((args, next + 1)),
+                Some(c) => return Err(Error::UnexpectedChar(next, *c)),
+                None => return Err(Error::UnexpectedEnd),
+            }
+        }
+    }
+
+    // a leaf without arguments, such as a key or a number
+    pub fn is_terminal(&self) -> bool {
+        self.args.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tree;
+    use super::super::Error;
+
+    #[test]
+    fn expression_parse() {
+        let tree = Tree::parse("and_v(v:pk(A),or_d(pk(B),older(1000)))").unwrap();
+        assert_eq!(tree.name, "and_v");
+        assert_eq!(tree.args.len(), 2);
+        assert_eq!(tree.args[0].name, "v:pk");
+        assert_eq!(tree.args[0].args[0].name, "A");
+        assert!(tree.args[0].args[0].is_terminal());
+        assert_eq!(tree.args[1].args[1].args[0].name, "1000");
+
+        assert_eq!(Tree::parse("0").unwrap(), Tree { name: "0".into(), args: vec![] });
+    }
+
+    #[test]
+    fn expression_parse_braces() {
+        let tree = Tree::parse("tr(K,{pk(A),{pk(B),pk(C)}})").unwrap();
+        assert_eq!(tree.args.len(), 2);
+        assert_eq!(tree.args[1].name, "{}");
+        assert_eq!(tree.args[1].args[0].name, "pk");
+        assert_eq!(tree.args[1].args[1].name, "{}");
+        assert_eq!(tree.args[1].args[1].args[1].args[0].name, "C");
+        assert!(matches!(Tree::parse("tr(K,{pk(A),pk(B))"), Err(Error::UnexpectedChar(17, ')'))));
+        assert!(matches!(Tree::parse("tr(K,pk(A)})"), Err(Error::UnexpectedChar(10, '}'))));
+    }
+
+    #[test]
+    fn expression_parse_failed() {
+        assert!(matches!(Tree::parse("pk(A"), Err(Error::UnexpectedEnd)));
+        assert!(matches!(Tree::parse("pk(A))"), Err(Error::UnexpectedChar(5, ')'))));
+        assert!(matches!(Tree::parse("pk(A,)"), Err(Error::EmptyName)));
+        assert!(matches!(Tree::parse(""), Err(Error::EmptyName)));
+    }
+}
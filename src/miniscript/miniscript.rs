@@ -0,0 +1,507 @@
+use crate::script::{CommandElement, Opcode, Script, ScriptBuilder, MAX_PUBKEYS_PER_MULTISIG};
+use crate::util::hash;
+use super::{Error, Tree, Type};
+use std::fmt;
+
+// relative and absolute lock times must fit into a positive script number of 4 bytes
+const MAX_LOCK_TIME: u32 = 0x7fff_ffff;
+
+// BIP379 fragments for P2WSH, keys are compressed public keys
+#[derive(Debug, Clone, PartialEq)]
+pub enum Miniscript {
+    False,
+    True,
+    PkK(Vec<u8>),
+    PkH(Vec<u8>),
+    Older(u32),
+    After(u32),
+    Sha256(Vec<u8>),
+    Hash256(Vec<u8>),
+    Ripemd160(Vec<u8>),
+    Hash160(Vec<u8>),
+    AndOr(Box<Self>, Box<Self>, Box<Self>),
+    AndV(Box<Self>, Box<Self>),
+    AndB(Box<Self>, Box<Self>),
+    OrB(Box<Self>, Box<Self>),
+    OrC(Box<Self>, Box<Self>),
+    OrD(Box<Self>, Box<Self>),
+    OrI(Box<Self>, Box<Self>),
+    Thresh(usize, Vec<Self>),
+    Multi(usize, Vec<Vec<u8>>),
+    // wrappers
+    Alt(Box<Self>),
+    Swap(Box<Self>),
+    Check(Box<Self>),
+    DupIf(Box<Self>),
+    Verify(Box<Self>),
+    NonZero(Box<Self>),
+    ZeroNotEqual(Box<Self>),
+}
+
+impl Miniscript {
+    // such as and_v(v:pk(K1),or_d(pk(K2),older(12960))), the sub-expressions are type checked
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let tree = Tree::parse(s)?;
+        Self::from_tree(&tree)
+    }
+
+    pub fn from_tree(tree: &Tree) -> Result<Self, Error> {
+        let (wrappers, name) = match tree.name.split_once(':') {
+            Some((wrappers, name)) => (wrappers, name),
+            None => ("", tree.name.as_str()),
+        };
+        let mut result = Self::from_fragment(name, &tree.args)?;
+        for wrapper in wrappers.chars().rev() {
+            let inner = Box::new(result);
+            result = match wrapper {
+                'a' => Self::Alt(inner),
+                's' => Self::Swap(inner),
+                'c' => Self::Check(inner),
+                'd' => Self::DupIf(inner),
+                'v' => Self::Verify(inner),
+                'j' => Self::NonZero(inner),
+                'n' => Self::ZeroNotEqual(inner),
+                't' => Self::AndV(inner, Box::new(Self::True)),
+                'l' => Self::OrI(Box::new(Self::False), inner),
+                'u' => Self::OrI(inner, Box::new(Self::False)),
+                _ => return Err(Error::UnknownWrapper(wrapper)),
+            };
+        }
+        result.ty()?;
+
+        Ok(result)
+    }
+
+    fn from_fragment(name: &str, args: &[Tree]) -> Result<Self, Error> {
+        let arg_count = match name {
+            "0" | "1" => 0,
+            "pk_k" | "pk_h" | "pk" | "pkh" | "older" | "after" | "sha256" | "hash256" | "ripemd160" | "hash160" => 1,
+            "and_v" | "and_b" | "and_n" | "or_b" | "or_c" | "or_d" | "or_i" => 2,
+            "andor" => 3,
+            "thresh" | "multi" => args.len().max(2),
+            _ => return Err(Error::UnknownFragment(name.into())),
+        };
+        if args.len() != arg_count {
+            return Err(Error::InvalidArgCount(name.into()));
+        }
+        let sub = |i: usize| Self::from_tree(&args[i]).map(Box::new);
+
+        let result = match name {
+            "0" => Self::False,
+            "1" => Self::True,
+            "pk_k" => Self::PkK(Self::parse_key(&args[0])?),
+            "pk_h" => Self::PkH(Self::parse_key(&args[0])?),
+            "pk" => Self::Check(Box::new(Self::PkK(Self::parse_key(&args[0])?))),
+            "pkh" => Self::Check(Box::new(Self::PkH(Self::parse_key(&args[0])?))),
+            "older" => Self::Older(Self::parse_lock_time(&args[0])?),
+            "after" => Self::After(Self::parse_lock_time(&args[0])?),
+            "sha256" => Self::Sha256(Self::parse_hash(&args[0], 32)?),
+            "hash256" => Self::Hash256(Self::parse_hash(&args[0], 32)?),
+            "ripemd160" => Self::Ripemd160(Self::parse_hash(&args[0], 20)?),
+            "hash160" => Self::Hash160(Self::parse_hash(&args[0], 20)?),
+            "andor" => Self::AndOr(sub(0)?, sub(1)?, sub(2)?),
+            "and_n" => Self::AndOr(sub(0)?, sub(1)?, Box::new(Self::False)),
+            "and_v" => Self::AndV(sub(0)?, sub(1)?),
+            "and_b" => Self::AndB(sub(0)?, sub(1)?),
+            "or_b" => Self::OrB(sub(0)?, sub(1)?),
+            "or_c" => Self::OrC(sub(0)?, sub(1)?),
+            "or_d" => Self::OrD(sub(0)?, sub(1)?),
+            "or_i" => Self::OrI(sub(0)?, sub(1)?),
+            "thresh" => {
+                let k = Self::parse_number(&args[0])? as usize;
+                let subs = args[1..].iter().map(Self::from_tree).collect::<Result<Vec<_>, _>>()?;
+                Self::Thresh(k, subs)
+            },
+            "multi" => {
+                let k = Self::parse_number(&args[0])? as usize;
+                let keys = args[1..].iter().map(Self::parse_key).collect::<Result<Vec<_>, _>>()?;
+                if k == 0 || k > keys.len() || keys.len() > MAX_PUBKEYS_PER_MULTISIG {
+                    return Err(Error::InvalidThreshold(k, keys.len()));
+                }
+                Self::Multi(k, keys)
+            },
+            _ => unreachable!("checked by arg_count"),
+        };
+
+        Ok(result)
+    }
+
+    // compressed public key in hex
+    fn parse_key(tree: &Tree) -> Result<Vec<u8>, Error> {
+        let invalid = || Error::InvalidKey(tree.name.clone());
+        if !tree.is_terminal() {
+            return Err(invalid());
+        }
+        let key = hex::decode(&tree.name).map_err(|_| invalid())?;
+        if key.len() != 33 || !(key[0] == 0x02 || key[0] == 0x03) {
+            return Err(invalid());
+        }
+        Ok(key)
+    }
+
+    fn parse_hash(tree: &Tree, len: usize) -> Result<Vec<u8>, Error> {
+        let invalid = || Error::InvalidHash(tree.name.clone());
+        if !tree.is_terminal() {
+            return Err(invalid());
+        }
+        let hash = hex::decode(&tree.name).map_err(|_| invalid())?;
+        if hash.len() != len {
+            return Err(invalid());
+        }
+        Ok(hash)
+    }
+
+    // decimal without sign or leading zeros
+    fn parse_number(tree: &Tree) -> Result<u32, Error> {
+        let invalid = || Error::InvalidNumber(tree.name.clone());
+        let name = &tree.name;
+        if !tree.is_terminal() || name.is_empty() || !name.chars().all(|c| c.is_ascii_digit()) || (name.starts_with('0') && name.len() > 1) {
+            return Err(invalid());
+        }
+        name.parse().map_err(|_| invalid())
+    }
+
+    fn parse_lock_time(tree: &Tree) -> Result<u32, Error> {
+        let n = Self::parse_number(tree)?;
+        if n == 0 || n > MAX_LOCK_TIME {
+            return Err(Error::InvalidNumber(tree.name.clone()));
+        }
+        Ok(n)
+    }
+
+    pub fn ty(&self) -> Result<Type, Error> {
+        let result = match self {
+            Self::False => Some(Type::zero()),
+            Self::True => Some(Type::one()),
+            Self::PkK(_) => Some(Type::pk_k()),
+            Self::PkH(_) => Some(Type::pk_h()),
+            Self::Older(_) | Self::After(_) => Some(Type::time_lock()),
+            Self::Sha256(_) | Self::Hash256(_) | Self::Ripemd160(_) | Self::Hash160(_) => Some(Type::hash()),
+            Self::AndOr(x, y, z) => Type::andor(x.ty()?, y.ty()?, z.ty()?),
+            Self::AndV(x, y) => Type::and_v(x.ty()?, y.ty()?),
+            Self::AndB(x, y) => Type::and_b(x.ty()?, y.ty()?),
+            Self::OrB(x, z) => Type::or_b(x.ty()?, z.ty()?),
+            Self::OrC(x, z) => Type::or_c(x.ty()?, z.ty()?),
+            Self::OrD(x, z) => Type::or_d(x.ty()?, z.ty()?),
+            Self::OrI(x, z) => Type::or_i(x.ty()?, z.ty()?),
+            Self::Thresh(k, subs) => {
+                let types = subs.iter().map(|sub| sub.ty()).collect::<Result<Vec<_>, _>>()?;
+                Type::thresh(*k, &types)
+            },
+            Self::Multi(..) => Some(Type::multi()),
+            Self::Alt(x) => Type::alt(x.ty()?),
+            Self::Swap(x) => Type::swap(x.ty()?),
+            Self::Check(x) => Type::check(x.ty()?),
+            Self::DupIf(x) => Type::dup_if(x.ty()?),
+            Self::Verify(x) => Type::verify(x.ty()?),
+            Self::NonZero(x) => Type::non_zero(x.ty()?),
+            Self::ZeroNotEqual(x) => Type::zero_not_equal(x.ty()?),
+        };
+        result.ok_or_else(|| Error::TypeCheck(self.to_string()))
+    }
+
+    // the top level of a script must be B, and no one can change the witness
+    pub fn is_non_malleable(&self) -> bool {
+        matches!(self.ty(), Ok(ty) if ty.m)
+    }
+
+    // every satisfaction needs a signature, so it cannot be spent by a third party
+    pub fn requires_signature(&self) -> bool {
+        matches!(self.ty(), Ok(ty) if ty.s)
+    }
+
+    pub fn to_script(&self) -> Script {
+        let mut cmds = Vec::new();
+        self.encode(&mut cmds);
+        // cmds of Script are in reversed order
+        cmds.reverse();
+        Script::new(cmds)
+    }
+
+    fn encode(&self, cmds: &mut Vec<CommandElement>) {
+        match self {
+            Self::False => cmds.push(Opcode::Op0.into()),
+            Self::True => cmds.push(Opcode::Op1.into()),
+            Self::PkK(key) => cmds.push(key.clone().into()),
+            Self::PkH(key) => cmds.extend(vec![
+                Opcode::OpDup.into(),
+                Opcode::OpHash160.into(),
+                hash::hash160(key).to_vec().into(),
+                Opcode::OpEqualverify.into(),
+            ]),
+            Self::Older(n) => cmds.extend(vec![ScriptBuilder::push_num(*n as i64), Opcode::OpChecksequenceverify.into()]),
+            Self::After(n) => cmds.extend(vec![ScriptBuilder::push_num(*n as i64), Opcode::OpChecklocktimeverify.into()]),
+            Self::Sha256(h) => Self::encode_hash(Opcode::OpSha256, h, cmds),
+            Self::Hash256(h) => Self::encode_hash(Opcode::OpHash256, h, cmds),
+            Self::Ripemd160(h) => Self::encode_hash(Opcode::OpRipemd160, h, cmds),
+            Self::Hash160(h) => Self::encode_hash(Opcode::OpHash160, h, cmds),
+            Self::AndOr(x, y, z) => {
+                x.encode(cmds);
+                cmds.push(Opcode::OpNotif.into());
+                z.encode(cmds);
+                cmds.push(Opcode::OpElse.into());
+                y.encode(cmds);
+                cmds.push(Opcode::OpEndif.into());
+            },
+            Self::AndV(x, y) => {
+                x.encode(cmds);
+                y.encode(cmds);
+            },
+            Self::AndB(x, y) => {
+                x.encode(cmds);
+                y.encode(cmds);
+                cmds.push(Opcode::OpBooland.into());
+            },
+            Self::OrB(x, z) => {
+                x.encode(cmds);
+                z.encode(cmds);
+                cmds.push(Opcode::OpBoolor.into());
+            },
+            Self::OrC(x, z) => {
+                x.encode(cmds);
+                cmds.push(Opcode::OpNotif.into());
+                z.encode(cmds);
+                cmds.push(Opcode::OpEndif.into());
+            },
+            Self::OrD(x, z) => {
+                x.encode(cmds);
+                cmds.push(Opcode::OpIfdup.into());
+                cmds.push(Opcode::OpNotif.into());
+                z.encode(cmds);
+                cmds.push(Opcode::OpEndif.into());
+            },
+            Self::OrI(x, z) => {
+                cmds.push(Opcode::OpIf.into());
+                x.encode(cmds);
+                cmds.push(Opcode::OpElse.into());
+                z.encode(cmds);
+                cmds.push(Opcode::OpEndif.into());
+            },
+            Self::Thresh(k, subs) => {
+                for (i, sub) in subs.iter().enumerate() {
+                    sub.encode(cmds);
+                    if i > 0 {
+                        cmds.push(Opcode::OpAdd.into());
+                    }
+                }
+                cmds.push(ScriptBuilder::push_num(*k as i64));
+                cmds.push(Opcode::OpEqual.into());
+            },
+            Self::Multi(k, keys) => {
+                cmds.push(ScriptBuilder::push_num(*k as i64));
+                for key in keys {
+                    cmds.push(key.clone().into());
+                }
+                cmds.push(ScriptBuilder::push_num(keys.len() as i64));
+                cmds.push(Opcode::OpCheckmultisig.into());
+            },
+            Self::Alt(x) => {
+                cmds.push(Opcode::OpToaltstack.into());
+                x.encode(cmds);
+                cmds.push(Opcode::OpFromaltstack.into());
+            },
+            Self::Swap(x) => {
+                cmds.push(Opcode::OpSwap.into());
+                x.encode(cmds);
+            },
+            Self::Check(x) => {
+                x.encode(cmds);
+                cmds.push(Opcode::OpChecksig.into());
+            },
+            Self::DupIf(x) => {
+                cmds.push(Opcode::OpDup.into());
+                cmds.push(Opcode::OpIf.into());
+                x.encode(cmds);
+                cmds.push(Opcode::OpEndif.into());
+            },
+            Self::Verify(x) => {
+                x.encode(cmds);
+                // the last opcode is replaced by its VERIFY version if there is one
+                let verify = match cmds.last() {
+                    Some(CommandElement::Op(Opcode::OpEqual)) => Some(Opcode::OpEqualverify),
+                    Some(CommandElement::Op(Opcode::OpNumequal)) => Some(Opcode::OpNumequalverify),
+                    Some(CommandElement::Op(Opcode::OpChecksig)) => Some(Opcode::OpChecksigverify),
+                    Some(CommandElement::Op(Opcode::OpCheckmultisig)) => Some(Opcode::OpCheckmultisigverify),
+                    _ => None,
+                };
+                match verify {
+                    Some(op) => *cmds.last_mut().expect("checked above") = op.into(),
+                    None => cmds.push(Opcode::OpVerify.into()),
+                }
+            },
+            Self::NonZero(x) => {
+                cmds.push(Opcode::OpSize.into());
+                cmds.push(Opcode::Op0notequal.into());
+                cmds.push(Opcode::OpIf.into());
+                x.encode(cmds);
+                cmds.push(Opcode::OpEndif.into());
+            },
+            Self::ZeroNotEqual(x) => {
+                x.encode(cmds);
+                cmds.push(Opcode::Op0notequal.into());
+            },
+        }
+    }
+
+    // the preimage is always 32 bytes
+    fn encode_hash(op: Opcode, h: &[u8], cmds: &mut Vec<CommandElement>) {
+        cmds.extend(vec![
+            Opcode::OpSize.into(),
+            ScriptBuilder::push_num(32),
+            Opcode::OpEqualverify.into(),
+            op.into(),
+            h.to_vec().into(),
+            Opcode::OpEqual.into(),
+        ]);
+    }
+
+    // wrapper letters and the fragment inside, t:, l:, u:, pk and pkh are shown in the short form
+    fn split_wrappers(&self) -> (String, &Self) {
+        let mut wrappers = String::new();
+        let mut node = self;
+        loop {
+            let (wrapper, inner) = match node {
+                Self::Check(x) if matches!(**x, Self::PkK(_) | Self::PkH(_)) => break,
+                Self::Alt(x) => ('a', x),
+                Self::Swap(x) => ('s', x),
+                Self::Check(x) => ('c', x),
+                Self::DupIf(x) => ('d', x),
+                Self::Verify(x) => ('v', x),
+                Self::NonZero(x) => ('j', x),
+                Self::ZeroNotEqual(x) => ('n', x),
+                Self::AndV(x, y) if **y == Self::True => ('t', x),
+                Self::OrI(x, z) if **x == Self::False => ('l', z),
+                Self::OrI(x, z) if **z == Self::False => ('u', x),
+                _ => break,
+            };
+            wrappers.push(wrapper);
+            node = inner;
+        }
+        (wrappers, node)
+    }
+}
+
+impl fmt::Display for Miniscript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (wrappers, node) = self.split_wrappers();
+        if !wrappers.is_empty() {
+            write!(f, "{}:", wrappers)?;
+        }
+        match node {
+            Self::False => write!(f, "0"),
+            Self::True => write!(f, "1"),
+            Self::Check(x) => match &**x {
+                Self::PkK(key) => write!(f, "pk({})", hex::encode(key)),
+                Self::PkH(key) => write!(f, "pkh({})", hex::encode(key)),
+                _ => unreachable!("other wrappers are split"),
+            },
+            Self::PkK(key) => write!(f, "pk_k({})", hex::encode(key)),
+            Self::PkH(key) => write!(f, "pk_h({})", hex::encode(key)),
+            Self::Older(n) => write!(f, "older({})", n),
+            Self::After(n) => write!(f, "after({})", n),
+            Self::Sha256(h) => write!(f, "sha256({})", hex::encode(h)),
+            Self::Hash256(h) => write!(f, "hash256({})", hex::encode(h)),
+            Self::Ripemd160(h) => write!(f, "ripemd160({})", hex::encode(h)),
+            Self::Hash160(h) => write!(f, "hash160({})", hex::encode(h)),
+            Self::AndOr(x, y, z) if **z == Self::False => write!(f, "and_n({},{})", x, y),
+            Self::AndOr(x, y, z) => write!(f, "andor({},{},{})", x, y, z),
+            Self::AndV(x, y) => write!(f, "and_v({},{})", x, y),
+            Self::AndB(x, y) => write!(f, "and_b({},{})", x, y),
+            Self::OrB(x, z) => write!(f, "or_b({},{})", x, z),
+            Self::OrC(x, z) => write!(f, "or_c({},{})", x, z),
+            Self::OrD(x, z) => write!(f, "or_d({},{})", x, z),
+            Self::OrI(x, z) => write!(f, "or_i({},{})", x, z),
+            Self::Thresh(k, subs) => {
+                write!(f, "thresh({}", k)?;
+                for sub in subs {
+                    write!(f, ",{}", sub)?;
+                }
+                write!(f, ")")
+            },
+            Self::Multi(k, keys) => {
+                write!(f, "multi({}", k)?;
+                for key in keys {
+                    write!(f, ",{}", hex::encode(key))?;
+                }
+                write!(f, ")")
+            },
+            _ => unreachable!("wrappers are split"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Miniscript;
+    use super::super::Error;
+
+    const KEY_1: &str = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+    const KEY_2: &str = "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+    const KEY_3: &str = "02e493dbf1c10d80f3581e4904930b1404cc6c13900ee0758474fa94abe8c4cd13";
+
+    fn compile(s: &str) -> String {
+        let s = s.replace("K1", KEY_1).replace("K2", KEY_2).replace("K3", KEY_3);
+        let ms = Miniscript::parse(&s).unwrap();
+        assert_eq!(ms.to_string(), s);
+        ms.to_script().to_string()
+            .replace(KEY_1, "K1").replace(KEY_2, "K2").replace(KEY_3, "K3")
+    }
+
+    #[test]
+    fn miniscript_compile() {
+        // examples of BIP379
+        assert_eq!(compile("pk(K1)"), "K1 OP_CHECKSIG");
+        assert_eq!(compile("or_b(pk(K1),s:pk(K2))"), "K1 OP_CHECKSIG OP_SWAP K2 OP_CHECKSIG OP_BOOLOR");
+        assert_eq!(compile("and_v(v:pk(K1),or_d(pk(K2),older(12960)))"),
+            "K1 OP_CHECKSIGVERIFY K2 OP_CHECKSIG OP_IFDUP OP_NOTIF 12960 OP_CHECKSEQUENCEVERIFY OP_ENDIF");
+        assert_eq!(compile("thresh(3,pk(K1),s:pk(K2),s:pk(K3),sln:older(12960))"),
+            "K1 OP_CHECKSIG OP_SWAP K2 OP_CHECKSIG OP_ADD OP_SWAP K3 OP_CHECKSIG OP_ADD \
+            OP_SWAP OP_IF 0 OP_ELSE 12960 OP_CHECKSEQUENCEVERIFY OP_0NOTEQUAL OP_ENDIF OP_ADD 3 OP_EQUAL");
+        assert_eq!(compile("andor(pk(K1),older(1008),pk(K2))"),
+            "K1 OP_CHECKSIG OP_NOTIF K2 OP_CHECKSIG OP_ELSE 1008 OP_CHECKSEQUENCEVERIFY OP_ENDIF");
+        assert_eq!(compile("multi(2,K1,K2,K3)"), "2 K1 K2 K3 3 OP_CHECKMULTISIG");
+    }
+
+    #[test]
+    fn miniscript_compile_hash_and_pkh() {
+        let h = "1".repeat(40);
+        let script = compile(&format!("t:or_c(pk(K1),and_v(v:pk(K2),or_c(pk(K3),v:hash160({}))))", h));
+        assert_eq!(script, format!("K1 OP_CHECKSIG OP_NOTIF K2 OP_CHECKSIGVERIFY K3 OP_CHECKSIG OP_NOTIF \
+            OP_SIZE 32 OP_EQUALVERIFY OP_HASH160 {} OP_EQUALVERIFY OP_ENDIF OP_ENDIF 1", h));
+
+        let script = compile("or_d(pk(K1),pkh(K2))");
+        assert!(script.starts_with("K1 OP_CHECKSIG OP_IFDUP OP_NOTIF OP_DUP OP_HASH160 "));
+        assert!(script.ends_with(" OP_EQUALVERIFY OP_CHECKSIG OP_ENDIF"));
+    }
+
+    #[test]
+    fn miniscript_types() {
+        let ms = Miniscript::parse(&format!("and_v(v:pk({}),or_d(pk({}),older(12960)))", KEY_1, KEY_2)).unwrap();
+        assert_eq!(ms.ty().unwrap().to_string(), "Bnsfm");
+        assert!(ms.is_non_malleable());
+        assert!(ms.requires_signature());
+
+        // either of the timelocks can be chosen by anyone
+        let ms = Miniscript::parse("or_i(older(1),after(2))").unwrap();
+        assert!(!ms.is_non_malleable());
+        assert!(!ms.requires_signature());
+    }
+
+    #[test]
+    fn miniscript_parse_failed() {
+        // X of and_v must be V
+        let s = format!("and_v(pk({}),pk({}))", KEY_1, KEY_2);
+        assert!(matches!(Miniscript::parse(&s), Err(Error::TypeCheck(_))));
+        // Z of or_b must be W
+        let s = format!("or_b(pk({}),pk({}))", KEY_1, KEY_2);
+        assert!(matches!(Miniscript::parse(&s), Err(Error::TypeCheck(_))));
+        assert!(matches!(Miniscript::parse("pk(02)"), Err(Error::InvalidKey(_))));
+        assert!(matches!(Miniscript::parse("older(0)"), Err(Error::InvalidNumber(_))));
+        assert!(matches!(Miniscript::parse("older(01)"), Err(Error::InvalidNumber(_))));
+        assert!(matches!(Miniscript::parse("sha256(00)"), Err(Error::InvalidHash(_))));
+        assert!(matches!(Miniscript::parse("x:older(1)"), Err(Error::UnknownWrapper('x'))));
+        assert!(matches!(Miniscript::parse("and(1,1)"), Err(Error::UnknownFragment(_))));
+        assert!(matches!(Miniscript::parse("or_i(1)"), Err(Error::InvalidArgCount(_))));
+        assert!(matches!(Miniscript::parse(&format!("multi(2,{})", KEY_1)), Err(Error::InvalidThreshold(2, 1))));
+    }
+}
@@ -0,0 +1,11 @@
+pub use error::Error;
+pub use expression::Tree;
+pub use types::{BasicType, Type};
+pub use miniscript::Miniscript;
+pub use satisfy::Satisfier;
+
+mod error;
+mod expression;
+mod types;
+mod miniscript;
+mod satisfy;
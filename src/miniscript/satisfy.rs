@@ -0,0 +1,290 @@
+use crate::transaction::Sequence;
+use crate::script::operator::LOCKTIME_THRESHOLD;
+use crate::util::hash;
+use super::{Error, Miniscript};
+use std::collections::HashMap;
+
+// witness elements from the bottom to the top of stack
+type Witness = Vec<Vec<u8>>;
+
+// what the spender has to satisfy a miniscript
+#[derive(Debug, Clone, Default)]
+pub struct Satisfier {
+    pub signatures: HashMap<Vec<u8>, Vec<u8>>, // public key -> DER signature with sighash byte
+    pub preimages: Vec<Vec<u8>>, // 32 bytes each, matched by the hash in the script
+    pub sequence: Option<u32>, // nSequence of the spending input, for older
+    pub lock_time: Option<u32>, // nLockTime of the spending transaction, for after
+}
+
+impl Satisfier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_signature(&mut self, key: &[u8], signature: Vec<u8>) {
+        self.signatures.insert(key.to_vec(), signature);
+    }
+
+    pub fn add_preimage(&mut self, preimage: Vec<u8>) {
+        self.preimages.push(preimage);
+    }
+
+    fn preimage(&self, hash_fn: fn(&[u8]) -> Vec<u8>, h: &[u8]) -> Option<Vec<u8>> {
+        self.preimages.iter().find(|preimage| preimage.len() == 32 && hash_fn(preimage) == h).cloned()
+    }
+
+    // BIP112, the same type and a sequence not less than required
+    fn check_older(&self, n: u32) -> bool {
+        let sequence = match self.sequence {
+            Some(sequence) => sequence,
+            None => return false,
+        };
+        if sequence & Sequence::DISABLE_FLAG != 0 {
+            return false;
+        }
+        let mask = Sequence::TYPE_FLAG | Sequence::LOCKTIME_MASK;
+        let (sequence, n) = (sequence & mask, n & mask);
+        (sequence < Sequence::TYPE_FLAG) == (n < Sequence::TYPE_FLAG) && sequence >= n
+    }
+
+    // BIP65, both are block heights or both are timestamps
+    fn check_after(&self, n: u32) -> bool {
+        match self.lock_time {
+            Some(lock_time) => (lock_time < LOCKTIME_THRESHOLD) == (n < LOCKTIME_THRESHOLD) && lock_time >= n,
+            None => false,
+        }
+    }
+}
+
+// the smaller witness, None if neither exists
+fn smaller(a: Option<Witness>, b: Option<Witness>) -> Option<Witness> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if witness_size(&b) < witness_size(&a) { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+fn witness_size(witness: &Witness) -> usize {
+    witness.iter().map(|ele| ele.len() + 1).sum()
+}
+
+// the witness of the sub-expression executed first is on the top
+fn concat(below: &Option<Witness>, above: &Option<Witness>) -> Option<Witness> {
+    match (below, above) {
+        (Some(below), Some(above)) => Some([below.clone(), above.clone()].concat()),
+        _ => None,
+    }
+}
+
+fn push(witness: &Option<Witness>, ele: Vec<u8>) -> Option<Witness> {
+    concat(witness, &Some(vec![ele]))
+}
+
+impl Miniscript {
+    // the smallest witness satisfying the script, the script itself is not included
+    pub fn satisfy(&self, satisfier: &Satisfier) -> Result<Witness, Error> {
+        self.satisfy_dissatisfy(satisfier).0.ok_or(Error::CouldNotSatisfy)
+    }
+
+    // @return (satisfaction, dissatisfaction)
+    fn satisfy_dissatisfy(&self, satisfier: &Satisfier) -> (Option<Witness>, Option<Witness>) {
+        match self {
+            Self::False => (None, Some(vec![])),
+            Self::True => (Some(vec![]), None),
+            Self::PkK(key) => {
+                let sat = satisfier.signatures.get(key).map(|sig| vec![sig.clone()]);
+                (sat, Some(vec![vec![]]))
+            },
+            Self::PkH(key) => {
+                let sat = satisfier.signatures.get(key).map(|sig| vec![sig.clone(), key.clone()]);
+                (sat, Some(vec![vec![], key.clone()]))
+            },
+            Self::Older(n) => (satisfier.check_older(*n).then(Vec::new), None),
+            Self::After(n) => (satisfier.check_after(*n).then(Vec::new), None),
+            // any 32 bytes other than the preimage dissatisfies a hash
+            Self::Sha256(h) => (satisfier.preimage(|p| hash::sha256(p).to_vec(), h).map(|p| vec![p]), Some(vec![vec![0; 32]])),
+            Self::Hash256(h) => (satisfier.preimage(|p| hash::hash256(p).to_vec(), h).map(|p| vec![p]), Some(vec![vec![0; 32]])),
+            Self::Ripemd160(h) => (satisfier.preimage(|p| hash::ripemd160(p).to_vec(), h).map(|p| vec![p]), Some(vec![vec![0; 32]])),
+            Self::Hash160(h) => (satisfier.preimage(|p| hash::hash160(p).to_vec(), h).map(|p| vec![p]), Some(vec![vec![0; 32]])),
+            Self::AndOr(x, y, z) => {
+                let (x_sat, x_dissat) = x.satisfy_dissatisfy(satisfier);
+                let (y_sat, _) = y.satisfy_dissatisfy(satisfier);
+                let (z_sat, z_dissat) = z.satisfy_dissatisfy(satisfier);
+                let sat = smaller(concat(&y_sat, &x_sat), concat(&z_sat, &x_dissat));
+                (sat, concat(&z_dissat, &x_dissat))
+            },
+            Self::AndV(x, y) => {
+                let (x_sat, _) = x.satisfy_dissatisfy(satisfier);
+                let (y_sat, y_dissat) = y.satisfy_dissatisfy(satisfier);
+                (concat(&y_sat, &x_sat), concat(&y_dissat, &x_sat))
+            },
+            Self::AndB(x, y) => {
+                let (x_sat, x_dissat) = x.satisfy_dissatisfy(satisfier);
+                let (y_sat, y_dissat) = y.satisfy_dissatisfy(satisfier);
+                (concat(&y_sat, &x_sat), concat(&y_dissat, &x_dissat))
+            },
+            Self::OrB(x, z) => {
+                let (x_sat, x_dissat) = x.satisfy_dissatisfy(satisfier);
+                let (z_sat, z_dissat) = z.satisfy_dissatisfy(satisfier);
+                let sat = smaller(concat(&z_dissat, &x_sat), concat(&z_sat, &x_dissat));
+                (sat, concat(&z_dissat, &x_dissat))
+            },
+            Self::OrC(x, z) => {
+                let (x_sat, x_dissat) = x.satisfy_dissatisfy(satisfier);
+                let (z_sat, _) = z.satisfy_dissatisfy(satisfier);
+                (smaller(x_sat, concat(&z_sat, &x_dissat)), None)
+            },
+            Self::OrD(x, z) => {
+                let (x_sat, x_dissat) = x.satisfy_dissatisfy(satisfier);
+                let (z_sat, z_dissat) = z.satisfy_dissatisfy(satisfier);
+                (smaller(x_sat, concat(&z_sat, &x_dissat)), concat(&z_dissat, &x_dissat))
+            },
+            // the top of stack chooses the branch of OP_IF
+            Self::OrI(x, z) => {
+                let (x_sat, x_dissat) = x.satisfy_dissatisfy(satisfier);
+                let (z_sat, z_dissat) = z.satisfy_dissatisfy(satisfier);
+                let sat = smaller(push(&x_sat, vec![1]), push(&z_sat, vec![]));
+                let dissat = smaller(push(&x_dissat, vec![1]), push(&z_dissat, vec![]));
+                (sat, dissat)
+            },
+            Self::Thresh(k, subs) => Self::satisfy_thresh(*k, subs, satisfier),
+            Self::Multi(k, keys) => {
+                // signatures are in the order of keys, the dummy element is for the bug of OP_CHECKMULTISIG
+                let mut sat = vec![vec![]];
+                sat.extend(keys.iter().filter_map(|key| satisfier.signatures.get(key).cloned()).take(*k));
+                let sat = if sat.len() == k + 1 { Some(sat) } else { None };
+                (sat, Some(vec![vec![]; k + 1]))
+            },
+            Self::Alt(x) | Self::Swap(x) | Self::Check(x) | Self::ZeroNotEqual(x) => x.satisfy_dissatisfy(satisfier),
+            Self::DupIf(x) => {
+                let (x_sat, _) = x.satisfy_dissatisfy(satisfier);
+                (push(&x_sat, vec![1]), Some(vec![vec![]]))
+            },
+            Self::Verify(x) => (x.satisfy_dissatisfy(satisfier).0, None),
+            Self::NonZero(x) => (x.satisfy_dissatisfy(satisfier).0, Some(vec![vec![]])),
+        }
+    }
+
+    // exactly k of the sub-expressions are satisfied, the others are dissatisfied
+    fn satisfy_thresh(k: usize, subs: &[Self], satisfier: &Satisfier) -> (Option<Witness>, Option<Witness>) {
+        let results: Vec<_> = subs.iter().map(|sub| sub.satisfy_dissatisfy(satisfier)).collect();
+        let dissat = results.iter().rev().try_fold(Vec::new(), |witness, (_, dissat)| concat(&Some(witness), dissat));
+
+        // satisfy the ones costing least more than being dissatisfied
+        let mut candidates: Vec<usize> = (0..subs.len()).filter(|i| results[*i].0.is_some()).collect();
+        candidates.sort_by_key(|i| {
+            let sat_size = witness_size(results[*i].0.as_ref().expect("filtered")) as i64;
+            let dissat_size = results[*i].1.as_ref().map_or(i64::MAX / 2, |w| witness_size(w) as i64);
+            sat_size - dissat_size
+        });
+        let chosen: Vec<usize> = candidates.into_iter().take(k).collect();
+        if chosen.len() < k {
+            return (None, dissat);
+        }
+        let sat = (0..subs.len()).rev().try_fold(Vec::new(), |witness, i| {
+            let part = if chosen.contains(&i) { &results[i].0 } else { &results[i].1 };
+            concat(&Some(witness), part)
+        });
+
+        (sat, dissat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::script::{Stack, ScriptExecutionContext, SigVersion, SignatureCheckerMocker, VerifyFlags, Interpreter};
+    use crate::secp256k1::PrivateKey;
+    use crate::transaction::{Transaction, Sequence, Version};
+    use crate::util::hash;
+    use primitive_types::U256;
+    use super::super::{Error, Miniscript};
+    use super::Satisfier;
+
+    // compressed keys of secret 1, 2 and 3, and their signatures of z = 1
+    fn keys_and_signatures() -> Vec<(String, Vec<u8>)> {
+        (1u32..=3).map(|secret| {
+            let private_key = PrivateKey::new(secret.into()).unwrap();
+            let key = private_key.pk_point().sec_compressed().unwrap();
+            let mut sig = hex::decode(private_key.sign_deterministic(U256::one()).unwrap().der()).unwrap();
+            sig.push(0x01);
+            (hex::encode(key), sig)
+        }).collect()
+    }
+
+    // the witness and the witness script are executed together
+    fn execute(ms: &Miniscript, witness: Vec<Vec<u8>>, sequence: u32) -> bool {
+        let bytes = hex::decode("0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600").unwrap();
+        let mut tx = Transaction::parse(&bytes).unwrap();
+        tx.version = Version::new(2);
+        tx.inputs[0].sequence = Sequence::new(sequence);
+        // z is always 1, the transaction is only for timelocks
+        let context = ScriptExecutionContext::for_input(&tx, 0, SigVersion::WitnessV0, VerifyFlags::standard())
+            .with_checker(Box::new(SignatureCheckerMocker(U256::one())));
+        Interpreter::new(&ms.to_script(), &context)
+            .with_stack(Stack::from(witness))
+            .finish()
+            .unwrap_or(false)
+    }
+
+    #[test]
+    fn miniscript_satisfy_signatures() {
+        let keys = keys_and_signatures();
+        let ms = Miniscript::parse(&format!("or_b(pk({}),s:pk({}))", keys[0].0, keys[1].0)).unwrap();
+        let mut satisfier = Satisfier::new();
+        assert!(matches!(ms.satisfy(&satisfier), Err(Error::CouldNotSatisfy)));
+
+        satisfier.add_signature(&hex::decode(&keys[1].0).unwrap(), keys[1].1.clone());
+        let witness = ms.satisfy(&satisfier).unwrap();
+        // the first key is dissatisfied by an empty signature on the top
+        assert_eq!(witness, vec![keys[1].1.clone(), vec![]]);
+        assert!(execute(&ms, witness, 0));
+
+        let ms = Miniscript::parse(&format!("multi(2,{},{},{})", keys[0].0, keys[1].0, keys[2].0)).unwrap();
+        satisfier.add_signature(&hex::decode(&keys[2].0).unwrap(), keys[2].1.clone());
+        let witness = ms.satisfy(&satisfier).unwrap();
+        assert_eq!(witness, vec![vec![], keys[1].1.clone(), keys[2].1.clone()]);
+        assert!(execute(&ms, witness, 0));
+    }
+
+    #[test]
+    fn miniscript_satisfy_timelock_and_hash() {
+        let keys = keys_and_signatures();
+        let ms = Miniscript::parse(&format!("and_v(v:pk({}),or_d(pk({}),older(144)))", keys[0].0, keys[1].0)).unwrap();
+        let mut satisfier = Satisfier::new();
+        satisfier.add_signature(&hex::decode(&keys[0].0).unwrap(), keys[0].1.clone());
+        assert!(ms.satisfy(&satisfier).is_err());
+        satisfier.sequence = Some(143);
+        assert!(ms.satisfy(&satisfier).is_err());
+
+        // the second key is not available, wait for the timelock
+        satisfier.sequence = Some(144);
+        let witness = ms.satisfy(&satisfier).unwrap();
+        assert_eq!(witness, vec![vec![], keys[0].1.clone()]);
+        assert!(execute(&ms, witness.clone(), 144));
+        assert!(!execute(&ms, witness, 143));
+
+        let preimage = vec![7u8; 32];
+        let h = hex::encode(hash::sha256(&preimage));
+        let ms = Miniscript::parse(&format!("and_v(v:sha256({}),pk({}))", h, keys[0].0)).unwrap();
+        assert!(ms.satisfy(&satisfier).is_err());
+        satisfier.add_preimage(preimage.clone());
+        let witness = ms.satisfy(&satisfier).unwrap();
+        assert_eq!(witness, vec![keys[0].1.clone(), preimage]);
+        assert!(execute(&ms, witness, 0));
+    }
+
+    #[test]
+    fn miniscript_satisfy_thresh() {
+        let keys = keys_and_signatures();
+        let ms = Miniscript::parse(&format!("thresh(2,pk({}),s:pk({}),sln:older(12960))", keys[0].0, keys[1].0)).unwrap();
+        let mut satisfier = Satisfier::new();
+        satisfier.add_signature(&hex::decode(&keys[1].0).unwrap(), keys[1].1.clone());
+        assert!(ms.satisfy(&satisfier).is_err());
+
+        satisfier.sequence = Some(12960);
+        let witness = ms.satisfy(&satisfier).unwrap();
+        // older is in the OP_ELSE branch of l:, the first key is dissatisfied
+        assert_eq!(witness, vec![vec![], keys[1].1.clone(), vec![]]);
+        assert!(execute(&ms, witness, 12960));
+    }
+}
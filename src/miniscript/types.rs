@@ -0,0 +1,333 @@
+use std::fmt;
+
+// what a fragment takes from and leaves on the stack
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BasicType {
+    B, // base: takes its inputs from the top, pushes nonzero on satisfaction and exactly 0 on dissatisfaction
+    V, // verify: takes its inputs from the top, pushes nothing and cannot be dissatisfied
+    K, // key: pushes a public key for which a signature is to be checked
+    W, // wrapped: like B, but takes its inputs from one below the top
+}
+
+// the rules are the correctness and malleability tables of BIP379
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Type {
+    pub base: BasicType,
+    pub z: bool, // zero-arg: always consumes exactly 0 stack elements
+    pub o: bool, // one-arg: always consumes exactly 1 stack element
+    pub n: bool, // nonzero: the top input of satisfaction is never zero
+    pub d: bool, // dissatisfiable: a dissatisfaction can be constructed without signature
+    pub u: bool, // unit: pushes exactly 1 on satisfaction
+    pub s: bool, // safe: every satisfaction needs a signature
+    pub f: bool, // forced: every dissatisfaction needs a signature
+    pub e: bool, // expressive: unique dissatisfaction without signature, others need a signature
+    pub m: bool, // non-malleable: a non-malleable satisfaction always exists
+}
+
+impl Type {
+    fn new(base: BasicType) -> Self {
+        Self { base, z: false, o: false, n: false, d: false, u: false, s: false, f: false, e: false, m: true }
+    }
+
+    pub fn zero() -> Self {
+        Self { z: true, u: true, d: true, s: true, e: true, ..Self::new(BasicType::B) }
+    }
+
+    pub fn one() -> Self {
+        Self { z: true, u: true, f: true, ..Self::new(BasicType::B) }
+    }
+
+    pub fn pk_k() -> Self {
+        Self { o: true, n: true, d: true, u: true, s: true, e: true, ..Self::new(BasicType::K) }
+    }
+
+    pub fn pk_h() -> Self {
+        Self { n: true, d: true, u: true, s: true, e: true, ..Self::new(BasicType::K) }
+    }
+
+    pub fn time_lock() -> Self {
+        Self { z: true, f: true, ..Self::new(BasicType::B) }
+    }
+
+    pub fn hash() -> Self {
+        Self { o: true, n: true, d: true, u: true, ..Self::new(BasicType::B) }
+    }
+
+    pub fn multi() -> Self {
+        Self { n: true, d: true, u: true, s: true, e: true, ..Self::new(BasicType::B) }
+    }
+
+    fn is_bkv(&self) -> bool {
+        self.base != BasicType::W
+    }
+
+    pub fn andor(x: Self, y: Self, z: Self) -> Option<Self> {
+        if x.base != BasicType::B || !x.d || !x.u || y.base != z.base || !y.is_bkv() {
+            return None;
+        }
+        Some(Self {
+            base: y.base,
+            z: x.z && y.z && z.z,
+            o: (x.z && y.o && z.o) || (x.o && y.z && z.z),
+            n: false,
+            d: z.d,
+            u: y.u && z.u,
+            s: z.s && (x.s || y.s),
+            f: z.f && (x.s || y.f),
+            e: x.e && z.e && (x.s || y.f),
+            m: x.m && y.m && z.m && x.e && (x.s || y.s || z.s),
+        })
+    }
+
+    pub fn and_v(x: Self, y: Self) -> Option<Self> {
+        if x.base != BasicType::V || !y.is_bkv() {
+            return None;
+        }
+        Some(Self {
+            base: y.base,
+            z: x.z && y.z,
+            o: (x.z && y.o) || (x.o && y.z),
+            n: x.n || (x.z && y.n),
+            d: false,
+            u: y.u,
+            s: x.s || y.s,
+            f: x.s || y.f,
+            e: false,
+            m: x.m && y.m,
+        })
+    }
+
+    pub fn and_b(x: Self, y: Self) -> Option<Self> {
+        if x.base != BasicType::B || y.base != BasicType::W {
+            return None;
+        }
+        Some(Self {
+            base: BasicType::B,
+            z: x.z && y.z,
+            o: (x.z && y.o) || (x.o && y.z),
+            n: x.n || (x.z && y.n),
+            d: x.d && y.d,
+            u: true,
+            s: x.s || y.s,
+            f: (x.f && (y.f || x.s)) || (y.s && y.f),
+            e: x.e && y.e && x.s && y.s,
+            m: x.m && y.m,
+        })
+    }
+
+    pub fn or_b(x: Self, z: Self) -> Option<Self> {
+        if x.base != BasicType::B || !x.d || z.base != BasicType::W || !z.d {
+            return None;
+        }
+        Some(Self {
+            base: BasicType::B,
+            z: x.z && z.z,
+            o: (x.z && z.o) || (x.o && z.z),
+            n: false,
+            d: true,
+            u: true,
+            s: x.s && z.s,
+            f: false,
+            e: x.e && z.e,
+            m: x.m && z.m && x.e && z.e && (x.s || z.s),
+        })
+    }
+
+    pub fn or_c(x: Self, z: Self) -> Option<Self> {
+        if x.base != BasicType::B || !x.d || !x.u || z.base != BasicType::V {
+            return None;
+        }
+        Some(Self {
+            base: BasicType::V,
+            z: x.z && z.z,
+            o: x.o && z.z,
+            n: false,
+            d: false,
+            u: false,
+            s: x.s && z.s,
+            f: true,
+            e: false,
+            m: x.m && z.m && x.e && (x.s || z.s),
+        })
+    }
+
+    pub fn or_d(x: Self, z: Self) -> Option<Self> {
+        if x.base != BasicType::B || !x.d || !x.u || z.base != BasicType::B {
+            return None;
+        }
+        Some(Self {
+            base: BasicType::B,
+            z: x.z && z.z,
+            o: x.o && z.z,
+            n: false,
+            d: z.d,
+            u: z.u,
+            s: x.s && z.s,
+            f: z.f,
+            e: x.e && z.e,
+            m: x.m && z.m && x.e && (x.s || z.s),
+        })
+    }
+
+    pub fn or_i(x: Self, z: Self) -> Option<Self> {
+        if x.base != z.base || !x.is_bkv() {
+            return None;
+        }
+        Some(Self {
+            base: x.base,
+            z: false,
+            o: x.z && z.z,
+            n: false,
+            d: x.d || z.d,
+            u: x.u && z.u,
+            s: x.s && z.s,
+            f: x.f && z.f,
+            e: (x.e && z.f) || (z.e && x.f),
+            m: x.m && z.m && (x.s || z.s),
+        })
+    }
+
+    pub fn thresh(k: usize, subs: &[Self]) -> Option<Self> {
+        if k == 0 || k > subs.len() {
+            return None;
+        }
+        for (i, sub) in subs.iter().enumerate() {
+            let base = if i == 0 { BasicType::B } else { BasicType::W };
+            if sub.base != base || !sub.d || !sub.u {
+                return None;
+            }
+        }
+        let zero_count = subs.iter().filter(|sub| sub.z).count();
+        let one_count = subs.iter().filter(|sub| sub.o).count();
+        let non_safe_count = subs.iter().filter(|sub| !sub.s).count();
+        let all_e = subs.iter().all(|sub| sub.e);
+        Some(Self {
+            base: BasicType::B,
+            z: zero_count == subs.len(),
+            o: zero_count + 1 == subs.len() && one_count == 1,
+            n: false,
+            d: true,
+            u: true,
+            s: non_safe_count < k,
+            f: false,
+            e: all_e && non_safe_count == 0,
+            m: subs.iter().all(|sub| sub.m) && all_e && non_safe_count <= k,
+        })
+    }
+
+    // a:X, OP_TOALTSTACK [X] OP_FROMALTSTACK
+    pub fn alt(x: Self) -> Option<Self> {
+        if x.base != BasicType::B {
+            return None;
+        }
+        Some(Self { base: BasicType::W, z: false, o: false, n: false, ..x })
+    }
+
+    // s:X, OP_SWAP [X]
+    pub fn swap(x: Self) -> Option<Self> {
+        if x.base != BasicType::B || !x.o {
+            return None;
+        }
+        Some(Self { base: BasicType::W, z: false, o: false, n: false, ..x })
+    }
+
+    // c:X, [X] OP_CHECKSIG
+    pub fn check(x: Self) -> Option<Self> {
+        if x.base != BasicType::K {
+            return None;
+        }
+        Some(Self { base: BasicType::B, u: true, ..x })
+    }
+
+    // d:X, OP_DUP OP_IF [X] OP_ENDIF
+    pub fn dup_if(x: Self) -> Option<Self> {
+        if x.base != BasicType::V || !x.z {
+            return None;
+        }
+        Some(Self { base: BasicType::B, z: false, o: true, n: true, d: true, u: false, f: false, e: true, ..x })
+    }
+
+    // v:X, [X] OP_VERIFY
+    pub fn verify(x: Self) -> Option<Self> {
+        if x.base != BasicType::B {
+            return None;
+        }
+        Some(Self { base: BasicType::V, d: false, u: false, f: true, e: false, ..x })
+    }
+
+    // j:X, OP_SIZE OP_0NOTEQUAL OP_IF [X] OP_ENDIF
+    pub fn non_zero(x: Self) -> Option<Self> {
+        if x.base != BasicType::B || !x.n {
+            return None;
+        }
+        Some(Self { z: false, d: true, f: false, e: x.f, ..x })
+    }
+
+    // n:X, [X] OP_0NOTEQUAL
+    pub fn zero_not_equal(x: Self) -> Option<Self> {
+        if x.base != BasicType::B {
+            return None;
+        }
+        Some(Self { u: true, ..x })
+    }
+}
+
+// such as "Bondusem"
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.base)?;
+        let properties = [
+            ('z', self.z), ('o', self.o), ('n', self.n), ('d', self.d), ('u', self.u),
+            ('s', self.s), ('f', self.f), ('e', self.e), ('m', self.m),
+        ];
+        for (c, has) in properties {
+            if has {
+                write!(f, "{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BasicType, Type};
+
+    #[test]
+    fn types_wrappers() {
+        // c:pk_k
+        assert_eq!(Type::check(Type::pk_k()).unwrap().to_string(), "Bondusem");
+        // v:pk
+        let v_pk = Type::verify(Type::check(Type::pk_k()).unwrap()).unwrap();
+        assert_eq!(v_pk.base, BasicType::V);
+        assert_eq!(v_pk.to_string(), "Vonsfm");
+        // s: needs o, a: does not
+        assert!(Type::swap(Type::time_lock()).is_none());
+        assert_eq!(Type::alt(Type::time_lock()).unwrap().base, BasicType::W);
+        assert!(Type::check(Type::hash()).is_none());
+    }
+
+    #[test]
+    fn types_malleability() {
+        let pk = Type::check(Type::pk_k()).unwrap();
+        // or_d(pk,older) can only be satisfied without signature by the timelock
+        let or_d = Type::or_d(pk, Type::time_lock()).unwrap();
+        assert!(or_d.m);
+        assert!(!or_d.s);
+        // or_d(sha256,pk) is malleable, the dissatisfaction of sha256 is not unique
+        assert!(!Type::or_d(Type::hash(), pk).unwrap().m);
+        // or_i of two timelocks is malleable, either branch can be chosen by anyone
+        assert!(!Type::or_i(Type::time_lock(), Type::time_lock()).unwrap().m);
+    }
+
+    #[test]
+    fn types_thresh() {
+        let pk = Type::check(Type::pk_k()).unwrap();
+        let s_pk = Type::swap(pk).unwrap();
+        assert!(Type::thresh(2, &[pk, s_pk, s_pk]).unwrap().s);
+        assert!(Type::thresh(0, &[pk]).is_none());
+        assert!(Type::thresh(2, &[pk]).is_none());
+        // the first one must be B, the others W
+        assert!(Type::thresh(1, &[pk, pk]).is_none());
+    }
+}
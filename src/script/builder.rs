@@ -1,17 +1,41 @@
 use crate::secp256k1::S256Point;
-use crate::util::hash::Hash160Value;
-use super::{CommandElement, Opcode, Script};
+use crate::util::hash::{self, Hash160Value, Hash256Value};
+use super::{CommandElement, Error, Num, Opcode, Script};
+
+// the hash of the 32 bytes preimage which claims a htlc
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashLock {
+    Sha256(Hash256Value),
+    Hash256(Hash256Value),
+}
+
+impl HashLock {
+    pub fn sha256(preimage: &[u8]) -> Self {
+        Self::Sha256(hash::sha256(preimage))
+    }
+
+    pub fn hash256(preimage: &[u8]) -> Self {
+        Self::Hash256(hash::hash256(preimage))
+    }
+
+    pub fn matches(&self, preimage: &[u8]) -> bool {
+        match self {
+            Self::Sha256(h) => preimage.len() == 32 && hash::sha256(preimage) == *h,
+            Self::Hash256(h) => preimage.len() == 32 && hash::hash256(preimage) == *h,
+        }
+    }
+}
 
 pub struct ScriptBuilder;
 
 impl ScriptBuilder {
     pub fn p2pk(pk: &S256Point) -> Result<Script, &'static str> {
         let mut commands = vec![CommandElement::Op(Opcode::OpChecksig)];
-        let mut pk_bytes = match pk.sec_compressed() { // default to compressed
+        let pk_bytes = match pk.sec_compressed() { // default to compressed
             Some(bytes) => bytes,
             None => return Err("pk is infinity"),
         };
-        pk_bytes.reverse();
+        // cmds of Script are in reversed order, but the bytes of each element are not
         commands.push(CommandElement::Data(pk_bytes));
 
         Ok(Script::new(commands))
@@ -25,12 +49,163 @@ impl ScriptBuilder {
 
         Script::new(commands)
     }
+
+    // OP_m <pubkey>... OP_n OP_CHECKMULTISIG, with compressed pubkeys
+    pub fn multisig(required: usize, pks: &[S256Point]) -> Result<Script, &'static str> {
+        if required == 0 || required > pks.len() || pks.len() > 16 {
+            return Err("invalid m-of-n for multisig");
+        }
+        let mut commands = vec![
+            CommandElement::Op(Opcode::OpCheckmultisig),
+            CommandElement::Op(Self::small_int(pks.len())),
+        ];
+        for pk in pks.iter().rev() {
+            match pk.sec_compressed() {
+                Some(bytes) => commands.push(CommandElement::Data(bytes)),
+                None => return Err("pk is infinity"),
+            }
+        }
+        commands.push(CommandElement::Op(Self::small_int(required)));
+
+        Ok(Script::new(commands))
+    }
+
+    // OP_HASH160 <hash160(redeem_script)> OP_EQUAL
+    pub fn p2sh(redeem_script: &Script) -> Result<Script, Error> {
+        let script_hash = hash::hash160(&redeem_script.raw_serialize()?);
+        let commands = vec![
+            CommandElement::Op(Opcode::OpEqual),
+            CommandElement::Data(script_hash.to_vec()),
+            CommandElement::Op(Opcode::OpHash160),
+        ];
+
+        Ok(Script::new(commands))
+    }
+
+    pub fn p2wpkh(pkh: &Hash160Value) -> Script {
+        Self::witness_program(0, pkh.to_vec())
+    }
+
+    // OP_0 <sha256(witness_script)>
+    pub fn p2wsh(witness_script: &Script) -> Result<Script, Error> {
+        let script_hash = hash::sha256(&witness_script.raw_serialize()?);
+        Ok(Self::witness_program(0, script_hash.to_vec()))
+    }
+
+    // p2wpkh as the redeem script of p2sh
+    pub fn p2sh_p2wpkh(pkh: &Hash160Value) -> Result<Script, Error> {
+        Self::p2sh(&Self::p2wpkh(pkh))
+    }
+
+    // OP_1 <x-only output key>, the key is already tweaked
+    pub fn p2tr(output_key: &[u8; 32]) -> Script {
+        Self::witness_program(1, output_key.to_vec())
+    }
+
+    // htlc we offer, the receiver claims by the preimage, we refund after `delay` of relative lock time (BIP112)
+    pub fn htlc_offered(hash_lock: &HashLock, receiver: &S256Point, sender: &S256Point, delay: u32) -> Result<Script, &'static str> {
+        Self::htlc(hash_lock, receiver, sender, delay, Opcode::OpChecksequenceverify)
+    }
+
+    // htlc we receive, we claim by the preimage, the sender refunds after the absolute `lock_time` (BIP65)
+    pub fn htlc_received(hash_lock: &HashLock, receiver: &S256Point, sender: &S256Point, lock_time: u32) -> Result<Script, &'static str> {
+        Self::htlc(hash_lock, receiver, sender, lock_time, Opcode::OpChecklocktimeverify)
+    }
+
+    // OP_IF OP_SIZE 32 OP_EQUALVERIFY <hash op> <hash> OP_EQUALVERIFY <claim pk>
+    // OP_ELSE <timeout> <lock op> OP_DROP <refund pk> OP_ENDIF OP_CHECKSIG
+    fn htlc(hash_lock: &HashLock, claim_pk: &S256Point, refund_pk: &S256Point, timeout: u32, lock_op: Opcode) -> Result<Script, &'static str> {
+        let (hash_op, h) = match hash_lock {
+            HashLock::Sha256(h) => (Opcode::OpSha256, h),
+            HashLock::Hash256(h) => (Opcode::OpHash256, h),
+        };
+        let claim_pk = claim_pk.sec_compressed().ok_or("pk is infinity")?;
+        let refund_pk = refund_pk.sec_compressed().ok_or("pk is infinity")?;
+        let mut commands = vec![
+            CommandElement::Op(Opcode::OpIf),
+            CommandElement::Op(Opcode::OpSize),
+            Self::push_num(32),
+            CommandElement::Op(Opcode::OpEqualverify),
+            CommandElement::Op(hash_op),
+            CommandElement::Data(h.to_vec()),
+            CommandElement::Op(Opcode::OpEqualverify),
+            CommandElement::Data(claim_pk),
+            CommandElement::Op(Opcode::OpElse),
+            Self::push_num(timeout as i64),
+            CommandElement::Op(lock_op),
+            CommandElement::Op(Opcode::OpDrop),
+            CommandElement::Data(refund_pk),
+            CommandElement::Op(Opcode::OpEndif),
+            CommandElement::Op(Opcode::OpChecksig),
+        ];
+        commands.reverse();
+
+        Ok(Script::new(commands))
+    }
+
+    // witness of the htlc claim path: <sig> <preimage> 1 <witness script>
+    pub fn htlc_claim_witness(witness_script: &Script, signature: Vec<u8>, preimage: Vec<u8>) -> Result<Vec<Vec<u8>>, Error> {
+        Ok(vec![signature, preimage, vec![1], witness_script.raw_serialize()?])
+    }
+
+    // witness of the htlc refund path: <sig> <> <witness script>
+    // the spending tx sets the sequence or lock time of the template
+    pub fn htlc_refund_witness(witness_script: &Script, signature: Vec<u8>) -> Result<Vec<Vec<u8>>, Error> {
+        Ok(vec![signature, vec![], witness_script.raw_serialize()?])
+    }
+
+    // OP_RETURN <data>, unspendable
+    pub fn op_return(data: &[u8]) -> Script {
+        let mut commands = vec![];
+        if !data.is_empty() {
+            commands.push(Self::push_data(data));
+        }
+        commands.push(CommandElement::Op(Opcode::OpReturn));
+
+        Script::new(commands)
+    }
+
+    // OP_n <program>, version 0 to 16
+    pub fn witness_program(version: usize, program: Vec<u8>) -> Script {
+        Script::new(vec![CommandElement::Data(program), CommandElement::Op(Self::small_int(version))])
+    }
+
+    // OP_0 to OP_16, the caller makes sure n is in range
+    fn small_int(n: usize) -> Opcode {
+        Opcode::from_small_int(n as i64).expect("small int is out of range")
+    }
+
+    // minimal push of a script number, -1 to 16 are pushed by opcode
+    pub fn push_num(n: i64) -> CommandElement {
+        match Opcode::from_small_int(n) {
+            Some(op) => CommandElement::Op(op),
+            None => CommandElement::Data(Num::from(n).encode()),
+        }
+    }
+
+    // minimal push, a single byte of 1 to 16 or 0x81 is pushed by opcode
+    pub fn push_data(data: &[u8]) -> CommandElement {
+        match data {
+            [] => CommandElement::Op(Opcode::Op0),
+            [n @ 1..=16] => CommandElement::Op(Self::small_int(*n as usize)),
+            [0x81] => CommandElement::Op(Opcode::Op1negate),
+            _ => CommandElement::Data(data.to_vec()),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::secp256k1::{PrivateKey, S256Point};
+    use crate::script::{operator, CommandElement, Error, Opcode, Script, ScriptType, VerifyFlags};
+    use crate::transaction::{LockTime, Sequence, SigHash, Transaction, TxOut, Version};
     use crate::util::{base58, hash};
-    use super::ScriptBuilder;
+    use primitive_types::U256;
+    use super::{HashLock, ScriptBuilder};
+
+    fn script_hex(script: &Script) -> String {
+        hex::encode(script.raw_serialize().unwrap())
+    }
 
     #[test]
     fn script_builder_p2pkh() {
@@ -39,4 +214,134 @@ mod tests {
         let script = ScriptBuilder::p2pkh(&receiver_h160);
         assert_eq!(hex::encode(script.serialize().unwrap()), "1976a914d52ad7ca9b3d096a38e752c2018e6fbc40cdf26f88ac");
     }
+
+    #[test]
+    fn script_builder_p2pk() {
+        let script = ScriptBuilder::p2pk(&S256Point::g()).unwrap();
+        assert_eq!(script_hex(&script), "210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac");
+        assert!(matches!(script.classify(), ScriptType::PubKey(_)));
+    }
+
+    #[test]
+    fn script_builder_multisig() {
+        let pks: Vec<S256Point> = (1u32..=3).map(|secret| PrivateKey::new(secret.into()).unwrap().pk_point().clone()).collect();
+        let script = ScriptBuilder::multisig(2, &pks).unwrap();
+        let script_type = ScriptType::Multisig {
+            required: 2,
+            keys: pks.iter().map(|pk| pk.sec_compressed().unwrap()).collect(),
+        };
+        assert_eq!(script.classify(), script_type);
+        assert_eq!(&script_hex(&script)[..4], "5221");
+        assert_eq!(&script_hex(&script)[(script.size() * 2 - 4)..], "53ae");
+
+        assert!(ScriptBuilder::multisig(0, &pks).is_err());
+        assert!(ScriptBuilder::multisig(4, &pks).is_err());
+        assert!(ScriptBuilder::multisig(1, &vec![S256Point::g(); 17]).is_err());
+    }
+
+    #[test]
+    fn script_builder_p2sh() {
+        // BIP143, P2SH-P2WPKH
+        let redeem_script = Script::parse_raw(&hex::decode("001479091972186c449eb1ded22b78e40d009bdf0089").unwrap()).unwrap();
+        let script = ScriptBuilder::p2sh(&redeem_script).unwrap();
+        assert_eq!(script_hex(&script), "a9144733f37cf4db86fbc2efed2500b4f4e49f31202387");
+
+        let pkh = hash::convert_slice_into_hash160(&hex::decode("79091972186c449eb1ded22b78e40d009bdf0089").unwrap());
+        assert_eq!(script_hex(&ScriptBuilder::p2sh_p2wpkh(&pkh).unwrap()), "a9144733f37cf4db86fbc2efed2500b4f4e49f31202387");
+    }
+
+    #[test]
+    fn script_builder_witness() {
+        // BIP173, the key is the generator
+        let pkh = S256Point::g().hash160(true);
+        assert_eq!(script_hex(&ScriptBuilder::p2wpkh(&pkh)), "0014751e76e8199196d454941c45d1b3a323f1433bd6");
+
+        let witness_script = ScriptBuilder::p2pk(&S256Point::g()).unwrap();
+        let script = ScriptBuilder::p2wsh(&witness_script).unwrap();
+        assert_eq!(script_hex(&script), "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262");
+
+        // BIP341, key path only
+        let output_key = hash::convert_slice_into_hash256(&hex::decode("53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343").unwrap());
+        let script = ScriptBuilder::p2tr(&output_key);
+        assert_eq!(script_hex(&script), "512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343");
+    }
+
+    #[test]
+    fn script_builder_op_return() {
+        assert_eq!(script_hex(&ScriptBuilder::op_return(b"hello world")), "6a0b68656c6c6f20776f726c64");
+        assert_eq!(script_hex(&ScriptBuilder::op_return(&[])), "6a");
+        assert_eq!(script_hex(&ScriptBuilder::op_return(&[16])), "6a60");
+        assert_eq!(script_hex(&ScriptBuilder::op_return(&[0x81])), "6a4f");
+        assert_eq!(script_hex(&ScriptBuilder::op_return(&[0])), "6a0100");
+        assert_eq!(script_hex(&ScriptBuilder::op_return(&[7; 80]))[..6], *"6a4c50");
+    }
+
+    #[test]
+    fn script_builder_push_num() {
+        assert_eq!(ScriptBuilder::push_num(0), CommandElement::Op(Opcode::Op0));
+        assert_eq!(ScriptBuilder::push_num(16), CommandElement::Op(Opcode::Op16));
+        assert_eq!(ScriptBuilder::push_num(-1), CommandElement::Op(Opcode::Op1negate));
+        assert_eq!(ScriptBuilder::push_num(17), CommandElement::Data(vec![17]));
+        assert_eq!(ScriptBuilder::push_num(12960), CommandElement::Data(vec![0xa0, 0x32]));
+    }
+
+    // spend the p2wsh htlc by input 1 of the BIP143 p2wsh transaction, claim by the preimage if any or refund
+    fn verify_htlc_spend(witness_script: &Script, key: &PrivateKey, preimage: Option<Vec<u8>>, sequence: u32, lock_time: u32) -> Result<bool, Error> {
+        let bytes = hex::decode("01000000000102fe3dc9208094f3ffd12645477b3dc56f60ec4fa8e6f5d67c565d1c6b9216b36e000000004847304402200af4e47c9b9629dbecc21f73af989bdaa911f7e6f6c2e9394588a3aa68f81e9902204f3fcf6ade7e5abb1295b6774c8e0abd94ae62217367096bc02ee5e435b67da201ffffffff0815cf020f013ed6cf91d29f4202e8a58726b1ac6c79da47c23d1bee0a6925f80000000000ffffffff0100f2052a010000001976a914a30741f8145e5acadf23f751864167f32e0963f788ac000347304402200de66acf4527789bfda55fc5459e214fa6083f936b430a762c629656216805ac0220396f550692cd347171cbc1ef1f51e15282e837bb2b30860dc77c8f78bc8501e503473044022027dc95ad6b740fe5129e7e62a75dd00f291a2aeb1200b84b09d9e3789406b6c002201a9ecd315dd6a0e632ab20bbb98948bc0c6fb204f2c286963bb48517a7058e27034721026dccc749adc2a9d0d89497ac511f760f45c47dc5ed9cf352a58ac706453880aeadab210255a9626aebf5e29c0e6538428ba0d1dcf6ca98ffdf086aa8ced5e0d0215ea465ac00000000").unwrap();
+        let mut tx = Transaction::parse(&bytes).unwrap();
+        tx.version = Version::new(2);
+        tx.inputs[1].sequence = Sequence::new(sequence);
+        tx.locktime = LockTime::new(lock_time);
+        let prevout = TxOut::new(4900000000u64, ScriptBuilder::p2wsh(witness_script)?.raw_serialize()?);
+
+        let z = tx.signature_hash_segwit_v0(1, &witness_script.raw_serialize()?, prevout.amount(), SigHash::All.into())?;
+        let sig = hex::decode(key.sign_deterministic(U256::from_big_endian(&z)).unwrap().der()).unwrap();
+        let sig = [sig, vec![SigHash::All.value()]].concat();
+
+        tx.inputs[1].witness = match preimage {
+            Some(preimage) => ScriptBuilder::htlc_claim_witness(witness_script, sig, preimage)?,
+            None => ScriptBuilder::htlc_refund_witness(witness_script, sig)?,
+        };
+        operator::verify_tx_input(&tx, 1, Some(prevout), VerifyFlags::standard())
+    }
+
+    #[test]
+    fn script_builder_htlc_offered() {
+        let receiver = PrivateKey::new(7u32.into()).unwrap();
+        let sender = PrivateKey::new(11u32.into()).unwrap();
+        let preimage = vec![0x42; 32];
+        let hash_lock = HashLock::sha256(&preimage);
+        assert!(hash_lock.matches(&preimage) && !hash_lock.matches(&[0x42; 31]));
+        let script = ScriptBuilder::htlc_offered(&hash_lock, receiver.pk_point(), sender.pk_point(), 144).unwrap();
+        let script_hex = script_hex(&script);
+        assert_eq!(&script_hex[..10], "6382012088");
+        assert_eq!(&script_hex[10..14], "a820");
+        assert!(script_hex.ends_with("67029000b2752103774ae7f858a9411e5ef4246b70c65aac5649980be5c17891bbec17895da008cb68ac"));
+
+        // the receiver claims at any time
+        assert!(verify_htlc_spend(&script, &receiver, Some(preimage.clone()), Sequence::FINAL, 0).unwrap());
+        assert!(matches!(verify_htlc_spend(&script, &receiver, Some(vec![0x43; 32]), Sequence::FINAL, 0), Err(Error::EqualVerify)));
+        // a failed signature must be empty under NULLFAIL
+        assert!(matches!(verify_htlc_spend(&script, &sender, Some(preimage), Sequence::FINAL, 0), Err(Error::SigNullFail)));
+        // the sender refunds after 144 blocks
+        assert!(verify_htlc_spend(&script, &sender, None, 144, 0).unwrap());
+        assert!(matches!(verify_htlc_spend(&script, &sender, None, 143, 0), Err(Error::UnsatisfiedLocktime)));
+    }
+
+    #[test]
+    fn script_builder_htlc_received() {
+        let receiver = PrivateKey::new(7u32.into()).unwrap();
+        let sender = PrivateKey::new(11u32.into()).unwrap();
+        let preimage = vec![0x42; 32];
+        let hash_lock = HashLock::hash256(&preimage);
+        let script = ScriptBuilder::htlc_received(&hash_lock, receiver.pk_point(), sender.pk_point(), 600_000).unwrap();
+        assert_eq!(&script_hex(&script)[10..14], "aa20");
+
+        assert!(verify_htlc_spend(&script, &receiver, Some(preimage), Sequence::FINAL, 0).unwrap());
+        // the preimage must be of 32 bytes
+        assert!(matches!(verify_htlc_spend(&script, &receiver, Some(vec![0x42; 33]), Sequence::FINAL, 0), Err(Error::EqualVerify)));
+        // the sender refunds at the height of 600000, the lock time is enabled by a non final sequence
+        assert!(verify_htlc_spend(&script, &sender, None, 0, 600_000).unwrap());
+        assert!(matches!(verify_htlc_spend(&script, &sender, None, 0, 599_999), Err(Error::UnsatisfiedLocktime)));
+    }
 }
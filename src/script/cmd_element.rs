@@ -1,5 +1,5 @@
 use crate::util::Reader;
-use super::{Opcode, Error};
+use super::{Opcode, Error, Num};
 use std::fmt;
 use std::convert::{TryFrom, From};
 
@@ -7,6 +7,7 @@ use std::convert::{TryFrom, From};
 pub enum CommandElement {
     Op(Opcode),
     Data(Vec<u8>), // length <= 520
+    NonMinimalData(u8, Vec<u8>), // pushed by a longer opcode than needed, the opcode is kept to serialize back
     Unknown(u8),
 }
 
@@ -17,75 +18,119 @@ impl CommandElement {
     }
 
     pub fn parse_reader(reader: &mut Reader) -> Result<Self, Error> {
-        let payload_len;
         let byte = reader.more(1)?[0];
-        match byte {
-            1..=75 => payload_len = byte as usize,
-            76 => payload_len = reader.more(1)?[0] as usize,
-            77 => {
-                payload_len = (reader.more(1)?[0] as usize) + (reader.more(1)?[0] as usize) << 8; // little_endian
-                if payload_len > 520 {
-                    return Err(Error::TooLongBytes);
-                }
-            },
+        let payload_len = match byte {
+            1..=75 => byte as usize,
+            0x4c => more(reader, 1)?[0] as usize,
+            0x4d => u16::from_le_bytes(<[u8; 2]>::try_from(more(reader, 2)?).expect("2 bytes")) as usize,
+            0x4e => u32::from_le_bytes(<[u8; 4]>::try_from(more(reader, 4)?).expect("4 bytes")) as usize,
             _ => {
                 match Opcode::from_u8(byte) {
                     Some(code) => return Ok(Self::Op(code)),
                     None => return Ok(Self::Unknown(byte)), // coinbase tx can have any bytes
                 };
             }
+        };
+        // the size limit of 520 is checked by the interpreter, data in unexecuted scripts can be longer
+        let data = more(reader, payload_len)?.to_vec();
+        if Self::push_opcode(data.len()) != byte {
+            return Ok(Self::NonMinimalData(byte, data));
         }
-        let data = reader.more(payload_len)?.to_vec();
         Ok(Self::Data(data))
     }
 
+    // a witness element is a stack item, not an opcode, it is pushed minimally to pass MINIMALDATA
     pub fn parse_witness(bytes: &[u8]) -> Result<Self, Error> {
-        if bytes.is_empty() {
-            return Err(Error::InvalidWitnessElement);
-        }
-        if bytes.len() == 1 {
-            let code = match Opcode::from_u8(bytes[0]) {
-                Some(code) => Self::Op(code),
-                None => Self::Unknown(bytes[0]),
-            };
-            return Ok(code);
-        }
-        Ok(Self::Data(bytes.to_vec()))
+        let ele = match bytes {
+            [] => Self::Op(Opcode::Op0),
+            [n @ 1..=16] => Self::Op(Opcode::from_small_int(*n as i64).expect("small int")),
+            [0x81] => Self::Op(Opcode::Op1negate),
+            _ => Self::Data(bytes.to_vec()),
+        };
+        Ok(ele)
     }
 
     pub fn serialize(&self, result: &mut Vec<u8>) -> Result<(), Error> {
         match self {
             CommandElement::Op(op) => result.push(op.value()),
-            CommandElement::Data(data) => {
-                let len = data.len();
-                if len <= 75 { // op
-                    let len = u8::try_from(len).expect("len is out of range of u8");
-                    result.push(len);
-                } else if len <= 255 {
-                    result.push(76u8);
-                    let len = u8::try_from(len).expect("len is out of range of u8");
-                    result.push(len);
-                } else if len <= 520 {
-                    result.push(77u8);
-                    let len = u16::try_from(len).expect("len is out of range of u16");
-                    let len_bytes = len.to_le_bytes();
-                    result.push(len_bytes[0]);
-                    result.push(len_bytes[1]);
-                } else {
-                    return Err(Error::TooLongBytes);
-                }
-                result.append(&mut data.clone());
-            }
+            CommandElement::Data(data) => Self::serialize_push(Self::push_opcode(data.len()), data, result)?,
+            CommandElement::NonMinimalData(opcode, data) => Self::serialize_push(*opcode, data, result)?,
             CommandElement::Unknown(byte) => result.push(*byte),
         }
 
         Ok(())
     }
 
+    fn serialize_push(opcode: u8, data: &[u8], result: &mut Vec<u8>) -> Result<(), Error> {
+        let len = data.len();
+        result.push(opcode);
+        match opcode {
+            0x4c => result.push(u8::try_from(len).map_err(|_| Error::TooLongBytes)?),
+            0x4d => result.extend_from_slice(&u16::try_from(len).map_err(|_| Error::TooLongBytes)?.to_le_bytes()),
+            0x4e => result.extend_from_slice(&u32::try_from(len).map_err(|_| Error::TooLongBytes)?.to_le_bytes()),
+            _ => (),
+        }
+        result.extend_from_slice(data);
+
+        Ok(())
+    }
+
+    // the shortest opcode to push data of len, OP_PUSHDATA1/2/4 is only used when the len cannot fit into the opcode
+    pub fn push_opcode(len: usize) -> u8 {
+        match len {
+            0..=75 => len as u8,
+            76..=0xff => 0x4c,
+            0x100..=0xffff => 0x4d,
+            _ => 0x4e,
+        }
+    }
+
+    // token of ASM: opcode name, decimal number, hex data or <hex data>
+    pub fn parse_asm(token: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidAsmToken(token.into());
+        if let Some(data) = token.strip_prefix('<').and_then(|t| t.strip_suffix('>')) {
+            return hex::decode(data).map(Self::Data).map_err(|_| invalid());
+        }
+        if let Ok(num) = token.parse::<i64>() {
+            if let Some(op) = Opcode::from_small_int(num) {
+                return Ok(Self::Op(op));
+            }
+            // data with at most 4 bytes is displayed as number
+            if num.abs() <= i32::MAX as i64 {
+                return Ok(Self::Data(Num::from(num).encode()));
+            }
+        }
+        if let Some(op) = Opcode::from_name(token) {
+            return Ok(Self::Op(op));
+        }
+        hex::decode(token).map(Self::Data).map_err(|_| invalid())
+    }
+
+    // size after serialized
+    pub fn size(&self) -> usize {
+        let (opcode, data) = match self {
+            Self::Data(data) => (Self::push_opcode(data.len()), data),
+            Self::NonMinimalData(opcode, data) => (*opcode, data),
+            _ => return 1,
+        };
+        let len_size = match opcode {
+            0x4c => 1,
+            0x4d => 2,
+            0x4e => 4,
+            _ => 0,
+        };
+        1 + len_size + data.len()
+    }
+
     pub fn is_data(&self) -> bool {
+        self.data().is_some()
+    }
+
+    // pushed data, whichever push opcode is used
+    pub fn data(&self) -> Option<&Vec<u8>> {
         match self {
-            CommandElement::Data(_) => true,
-            _ => false,
+            CommandElement::Data(data) | CommandElement::NonMinimalData(_, data) => Some(data),
+            _ => None,
         }
     }
 
@@ -97,17 +142,41 @@ impl CommandElement {
     }
 }
 
+// the push data runs past the end of script
+fn more<'a>(reader: &'a mut Reader, count: usize) -> Result<&'a [u8], Error> {
+    reader.more(count).map_err(|_| Error::InvalidBytes)
+}
+
 impl fmt::Debug for CommandElement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let msg = match self {
             Self::Op(code) => format!("OpCode({:?})", code),
             Self::Data(data) => format!("Data({})", hex::encode(data)),
+            Self::NonMinimalData(opcode, data) => format!("NonMinimalData({:x}, {})", opcode, hex::encode(data)),
             Self::Unknown(byte) => format!("Unknown({:x})", byte),
         };
         write!(f, "{}", msg)
     }
 }
 
+// the same as asm of decodescript in bitcoin core
+impl fmt::Display for CommandElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Op(op) => match op.small_int() {
+                Some(n) => write!(f, "{}", n),
+                None => write!(f, "{}", op.name()),
+            },
+            Self::Data(data) | Self::NonMinimalData(_, data) if data.len() <= 4 => {
+                let num = Num::decode(data.clone()).expect("4 bytes never overflow");
+                write!(f, "{}", num.value())
+            },
+            Self::Data(data) | Self::NonMinimalData(_, data) => write!(f, "{}", hex::encode(data)),
+            Self::Unknown(_) => write!(f, "OP_UNKNOWN"),
+        }
+    }
+}
+
 impl From<Opcode> for CommandElement {
     fn from(opcode: Opcode) -> Self {
         CommandElement::Op(opcode)
@@ -119,3 +188,65 @@ impl From<Vec<u8>> for CommandElement {
         CommandElement::Data(data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandElement, Error};
+
+    fn round_trip(bytes: &[u8]) -> CommandElement {
+        let cmd = CommandElement::parse(bytes).unwrap();
+        let mut result = vec![];
+        cmd.serialize(&mut result).unwrap();
+        assert_eq!(result, bytes);
+        assert_eq!(cmd.size(), bytes.len());
+        cmd
+    }
+
+    #[test]
+    fn cmd_element_push_data() {
+        let mut bytes = vec![0x4c, 76];
+        bytes.extend_from_slice(&[1u8; 76]);
+        assert_eq!(round_trip(&bytes), CommandElement::Data(vec![1u8; 76]));
+
+        // 0x012c, not (0x2c + 0x01) << 8
+        let mut bytes = vec![0x4d, 0x2c, 0x01];
+        bytes.extend_from_slice(&[1u8; 300]);
+        assert_eq!(round_trip(&bytes), CommandElement::Data(vec![1u8; 300]));
+
+        let mut bytes = vec![0x4e, 0x70, 0x11, 0x01, 0x00];
+        bytes.extend_from_slice(&[1u8; 0x011170]);
+        assert_eq!(round_trip(&bytes), CommandElement::Data(vec![1u8; 0x011170]));
+    }
+
+    #[test]
+    fn cmd_element_non_minimal_push() {
+        assert_eq!(round_trip(&[0x4c, 0x01, 0x11]), CommandElement::NonMinimalData(0x4c, vec![0x11]));
+        assert_eq!(round_trip(&[0x4c, 0x00]), CommandElement::NonMinimalData(0x4c, vec![]));
+        assert_eq!(round_trip(&[0x4d, 0x01, 0x00, 0x11]), CommandElement::NonMinimalData(0x4d, vec![0x11]));
+        assert_eq!(round_trip(&[0x4e, 0x01, 0x00, 0x00, 0x00, 0x11]), CommandElement::NonMinimalData(0x4e, vec![0x11]));
+        assert_eq!(CommandElement::NonMinimalData(0x4c, vec![0x11]).data(), Some(&vec![0x11]));
+        assert!(matches!(CommandElement::parse(&[0x4e, 0x01, 0x00]), Err(Error::InvalidBytes)));
+        assert!(matches!(CommandElement::parse(&[0x02, 0x11]), Err(Error::InvalidBytes)));
+    }
+
+    #[test]
+    fn cmd_element_parse_witness() {
+        use super::Opcode;
+        // the same stack item as the witness element, not the opcode of the byte
+        assert_eq!(CommandElement::parse_witness(&[]).unwrap(), CommandElement::Op(Opcode::Op0));
+        assert_eq!(CommandElement::parse_witness(&[1]).unwrap(), CommandElement::Op(Opcode::Op1));
+        assert_eq!(CommandElement::parse_witness(&[0x81]).unwrap(), CommandElement::Op(Opcode::Op1negate));
+        assert_eq!(CommandElement::parse_witness(&[0x51]).unwrap(), CommandElement::Data(vec![0x51]));
+        assert_eq!(CommandElement::parse_witness(&[0xac, 0x51]).unwrap(), CommandElement::Data(vec![0xac, 0x51]));
+    }
+
+    #[test]
+    fn cmd_element_push_opcode() {
+        assert_eq!(CommandElement::push_opcode(0), 0);
+        assert_eq!(CommandElement::push_opcode(75), 75);
+        assert_eq!(CommandElement::push_opcode(76), 0x4c);
+        assert_eq!(CommandElement::push_opcode(0xff), 0x4c);
+        assert_eq!(CommandElement::push_opcode(0x100), 0x4d);
+        assert_eq!(CommandElement::push_opcode(0x10000), 0x4e);
+    }
+}
@@ -0,0 +1,59 @@
+use super::Error;
+
+// the execution state of nested OP_IF/OP_NOTIF, command is executed only when all are true
+#[derive(Debug, Default)]
+pub struct ConditionStack(Vec<bool>);
+
+impl ConditionStack {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn inner(&self) -> &Vec<bool> {
+        &self.0
+    }
+
+    pub fn all_true(&self) -> bool {
+        !self.0.contains(&false)
+    }
+
+    pub fn push(&mut self, condition: bool) {
+        self.0.push(condition);
+    }
+
+    pub fn pop(&mut self) -> Result<(), Error> {
+        self.0.pop().ok_or(Error::UnbalancedConditional)?;
+        Ok(())
+    }
+
+    pub fn toggle_top(&mut self) -> Result<(), Error> {
+        let top = self.0.last_mut().ok_or(Error::UnbalancedConditional)?;
+        *top = !*top;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConditionStack;
+
+    #[test]
+    fn condition_stack_nested() {
+        let mut conditions = ConditionStack::new();
+        assert!(conditions.all_true());
+        conditions.push(true);
+        conditions.push(false);
+        assert!(!conditions.all_true());
+        conditions.toggle_top().unwrap();
+        assert!(conditions.all_true());
+        conditions.pop().unwrap();
+        conditions.pop().unwrap();
+        assert!(conditions.is_empty());
+        assert!(conditions.pop().is_err());
+        assert!(conditions.toggle_top().is_err());
+    }
+}
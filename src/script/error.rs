@@ -1,11 +1,12 @@
 use crate::transaction::Error as TransactionError;
+use super::Opcode;
 
 #[derive(Debug)]
 pub enum Error {
     // CommandElement
     EmptyBytes,
-    TooLongBytes, // should <= 520 within Script::parse
-    InvalidBytes,
+    TooLongBytes, // data is too long for its push opcode
+    InvalidBytes, // a push runs past the end of script
     InvalidOpcode,
     InvalidTxFee,
     InvalidWitnessElement,
@@ -14,16 +15,78 @@ pub enum Error {
 
     // check
     SigHashIsNotTheSame,
+    NegativeLocktime,
+    UnsatisfiedLocktime,
+    MissingTransaction,
     PublicKeyIsTooMuchForCheckMultisig,
+    SigCount, // more signatures than public keys for OP_CHECKMULTISIG
+    EvalFalse, // script finished without error but the top of stack is false
+    Verify,
+    EqualVerify,
+    NumEqualVerify,
+    CheckSigVerify,
+    CheckMultisigVerify,
+
+    // VerifyFlags
+    SigDer,
+    SigHighS,
+    SigHashType,
+    PubKeyType,
+    WitnessPubKeyType,
+    SigNullDummy,
+    SigNullFail,
+    SigPushOnly,
+    MinimalData,
+    CleanStack,
+    OpCodeSeparator,
+    DiscourageUpgradableNops,
+    DiscourageUpgradableWitnessProgram,
+    DiscourageUpgradableTaprootVersion,
+    DiscourageUpgradablePubKeyType,
+    DiscourageOpSuccess,
+
+    // Segwit
+    WitnessMalleated, // script_sig is not empty for a native witness program
+    WitnessMalleatedP2sh, // script_sig is not exactly the push of the nested witness program
+    WitnessProgramWrongLength,
+    WitnessUnexpected, // witness is not empty for an input which does not spend a witness program
+
+    // Taproot
+    WitnessProgramWitnessEmpty,
+    WitnessProgramMismatch, // control block does not commit to the output key
+    TaprootWrongControlSize,
+    TapscriptValidationWeight,
+    TapscriptCheckmultisig,
+    SchnorrSig,
+    SchnorrSigSize,
+    SchnorrSigHashType,
 
     // Stack
     EmptyStack,
+    InvalidStackOperation,
+    InvalidAltStackOperation,
+    StackSize, // more than 1000 elements in main stack and alt stack
+    ScriptSize,
+    PushSize,
+    OpCount,
+
+    // Opcode
+    DisabledOpcode(Opcode),
+    BadOpcode(Opcode), // reserved opcodes which fail when executed
+    OpReturn,
+
+    // Condition
+    UnbalancedConditional,
+    MinimalIf,
 
     // Num
     NumDecodeOverflow,
+    NumSize,
+    NumNotMinimal, // MINIMALDATA requires the minimal encoding of a number operand
 
     // Script
     UnknownByteInScript(u8),
+    InvalidAsmToken(String),
     EmptyScript,
     InvalidBlockHeightInCoinbase,
 
@@ -0,0 +1,117 @@
+use super::{ConditionStack, Error, Script, Stack};
+
+// the number of elements in main stack and alt stack together
+pub const MAX_STACK_SIZE: usize = 1000;
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+// opcodes other than pushes, and public keys of OP_CHECKMULTISIG
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+// BIP342, every signature check in tapscript consumes the budget given by the witness size
+pub const VALIDATION_WEIGHT_PER_SIGOP_PASSED: i64 = 50;
+pub const VALIDATION_WEIGHT_OFFSET: i64 = 50;
+
+// stacks used while executing a script
+#[derive(Debug, Default)]
+pub struct ExecutionContext {
+    pub stack: Stack,
+    pub alt_stack: Stack,
+    pub conditions: ConditionStack,
+    pub op_count: usize,
+    pub validation_weight_left: i64, // only for tapscript
+    pub code_separator_pos: Option<usize>, // opcode position of the last executed OP_CODESEPARATOR
+    pub script_code: Script, // the executed script after the last OP_CODESEPARATOR, signed by ECDSA
+}
+
+impl ExecutionContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn move_to_alt_stack(&mut self) -> Result<(), Error> {
+        let ele = self.stack.pop()?;
+        self.alt_stack.push(ele);
+        Ok(())
+    }
+
+    pub fn move_from_alt_stack(&mut self) -> Result<(), Error> {
+        let ele = self.alt_stack.pop().map_err(|_| Error::InvalidAltStackOperation)?;
+        self.stack.push(ele);
+        Ok(())
+    }
+
+    pub fn add_op_count(&mut self, n: usize) -> Result<(), Error> {
+        self.op_count += n;
+        if self.op_count > MAX_OPS_PER_SCRIPT {
+            return Err(Error::OpCount);
+        }
+        Ok(())
+    }
+
+    pub fn consume_validation_weight(&mut self) -> Result<(), Error> {
+        self.validation_weight_left -= VALIDATION_WEIGHT_PER_SIGOP_PASSED;
+        if self.validation_weight_left < 0 {
+            return Err(Error::TapscriptValidationWeight);
+        }
+        Ok(())
+    }
+
+    pub fn check_stack_size(&self) -> Result<(), Error> {
+        if self.stack.len() + self.alt_stack.len() > MAX_STACK_SIZE {
+            return Err(Error::StackSize);
+        }
+        Ok(())
+    }
+}
+
+impl From<Stack> for ExecutionContext {
+    fn from(stack: Stack) -> Self {
+        Self { stack, ..Self::default() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::script::{Error, Stack};
+    use super::{ExecutionContext, MAX_OPS_PER_SCRIPT, MAX_STACK_SIZE, VALIDATION_WEIGHT_PER_SIGOP_PASSED};
+
+    #[test]
+    fn execution_context_alt_stack() {
+        let mut context = ExecutionContext::from(Stack::from(vec![vec![1], vec![2]]));
+        context.move_to_alt_stack().unwrap();
+        context.move_to_alt_stack().unwrap();
+        assert!(context.stack.is_empty());
+        assert_eq!(context.alt_stack.inner(), &vec![vec![2], vec![1]]);
+        assert!(matches!(context.move_to_alt_stack(), Err(Error::EmptyStack)));
+
+        context.move_from_alt_stack().unwrap();
+        assert_eq!(context.stack.inner(), &vec![vec![1]]);
+        context.move_from_alt_stack().unwrap();
+        assert!(matches!(context.move_from_alt_stack(), Err(Error::InvalidAltStackOperation)));
+    }
+
+    #[test]
+    fn execution_context_op_count() {
+        let mut context = ExecutionContext::new();
+        context.add_op_count(MAX_OPS_PER_SCRIPT - 20).unwrap();
+        context.add_op_count(20).unwrap();
+        assert!(matches!(context.add_op_count(1), Err(Error::OpCount)));
+    }
+
+    #[test]
+    fn execution_context_validation_weight() {
+        let mut context = ExecutionContext::new();
+        context.validation_weight_left = VALIDATION_WEIGHT_PER_SIGOP_PASSED * 2;
+        context.consume_validation_weight().unwrap();
+        context.consume_validation_weight().unwrap();
+        assert!(matches!(context.consume_validation_weight(), Err(Error::TapscriptValidationWeight)));
+    }
+
+    #[test]
+    fn execution_context_stack_size() {
+        let mut context = ExecutionContext::from(Stack::from(vec![vec![]; MAX_STACK_SIZE - 1]));
+        context.alt_stack.push(vec![]);
+        assert!(context.check_stack_size().is_ok());
+        context.alt_stack.push(vec![]);
+        assert!(matches!(context.check_stack_size(), Err(Error::StackSize)));
+    }
+}
@@ -0,0 +1,562 @@
+use super::{CommandElement, ConditionStack, ExecutionContext, operator, Stack, Error, Opcode, Script, ScriptExecutionContext, SigVersion, VerifyFlags, MAX_SCRIPT_ELEMENT_SIZE, MAX_SCRIPT_SIZE};
+use std::fmt;
+
+// state of main stack, alt stack and condition stack after one command
+#[derive(Debug, Clone)]
+pub struct TraceStep {
+    pub pc: usize, // index of the command in the executed script
+    pub cmd: CommandElement,
+    pub executed: bool, // false if it is skipped in an unexecuted branch
+    pub failed: bool, // the command makes script fail, such as OP_VERIFY with false
+    pub stack: Vec<Vec<u8>>,
+    pub alt_stack: Vec<Vec<u8>>,
+    pub conditions: Vec<bool>,
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = if self.failed { "x" } else if self.executed { " " } else { "-" };
+        let format_stack = |stack: &Vec<Vec<u8>>| stack.iter().rev().map(hex::encode).collect::<Vec<_>>().join(" ");
+        write!(f, "#{:04} {} {:<24} | stack: [{}] | altstack: [{}] | conditions: {:?}",
+            self.pc, mark, self.cmd.to_string(), format_stack(&self.stack), format_stack(&self.alt_stack), self.conditions)
+    }
+}
+
+// execute a script command by command, it is an iterator of TraceStep for debugging
+pub struct Interpreter<'a> {
+    cmds: Vec<CommandElement>, // remaining commands in reversed order
+    script_len: Option<usize>, // commands of the script, the others are of script_sig not yet finished
+    pc: usize,
+    context: ExecutionContext,
+    script_context: &'a ScriptExecutionContext<'a>,
+    failed: Option<CommandElement>, // the command which makes script fail
+    redeem_script: Option<Vec<u8>>, // BIP16, the top of stack left by script_sig, run if the p2sh script succeeds
+    finished: bool,
+}
+
+impl<'a> Interpreter<'a> {
+    // @param script: it is signed as the script code until an OP_CODESEPARATOR is executed
+    pub fn new(script: &Script, script_context: &'a ScriptExecutionContext<'a>) -> Self {
+        let context = ExecutionContext { script_code: script.clone(), ..ExecutionContext::new() };
+        Self {
+            cmds: script.cmds().clone(),
+            script_len: None,
+            pc: 0,
+            context,
+            script_context,
+            failed: None,
+            redeem_script: None,
+            finished: false,
+        }
+    }
+
+    // the script_sig is executed before the script as a separate script, only the stack is passed on
+    // it is not signed
+    pub fn with_script_sig(mut self, script_sig: &Script) -> Self {
+        if !script_sig.is_empty() {
+            self.script_len = Some(self.cmds.len());
+            self.cmds.extend(script_sig.cmds().iter().cloned());
+        }
+        self
+    }
+
+    // witness elements which are not part of the script, such as the stack of tapscript
+    pub fn with_stack(mut self, stack: Stack) -> Self {
+        self.context.stack = stack;
+        self
+    }
+
+    // BIP342, the budget of signature checks
+    pub fn with_validation_weight(mut self, weight: i64) -> Self {
+        self.context.validation_weight_left = weight;
+        self
+    }
+
+    pub fn stack(&self) -> &Stack {
+        &self.context.stack
+    }
+
+    pub fn alt_stack(&self) -> &Stack {
+        &self.context.alt_stack
+    }
+
+    pub fn conditions(&self) -> &ConditionStack {
+        &self.context.conditions
+    }
+
+    // the next command to be executed
+    pub fn peek(&self) -> Option<&CommandElement> {
+        if self.finished {
+            return None;
+        }
+        self.cmds.last()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // execute the next command
+    pub fn step(&mut self) -> Result<Option<TraceStep>, Error> {
+        if self.finished {
+            return Ok(None);
+        }
+        if self.pc == 0 {
+            let start = self.script_len.unwrap_or(0);
+            if let Err(e) = self.check_script_size(&self.cmds[start..]) {
+                self.finished = true;
+                return Err(e);
+            }
+        }
+        if let Err(e) = self.finish_script_sig().and_then(|_| self.start_redeem_script()) {
+            self.finished = true;
+            return Err(e);
+        }
+        let cmd = match self.cmds.pop() {
+            Some(cmd) => cmd,
+            None => {
+                self.finished = true;
+                return Ok(None);
+            },
+        };
+        let pc = self.pc;
+        self.pc += 1;
+
+        let result = self.execute(cmd.clone()).and_then(|result| {
+            self.context.check_stack_size()?;
+            Ok(result)
+        });
+        match result {
+            Ok((executed, success)) => {
+                if !success {
+                    self.failed = Some(cmd.clone());
+                    self.finished = true;
+                }
+                Ok(Some(self.trace(pc, cmd, executed, !success)))
+            },
+            Err(e) => {
+                self.finished = true;
+                Err(e)
+            },
+        }
+    }
+
+    // @return (executed, success)
+    fn execute(&mut self, cmd: CommandElement) -> Result<(bool, bool), Error> {
+        // limits are checked even in an unexecuted branch
+        if let Some(data) = cmd.data() {
+            if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                return Err(Error::PushSize);
+            }
+        }
+        let sig_version = self.script_context.sig_version;
+        let flags = self.script_context.flags;
+        if let CommandElement::Op(op) = cmd {
+            if op.value() > Opcode::Op16.value() && sig_version != SigVersion::Tapscript {
+                self.context.add_op_count(1)?;
+            }
+            match op {
+                Opcode::OpIf | Opcode::OpNotif | Opcode::OpElse | Opcode::OpEndif => {
+                    let context = &mut self.context;
+                    operator::evaluate_flow_control(op, &mut context.stack, &mut context.conditions, sig_version, flags)?;
+                    return Ok((true, true));
+                },
+                // fail even if in an unexecuted branch
+                Opcode::OpVerif | Opcode::OpVernotif => return Err(Error::BadOpcode(op)),
+                _ if op.is_disabled() => return Err(Error::DisabledOpcode(op)),
+                Opcode::OpCodeseparator if sig_version == SigVersion::Base && flags.contains(VerifyFlags::CONST_SCRIPTCODE) => {
+                    return Err(Error::OpCodeSeparator);
+                },
+                _ => {},
+            }
+        }
+        if !self.context.conditions.all_true() {
+            return Ok((false, true));
+        }
+
+        // the remaining commands are signed from now on
+        if cmd == CommandElement::Op(Opcode::OpCodeseparator) {
+            self.context.code_separator_pos = Some(self.pc - 1);
+            self.context.script_code = Script::new(self.cmds.clone());
+        }
+        if !operator::evaluate_command(cmd, &mut self.context, self.script_context)? {
+            return Ok((true, false));
+        }
+
+        Ok((true, true))
+    }
+
+    // tapscript has no limit of script size
+    fn check_script_size(&self, cmds: &[CommandElement]) -> Result<(), Error> {
+        if self.script_context.sig_version != SigVersion::Tapscript && Script::new(cmds.to_vec()).size() > MAX_SCRIPT_SIZE {
+            return Err(Error::ScriptSize);
+        }
+        Ok(())
+    }
+
+    // a branch can not span script_sig and script, and the alt stack and op count start over
+    fn finish_script_sig(&mut self) -> Result<(), Error> {
+        if self.script_len != Some(self.cmds.len()) {
+            return Ok(());
+        }
+        self.script_len = None;
+        if !self.context.conditions.is_empty() {
+            return Err(Error::UnbalancedConditional);
+        }
+        self.context.alt_stack = Stack::new();
+        self.context.op_count = 0;
+        // BIP16 applies once, only if the whole script is the p2sh template
+        let flags = self.script_context.flags;
+        if flags.contains(VerifyFlags::P2SH) && self.script_context.sig_version == SigVersion::Base && Script::get_hash160_if_p2sh(&self.cmds).is_some() {
+            self.redeem_script = self.context.stack.inner().last().cloned();
+        }
+        self.check_script_size(&self.cmds)
+    }
+
+    // the redeem script runs on the stack left by script_sig, a false p2sh script is left as the result
+    fn start_redeem_script(&mut self) -> Result<(), Error> {
+        if !self.cmds.is_empty() || !self.context.stack.inner().last().is_some_and(|ele| operator::cast_to_bool(ele)) {
+            return Ok(());
+        }
+        let redeem_script = match self.redeem_script.take() {
+            Some(redeem_script) => Script::parse_raw(&redeem_script)?,
+            None => return Ok(()),
+        };
+        self.context.stack.pop()?;
+        self.context.alt_stack = Stack::new();
+        self.context.op_count = 0;
+        self.cmds = redeem_script.cmds().clone();
+        self.context.script_code = redeem_script;
+        Ok(())
+    }
+
+    fn trace(&self, pc: usize, cmd: CommandElement, executed: bool, failed: bool) -> TraceStep {
+        TraceStep {
+            pc,
+            cmd,
+            executed,
+            failed,
+            stack: self.context.stack.inner().clone(),
+            alt_stack: self.context.alt_stack.inner().clone(),
+            conditions: self.context.conditions.inner().clone(),
+        }
+    }
+
+    // execute the remaining commands and check the final stack
+    pub fn finish(mut self) -> Result<bool, Error> {
+        while self.step()?.is_some() {}
+        // a *VERIFY opcode fails with its own error, the others leave false as the result
+        if let Some(cmd) = self.failed {
+            return match cmd {
+                CommandElement::Op(op) => operator::verify_error(op).map_or(Ok(false), Err),
+                _ => Ok(false),
+            };
+        }
+        if !self.context.conditions.is_empty() {
+            return Err(Error::UnbalancedConditional);
+        }
+
+        let stack = &self.context.stack;
+        // witness script always requires exactly one element left
+        if self.script_context.sig_version != SigVersion::Base && stack.len() != 1 {
+            return Err(Error::CleanStack);
+        }
+        let result = match stack.inner().last() {
+            Some(ele) => operator::cast_to_bool(ele),
+            None => false,
+        };
+        if result && self.script_context.flags.contains(VerifyFlags::CLEANSTACK) && stack.len() != 1 {
+            return Err(Error::CleanStack);
+        }
+
+        Ok(result)
+    }
+}
+
+impl<'a> Iterator for Interpreter<'a> {
+    type Item = Result<TraceStep, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::script::{CommandElement, Error, Opcode, Script, ScriptExecutionContext, SigVersion, SignatureCheckerMocker, VerifyFlags};
+    use primitive_types::U256;
+    use super::Interpreter;
+
+    fn mocker() -> ScriptExecutionContext<'static> {
+        ScriptExecutionContext::new(SigVersion::Base, VerifyFlags::consensus())
+            .with_checker(Box::new(SignatureCheckerMocker(U256::zero())))
+    }
+
+    #[test]
+    fn interpreter_trace_every_step() {
+        let script = Script::from_asm("2 3 OP_ADD 5 OP_EQUAL").unwrap();
+        let context = mocker();
+        let steps: Vec<_> = Interpreter::new(&script, &context)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(steps.len(), 5);
+        assert_eq!(steps[2].pc, 2);
+        assert_eq!(steps[2].cmd, CommandElement::Op(Opcode::OpAdd));
+        assert_eq!(steps[2].stack, vec![vec![5]]);
+        assert_eq!(steps[4].stack, vec![vec![1]]);
+        assert!(steps.iter().all(|step| step.executed && !step.failed));
+    }
+
+    #[test]
+    fn interpreter_step_through() {
+        let script = Script::from_asm("1 OP_IF 2 OP_ELSE 3 OP_ENDIF").unwrap();
+        let context = mocker();
+        let mut interpreter = Interpreter::new(&script, &context);
+
+        assert_eq!(interpreter.peek(), Some(&CommandElement::Op(Opcode::Op1)));
+        interpreter.step().unwrap();
+        let step = interpreter.step().unwrap().unwrap();
+        assert_eq!(step.conditions, vec![true]);
+        assert!(interpreter.step().unwrap().unwrap().executed); // 2
+        interpreter.step().unwrap(); // OP_ELSE
+        let step = interpreter.step().unwrap().unwrap(); // 3
+        assert!(!step.executed);
+        assert_eq!(step.conditions, vec![false]);
+        assert_eq!(interpreter.stack().inner(), &vec![vec![2]]);
+
+        assert!(interpreter.finish().unwrap());
+    }
+
+    #[test]
+    fn interpreter_show_failed_command() {
+        let script = Script::from_asm("1 2 OP_EQUALVERIFY 1").unwrap();
+        let context = mocker();
+        let mut interpreter = Interpreter::new(&script, &context);
+        let steps: Vec<_> = interpreter.by_ref().collect::<Result<_, _>>().unwrap();
+
+        let last = steps.last().unwrap();
+        assert!(last.failed);
+        assert_eq!(last.cmd, CommandElement::Op(Opcode::OpEqualverify));
+        assert_eq!(steps.len(), 3);
+        assert!(interpreter.is_finished());
+        assert!(matches!(interpreter.finish(), Err(Error::EqualVerify)));
+    }
+
+    #[test]
+    fn interpreter_verify_errors() {
+        let context = mocker();
+        let finish = |asm: &str| Interpreter::new(&Script::from_asm(asm).unwrap(), &context).finish();
+        assert!(matches!(finish("0 OP_VERIFY 1"), Err(Error::Verify)));
+        assert!(matches!(finish("1 2 OP_NUMEQUALVERIFY 1"), Err(Error::NumEqualVerify)));
+        assert!(matches!(finish("0 0 OP_CHECKSIGVERIFY 1"), Err(Error::CheckSigVerify)));
+        // OP_EQUAL leaves false as the result
+        assert!(!finish("1 2 OP_EQUAL").unwrap());
+    }
+
+    #[test]
+    fn interpreter_stop_at_error() {
+        let script = Script::from_asm("1 OP_RETURN 1").unwrap();
+        let context = mocker();
+        let mut interpreter = Interpreter::new(&script, &context);
+
+        assert!(interpreter.next().unwrap().is_ok());
+        assert!(matches!(interpreter.next(), Some(Err(Error::OpReturn))));
+        assert!(interpreter.next().is_none());
+    }
+
+    #[test]
+    fn interpreter_alt_stack() {
+        let script = Script::from_asm("1 2 OP_TOALTSTACK 3 OP_FROMALTSTACK OP_ADD 5 OP_EQUAL").unwrap();
+        let context = mocker();
+        let steps: Vec<_> = Interpreter::new(&script, &context)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(steps[2].stack, vec![vec![1]]);
+        assert_eq!(steps[2].alt_stack, vec![vec![2]]);
+        assert_eq!(steps[4].stack, vec![vec![1], vec![3], vec![2]]);
+        assert!(steps[4].alt_stack.is_empty());
+
+        let context = mocker();
+        assert!(script.evaluate(&context).unwrap());
+        let script = Script::from_asm("1 OP_FROMALTSTACK").unwrap();
+        assert!(matches!(script.evaluate(&context), Err(Error::InvalidAltStackOperation)));
+    }
+
+    #[test]
+    fn interpreter_script_sig_is_separate() {
+        let context = mocker();
+        let run = |script_sig: &str, script: &str| {
+            let script_sig = Script::from_asm(script_sig).unwrap();
+            let script = Script::from_asm(script).unwrap();
+            Interpreter::new(&script, &context).with_script_sig(&script_sig).finish()
+        };
+
+        assert!(run("1 1", "OP_IF 1 OP_ENDIF").unwrap());
+        // a branch can not be opened in script_sig and closed in script
+        assert!(matches!(run("1 OP_IF", "1 OP_ENDIF"), Err(Error::UnbalancedConditional)));
+        assert!(matches!(run("0 OP_IF", "OP_ENDIF 1"), Err(Error::UnbalancedConditional)));
+        // the alt stack is not passed on
+        assert!(matches!(run("1 OP_TOALTSTACK", "OP_FROMALTSTACK"), Err(Error::InvalidAltStackOperation)));
+    }
+
+    #[test]
+    fn interpreter_final_stack() {
+        let run = |asm: &str, sig_version: SigVersion, flags: VerifyFlags| {
+            let context = ScriptExecutionContext::new(sig_version, flags).with_checker(Box::new(SignatureCheckerMocker(U256::zero())));
+            Script::from_asm(asm).unwrap().evaluate(&context)
+        };
+
+        // an empty stack is false rather than an error
+        assert!(!run("", SigVersion::Base, VerifyFlags::consensus()).unwrap());
+        assert!(run("1 1", SigVersion::Base, VerifyFlags::consensus()).unwrap());
+        assert!(matches!(run("1 1", SigVersion::Base, VerifyFlags::standard()), Err(Error::CleanStack)));
+        assert!(!run("1 0", SigVersion::Base, VerifyFlags::standard()).unwrap());
+        // witness script requires exactly one element whatever it is
+        assert!(matches!(run("1 0", SigVersion::WitnessV0, VerifyFlags::consensus()), Err(Error::CleanStack)));
+        assert!(matches!(run("", SigVersion::WitnessV0, VerifyFlags::consensus()), Err(Error::CleanStack)));
+        assert!(!run("0", SigVersion::WitnessV0, VerifyFlags::consensus()).unwrap());
+    }
+
+    #[test]
+    fn interpreter_p2sh_after_script_sig() {
+        use crate::util::hash;
+
+        // the remaining commands look like p2sh in the middle of script_sig, but script is only OP_EQUAL
+        let redeem = vec![0x00];
+        let script_sig = Script::new(vec![hash::hash160(&redeem).to_vec().into(), Opcode::OpHash160.into(), redeem.into()]);
+        let script = Script::from_asm("OP_EQUAL").unwrap();
+        let context = mocker();
+        assert!(Interpreter::new(&script, &context).with_script_sig(&script_sig).finish().unwrap());
+
+        // the remaining commands look like p2sh after OP_DROP, but the whole script is not
+        let script = Script::new(vec![Opcode::OpEqual.into(), hash::hash160(&[0x00]).to_vec().into(), Opcode::OpHash160.into(), Opcode::OpDrop.into(), Opcode::Op1.into()]);
+        let script_sig = Script::new(vec![vec![0x00].into()]);
+        assert!(Interpreter::new(&script, &context).with_script_sig(&script_sig).finish().unwrap());
+    }
+
+    #[test]
+    fn interpreter_stack_size_limit() {
+        use crate::script::MAX_STACK_SIZE;
+
+        // pushes are not limited by op count
+        let asm = vec!["1"; MAX_STACK_SIZE].join(" ");
+        let script = Script::from_asm(&asm).unwrap();
+        let context = mocker();
+        assert!(script.evaluate(&context).unwrap());
+        let script = Script::from_asm(&format!("{} 1", asm)).unwrap();
+        assert!(matches!(script.evaluate(&context), Err(Error::StackSize)));
+
+        // alt stack is counted too
+        let script = Script::from_asm(&format!("{} OP_TOALTSTACK OP_DUP OP_DUP", asm)).unwrap();
+        assert!(matches!(script.evaluate(&context), Err(Error::StackSize)));
+    }
+
+    #[test]
+    fn interpreter_script_size_limit() {
+        use crate::script::MAX_SCRIPT_SIZE;
+
+        let context = mocker();
+        let script = Script::new(vec![vec![0u8; 500].into(); MAX_SCRIPT_SIZE / 503]);
+        assert!(script.size() <= MAX_SCRIPT_SIZE);
+        assert!(script.evaluate(&context).is_ok());
+        let script = Script::new(vec![vec![0u8; 500].into(); MAX_SCRIPT_SIZE / 503 + 1]);
+        assert!(matches!(script.evaluate(&context), Err(Error::ScriptSize)));
+
+        // script_sig and script are limited separately
+        let half = Script::new(vec![vec![0u8; 500].into(); MAX_SCRIPT_SIZE / 503]);
+        let script = Script::new([vec![Opcode::Op1.into()], half.cmds().clone()].concat());
+        assert!(Interpreter::new(&script, &context).with_script_sig(&half).finish().unwrap());
+        let script_sig = Script::new(vec![vec![0u8; 500].into(); MAX_SCRIPT_SIZE / 503 + 1]);
+        assert!(matches!(Interpreter::new(&script, &context).with_script_sig(&script_sig).finish(), Err(Error::ScriptSize)));
+    }
+
+    #[test]
+    fn interpreter_push_size_limit() {
+        use crate::script::MAX_SCRIPT_ELEMENT_SIZE;
+
+        let context = mocker();
+        let script = Script::new(vec![vec![1u8; MAX_SCRIPT_ELEMENT_SIZE].into()]);
+        assert!(script.evaluate(&context).unwrap());
+        // OP_0 OP_IF <521 bytes> OP_ENDIF OP_1
+        let script = Script::new(vec![
+            Opcode::Op1.into(),
+            Opcode::OpEndif.into(),
+            vec![1u8; MAX_SCRIPT_ELEMENT_SIZE + 1].into(),
+            Opcode::OpIf.into(),
+            Opcode::Op0.into(),
+        ]);
+        assert!(matches!(script.evaluate(&context), Err(Error::PushSize)));
+    }
+
+    #[test]
+    fn interpreter_op_count_limit() {
+        use crate::script::MAX_OPS_PER_SCRIPT;
+
+        let context = mocker();
+        // push opcodes are not counted
+        let asm = format!("{} 1", vec!["1"; MAX_OPS_PER_SCRIPT + 1].join(" "));
+        assert!(Script::from_asm(&asm).unwrap().evaluate(&context).is_ok());
+        // OP_NOP is counted even in an unexecuted branch, so are OP_IF and OP_ENDIF
+        let asm = format!("0 OP_IF {} OP_ENDIF 1", vec!["OP_NOP"; MAX_OPS_PER_SCRIPT - 2].join(" "));
+        assert!(Script::from_asm(&asm).unwrap().evaluate(&context).unwrap());
+        let asm = format!("0 OP_IF {} OP_ENDIF 1", vec!["OP_NOP"; MAX_OPS_PER_SCRIPT - 1].join(" "));
+        assert!(matches!(Script::from_asm(&asm).unwrap().evaluate(&context), Err(Error::OpCount)));
+        // public keys of OP_CHECKMULTISIG are counted
+        let multisig = format!("0 0 {} 20 OP_CHECKMULTISIG", vec!["1"; 20].join(" "));
+        let asm = format!("{} {}", multisig, vec!["OP_NOP"; MAX_OPS_PER_SCRIPT - 21].join(" "));
+        assert!(Script::from_asm(&asm).unwrap().evaluate(&context).unwrap());
+        let asm = format!("{} {}", multisig, vec!["OP_NOP"; MAX_OPS_PER_SCRIPT - 20].join(" "));
+        assert!(matches!(Script::from_asm(&asm).unwrap().evaluate(&context), Err(Error::OpCount)));
+    }
+
+    #[test]
+    fn interpreter_number_size_limit() {
+        let context = mocker();
+        // 2147483647 OP_1ADD is 5 bytes, it cannot be used as operand any more
+        let script = Script::from_asm("2147483647 OP_1ADD <0000008000> OP_EQUAL").unwrap();
+        assert!(script.evaluate(&context).unwrap());
+        let script = Script::from_asm("2147483647 OP_1ADD OP_1ADD").unwrap();
+        assert!(matches!(script.evaluate(&context), Err(Error::NumSize)));
+    }
+
+    #[test]
+    fn interpreter_display_step() {
+        let script = Script::from_asm("0 OP_IF 1 OP_ENDIF").unwrap();
+        let context = mocker();
+        let steps: Vec<_> = Interpreter::new(&script, &context)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(steps[2].to_string(), "#0002 - 1                        | stack: [] | altstack: [] | conditions: [false]");
+    }
+
+    // <pk> OP_CHECKSIGVERIFY OP_CODESEPARATOR <pk> OP_CHECKSIG, signed by the real legacy signature hash
+    #[test]
+    fn interpreter_code_separator_script_code() {
+        use crate::secp256k1::PrivateKey;
+        use crate::transaction::{SigHash, Transaction};
+
+        let bytes = hex::decode("0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600").unwrap();
+        let tx = Transaction::parse(&bytes).unwrap();
+        let key = PrivateKey::new(7u32.into()).unwrap();
+        let pk = hex::encode(key.pk_point().sec_compressed().unwrap());
+        let script = Script::from_asm(&format!("<{}> OP_CHECKSIGVERIFY OP_CODESEPARATOR <{}> OP_CHECKSIG", pk, pk)).unwrap();
+        let after_separator = Script::from_asm(&format!("<{}> OP_CHECKSIG", pk)).unwrap();
+
+        let context = ScriptExecutionContext::for_input(&tx, 0, SigVersion::Base, VerifyFlags::consensus());
+        let sign = |script_code: &Script| {
+            let z = context.signature_hash(script_code, SigHash::All.into()).unwrap();
+            let sig = hex::decode(key.sign_deterministic(U256::from_big_endian(&z)).unwrap().der()).unwrap();
+            [sig, vec![SigHash::All.value()]].concat()
+        };
+        let verify = |first: Vec<u8>, second: Vec<u8>| {
+            let script_sig = Script::new(vec![first.into(), second.into()]);
+            Interpreter::new(&script, &context).with_script_sig(&script_sig).finish()
+        };
+
+        // the first signature is checked before OP_CODESEPARATOR
+        assert!(verify(sign(&script), sign(&after_separator)).unwrap());
+        assert!(!verify(sign(&script), sign(&script)).unwrap());
+        assert!(matches!(verify(sign(&after_separator), sign(&after_separator)), Err(Error::CheckSigVerify)));
+    }
+}
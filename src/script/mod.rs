@@ -1,11 +1,19 @@
-pub use script::Script;
+pub use script::{Script, MAX_PUBKEYS_PER_MULTISIG};
 pub use stack::Stack;
 pub use opcode::Opcode;
 pub use cmd_element::CommandElement;
-pub use num::Num;
+pub use num::{Num, MAX_NUM_SIZE};
 pub use error::Error;
-pub use builder::ScriptBuilder;
-pub use z_provider::{ZProvider, ZProviderMocker, TransactionLegacyZProvider, TransactionWitnessP2pkhZProvider};
+pub use builder::{HashLock, ScriptBuilder};
+pub use sig_version::SigVersion;
+pub use condition_stack::ConditionStack;
+pub use verify_flags::VerifyFlags;
+pub use interpreter::{Interpreter, TraceStep};
+pub use script_type::ScriptType;
+pub use execution_context::{ExecutionContext, MAX_STACK_SIZE, MAX_SCRIPT_SIZE, MAX_SCRIPT_ELEMENT_SIZE, MAX_OPS_PER_SCRIPT, VALIDATION_WEIGHT_PER_SIGOP_PASSED, VALIDATION_WEIGHT_OFFSET};
+pub use script_execution_context::ScriptExecutionContext;
+pub use signature_checker::{SignatureChecker, SignatureCheckerMocker, TransactionSignatureChecker};
+pub use taproot::{ControlBlock, TAPROOT_LEAF_TAPSCRIPT, ANNEX_TAG};
 
 mod cmd_element;
 mod opcode;
@@ -15,4 +23,14 @@ mod num;
 mod error;
 mod stack;
 mod builder;
-mod z_provider;
+mod script_execution_context;
+mod signature_checker;
+mod sig_version;
+mod condition_stack;
+mod verify_flags;
+mod interpreter;
+mod execution_context;
+mod script_type;
+pub mod taproot;
+#[cfg(test)]
+mod test_vectors;
@@ -2,6 +2,9 @@ use std::convert::From;
 use super::Error;
 use std::ops::Add;
 
+// operands of arithmetic opcodes are limited to 4 bytes, but results may overflow it
+pub const MAX_NUM_SIZE: usize = 4;
+
 pub struct Num(i64);
 
 impl From<i64> for Num {
@@ -16,8 +19,8 @@ impl Num {
         if self.0 == 0 {
             return result;
         }
-        // value
-        let mut abs = self.0.abs();
+        // value, i64::MIN has no positive counterpart
+        let mut abs = self.0.unsigned_abs();
         while abs > 0 {
             result.push(abs as u8 & 0xff);
             abs >>= 8;
@@ -41,6 +44,9 @@ impl Num {
         if bytes.len() == 0 {
             return Ok(Self(0));
         }
+        if bytes.len() > 8 {
+            return Err(Error::NumDecodeOverflow);
+        }
         // value
         bytes.reverse();
         let negative = bytes[0] & 0x80 > 0;
@@ -66,6 +72,32 @@ impl Num {
         Ok(Self(result))
     }
 
+    pub fn decode_with_max_size(bytes: Vec<u8>, max_size: usize) -> Result<Self, Error> {
+        if bytes.len() > max_size {
+            return Err(Error::NumSize);
+        }
+        Self::decode(bytes)
+    }
+
+    // CScriptNum of bitcoin core, MINIMALDATA requires the minimal encoding
+    pub fn decode_script_num(bytes: Vec<u8>, max_size: usize, require_minimal: bool) -> Result<Self, Error> {
+        if require_minimal && !Self::is_minimally_encoded(&bytes) {
+            return Err(Error::NumNotMinimal);
+        }
+        Self::decode_with_max_size(bytes, max_size)
+    }
+
+    // no zero padding on the most significant byte, unless it is for the sign bit
+    // negative zero (0x80) is not minimal either
+    pub fn is_minimally_encoded(bytes: &[u8]) -> bool {
+        match bytes {
+            [] => true,
+            [.., last] if last & 0x7f != 0 => true,
+            [_] => false,
+            [.., second_last, _] => second_last & 0x80 != 0,
+        }
+    }
+
     pub fn value(&self) -> i64 {
         self.0
     }
@@ -81,7 +113,8 @@ impl Add for Num {
 
 #[cfg(test)]
 mod tests {
-    use super::Num;
+    use super::{Num, MAX_NUM_SIZE};
+    use crate::script::Error;
 
     #[test]
     fn script_num_encode_0() {
@@ -99,6 +132,14 @@ mod tests {
         assert_eq!(num.encode(), bytes);
     }
 
+    #[test]
+    fn script_num_decode_with_max_size() {
+        let num = Num::decode_with_max_size(vec![0xff, 0xff, 0xff, 0xff], MAX_NUM_SIZE).unwrap();
+        assert_eq!(num.value(), -0x7fffffff);
+        let result = Num::decode_with_max_size(vec![0xff, 0xff, 0xff, 0x7f, 0], MAX_NUM_SIZE);
+        assert!(matches!(result, Err(Error::NumSize)));
+    }
+
     #[test]
     fn script_num_encode_2() {
         let num = Num::from(999i64);
@@ -106,4 +147,30 @@ mod tests {
         assert_eq!(num.value(), 999);
         assert_eq!(num.encode(), bytes);
     }
+
+    #[test]
+    fn script_num_encode_min() {
+        assert_eq!(Num::from(i64::MIN).encode(), vec![0, 0, 0, 0, 0, 0, 0, 0x80, 0x80]);
+        assert_eq!(Num::from(-0x80).encode(), vec![0x80, 0x80]);
+        assert_eq!(Num::from(0xff).encode(), vec![0xff, 0]);
+        assert!(matches!(Num::decode(vec![1; 9]), Err(Error::NumDecodeOverflow)));
+    }
+
+    #[test]
+    fn script_num_minimally_encoded() {
+        assert!(Num::is_minimally_encoded(&[]));
+        assert!(Num::is_minimally_encoded(&[0x01]));
+        assert!(Num::is_minimally_encoded(&[0xff, 0x00]));
+        assert!(Num::is_minimally_encoded(&[0xff, 0x80]));
+        assert!(!Num::is_minimally_encoded(&[0x00]));
+        assert!(!Num::is_minimally_encoded(&[0x80]));
+        assert!(!Num::is_minimally_encoded(&[0x01, 0x00]));
+        assert!(!Num::is_minimally_encoded(&[0x01, 0x80]));
+
+        for i in -1000i64..1000 {
+            assert!(Num::is_minimally_encoded(&Num::from(i).encode()));
+        }
+        assert!(matches!(Num::decode_script_num(vec![0x01, 0x00], MAX_NUM_SIZE, true), Err(Error::NumNotMinimal)));
+        assert_eq!(Num::decode_script_num(vec![0x01, 0x00], MAX_NUM_SIZE, false).unwrap().value(), 1);
+    }
 }
@@ -3,6 +3,8 @@ use std::fmt;
 #[derive(Copy, Clone, PartialEq)]
 pub enum Opcode {
     Op0 = 0x00,
+    Op1negate = 0x4f,
+    OpReserved = 0x50,
     Op1 = 0x51,
     Op2 = 0x52,
     Op3 = 0x53,
@@ -19,22 +21,104 @@ pub enum Opcode {
     Op14 = 0x5e,
     Op15 = 0x5f,
     Op16 = 0x60,
+    OpNop = 0x61,
+    OpVer = 0x62,
+    OpIf = 0x63,
+    OpNotif = 0x64,
+    OpVerif = 0x65,
+    OpVernotif = 0x66,
+    OpElse = 0x67,
+    OpEndif = 0x68,
     OpVerify = 0x69,
+    OpReturn = 0x6a,
+    OpToaltstack = 0x6b,
+    OpFromaltstack = 0x6c,
+    Op2drop = 0x6d,
+    Op2dup = 0x6e,
+    Op3dup = 0x6f,
+    Op2over = 0x70,
+    Op2rot = 0x71,
+    Op2swap = 0x72,
+    OpIfdup = 0x73,
+    OpDepth = 0x74,
+    OpDrop = 0x75,
     OpDup = 0x76,
+    OpNip = 0x77,
+    OpOver = 0x78,
+    OpPick = 0x79,
+    OpRoll = 0x7a,
+    OpRot = 0x7b,
+    OpSwap = 0x7c,
+    OpTuck = 0x7d,
+    OpCat = 0x7e,
+    OpSubstr = 0x7f,
+    OpLeft = 0x80,
+    OpRight = 0x81,
+    OpSize = 0x82,
+    OpInvert = 0x83,
+    OpAnd = 0x84,
+    OpOr = 0x85,
+    OpXor = 0x86,
     OpEqual = 0x87,
     OpEqualverify = 0x88,
+    OpReserved1 = 0x89,
+    OpReserved2 = 0x8a,
+    Op1add = 0x8b,
+    Op1sub = 0x8c,
+    Op2mul = 0x8d,
+    Op2div = 0x8e,
+    OpNegate = 0x8f,
+    OpAbs = 0x90,
+    OpNot = 0x91,
+    Op0notequal = 0x92,
     OpAdd = 0x93,
+    OpSub = 0x94,
+    OpMul = 0x95,
+    OpDiv = 0x96,
+    OpMod = 0x97,
+    OpLshift = 0x98,
+    OpRshift = 0x99,
+    OpBooland = 0x9a,
+    OpBoolor = 0x9b,
+    OpNumequal = 0x9c,
+    OpNumequalverify = 0x9d,
+    OpNumnotequal = 0x9e,
+    OpLessthan = 0x9f,
+    OpGreaterthan = 0xa0,
+    OpLessthanorequal = 0xa1,
+    OpGreaterthanorequal = 0xa2,
+    OpMin = 0xa3,
+    OpMax = 0xa4,
+    OpWithin = 0xa5,
+    OpRipemd160 = 0xa6,
+    OpSha1 = 0xa7,
+    OpSha256 = 0xa8,
     OpHash160 = 0xa9,
+    OpHash256 = 0xaa,
     OpCodeseparator = 0xab,
     OpChecksig = 0xac,
     OpChecksigverify = 0xad,
     OpCheckmultisig = 0xae,
+    OpCheckmultisigverify = 0xaf,
+    OpNop1 = 0xb0,
+    OpChecklocktimeverify = 0xb1,
+    OpChecksequenceverify = 0xb2,
+    OpNop4 = 0xb3,
+    OpNop5 = 0xb4,
+    OpNop6 = 0xb5,
+    OpNop7 = 0xb6,
+    OpNop8 = 0xb7,
+    OpNop9 = 0xb8,
+    OpNop10 = 0xb9,
+    OpChecksigadd = 0xba, // BIP342, only in tapscript
 }
 
 impl Opcode {
     pub fn from_u8(u: u8) -> Option<Self> {
         match u {
             0x00 => Some(Self::Op0),
+            0x4f => Some(Self::Op1negate),
+            0x50 => Some(Self::OpReserved),
             0x51 => Some(Self::Op1),
             0x52 => Some(Self::Op2),
             0x53 => Some(Self::Op3),
@@ -51,16 +135,96 @@ impl Opcode {
             0x5e => Some(Self::Op14),
             0x5f => Some(Self::Op15),
             0x60 => Some(Self::Op16),
+            0x61 => Some(Self::OpNop),
+            0x62 => Some(Self::OpVer),
+            0x63 => Some(Self::OpIf),
+            0x64 => Some(Self::OpNotif),
+            0x65 => Some(Self::OpVerif),
+            0x66 => Some(Self::OpVernotif),
+            0x67 => Some(Self::OpElse),
+            0x68 => Some(Self::OpEndif),
             0x69 => Some(Self::OpVerify),
+            0x6a => Some(Self::OpReturn),
+            0x6b => Some(Self::OpToaltstack),
+            0x6c => Some(Self::OpFromaltstack),
+            0x6d => Some(Self::Op2drop),
+            0x6e => Some(Self::Op2dup),
+            0x6f => Some(Self::Op3dup),
+            0x70 => Some(Self::Op2over),
+            0x71 => Some(Self::Op2rot),
+            0x72 => Some(Self::Op2swap),
+            0x73 => Some(Self::OpIfdup),
+            0x74 => Some(Self::OpDepth),
+            0x75 => Some(Self::OpDrop),
             0x76 => Some(Self::OpDup),
+            0x77 => Some(Self::OpNip),
+            0x78 => Some(Self::OpOver),
+            0x79 => Some(Self::OpPick),
+            0x7a => Some(Self::OpRoll),
+            0x7b => Some(Self::OpRot),
+            0x7c => Some(Self::OpSwap),
+            0x7d => Some(Self::OpTuck),
+            0x7e => Some(Self::OpCat),
+            0x7f => Some(Self::OpSubstr),
+            0x80 => Some(Self::OpLeft),
+            0x81 => Some(Self::OpRight),
+            0x82 => Some(Self::OpSize),
+            0x83 => Some(Self::OpInvert),
+            0x84 => Some(Self::OpAnd),
+            0x85 => Some(Self::OpOr),
+            0x86 => Some(Self::OpXor),
             0x87 => Some(Self::OpEqual),
             0x88 => Some(Self::OpEqualverify),
+            0x89 => Some(Self::OpReserved1),
+            0x8a => Some(Self::OpReserved2),
+            0x8b => Some(Self::Op1add),
+            0x8c => Some(Self::Op1sub),
+            0x8d => Some(Self::Op2mul),
+            0x8e => Some(Self::Op2div),
+            0x8f => Some(Self::OpNegate),
+            0x90 => Some(Self::OpAbs),
+            0x91 => Some(Self::OpNot),
+            0x92 => Some(Self::Op0notequal),
             0x93 => Some(Self::OpAdd),
+            0x94 => Some(Self::OpSub),
+            0x95 => Some(Self::OpMul),
+            0x96 => Some(Self::OpDiv),
+            0x97 => Some(Self::OpMod),
+            0x98 => Some(Self::OpLshift),
+            0x99 => Some(Self::OpRshift),
+            0x9a => Some(Self::OpBooland),
+            0x9b => Some(Self::OpBoolor),
+            0x9c => Some(Self::OpNumequal),
+            0x9d => Some(Self::OpNumequalverify),
+            0x9e => Some(Self::OpNumnotequal),
+            0x9f => Some(Self::OpLessthan),
+            0xa0 => Some(Self::OpGreaterthan),
+            0xa1 => Some(Self::OpLessthanorequal),
+            0xa2 => Some(Self::OpGreaterthanorequal),
+            0xa3 => Some(Self::OpMin),
+            0xa4 => Some(Self::OpMax),
+            0xa5 => Some(Self::OpWithin),
+            0xa6 => Some(Self::OpRipemd160),
+            0xa7 => Some(Self::OpSha1),
+            0xa8 => Some(Self::OpSha256),
             0xa9 => Some(Self::OpHash160),
+            0xaa => Some(Self::OpHash256),
             0xab => Some(Self::OpCodeseparator),
             0xac => Some(Self::OpChecksig),
             0xad => Some(Self::OpChecksigverify),
             0xae => Some(Self::OpCheckmultisig),
+            0xaf => Some(Self::OpCheckmultisigverify),
+            0xb0 => Some(Self::OpNop1),
+            0xb1 => Some(Self::OpChecklocktimeverify),
+            0xb2 => Some(Self::OpChecksequenceverify),
+            0xb3 => Some(Self::OpNop4),
+            0xb4 => Some(Self::OpNop5),
+            0xb5 => Some(Self::OpNop6),
+            0xb6 => Some(Self::OpNop7),
+            0xb7 => Some(Self::OpNop8),
+            0xb8 => Some(Self::OpNop9),
+            0xb9 => Some(Self::OpNop10),
+            0xba => Some(Self::OpChecksigadd),
             _ => None
         }
     }
@@ -68,12 +232,174 @@ impl Opcode {
     pub fn value(&self) -> u8 {
         *self as u8
     }
+
+    // name used by bitcoin core, such as OP_CHECKSIG
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Op0 => "OP_0",
+            Self::Op1negate => "OP_1NEGATE",
+            Self::OpReserved => "OP_RESERVED",
+            Self::Op1 => "OP_1",
+            Self::Op2 => "OP_2",
+            Self::Op3 => "OP_3",
+            Self::Op4 => "OP_4",
+            Self::Op5 => "OP_5",
+            Self::Op6 => "OP_6",
+            Self::Op7 => "OP_7",
+            Self::Op8 => "OP_8",
+            Self::Op9 => "OP_9",
+            Self::Op10 => "OP_10",
+            Self::Op11 => "OP_11",
+            Self::Op12 => "OP_12",
+            Self::Op13 => "OP_13",
+            Self::Op14 => "OP_14",
+            Self::Op15 => "OP_15",
+            Self::Op16 => "OP_16",
+            Self::OpNop => "OP_NOP",
+            Self::OpVer => "OP_VER",
+            Self::OpIf => "OP_IF",
+            Self::OpNotif => "OP_NOTIF",
+            Self::OpVerif => "OP_VERIF",
+            Self::OpVernotif => "OP_VERNOTIF",
+            Self::OpElse => "OP_ELSE",
+            Self::OpEndif => "OP_ENDIF",
+            Self::OpVerify => "OP_VERIFY",
+            Self::OpReturn => "OP_RETURN",
+            Self::OpToaltstack => "OP_TOALTSTACK",
+            Self::OpFromaltstack => "OP_FROMALTSTACK",
+            Self::Op2drop => "OP_2DROP",
+            Self::Op2dup => "OP_2DUP",
+            Self::Op3dup => "OP_3DUP",
+            Self::Op2over => "OP_2OVER",
+            Self::Op2rot => "OP_2ROT",
+            Self::Op2swap => "OP_2SWAP",
+            Self::OpIfdup => "OP_IFDUP",
+            Self::OpDepth => "OP_DEPTH",
+            Self::OpDrop => "OP_DROP",
+            Self::OpDup => "OP_DUP",
+            Self::OpNip => "OP_NIP",
+            Self::OpOver => "OP_OVER",
+            Self::OpPick => "OP_PICK",
+            Self::OpRoll => "OP_ROLL",
+            Self::OpRot => "OP_ROT",
+            Self::OpSwap => "OP_SWAP",
+            Self::OpTuck => "OP_TUCK",
+            Self::OpCat => "OP_CAT",
+            Self::OpSubstr => "OP_SUBSTR",
+            Self::OpLeft => "OP_LEFT",
+            Self::OpRight => "OP_RIGHT",
+            Self::OpSize => "OP_SIZE",
+            Self::OpInvert => "OP_INVERT",
+            Self::OpAnd => "OP_AND",
+            Self::OpOr => "OP_OR",
+            Self::OpXor => "OP_XOR",
+            Self::OpEqual => "OP_EQUAL",
+            Self::OpEqualverify => "OP_EQUALVERIFY",
+            Self::OpReserved1 => "OP_RESERVED1",
+            Self::OpReserved2 => "OP_RESERVED2",
+            Self::Op1add => "OP_1ADD",
+            Self::Op1sub => "OP_1SUB",
+            Self::Op2mul => "OP_2MUL",
+            Self::Op2div => "OP_2DIV",
+            Self::OpNegate => "OP_NEGATE",
+            Self::OpAbs => "OP_ABS",
+            Self::OpNot => "OP_NOT",
+            Self::Op0notequal => "OP_0NOTEQUAL",
+            Self::OpAdd => "OP_ADD",
+            Self::OpSub => "OP_SUB",
+            Self::OpMul => "OP_MUL",
+            Self::OpDiv => "OP_DIV",
+            Self::OpMod => "OP_MOD",
+            Self::OpLshift => "OP_LSHIFT",
+            Self::OpRshift => "OP_RSHIFT",
+            Self::OpBooland => "OP_BOOLAND",
+            Self::OpBoolor => "OP_BOOLOR",
+            Self::OpNumequal => "OP_NUMEQUAL",
+            Self::OpNumequalverify => "OP_NUMEQUALVERIFY",
+            Self::OpNumnotequal => "OP_NUMNOTEQUAL",
+            Self::OpLessthan => "OP_LESSTHAN",
+            Self::OpGreaterthan => "OP_GREATERTHAN",
+            Self::OpLessthanorequal => "OP_LESSTHANOREQUAL",
+            Self::OpGreaterthanorequal => "OP_GREATERTHANOREQUAL",
+            Self::OpMin => "OP_MIN",
+            Self::OpMax => "OP_MAX",
+            Self::OpWithin => "OP_WITHIN",
+            Self::OpRipemd160 => "OP_RIPEMD160",
+            Self::OpSha1 => "OP_SHA1",
+            Self::OpSha256 => "OP_SHA256",
+            Self::OpHash160 => "OP_HASH160",
+            Self::OpHash256 => "OP_HASH256",
+            Self::OpCodeseparator => "OP_CODESEPARATOR",
+            Self::OpChecksig => "OP_CHECKSIG",
+            Self::OpChecksigverify => "OP_CHECKSIGVERIFY",
+            Self::OpCheckmultisig => "OP_CHECKMULTISIG",
+            Self::OpCheckmultisigverify => "OP_CHECKMULTISIGVERIFY",
+            Self::OpNop1 => "OP_NOP1",
+            Self::OpChecklocktimeverify => "OP_CHECKLOCKTIMEVERIFY",
+            Self::OpChecksequenceverify => "OP_CHECKSEQUENCEVERIFY",
+            Self::OpNop4 => "OP_NOP4",
+            Self::OpNop5 => "OP_NOP5",
+            Self::OpNop6 => "OP_NOP6",
+            Self::OpNop7 => "OP_NOP7",
+            Self::OpNop8 => "OP_NOP8",
+            Self::OpNop9 => "OP_NOP9",
+            Self::OpNop10 => "OP_NOP10",
+            Self::OpChecksigadd => "OP_CHECKSIGADD",
+        }
+    }
+
+    // accept the name with or without OP_ prefix, and the aliases OP_FALSE, OP_TRUE, OP_NOP2 and OP_NOP3
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.strip_prefix("OP_").unwrap_or(name);
+        match name {
+            "FALSE" => return Some(Self::Op0),
+            "TRUE" => return Some(Self::Op1),
+            "NOP2" => return Some(Self::OpChecklocktimeverify),
+            "NOP3" => return Some(Self::OpChecksequenceverify),
+            _ => {},
+        }
+        (0..=0xffu8)
+            .filter_map(Self::from_u8)
+            .find(|op| &op.name()[3..] == name)
+    }
+
+    // disabled opcodes fail the script even in an unexecuted branch
+    pub fn is_disabled(&self) -> bool {
+        matches!(
+            self,
+            Self::OpCat | Self::OpSubstr | Self::OpLeft | Self::OpRight |
+            Self::OpInvert | Self::OpAnd | Self::OpOr | Self::OpXor |
+            Self::Op2mul | Self::Op2div | Self::OpMul | Self::OpDiv |
+            Self::OpMod | Self::OpLshift | Self::OpRshift
+        )
+    }
+
+    // OP_1NEGATE, OP_1 ~ OP_16
+    pub fn small_int(&self) -> Option<i64> {
+        match self {
+            Self::Op0 => Some(0),
+            Self::Op1negate => Some(-1),
+            _ if (0x51..=0x60).contains(&self.value()) => Some(self.value() as i64 - 0x50),
+            _ => None,
+        }
+    }
+
+    pub fn from_small_int(n: i64) -> Option<Self> {
+        match n {
+            0 => Some(Self::Op0),
+            -1 => Some(Self::Op1negate),
+            1..=16 => Self::from_u8(0x50 + n as u8),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Debug for Opcode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let result = match self {
             Self::Op0 => "Op0",
+            Self::Op1negate => "Op1negate",
+            Self::OpReserved => "OpReserved",
             Self::Op1 => "Op1",
             Self::Op2 => "Op2",
             Self::Op3 => "Op3",
@@ -90,16 +416,96 @@ impl fmt::Debug for Opcode {
             Self::Op14 => "Op14",
             Self::Op15 => "Op15",
             Self::Op16 => "Op16",
+            Self::OpNop => "OpNop",
+            Self::OpVer => "OpVer",
+            Self::OpIf => "OpIf",
+            Self::OpNotif => "OpNotif",
+            Self::OpVerif => "OpVerif",
+            Self::OpVernotif => "OpVernotif",
+            Self::OpElse => "OpElse",
+            Self::OpEndif => "OpEndif",
             Self::OpVerify => "OpVerify",
+            Self::OpReturn => "OpReturn",
+            Self::OpToaltstack => "OpToaltstack",
+            Self::OpFromaltstack => "OpFromaltstack",
+            Self::Op2drop => "Op2drop",
+            Self::Op2dup => "Op2dup",
+            Self::Op3dup => "Op3dup",
+            Self::Op2over => "Op2over",
+            Self::Op2rot => "Op2rot",
+            Self::Op2swap => "Op2swap",
+            Self::OpIfdup => "OpIfdup",
+            Self::OpDepth => "OpDepth",
+            Self::OpDrop => "OpDrop",
             Self::OpDup => "OpDup",
+            Self::OpNip => "OpNip",
+            Self::OpOver => "OpOver",
+            Self::OpPick => "OpPick",
+            Self::OpRoll => "OpRoll",
+            Self::OpRot => "OpRot",
+            Self::OpSwap => "OpSwap",
+            Self::OpTuck => "OpTuck",
+            Self::OpCat => "OpCat",
+            Self::OpSubstr => "OpSubstr",
+            Self::OpLeft => "OpLeft",
+            Self::OpRight => "OpRight",
+            Self::OpSize => "OpSize",
+            Self::OpInvert => "OpInvert",
+            Self::OpAnd => "OpAnd",
+            Self::OpOr => "OpOr",
+            Self::OpXor => "OpXor",
             Self::OpEqual => "OpEqual",
             Self::OpEqualverify => "OpEqualverify",
+            Self::OpReserved1 => "OpReserved1",
+            Self::OpReserved2 => "OpReserved2",
+            Self::Op1add => "Op1add",
+            Self::Op1sub => "Op1sub",
+            Self::Op2mul => "Op2mul",
+            Self::Op2div => "Op2div",
+            Self::OpNegate => "OpNegate",
+            Self::OpAbs => "OpAbs",
+            Self::OpNot => "OpNot",
+            Self::Op0notequal => "Op0notequal",
             Self::OpAdd => "OpAdd",
+            Self::OpSub => "OpSub",
+            Self::OpMul => "OpMul",
+            Self::OpDiv => "OpDiv",
+            Self::OpMod => "OpMod",
+            Self::OpLshift => "OpLshift",
+            Self::OpRshift => "OpRshift",
+            Self::OpBooland => "OpBooland",
+            Self::OpBoolor => "OpBoolor",
+            Self::OpNumequal => "OpNumequal",
+            Self::OpNumequalverify => "OpNumequalverify",
+            Self::OpNumnotequal => "OpNumnotequal",
+            Self::OpLessthan => "OpLessthan",
+            Self::OpGreaterthan => "OpGreaterthan",
+            Self::OpLessthanorequal => "OpLessthanorequal",
+            Self::OpGreaterthanorequal => "OpGreaterthanorequal",
+            Self::OpMin => "OpMin",
+            Self::OpMax => "OpMax",
+            Self::OpWithin => "OpWithin",
+            Self::OpRipemd160 => "OpRipemd160",
+            Self::OpSha1 => "OpSha1",
+            Self::OpSha256 => "OpSha256",
             Self::OpHash160 => "OpHash160",
+            Self::OpHash256 => "OpHash256",
             Self::OpCodeseparator => "OpCodeseparator",
             Self::OpChecksig => "OpChecksig",
             Self::OpChecksigverify => "OpChecksigverify",
             Self::OpCheckmultisig => "OpCheckmultisig",
+            Self::OpCheckmultisigverify => "OpCheckmultisigverify",
+            Self::OpNop1 => "OpNop1",
+            Self::OpChecklocktimeverify => "OpChecklocktimeverify",
+            Self::OpChecksequenceverify => "OpChecksequenceverify",
+            Self::OpNop4 => "OpNop4",
+            Self::OpNop5 => "OpNop5",
+            Self::OpNop6 => "OpNop6",
+            Self::OpNop7 => "OpNop7",
+            Self::OpNop8 => "OpNop8",
+            Self::OpNop9 => "OpNop9",
+            Self::OpNop10 => "OpNop10",
+            Self::OpChecksigadd => "OpChecksigadd",
         };
         write!(f, "{}", result)
     }
@@ -128,4 +534,59 @@ mod tests {
     fn opcode_from_u8_ac() {
         assert_eq!(Opcode::from_u8(0xac), Some(Opcode::OpChecksig));
     }
+
+    #[test]
+    fn opcode_from_u8_round_trip() {
+        for byte in 0..=0xffu8 {
+            if let Some(op) = Opcode::from_u8(byte) {
+                assert_eq!(op.value(), byte);
+            }
+        }
+    }
+
+    #[test]
+    fn opcode_from_u8_unknown() {
+        assert_eq!(Opcode::from_u8(0xba), Some(Opcode::OpChecksigadd));
+        assert_eq!(Opcode::from_u8(0xbb), None);
+        assert_eq!(Opcode::from_u8(0xff), None);
+    }
+
+    #[test]
+    fn opcode_small_int() {
+        assert_eq!(Opcode::Op1negate.small_int(), Some(-1));
+        assert_eq!(Opcode::Op0.small_int(), Some(0));
+        assert_eq!(Opcode::Op16.small_int(), Some(16));
+        assert_eq!(Opcode::OpNop.small_int(), None);
+        for n in -1..=16 {
+            assert_eq!(Opcode::from_small_int(n).unwrap().small_int(), Some(n));
+        }
+        assert_eq!(Opcode::from_small_int(17), None);
+    }
+
+    #[test]
+    fn opcode_name() {
+        assert_eq!(Opcode::OpDup.name(), "OP_DUP");
+        assert_eq!(Opcode::Op1negate.name(), "OP_1NEGATE");
+        assert_eq!(Opcode::OpChecklocktimeverify.name(), "OP_CHECKLOCKTIMEVERIFY");
+        for byte in 0..=0xffu8 {
+            if let Some(op) = Opcode::from_u8(byte) {
+                assert_eq!(Opcode::from_name(op.name()), Some(op));
+            }
+        }
+    }
+
+    #[test]
+    fn opcode_from_name_alias() {
+        assert_eq!(Opcode::from_name("CHECKSIG"), Some(Opcode::OpChecksig));
+        assert_eq!(Opcode::from_name("OP_TRUE"), Some(Opcode::Op1));
+        assert_eq!(Opcode::from_name("OP_NOP3"), Some(Opcode::OpChecksequenceverify));
+        assert_eq!(Opcode::from_name("OP_UNKNOWN"), None);
+    }
+
+    #[test]
+    fn opcode_is_disabled() {
+        assert!(Opcode::OpCat.is_disabled());
+        assert!(Opcode::OpMul.is_disabled());
+        assert!(!Opcode::OpAdd.is_disabled());
+    }
 }
@@ -1,18 +1,20 @@
-use crate::secp256k1::{S256Point, Signature};
-use crate::transaction::{Transaction, TxOut, SigHash};
-use crate::util::{hash, varint};
-use super::{CommandElement, Opcode, Num, Stack, Script, ScriptBuilder, ZProvider, TransactionLegacyZProvider, TransactionWitnessP2pkhZProvider};
-use super::error::Error;
+use crate::secp256k1::{S256Point, SchnorrSignature, Signature};
+use crate::transaction::{Transaction, TxOut, SigHash, Sequence};
+use crate::util::hash;
 use primitive_types::U256;
+use super::{taproot, CommandElement, ConditionStack, ExecutionContext, Interpreter, Opcode, Num, Stack, Script, ScriptBuilder, ScriptExecutionContext, SigVersion, VerifyFlags, MAX_NUM_SIZE, MAX_PUBKEYS_PER_MULTISIG};
+use super::error::Error;
+
+// nLockTime below it is block height, otherwise it is timestamp
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
 
 pub fn verify_tx(tx: &Transaction) -> Result<bool, Error> {
-    let mut z_provider = Box::new(TransactionLegacyZProvider::from(tx.clone())) as Box<dyn ZProvider>;
+    let flags = VerifyFlags::consensus();
 
     let mut amount_in = 0;
     for (i, input) in tx.inputs.iter().enumerate() {
         let output_ref = input.get_output_ref()?;
-        let combined_script = Script::parse_raw(&output_ref.script())? + Script::parse_raw(&input.script)?;
-        if !combined_script.evaluate(i, &mut z_provider)? {
+        if !verify_tx_input(tx, i, Some(output_ref.clone()), flags)? {
             return Ok(false);
         }
         amount_in += output_ref.amount();
@@ -29,7 +31,16 @@ pub fn verify_tx(tx: &Transaction) -> Result<bool, Error> {
     Ok(true)
 }
 
-pub fn convert_script(tx: &Transaction, input_index: usize, prevout: Option<TxOut>) -> Result<(Script, Script, Box<dyn ZProvider>), Error> {
+// result of checking an input against both consensus rules and standardness policy
+#[derive(Debug)]
+pub enum InputVerdict {
+    Valid,
+    NonStandard(Error), // valid in a block, but would not be relayed
+    Invalid(Error),
+}
+
+// @return (script_pubkey, script_sig, sig_version), script_pubkey is the witness script or the witness program nested in p2sh if any
+pub fn convert_script(tx: &Transaction, input_index: usize, prevout: Option<TxOut>, flags: VerifyFlags) -> Result<(Script, Script, SigVersion), Error> {
     let input = &tx.inputs[input_index]; // TODO check len
     let prevout = match prevout {
         Some(prevout) => prevout,
@@ -38,158 +49,411 @@ pub fn convert_script(tx: &Transaction, input_index: usize, prevout: Option<TxOu
 
     let mut script_pubkey = Script::parse_raw(prevout.script())?;
     let mut script_sig = Script::parse_raw(&input.script)?;
-    let provider_box;
+    let mut sig_version = SigVersion::Base;
+
+    if flags.contains(VerifyFlags::SIGPUSHONLY) && !script_sig.is_push_only() {
+        return Err(Error::SigPushOnly);
+    }
 
     // check is p2sh or not
-    if script_pubkey.is_p2sh_pubkey() && script_sig.get_bottom_as_data().is_some() {
+    let is_p2sh = flags.contains(VerifyFlags::P2SH) && script_pubkey.is_p2sh_pubkey();
+    if is_p2sh && !script_sig.is_push_only() {
+        return Err(Error::SigPushOnly);
+    }
+    if is_p2sh && script_sig.get_bottom_as_data().is_some() {
         let redeem_script = script_sig.get_bottom_as_data().unwrap();
         let redeem_hash_160 = &hash::hash160(&redeem_script)[..];
         let redeem_hash_expect = &script_pubkey.get_index_as_data(1).unwrap()[..];
         if redeem_hash_160 != redeem_hash_expect {
             return Err(Error::InvalidRedeemScript);
         }
-        script_pubkey = Script::parse_raw(&redeem_script)?;
-
-        let mut script_sig_cmds = script_sig.cmds().clone();
-        script_sig_cmds.remove(0); // remove redeem script
-        script_sig = Script::new(script_sig_cmds);
+        // the other redeem scripts are run by the interpreter after the p2sh script
+        let redeem_script = Script::parse_raw(&redeem_script)?;
+        if flags.contains(VerifyFlags::WITNESS) && redeem_script.witness_program().is_some() {
+            script_pubkey = redeem_script;
+            let mut script_sig_cmds = script_sig.cmds().clone();
+            script_sig_cmds.remove(0); // remove redeem script
+            script_sig = Script::new(script_sig_cmds);
+        }
     }
 
-    // check is witness or not
-    if script_pubkey.is_p2wpkh_pubkey() && script_sig.is_empty() {
+    // a witness program is spent natively by an empty script_sig, or nested in p2sh by the push of itself
+    let witness_program = script_pubkey.witness_program().filter(|_| flags.contains(VerifyFlags::WITNESS));
+    let is_witness = witness_program.is_some();
+    if is_witness && !script_sig.is_empty() {
+        return Err(if is_p2sh { Error::WitnessMalleatedP2sh } else { Error::WitnessMalleated });
+    }
+    if !is_witness && flags.contains(VerifyFlags::WITNESS) && !input.witness.is_empty() {
+        return Err(Error::WitnessUnexpected);
+    }
+    if matches!(&witness_program, Some((0, program)) if program.len() != 20 && program.len() != 32) {
+        return Err(Error::WitnessProgramWrongLength);
+    }
+    if is_witness && script_pubkey.is_p2wpkh_pubkey() {
+        if input.witness.len() != 2 {
+            return Err(Error::WitnessProgramMismatch);
+        }
         let pk_hash = hash::convert_slice_into_hash160(&script_pubkey.get_bottom_as_data().unwrap());
         script_pubkey = ScriptBuilder::p2pkh(&pk_hash);
-        script_sig = Script::parse_witness(&input.witness).unwrap(); // witness as sig
-
-        let mut provider = TransactionWitnessP2pkhZProvider::from(tx.clone());
-        let cache_key = [input.prev_tx.to_vec(), input.prev_index.serialize().to_vec()].concat();
-        provider.prevout_cache.insert(cache_key, (prevout, script_pubkey.clone()));
-        provider_box = Box::new(provider) as Box<dyn ZProvider>;
-    } else if script_pubkey.is_p2wsh_pubkey() && script_sig.is_empty() {
-        let script_witness = Script::parse_witness(&input.witness).unwrap();
-        let redeem_script = script_witness.get_bottom_as_data().unwrap();
-        let redeem_sha256 = &hash::sha256(&redeem_script)[..];
+        script_sig = Script::parse_witness(&input.witness)?; // witness as sig
+        sig_version = SigVersion::WitnessV0;
+    } else if is_witness && script_pubkey.is_p2wsh_pubkey() {
+        let redeem_script = input.witness.last().ok_or(Error::WitnessProgramWitnessEmpty)?;
+        let redeem_sha256 = &hash::sha256(redeem_script)[..];
         let redeem_hash_expect = &script_pubkey.get_bottom_as_data().unwrap()[..];
         if redeem_sha256 != redeem_hash_expect {
             return Err(Error::InvalidWitnessRedeemScript);
         }
-        script_pubkey = Script::parse_raw(&redeem_script)?;
+        // the witness script is the script code of any shape, it is signed as is
+        script_pubkey = Script::parse_raw(redeem_script)?;
+        script_sig = Script::parse_witness(&input.witness[..input.witness.len() - 1].to_vec())?;
+        sig_version = SigVersion::WitnessV0;
+    } else if is_witness && matches!(script_pubkey.witness_program(), Some((version, _)) if version != 0) {
+        if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM) {
+            return Err(Error::DiscourageUpgradableWitnessProgram);
+        }
+        // unknown witness version is anyone-can-spend for soft-fork upgrade
+        script_pubkey = Script::new(vec![Opcode::Op1.into()]);
+    }
+
+    Ok((script_pubkey, script_sig, sig_version))
+}
+
+pub fn verify_tx_input(tx: &Transaction, input_index: usize, prevout: Option<TxOut>, flags: VerifyFlags) -> Result<bool, Error> {
+    let input = tx.inputs.get(input_index).ok_or(Error::MissingTransaction)?;
+    let prevout = match prevout {
+        Some(prevout) => prevout,
+        None => input.get_output_ref()?,
+    };
+    // taproot signs all spent outputs, the others are fetched only when needed
+    if taproot_program(tx, input_index, &prevout, flags).is_some() {
+        let mut prevouts = Vec::new();
+        for (i, input) in tx.inputs.iter().enumerate() {
+            if i == input_index {
+                prevouts.push(prevout.clone());
+            } else {
+                prevouts.push(input.get_output_ref()?);
+            }
+        }
+        return verify_tx_input_with_prevouts(tx, input_index, &prevouts, flags);
+    }
 
-        let mut script_sig_cmds = script_witness.cmds().clone();
-        script_sig_cmds.remove(0); // remove redeem script
-        script_sig = Script::new(script_sig_cmds);
+    let (script_pubkey, script_sig, sig_version) = convert_script(tx, input_index, Some(prevout.clone()), flags)?;
+    let context = ScriptExecutionContext::for_input(tx, input_index, sig_version, flags).with_prevout(prevout);
 
-        let mut provider = TransactionWitnessP2pkhZProvider::from(tx.clone());
-        let cache_key = [input.prev_tx.to_vec(), input.prev_index.serialize().to_vec()].concat();
-        provider.prevout_cache.insert(cache_key, (prevout, script_pubkey.clone()));
-        provider_box = Box::new(provider) as Box<dyn ZProvider>;
-    } else {
-        let provider = TransactionLegacyZProvider::from(tx.clone());
-        provider_box = Box::new(provider) as Box<dyn ZProvider>;
+    Interpreter::new(&script_pubkey, &context).with_script_sig(&script_sig).finish()
+}
+
+// @param prevouts: the output spent by each input of tx
+pub fn verify_tx_input_with_prevouts(tx: &Transaction, input_index: usize, prevouts: &[TxOut], flags: VerifyFlags) -> Result<bool, Error> {
+    if prevouts.len() != tx.inputs.len() || input_index >= prevouts.len() {
+        return Err(Error::MissingTransaction);
     }
+    let prevout = &prevouts[input_index];
+    match taproot_program(tx, input_index, prevout, flags) {
+        Some(program) => taproot::verify_taproot(tx, input_index, prevouts, &program, flags),
+        None => verify_tx_input(tx, input_index, Some(prevout.clone()), flags),
+    }
+}
 
-    Ok((script_pubkey, script_sig, provider_box))
+// the 32 bytes program of segwit v1 output spent by a native witness input
+fn taproot_program(tx: &Transaction, input_index: usize, prevout: &TxOut, flags: VerifyFlags) -> Option<Vec<u8>> {
+    if !flags.contains(VerifyFlags::WITNESS | VerifyFlags::TAPROOT) || !tx.inputs[input_index].script.is_empty() {
+        return None;
+    }
+    match Script::parse_raw(prevout.script()).ok()?.witness_program() {
+        Some((1, program)) if program.len() == 32 => Some(program),
+        _ => None,
+    }
 }
 
-pub fn verify_tx_input(tx: &Transaction, input_index: usize, prevout: Option<TxOut>) -> Result<bool, Error> {
-    let (script_pubkey, script_sig, mut provider) = convert_script(tx, input_index, prevout)?;
-    let combined_script = script_pubkey + script_sig;
+// consensus is checked first, so a non-standard verdict always means the input is valid in a block
+pub fn check_tx_input(tx: &Transaction, input_index: usize, prevout: Option<TxOut>) -> Result<InputVerdict, Error> {
+    let prevout = match prevout {
+        Some(prevout) => prevout,
+        None => tx.inputs[input_index].get_output_ref()?,
+    };
+
+    match verify_tx_input(tx, input_index, Some(prevout.clone()), VerifyFlags::consensus()) {
+        Ok(true) => {},
+        Ok(false) => return Ok(InputVerdict::Invalid(Error::EvalFalse)),
+        Err(e) => return Ok(InputVerdict::Invalid(e)),
+    }
+    let verdict = match verify_tx_input(tx, input_index, Some(prevout), VerifyFlags::standard()) {
+        Ok(true) => InputVerdict::Valid,
+        Ok(false) => InputVerdict::NonStandard(Error::EvalFalse),
+        Err(e) => InputVerdict::NonStandard(e),
+    };
 
-    Ok(combined_script.evaluate(input_index, &mut provider).unwrap())
+    Ok(verdict)
 }
 
-pub fn check_signature(pk_raw: Vec<u8>, sig_raw: Vec<u8>, index: usize, z_privoder: &mut Box<dyn ZProvider>) -> Result<bool, Error>  {
-    let pk = S256Point::parse(&pk_raw).map_err(|_| Error::InvalidPublicKey)?;
-    let (sig, used) = Signature::parse_der(&sig_raw).map_err(|_| Error::InvalidSignature)?;
+// empty signature is always allowed, it is the way to fail a check on purpose
+fn check_signature_encoding(sig: &[u8], flags: VerifyFlags) -> Result<(), Error> {
+    if sig.is_empty() {
+        return Ok(());
+    }
+    let strict_der = VerifyFlags::DERSIG | VerifyFlags::LOW_S | VerifyFlags::STRICTENC;
+    if flags.intersects(strict_der) && Signature::check_strict_der(sig).is_err() {
+        return Err(Error::SigDer);
+    }
+    if flags.contains(VerifyFlags::LOW_S) {
+        if let Ok((signature, _)) = Signature::parse_der(sig) {
+            if !signature.is_low_s() {
+                return Err(Error::SigHighS);
+            }
+        }
+    }
+    if flags.contains(VerifyFlags::STRICTENC) && SigHash::parse(sig[sig.len() - 1]).is_err() {
+        return Err(Error::SigHashType);
+    }
+    Ok(())
+}
 
-    let sighash = if used + 1 == sig_raw.len() {
-        SigHash::parse(sig_raw[used])?
-    } else {
-        SigHash::All // default is all
+fn check_public_key_encoding(pk: &[u8], flags: VerifyFlags, sig_version: SigVersion) -> Result<(), Error> {
+    let is_compressed = pk.len() == 33 && (pk[0] == 0x02 || pk[0] == 0x03);
+    let is_uncompressed = pk.len() == 65 && pk[0] == 0x04;
+    if flags.contains(VerifyFlags::STRICTENC) && !is_compressed && !is_uncompressed {
+        return Err(Error::PubKeyType);
+    }
+    if flags.contains(VerifyFlags::WITNESS_PUBKEYTYPE) && sig_version == SigVersion::WitnessV0 && !is_compressed {
+        return Err(Error::WitnessPubKeyType);
+    }
+    Ok(())
+}
+
+// @param script_code: the executed script after the last OP_CODESEPARATOR
+pub fn check_signature(pk_raw: Vec<u8>, sig_raw: Vec<u8>, script_code: &Script, script_context: &ScriptExecutionContext) -> Result<bool, Error>  {
+    let flags = script_context.flags;
+    check_signature_encoding(&sig_raw, flags)?;
+    check_public_key_encoding(&pk_raw, flags, script_context.sig_version)?;
+
+    // a signature cannot sign itself, so it is removed from the legacy script code
+    let script_code = match script_context.sig_version {
+        SigVersion::Base => script_code.find_and_delete(&sig_raw),
+        _ => script_code.clone(),
     };
-    let z = z_privoder.z_u256(index, sighash, None, None)?;
+    let result = verify_signature(&pk_raw, &sig_raw, &script_code, script_context)?;
+    if !result && flags.contains(VerifyFlags::NULLFAIL) && !sig_raw.is_empty() {
+        return Err(Error::SigNullFail);
+    }
+
+    Ok(result)
+}
+
+// BIP340 signature of taproot, it is an error if a non-empty signature fails
+// @param sig_raw: 64 bytes with SIGHASH_DEFAULT, or 65 bytes with the hash type at the end
+pub fn check_schnorr_signature(sig_raw: &[u8], pk_raw: &[u8], script_context: &ScriptExecutionContext, code_separator_pos: Option<usize>) -> Result<(), Error> {
+    let (sig_bytes, hash_type) = match sig_raw.len() {
+        64 => (sig_raw, 0x00),
+        65 if sig_raw[64] != 0x00 => (&sig_raw[..64], sig_raw[64]),
+        65 => return Err(Error::SchnorrSigHashType),
+        _ => return Err(Error::SchnorrSigSize),
+    };
+    let sig = SchnorrSignature::parse(sig_bytes).map_err(|_| Error::SchnorrSigSize)?;
+    let z = script_context.signature_hash_taproot(hash_type, code_separator_pos).map_err(|_| Error::SchnorrSigHashType)?;
+    if !sig.verify(&z, pk_raw) {
+        return Err(Error::SchnorrSig);
+    }
+    Ok(())
+}
+
+// BIP342, an empty signature makes the check fail without an error
+fn check_tapscript_signature(pk_raw: &[u8], sig_raw: &[u8], context: &mut ExecutionContext, script_context: &ScriptExecutionContext) -> Result<bool, Error> {
+    let success = !sig_raw.is_empty();
+    if success {
+        context.consume_validation_weight()?;
+    }
+    match pk_raw.len() {
+        0 => return Err(Error::PubKeyType),
+        32 if success => check_schnorr_signature(sig_raw, pk_raw, script_context, context.code_separator_pos)?,
+        32 => {},
+        // unknown public key type is reserved for soft-fork upgrade
+        _ if script_context.flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_PUBKEYTYPE) => return Err(Error::DiscourageUpgradablePubKeyType),
+        _ => {},
+    }
+    Ok(success)
+}
+
+// the byte after DER, an undefined type is signed as it is and only rejected by STRICTENC
+fn hash_type(sig_raw: &[u8], der_len: usize) -> u32 {
+    match sig_raw.get(der_len) {
+        Some(byte) => *byte as u32,
+        None => SigHash::All.into(), // default is all
+    }
+}
+
+fn verify_signature(pk_raw: &[u8], sig_raw: &[u8], script_code: &Script, script_context: &ScriptExecutionContext) -> Result<bool, Error>  {
+    // an undecodable key or signature is not an error, the check just fails
+    let pk = match S256Point::parse(pk_raw) {
+        Ok(pk) => pk,
+        Err(_) => return Ok(false),
+    };
+    let (sig, used) = match Signature::parse_der(sig_raw) {
+        Ok(result) => result,
+        Err(_) => return Ok(false),
+    };
+
+    let z = U256::from_big_endian(&script_context.signature_hash(script_code, hash_type(sig_raw, used))?);
 
     Ok(sig.verify(z, pk))
 }
 
-pub fn check_multiple_signature(public_keys: Vec<Vec<u8>>, signatures: Vec<Vec<u8>>, index: usize, z_privoder: &mut Box<dyn ZProvider>) -> Result<bool, Error>  {
-    let mut pks = Vec::new();
-    for public_key in public_keys {
-        let pk = S256Point::parse(&public_key).map_err(|_| Error::InvalidPublicKey)?;
-        pks.push(pk);
+// keys and signatures are both in the order of popping from stack,
+// the signatures must match the keys in the same order as bitcoin core does
+pub fn check_multiple_signature(public_keys: Vec<Vec<u8>>, signatures: Vec<Vec<u8>>, script_code: &Script, script_context: &ScriptExecutionContext) -> Result<bool, Error>  {
+    let flags = script_context.flags;
+    let sig_version = script_context.sig_version;
+    // all the signatures are removed from the legacy script code before any check
+    let mut script_code = script_code.clone();
+    if sig_version == SigVersion::Base {
+        for sig_raw in &signatures {
+            script_code = script_code.find_and_delete(sig_raw);
+        }
     }
 
-    let mut z = U256::zero();
-    let mut sighash: Option<SigHash> = None;
     let mut sigs = Vec::new();
-    for sig_raw in signatures {
-        let (sig, used) = Signature::parse_der(&sig_raw).map_err(|_| Error::InvalidSignature)?;
-        sigs.push(sig);
-
-        let sighash_now = if used + 1 == sig_raw.len() {
-            SigHash::parse(sig_raw[used])?
-        } else {
-            SigHash::All // default is all
-        };
-        match sighash {
-            None => {
-                sighash = Some(sighash_now);
-                z = z_privoder.z_u256(index, sighash_now, None, None)?;
+    for sig_raw in &signatures {
+        let parsed = match Signature::parse_der(sig_raw) {
+            Ok((sig, used)) => {
+                let z = U256::from_big_endian(&script_context.signature_hash(&script_code, hash_type(sig_raw, used))?);
+                Some((sig, z))
             },
-            Some(sighash) => {
-                if sighash != sighash_now {
-                    return Err(Error::SigHashIsNotTheSame);
-                }
-            }
-        }
+            Err(_) => None,
+        };
+        sigs.push(parsed);
     }
 
-    let mut correct_count = 0;
-    for pk in &pks {
-        for sig in &sigs {
-            if sig.verify(z, pk.clone()) {
-                correct_count += 1;
-                break;
+    // encoding is only checked for the pairs really compared, as bitcoin core does
+    let mut key_i = 0;
+    let mut sig_i = 0;
+    let mut success = true;
+    while success && sig_i < sigs.len() {
+        check_signature_encoding(&signatures[sig_i], flags)?;
+        check_public_key_encoding(&public_keys[key_i], flags, sig_version)?;
+        if let (Some((sig, z)), Ok(pk)) = (&sigs[sig_i], S256Point::parse(&public_keys[key_i])) {
+            if sig.verify(*z, pk) {
+                sig_i += 1;
             }
         }
+        key_i += 1;
+        if sigs.len() - sig_i > public_keys.len() - key_i {
+            success = false;
+        }
     }
 
-    Ok(correct_count == sigs.len())
-}
-
-pub fn evaluate_p2sh(cmds: &mut Vec<CommandElement>, stack: &mut Stack, hash160: &Vec<u8>) -> Result<bool, Error> {
-    let hash160_expect = hash::convert_slice_into_hash160(hash160);
-    let redeem_script_raw = stack.pop()?;
-    let hash160_real = hash::hash160(&redeem_script_raw);
-    if hash160_expect != hash160_real {
-        return Ok(false);
+    if !success && flags.contains(VerifyFlags::NULLFAIL) && signatures.iter().any(|sig| !sig.is_empty()) {
+        return Err(Error::SigNullFail);
     }
 
-    let redeem_script = Script::parse(&redeem_script_raw)?;
-    cmds.clear();
-    cmds.append(&mut redeem_script.cmds().clone());
+    Ok(success)
+}
 
-    Ok(true)
+// data which can be pushed by OP_0, OP_1NEGATE or OP_1..OP_16 must not use a data push
+fn is_minimal_push(data: &[u8]) -> bool {
+    match data {
+        [] => false,
+        [byte] => !(1..=16).contains(byte) && *byte != 0x81,
+        _ => true,
+    }
 }
 
-pub fn evaluate_command(cmd: CommandElement, stack: &mut Stack, index: usize, z_privoder: &mut Box<dyn ZProvider>) -> Result<bool, Error> {
+pub fn evaluate_command(cmd: CommandElement, context: &mut ExecutionContext, script_context: &ScriptExecutionContext) -> Result<bool, Error> {
+    let flags = script_context.flags;
     let mut result = true;
     match cmd {
-        CommandElement::Op(op) => result = evaluate_opcode(op, stack, index, z_privoder)?,
-        CommandElement::Data(data) => stack.push(data),
+        CommandElement::Op(op) => result = evaluate_opcode(op, context, script_context)?,
+        CommandElement::Data(data) => {
+            if flags.contains(VerifyFlags::MINIMALDATA) && !is_minimal_push(&data) {
+                return Err(Error::MinimalData);
+            }
+            context.stack.push(data)
+        },
+        CommandElement::NonMinimalData(_, data) => {
+            if flags.contains(VerifyFlags::MINIMALDATA) {
+                return Err(Error::MinimalData);
+            }
+            context.stack.push(data)
+        },
         CommandElement::Unknown(byte) => return Err(Error::UnknownByteInScript(byte)),
     };
 
     Ok(result)
 }
 
-fn evaluate_opcode(op: Opcode, stack: &mut Stack, index: usize, z_privoder: &mut Box<dyn ZProvider>) -> Result<bool, Error> {
-    let mut result = true;
+// OP_IF, OP_NOTIF, OP_ELSE and OP_ENDIF, they are evaluated even if in an unexecuted branch
+pub fn evaluate_flow_control(op: Opcode, stack: &mut Stack, conditions: &mut ConditionStack, sig_version: SigVersion, flags: VerifyFlags) -> Result<(), Error> {
     match op {
-        Opcode::Op0 => {
-            let num = Num::from(0);
-            stack.push(num.encode());
+        Opcode::OpIf | Opcode::OpNotif => {
+            let mut condition = false;
+            if conditions.all_true() {
+                let ele = stack.pop().map_err(|_| Error::UnbalancedConditional)?;
+                // MINIMALIF: argument of witness script must be exactly empty or 0x01, it is consensus in tapscript
+                let minimal_if = sig_version == SigVersion::Tapscript || (sig_version == SigVersion::WitnessV0 && flags.contains(VerifyFlags::MINIMALIF));
+                if minimal_if && !(ele.is_empty() || ele == [1]) {
+                    return Err(Error::MinimalIf);
+                }
+                condition = cast_to_bool(&ele);
+                if op == Opcode::OpNotif {
+                    condition = !condition;
+                }
+            }
+            conditions.push(condition);
         },
+        Opcode::OpElse => conditions.toggle_top()?,
+        Opcode::OpEndif => conditions.pop()?,
+        _ => return Err(Error::InvalidOpcode),
+    }
+    Ok(())
+}
+
+// any non-zero bytes is true, except negative zero
+pub fn cast_to_bool(bytes: &[u8]) -> bool {
+    for (i, byte) in bytes.iter().enumerate() {
+        if *byte != 0 {
+            return !(i == bytes.len() - 1 && *byte == 0x80);
+        }
+    }
+    false
+}
+
+fn encode_bool(b: bool) -> Vec<u8> {
+    if b { vec![1] } else { vec![] } // 0 is empty bytes
+}
+
+fn pop_num(stack: &mut Stack, flags: VerifyFlags) -> Result<i64, Error> {
+    let ele = stack.pop()?;
+    Ok(Num::decode_script_num(ele, MAX_NUM_SIZE, flags.contains(VerifyFlags::MINIMALDATA))?.value())
+}
+
+// the error of a *VERIFY opcode which finds false on the stack
+pub fn verify_error(op: Opcode) -> Option<Error> {
+    match op {
+        Opcode::OpVerify => Some(Error::Verify),
+        Opcode::OpEqualverify => Some(Error::EqualVerify),
+        Opcode::OpNumequalverify => Some(Error::NumEqualVerify),
+        Opcode::OpChecksigverify => Some(Error::CheckSigVerify),
+        Opcode::OpCheckmultisigverify => Some(Error::CheckMultisigVerify),
+        _ => None,
+    }
+}
+
+fn pop_bool(stack: &mut Stack) -> Result<bool, Error> {
+    let ele = stack.pop()?;
+    Ok(cast_to_bool(&ele))
+}
+
+fn evaluate_opcode(op: Opcode, context: &mut ExecutionContext, script_context: &ScriptExecutionContext) -> Result<bool, Error> {
+    let flags = script_context.flags;
+    let sig_version = script_context.sig_version;
+    let stack = &mut context.stack;
+    if op.is_disabled() {
+        return Err(Error::DisabledOpcode(op));
+    }
+    match op {
+        // constants
+        Opcode::Op0 |
+        Opcode::Op1negate |
         Opcode::Op1 |
         Opcode::Op2 |
         Opcode::Op3 |
@@ -206,96 +470,758 @@ fn evaluate_opcode(op: Opcode, stack: &mut Stack, index: usize, z_privoder: &mut
         Opcode::Op14 |
         Opcode::Op15 |
         Opcode::Op16 => {
-            let num = op.value() - 0x50;
-            let num = Num::from(num as i64).encode();
-            stack.push(num);
+            let num = op.small_int().expect("small int opcode");
+            stack.push(Num::from(num).encode());
         },
-        Opcode::OpVerify => {
-            let ele = stack.pop()?;
-            if Num::decode(ele)?.value() == 0 {
-                return Ok(false);
+
+        // flow control
+        Opcode::OpNop => {},
+        Opcode::OpNop1 |
+        Opcode::OpNop4 |
+        Opcode::OpNop5 |
+        Opcode::OpNop6 |
+        Opcode::OpNop7 |
+        Opcode::OpNop8 |
+        Opcode::OpNop9 |
+        Opcode::OpNop10 => {
+            if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS) {
+                return Err(Error::DiscourageUpgradableNops);
+            }
+        },
+        Opcode::OpChecklocktimeverify |
+        Opcode::OpChecksequenceverify if !flags.contains(timelock_flag(op)) => {
+            // not activated, it is still OP_NOP2 or OP_NOP3
+            if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS) {
+                return Err(Error::DiscourageUpgradableNops);
+            }
+        },
+        Opcode::OpChecklocktimeverify => {
+            let lock_time = peek_lock_time(stack, flags)?;
+            if !script_context.check_lock_time(lock_time)? {
+                return Err(Error::UnsatisfiedLocktime);
+            }
+        },
+        Opcode::OpChecksequenceverify => {
+            let sequence = peek_lock_time(stack, flags)?;
+            // disable flag is set, behave as NOP for soft-fork upgrade
+            if sequence & Sequence::DISABLE_FLAG as i64 != 0 {
+                return Ok(true);
+            }
+            if !script_context.check_sequence(sequence)? {
+                return Err(Error::UnsatisfiedLocktime);
+            }
+        },
+        Opcode::OpIf |
+        Opcode::OpNotif |
+        Opcode::OpElse |
+        Opcode::OpEndif => return Err(Error::InvalidOpcode), // need condition stack, see evaluate_flow_control
+        Opcode::OpVerify => return pop_bool(stack),
+        Opcode::OpReturn => return Err(Error::OpReturn),
+        Opcode::OpReserved |
+        Opcode::OpVer |
+        Opcode::OpVerif |
+        Opcode::OpVernotif |
+        Opcode::OpReserved1 |
+        Opcode::OpReserved2 => return Err(Error::BadOpcode(op)),
+
+        // stack
+        Opcode::OpToaltstack => context.move_to_alt_stack()?,
+        Opcode::OpFromaltstack => context.move_from_alt_stack()?,
+        Opcode::Op2drop => {
+            stack.pop()?;
+            stack.pop()?;
+        },
+        Opcode::Op2dup => {
+            let ele1 = stack.peek(1)?.clone();
+            let ele2 = stack.peek(0)?.clone();
+            stack.push(ele1);
+            stack.push(ele2);
+        },
+        Opcode::Op3dup => {
+            let ele1 = stack.peek(2)?.clone();
+            let ele2 = stack.peek(1)?.clone();
+            let ele3 = stack.peek(0)?.clone();
+            stack.push(ele1);
+            stack.push(ele2);
+            stack.push(ele3);
+        },
+        Opcode::Op2over => {
+            let ele1 = stack.peek(3)?.clone();
+            let ele2 = stack.peek(2)?.clone();
+            stack.push(ele1);
+            stack.push(ele2);
+        },
+        Opcode::Op2rot => {
+            let ele1 = stack.remove(5)?;
+            let ele2 = stack.remove(4)?;
+            stack.push(ele1);
+            stack.push(ele2);
+        },
+        Opcode::Op2swap => {
+            let ele1 = stack.remove(3)?;
+            let ele2 = stack.remove(2)?;
+            stack.push(ele1);
+            stack.push(ele2);
+        },
+        Opcode::OpIfdup => {
+            let ele = stack.peek(0)?.clone();
+            if cast_to_bool(&ele) {
+                stack.push(ele);
             }
         },
+        Opcode::OpDepth => {
+            let depth = Num::from(stack.len() as i64);
+            stack.push(depth.encode());
+        },
+        Opcode::OpDrop => {
+            stack.pop()?;
+        },
         Opcode::OpDup => {
             let ele = stack.pop()?;
             stack.push(ele.clone());
             stack.push(ele);
         },
+        Opcode::OpNip => {
+            stack.remove(1)?;
+        },
+        Opcode::OpOver => {
+            let ele = stack.peek(1)?.clone();
+            stack.push(ele);
+        },
+        Opcode::OpPick |
+        Opcode::OpRoll => {
+            let n = pop_num(stack, flags)?;
+            if n < 0 {
+                return Err(Error::InvalidStackOperation);
+            }
+            let ele = if op == Opcode::OpPick {
+                stack.peek(n as usize)?.clone()
+            } else {
+                stack.remove(n as usize)?
+            };
+            stack.push(ele);
+        },
+        Opcode::OpRot => {
+            let ele = stack.remove(2)?;
+            stack.push(ele);
+        },
+        Opcode::OpSwap => {
+            let ele = stack.remove(1)?;
+            stack.push(ele);
+        },
+        Opcode::OpTuck => {
+            let ele = stack.peek(0)?.clone();
+            stack.insert(2, ele)?;
+        },
+
+        // splice
+        Opcode::OpSize => {
+            let size = Num::from(stack.peek(0)?.len() as i64);
+            stack.push(size.encode());
+        },
+
+        // bitwise logic
         Opcode::OpEqual => {
-            result = stack.pop()? == stack.pop()?;
-            if result { stack.push(vec![1]) } else { stack.push(vec![]) }; // 0 is empty bytes
+            let result = stack.pop()? == stack.pop()?;
+            stack.push(encode_bool(result));
         },
-        Opcode::OpEqualverify => result = stack.pop()? == stack.pop()?,
-        Opcode::OpAdd => {
-            let left = stack.pop()?;
-            let left = Num::decode(left)?;
-            let right = stack.pop()?;
-            let right = Num::decode(right)?;
+        Opcode::OpEqualverify => return Ok(stack.pop()? == stack.pop()?),
 
-            let result = left + right;
-            stack.push(result.encode());
+        // arithmetic
+        Opcode::Op1add |
+        Opcode::Op1sub |
+        Opcode::OpNegate |
+        Opcode::OpAbs |
+        Opcode::OpNot |
+        Opcode::Op0notequal => {
+            let num = pop_num(stack, flags)?;
+            let result = match op {
+                Opcode::Op1add => num + 1,
+                Opcode::Op1sub => num - 1,
+                Opcode::OpNegate => -num,
+                Opcode::OpAbs => num.abs(),
+                Opcode::OpNot => (num == 0) as i64,
+                _ => (num != 0) as i64, // Op0notequal
+            };
+            stack.push(Num::from(result).encode());
+        },
+        Opcode::OpAdd |
+        Opcode::OpSub |
+        Opcode::OpBooland |
+        Opcode::OpBoolor |
+        Opcode::OpNumequal |
+        Opcode::OpNumequalverify |
+        Opcode::OpNumnotequal |
+        Opcode::OpLessthan |
+        Opcode::OpGreaterthan |
+        Opcode::OpLessthanorequal |
+        Opcode::OpGreaterthanorequal |
+        Opcode::OpMin |
+        Opcode::OpMax => {
+            let right = pop_num(stack, flags)?;
+            let left = pop_num(stack, flags)?;
+            let result = match op {
+                Opcode::OpAdd => left + right,
+                Opcode::OpSub => left - right,
+                Opcode::OpBooland => (left != 0 && right != 0) as i64,
+                Opcode::OpBoolor => (left != 0 || right != 0) as i64,
+                Opcode::OpNumequal | Opcode::OpNumequalverify => (left == right) as i64,
+                Opcode::OpNumnotequal => (left != right) as i64,
+                Opcode::OpLessthan => (left < right) as i64,
+                Opcode::OpGreaterthan => (left > right) as i64,
+                Opcode::OpLessthanorequal => (left <= right) as i64,
+                Opcode::OpGreaterthanorequal => (left >= right) as i64,
+                Opcode::OpMin => left.min(right),
+                _ => left.max(right), // OpMax
+            };
+            if op == Opcode::OpNumequalverify {
+                return Ok(result != 0);
+            }
+            stack.push(Num::from(result).encode());
+        },
+        Opcode::OpWithin => {
+            let max = pop_num(stack, flags)?;
+            let min = pop_num(stack, flags)?;
+            let num = pop_num(stack, flags)?;
+            stack.push(encode_bool(min <= num && num < max));
+        },
+
+        // crypto
+        Opcode::OpRipemd160 => {
+            let ele = stack.pop()?;
+            stack.push(hash::ripemd160(&ele).to_vec());
+        },
+        Opcode::OpSha1 => {
+            let ele = stack.pop()?;
+            stack.push(hash::sha1(&ele).to_vec());
+        },
+        Opcode::OpSha256 => {
+            let ele = stack.pop()?;
+            stack.push(hash::sha256(&ele).to_vec());
         },
         Opcode::OpHash160 => {
             let ele = stack.pop()?;
-            let ele = hash::hash160(&ele).to_vec();
-            stack.push(ele);
+            stack.push(hash::hash160(&ele).to_vec());
         },
-        Opcode::OpChecksig => {
+        Opcode::OpHash256 => {
+            let ele = stack.pop()?;
+            stack.push(hash::hash256(&ele).to_vec());
+        },
+        Opcode::OpCodeseparator => {}, // the script code is tracked by the interpreter
+        Opcode::OpChecksig |
+        Opcode::OpChecksigverify if sig_version == SigVersion::Tapscript => {
             let pk = stack.pop()?;
             let sig = stack.pop()?;
-            result = check_signature(pk, sig, index, z_privoder)?;
-
-            let stack_result = if result { vec![1] } else { vec![] };
-            stack.push(stack_result);
-        },
-        Opcode::OpCodeseparator => {
-            // nothing now
-            // TODO make signature correct
+            let result = check_tapscript_signature(&pk, &sig, context, script_context)?;
+            if op == Opcode::OpChecksigverify {
+                return Ok(result);
+            }
+            context.stack.push(encode_bool(result));
         },
-        Opcode::OpChecksigverify => {
-            // op_checksig (TODO use function)
+        Opcode::OpChecksigadd if sig_version == SigVersion::Tapscript => {
             let pk = stack.pop()?;
+            let n = pop_num(stack, flags)?;
             let sig = stack.pop()?;
-            result = check_signature(pk, sig, index, z_privoder)?;
-
-            let stack_result = if result { vec![1] } else { vec![] };
-            stack.push(stack_result);
-            // op_verify (TODO use function)
-            let ele = stack.pop()?;
-            if Num::decode(ele)?.value() == 0 {
-                return Ok(false);
-            }
+            let result = check_tapscript_signature(&pk, &sig, context, script_context)?;
+            context.stack.push(Num::from(n + result as i64).encode());
+        },
+        Opcode::OpChecksigadd => return Err(Error::BadOpcode(op)),
+        Opcode::OpCheckmultisig |
+        Opcode::OpCheckmultisigverify if sig_version == SigVersion::Tapscript => return Err(Error::TapscriptCheckmultisig),
+        Opcode::OpChecksig => {
+            let result = op_checksig(context, script_context)?;
+            context.stack.push(encode_bool(result));
         },
+        Opcode::OpChecksigverify => return op_checksig(context, script_context),
         Opcode::OpCheckmultisig => {
-            let (n, _) = varint::decode(&stack.pop()?)?;
-            if n > 20 {
-                return Err(Error::PublicKeyIsTooMuchForCheckMultisig);
-            }
+            let result = op_checkmultisig(context, script_context)?;
+            context.stack.push(encode_bool(result));
+        },
+        Opcode::OpCheckmultisigverify => return op_checkmultisig(context, script_context),
 
-            let mut public_keys = Vec::new();
-            for _ in 0..n {
-                public_keys.push(stack.pop()?);
-            }
+        // disabled opcodes have been rejected above
+        Opcode::OpCat |
+        Opcode::OpSubstr |
+        Opcode::OpLeft |
+        Opcode::OpRight |
+        Opcode::OpInvert |
+        Opcode::OpAnd |
+        Opcode::OpOr |
+        Opcode::OpXor |
+        Opcode::Op2mul |
+        Opcode::Op2div |
+        Opcode::OpMul |
+        Opcode::OpDiv |
+        Opcode::OpMod |
+        Opcode::OpLshift |
+        Opcode::OpRshift => return Err(Error::DisabledOpcode(op)),
+    };
+    Ok(true)
+}
 
-            let (m, _) = varint::decode(&stack.pop()?)?;
-            let mut signatures = Vec::new();
-            for _ in 0..m {
-                signatures.push(stack.pop()?);
-            }
+fn timelock_flag(op: Opcode) -> VerifyFlags {
+    match op {
+        Opcode::OpChecklocktimeverify => VerifyFlags::CHECKLOCKTIMEVERIFY,
+        _ => VerifyFlags::CHECKSEQUENCEVERIFY,
+    }
+}
 
-            stack.pop()?; // fix satoshi bug
+// lock time is allowed to be 5-bytes number, and the top is kept in stack
+fn peek_lock_time(stack: &Stack, flags: VerifyFlags) -> Result<i64, Error> {
+    let ele = stack.peek(0)?;
+    let lock_time = Num::decode_script_num(ele.clone(), 5, flags.contains(VerifyFlags::MINIMALDATA))?.value();
+    if lock_time < 0 {
+        return Err(Error::NegativeLocktime);
+    }
+    Ok(lock_time)
+}
 
-            let result = check_multiple_signature(public_keys, signatures, index, z_privoder)?;
-            let stack_result = if result { vec![1] } else { vec![] };
-            stack.push(stack_result);
-        }
+// BIP65
+pub fn check_lock_time(tx: &Transaction, index: usize, lock_time: i64) -> bool {
+    let tx_lock_time = tx.locktime.value() as i64;
+    // both are block height or both are timestamp
+    let threshold = LOCKTIME_THRESHOLD as i64;
+    if (tx_lock_time < threshold) != (lock_time < threshold) {
+        return false;
+    }
+    if lock_time > tx_lock_time {
+        return false;
+    }
+    // nLockTime is ignored if the input is final
+    match tx.inputs.get(index) {
+        Some(input) => !input.sequence.is_final(),
+        None => false,
+    }
+}
+
+// BIP112
+pub fn check_sequence(tx: &Transaction, index: usize, sequence: i64) -> bool {
+    let tx_sequence = match tx.inputs.get(index) {
+        Some(input) => input.sequence.value() as i64,
+        None => return false,
     };
-    Ok(result)
+    // BIP68 is only enforced for version >= 2
+    if (tx.version.value() as i32) < 2 {
+        return false;
+    }
+    if tx_sequence & Sequence::DISABLE_FLAG as i64 != 0 {
+        return false;
+    }
+
+    let mask = (Sequence::TYPE_FLAG | Sequence::LOCKTIME_MASK) as i64;
+    let tx_sequence_masked = tx_sequence & mask;
+    let sequence_masked = sequence & mask;
+    // both are block-based or both are time-based
+    let type_flag = Sequence::TYPE_FLAG as i64;
+    if (tx_sequence_masked < type_flag) != (sequence_masked < type_flag) {
+        return false;
+    }
+
+    sequence_masked <= tx_sequence_masked
+}
+
+fn op_checksig(context: &mut ExecutionContext, script_context: &ScriptExecutionContext) -> Result<bool, Error> {
+    let pk = context.stack.pop()?;
+    let sig = context.stack.pop()?;
+    check_signature(pk, sig, &context.script_code, script_context)
+}
+
+fn op_checkmultisig(context: &mut ExecutionContext, script_context: &ScriptExecutionContext) -> Result<bool, Error> {
+    let flags = script_context.flags;
+    let n = pop_num(&mut context.stack, flags)?;
+    if !(0..=MAX_PUBKEYS_PER_MULTISIG as i64).contains(&n) {
+        return Err(Error::PublicKeyIsTooMuchForCheckMultisig);
+    }
+    context.add_op_count(n as usize)?;
+
+    let stack = &mut context.stack;
+
+    let mut public_keys = Vec::new();
+    for _ in 0..n {
+        public_keys.push(stack.pop()?);
+    }
+
+    let m = pop_num(stack, flags)?;
+    if m < 0 || m > n {
+        return Err(Error::SigCount);
+    }
+    let mut signatures = Vec::new();
+    for _ in 0..m {
+        signatures.push(stack.pop()?);
+    }
+
+    // fix satoshi bug, BIP147 requires the dummy to be empty
+    let dummy = stack.pop()?;
+    if flags.contains(VerifyFlags::NULLDUMMY) && !dummy.is_empty() {
+        return Err(Error::SigNullDummy);
+    }
+
+    check_multiple_signature(public_keys, signatures, &context.script_code, script_context)
 }
 
 #[cfg(test)]
 mod tests {
     use crate::transaction::{Transaction, TxOut};
+    use crate::script::{CommandElement, Error, ExecutionContext, Opcode, ScriptExecutionContext, SigVersion, SignatureCheckerMocker, Stack, VerifyFlags};
+    use crate::util::hash;
+    use primitive_types::U256;
+
+    fn evaluate_op(elements: Vec<Vec<u8>>, op: Opcode) -> Result<(bool, Vec<Vec<u8>>), Error> {
+        let flags = VerifyFlags::CHECKLOCKTIMEVERIFY | VerifyFlags::CHECKSEQUENCEVERIFY;
+        evaluate_op_with_flags(elements, op, flags)
+    }
+
+    // signatures sign z = 1, there is no transaction
+    fn mocker(sig_version: SigVersion, flags: VerifyFlags) -> ScriptExecutionContext<'static> {
+        ScriptExecutionContext::new(sig_version, flags).with_checker(Box::new(SignatureCheckerMocker(U256::one())))
+    }
+
+    fn evaluate_op_with_flags(elements: Vec<Vec<u8>>, op: Opcode, flags: VerifyFlags) -> Result<(bool, Vec<Vec<u8>>), Error> {
+        let mut context = ExecutionContext::from(Stack::from(elements));
+        let result = super::evaluate_command(CommandElement::Op(op), &mut context, &mocker(SigVersion::Base, flags))?;
+        Ok((result, context.stack.inner().clone()))
+    }
+
+    fn stack_after(elements: Vec<Vec<u8>>, op: Opcode) -> Vec<Vec<u8>> {
+        let (result, stack) = evaluate_op(elements, op).unwrap();
+        assert!(result);
+        stack
+    }
+
+    #[test]
+    fn operator_cast_to_bool() {
+        assert!(!super::cast_to_bool(&[]));
+        assert!(!super::cast_to_bool(&[0, 0]));
+        assert!(!super::cast_to_bool(&[0, 0x80])); // negative zero
+        assert!(super::cast_to_bool(&[0x80, 0]));
+        assert!(super::cast_to_bool(&[0, 1]));
+    }
+
+    #[test]
+    fn operator_constants() {
+        assert_eq!(stack_after(vec![], Opcode::Op0), vec![vec![]]);
+        assert_eq!(stack_after(vec![], Opcode::Op1negate), vec![vec![0x81]]);
+        assert_eq!(stack_after(vec![], Opcode::Op16), vec![vec![16]]);
+    }
+
+    #[test]
+    fn operator_nop() {
+        assert_eq!(stack_after(vec![vec![1]], Opcode::OpNop), vec![vec![1]]);
+        assert_eq!(stack_after(vec![vec![1]], Opcode::OpNop10), vec![vec![1]]);
+    }
+
+    #[test]
+    fn operator_verify() {
+        assert!(evaluate_op(vec![vec![1]], Opcode::OpVerify).unwrap().0);
+        assert!(!evaluate_op(vec![vec![0x80]], Opcode::OpVerify).unwrap().0);
+        assert!(evaluate_op(vec![vec![0; 33]], Opcode::OpVerify).is_ok()); // not limited as number
+    }
+
+    #[test]
+    fn operator_return_and_reserved() {
+        assert!(matches!(evaluate_op(vec![], Opcode::OpReturn), Err(Error::OpReturn)));
+        assert!(matches!(evaluate_op(vec![], Opcode::OpReserved), Err(Error::BadOpcode(Opcode::OpReserved))));
+        assert!(matches!(evaluate_op(vec![], Opcode::OpVer), Err(Error::BadOpcode(Opcode::OpVer))));
+    }
+
+    #[test]
+    fn operator_disabled() {
+        for op in [Opcode::OpCat, Opcode::OpSubstr, Opcode::OpInvert, Opcode::Op2mul, Opcode::OpMul, Opcode::OpLshift] {
+            assert!(matches!(evaluate_op(vec![vec![1], vec![1]], op), Err(Error::DisabledOpcode(_))));
+        }
+    }
+
+    #[test]
+    fn operator_stack_drop_dup() {
+        assert_eq!(stack_after(vec![vec![1], vec![2], vec![3]], Opcode::Op2drop), vec![vec![1]]);
+        assert_eq!(stack_after(vec![vec![1], vec![2]], Opcode::Op2dup), vec![vec![1], vec![2], vec![1], vec![2]]);
+        assert_eq!(stack_after(vec![vec![1], vec![2], vec![3]], Opcode::Op3dup), vec![vec![1], vec![2], vec![3], vec![1], vec![2], vec![3]]);
+        assert_eq!(stack_after(vec![vec![1], vec![2]], Opcode::OpDrop), vec![vec![1]]);
+        assert_eq!(stack_after(vec![vec![1]], Opcode::OpDup), vec![vec![1], vec![1]]);
+        assert!(matches!(evaluate_op(vec![vec![1]], Opcode::Op2dup), Err(Error::InvalidStackOperation)));
+    }
+
+    #[test]
+    fn operator_stack_over_rot_swap() {
+        let ele = |n: u8| vec![n];
+        assert_eq!(stack_after(vec![ele(1), ele(2), ele(3), ele(4)], Opcode::Op2over), vec![ele(1), ele(2), ele(3), ele(4), ele(1), ele(2)]);
+        assert_eq!(stack_after(vec![ele(1), ele(2), ele(3), ele(4), ele(5), ele(6)], Opcode::Op2rot), vec![ele(3), ele(4), ele(5), ele(6), ele(1), ele(2)]);
+        assert_eq!(stack_after(vec![ele(1), ele(2), ele(3), ele(4)], Opcode::Op2swap), vec![ele(3), ele(4), ele(1), ele(2)]);
+        assert_eq!(stack_after(vec![ele(1), ele(2)], Opcode::OpOver), vec![ele(1), ele(2), ele(1)]);
+        assert_eq!(stack_after(vec![ele(1), ele(2), ele(3)], Opcode::OpRot), vec![ele(2), ele(3), ele(1)]);
+        assert_eq!(stack_after(vec![ele(1), ele(2)], Opcode::OpSwap), vec![ele(2), ele(1)]);
+        assert_eq!(stack_after(vec![ele(1), ele(2)], Opcode::OpTuck), vec![ele(2), ele(1), ele(2)]);
+        assert_eq!(stack_after(vec![ele(1), ele(2)], Opcode::OpNip), vec![ele(2)]);
+    }
+
+    #[test]
+    fn operator_stack_pick_roll() {
+        let ele = |n: u8| vec![n];
+        assert_eq!(stack_after(vec![ele(1), ele(2), ele(3), ele(2)], Opcode::OpPick), vec![ele(1), ele(2), ele(3), ele(1)]);
+        assert_eq!(stack_after(vec![ele(1), ele(2), ele(3), ele(2)], Opcode::OpRoll), vec![ele(2), ele(3), ele(1)]);
+        assert_eq!(stack_after(vec![ele(1), ele(2), vec![]], Opcode::OpRoll), vec![ele(1), ele(2)]);
+        assert!(matches!(evaluate_op(vec![ele(1), ele(1)], Opcode::OpPick), Err(Error::InvalidStackOperation)));
+        assert!(matches!(evaluate_op(vec![ele(1), vec![0x81]], Opcode::OpPick), Err(Error::InvalidStackOperation)));
+    }
+
+    #[test]
+    fn operator_stack_ifdup_depth_size() {
+        assert_eq!(stack_after(vec![vec![1]], Opcode::OpIfdup), vec![vec![1], vec![1]]);
+        assert_eq!(stack_after(vec![vec![]], Opcode::OpIfdup), vec![vec![]]);
+        assert_eq!(stack_after(vec![vec![1], vec![2]], Opcode::OpDepth), vec![vec![1], vec![2], vec![2]]);
+        assert_eq!(stack_after(vec![], Opcode::OpDepth), vec![vec![]]);
+        assert_eq!(stack_after(vec![vec![0; 200]], Opcode::OpSize), vec![vec![0; 200], vec![200, 0]]);
+    }
+
+    #[test]
+    fn operator_equal() {
+        assert_eq!(stack_after(vec![vec![1], vec![1]], Opcode::OpEqual), vec![vec![1]]);
+        assert_eq!(stack_after(vec![vec![1], vec![2]], Opcode::OpEqual), vec![vec![]]);
+        assert!(!evaluate_op(vec![vec![1], vec![2]], Opcode::OpEqualverify).unwrap().0);
+    }
+
+    #[test]
+    fn operator_arithmetic_unary() {
+        assert_eq!(stack_after(vec![vec![1]], Opcode::Op1add), vec![vec![2]]);
+        assert_eq!(stack_after(vec![vec![1]], Opcode::Op1sub), vec![vec![]]);
+        assert_eq!(stack_after(vec![vec![5]], Opcode::OpNegate), vec![vec![0x85]]);
+        assert_eq!(stack_after(vec![vec![0x85]], Opcode::OpAbs), vec![vec![5]]);
+        assert_eq!(stack_after(vec![vec![]], Opcode::OpNot), vec![vec![1]]);
+        assert_eq!(stack_after(vec![vec![7]], Opcode::OpNot), vec![vec![]]);
+        assert_eq!(stack_after(vec![vec![7]], Opcode::Op0notequal), vec![vec![1]]);
+    }
+
+    #[test]
+    fn operator_arithmetic_binary() {
+        assert_eq!(stack_after(vec![vec![5], vec![3]], Opcode::OpAdd), vec![vec![8]]);
+        assert_eq!(stack_after(vec![vec![3], vec![5]], Opcode::OpSub), vec![vec![0x82]]);
+        assert_eq!(stack_after(vec![vec![1], vec![]], Opcode::OpBooland), vec![vec![]]);
+        assert_eq!(stack_after(vec![vec![1], vec![]], Opcode::OpBoolor), vec![vec![1]]);
+        assert_eq!(stack_after(vec![vec![3], vec![3]], Opcode::OpNumequal), vec![vec![1]]);
+        assert_eq!(stack_after(vec![vec![3], vec![3]], Opcode::OpNumnotequal), vec![vec![]]);
+        assert_eq!(stack_after(vec![vec![3], vec![5]], Opcode::OpLessthan), vec![vec![1]]);
+        assert_eq!(stack_after(vec![vec![3], vec![5]], Opcode::OpGreaterthan), vec![vec![]]);
+        assert_eq!(stack_after(vec![vec![5], vec![5]], Opcode::OpLessthanorequal), vec![vec![1]]);
+        assert_eq!(stack_after(vec![vec![4], vec![5]], Opcode::OpGreaterthanorequal), vec![vec![]]);
+        assert_eq!(stack_after(vec![vec![4], vec![0x85]], Opcode::OpMin), vec![vec![0x85]]);
+        assert_eq!(stack_after(vec![vec![4], vec![0x85]], Opcode::OpMax), vec![vec![4]]);
+    }
+
+    #[test]
+    fn operator_numequalverify() {
+        let (result, stack) = evaluate_op(vec![vec![3], vec![3]], Opcode::OpNumequalverify).unwrap();
+        assert!(result);
+        assert!(stack.is_empty());
+        assert!(!evaluate_op(vec![vec![3], vec![4]], Opcode::OpNumequalverify).unwrap().0);
+    }
+
+    #[test]
+    fn operator_within() {
+        assert_eq!(stack_after(vec![vec![3], vec![3], vec![5]], Opcode::OpWithin), vec![vec![1]]);
+        assert_eq!(stack_after(vec![vec![5], vec![3], vec![5]], Opcode::OpWithin), vec![vec![]]);
+        assert_eq!(stack_after(vec![vec![2], vec![3], vec![5]], Opcode::OpWithin), vec![vec![]]);
+    }
+
+    #[test]
+    fn operator_crypto() {
+        let data = b"hello".to_vec();
+        assert_eq!(stack_after(vec![data.clone()], Opcode::OpRipemd160), vec![hash::ripemd160(&data).to_vec()]);
+        assert_eq!(stack_after(vec![data.clone()], Opcode::OpSha1), vec![hash::sha1(&data).to_vec()]);
+        assert_eq!(stack_after(vec![data.clone()], Opcode::OpSha256), vec![hash::sha256(&data).to_vec()]);
+        assert_eq!(stack_after(vec![data.clone()], Opcode::OpHash160), vec![hash::hash160(&data).to_vec()]);
+        assert_eq!(stack_after(vec![data.clone()], Opcode::OpHash256), vec![hash::hash256(&data).to_vec()]);
+    }
+
+    #[test]
+    fn operator_checksig_push_false_for_invalid_signature() {
+        use crate::secp256k1::PrivateKey;
+        let sk = PrivateKey::new(1.into()).unwrap();
+        let pk = sk.pk_point().sec_compressed().unwrap();
+        assert_eq!(stack_after(vec![vec![], pk.clone()], Opcode::OpChecksig), vec![vec![]]);
+        assert!(!evaluate_op(vec![vec![], pk], Opcode::OpChecksigverify).unwrap().0);
+    }
+
+    #[test]
+    fn operator_checkmultisig_keep_order() {
+        use crate::secp256k1::PrivateKey;
+        let sk1 = PrivateKey::new(911.into()).unwrap();
+        let sk2 = PrivateKey::new(500.into()).unwrap();
+        let pk1 = sk1.pk_point().sec_compressed().unwrap();
+        let pk2 = sk2.pk_point().sec_compressed().unwrap();
+        let sig1 = [hex::decode(sk1.sign_deterministic(U256::one()).unwrap().der()).unwrap(), vec![1u8]].concat();
+        let sig2 = [hex::decode(sk2.sign_deterministic(U256::one()).unwrap().der()).unwrap(), vec![1u8]].concat();
+
+        let stack = |sig1: Vec<u8>, sig2: Vec<u8>| vec![vec![], sig1, sig2, vec![2], pk1.clone(), pk2.clone(), vec![2]];
+        assert_eq!(stack_after(stack(sig1.clone(), sig2.clone()), Opcode::OpCheckmultisig), vec![vec![1]]);
+        assert_eq!(stack_after(stack(sig2.clone(), sig1.clone()), Opcode::OpCheckmultisig), vec![vec![]]);
+        assert!(evaluate_op(stack(sig1, sig2), Opcode::OpCheckmultisigverify).unwrap().0);
+    }
+
+    #[test]
+    fn operator_checkmultisig_counts() {
+        assert!(matches!(evaluate_op(vec![vec![], vec![], vec![21]], Opcode::OpCheckmultisig), Err(Error::PublicKeyIsTooMuchForCheckMultisig)));
+        assert!(matches!(evaluate_op(vec![vec![], vec![], vec![0x81]], Opcode::OpCheckmultisig), Err(Error::PublicKeyIsTooMuchForCheckMultisig)));
+        assert!(matches!(evaluate_op(vec![vec![], vec![2], vec![1], vec![1]], Opcode::OpCheckmultisig), Err(Error::SigCount)));
+        assert!(matches!(evaluate_op(vec![vec![], vec![0x81], vec![]], Opcode::OpCheckmultisig), Err(Error::SigCount)));
+    }
+
+    #[test]
+    fn operator_checksig_encoding_flags() {
+        let pk = hex::decode("0349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278a").unwrap();
+        let op = Opcode::OpChecksig;
+
+        // invalid signature only fails the check without flags
+        assert_eq!(stack_after(vec![vec![1, 2], pk.clone()], op), vec![vec![]]);
+        let result = evaluate_op_with_flags(vec![vec![1, 2], pk.clone()], op, VerifyFlags::DERSIG);
+        assert!(matches!(result, Err(Error::SigDer)));
+        let result = evaluate_op_with_flags(vec![vec![1, 2], pk.clone()], op, VerifyFlags::NULLFAIL);
+        assert!(matches!(result, Err(Error::SigNullFail)));
+        // empty signature is allowed by all the flags
+        let (_, stack) = evaluate_op_with_flags(vec![vec![], pk.clone()], op, VerifyFlags::standard()).unwrap();
+        assert_eq!(stack, vec![vec![]]);
+
+        // public key type
+        let result = evaluate_op_with_flags(vec![vec![], vec![2, 3, 4]], op, VerifyFlags::STRICTENC);
+        assert!(matches!(result, Err(Error::PubKeyType)));
+        let uncompressed = hex::decode("04887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34").unwrap();
+        assert!(evaluate_op_with_flags(vec![vec![], uncompressed.clone()], op, VerifyFlags::WITNESS_PUBKEYTYPE).is_ok());
+        let mut context = ExecutionContext::from(Stack::from(vec![vec![], uncompressed]));
+        let result = super::evaluate_command(CommandElement::Op(op), &mut context, &mocker(SigVersion::WitnessV0, VerifyFlags::WITNESS_PUBKEYTYPE));
+        assert!(matches!(result, Err(Error::WitnessPubKeyType)));
+    }
+
+    #[test]
+    fn operator_checksig_signature_flags() {
+        use crate::secp256k1::{PrivateKey, S256Curve, Signature};
+
+        let private_key = PrivateKey::new(911.into()).unwrap();
+        let pk = private_key.pk_point().sec_compressed().unwrap();
+        let sig = private_key.sign_deterministic(U256::one()).unwrap();
+        let mut sig_all = hex::decode(sig.der()).unwrap();
+        sig_all.push(0x01);
+        assert_eq!(stack_after(vec![sig_all.clone(), pk.clone()], Opcode::OpChecksig), vec![vec![1]]);
+        let (_, stack) = evaluate_op_with_flags(vec![sig_all.clone(), pk.clone()], Opcode::OpChecksig, VerifyFlags::standard()).unwrap();
+        assert_eq!(stack, vec![vec![1]]);
+
+        // high s is still valid, but not standard
+        let high_s = Signature::new(sig.r(), S256Curve::n() - sig.s());
+        let mut sig_high_s = hex::decode(high_s.der()).unwrap();
+        sig_high_s.push(0x01);
+        let (_, stack) = evaluate_op_with_flags(vec![sig_high_s.clone(), pk.clone()], Opcode::OpChecksig, VerifyFlags::consensus()).unwrap();
+        assert_eq!(stack, vec![vec![1]]);
+        let result = evaluate_op_with_flags(vec![sig_high_s, pk.clone()], Opcode::OpChecksig, VerifyFlags::LOW_S);
+        assert!(matches!(result, Err(Error::SigHighS)));
+
+        // undefined sighash type
+        let mut sig_undefined = sig_all;
+        *sig_undefined.last_mut().unwrap() = 0x04;
+        let result = evaluate_op_with_flags(vec![sig_undefined, pk], Opcode::OpChecksig, VerifyFlags::STRICTENC);
+        assert!(matches!(result, Err(Error::SigHashType)));
+    }
+
+    #[test]
+    fn operator_checkmultisig_null_dummy() {
+        // <dummy> 0 0 OP_CHECKMULTISIG
+        let stack = vec![vec![1], vec![], vec![]];
+        assert_eq!(stack_after(stack.clone(), Opcode::OpCheckmultisig), vec![vec![1]]);
+        let result = evaluate_op_with_flags(stack, Opcode::OpCheckmultisig, VerifyFlags::NULLDUMMY);
+        assert!(matches!(result, Err(Error::SigNullDummy)));
+
+        // 0 <sig> 1 <pk> 1 OP_CHECKMULTISIG fails with a non-empty signature
+        let pk = hex::decode("0349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278a").unwrap();
+        let stack = vec![vec![], vec![1, 2], vec![1], pk, vec![1]];
+        assert_eq!(stack_after(stack.clone(), Opcode::OpCheckmultisig), vec![vec![]]);
+        let result = evaluate_op_with_flags(stack, Opcode::OpCheckmultisig, VerifyFlags::NULLFAIL);
+        assert!(matches!(result, Err(Error::SigNullFail)));
+    }
+
+    fn tx_for_timelock() -> Transaction {
+        let bytes = hex::decode("0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600").unwrap();
+        Transaction::parse(&bytes).unwrap() // locktime is 410393, sequence is 0xfffffffe
+    }
+
+    // <n> op OP_DROP OP_1
+    fn evaluate_timelock(tx: Transaction, op: Opcode, n: i64) -> Result<bool, Error> {
+        use crate::script::{Num, Script};
+
+        let script = Script::new(vec![Opcode::Op1.into(), Opcode::OpDrop.into(), op.into(), Num::from(n).encode().into()]);
+        let flags = VerifyFlags::CHECKLOCKTIMEVERIFY | VerifyFlags::CHECKSEQUENCEVERIFY;
+        script.evaluate(&ScriptExecutionContext::for_input(&tx, 0, SigVersion::Base, flags))
+    }
+
+    #[test]
+    fn operator_checklocktimeverify() {
+        let op = Opcode::OpChecklocktimeverify;
+        assert!(evaluate_timelock(tx_for_timelock(), op, 410000).unwrap());
+        assert!(evaluate_timelock(tx_for_timelock(), op, 410393).unwrap());
+        assert!(matches!(evaluate_timelock(tx_for_timelock(), op, 410394), Err(Error::UnsatisfiedLocktime)));
+        assert!(matches!(evaluate_timelock(tx_for_timelock(), op, 500_000_001), Err(Error::UnsatisfiedLocktime)));
+        assert!(matches!(evaluate_timelock(tx_for_timelock(), op, -1), Err(Error::NegativeLocktime)));
+    }
+
+    #[test]
+    fn operator_checklocktimeverify_timestamp() {
+        use crate::transaction::LockTime;
+
+        let mut tx = tx_for_timelock();
+        tx.locktime = LockTime::new(1_600_000_000);
+        let op = Opcode::OpChecklocktimeverify;
+        assert!(evaluate_timelock(tx.clone(), op, 1_500_000_000).unwrap());
+        assert!(matches!(evaluate_timelock(tx.clone(), op, 1_600_000_001), Err(Error::UnsatisfiedLocktime)));
+        assert!(matches!(evaluate_timelock(tx, op, 400_000), Err(Error::UnsatisfiedLocktime)));
+    }
+
+    #[test]
+    fn operator_checklocktimeverify_final_input() {
+        use crate::transaction::Sequence;
+
+        let mut tx = tx_for_timelock();
+        tx.inputs[0].sequence = Sequence::new(Sequence::FINAL);
+        let result = evaluate_timelock(tx, Opcode::OpChecklocktimeverify, 410000);
+        assert!(matches!(result, Err(Error::UnsatisfiedLocktime)));
+    }
+
+    #[test]
+    fn operator_checklocktimeverify_without_transaction() {
+        let result = evaluate_op(vec![vec![1]], Opcode::OpChecklocktimeverify);
+        assert!(matches!(result, Err(Error::MissingTransaction)));
+        let result = evaluate_op(vec![], Opcode::OpChecklocktimeverify);
+        assert!(matches!(result, Err(Error::InvalidStackOperation)));
+    }
+
+    #[test]
+    fn operator_checksequenceverify() {
+        use crate::transaction::{Sequence, Version, RelativeLockTime};
+
+        let op = Opcode::OpChecksequenceverify;
+        let mut tx = tx_for_timelock();
+        tx.version = Version::new(2);
+        tx.inputs[0].sequence = Sequence::from_relative_lock_time(RelativeLockTime::Blocks(10));
+        assert!(evaluate_timelock(tx.clone(), op, 10).unwrap());
+        assert!(evaluate_timelock(tx.clone(), op, 5).unwrap());
+        assert!(matches!(evaluate_timelock(tx.clone(), op, 11), Err(Error::UnsatisfiedLocktime)));
+        // time-based lock cannot be satisfied by block-based sequence
+        let time_lock = Sequence::from_relative_lock_time(RelativeLockTime::Time(1)).value() as i64;
+        assert!(matches!(evaluate_timelock(tx.clone(), op, time_lock), Err(Error::UnsatisfiedLocktime)));
+        // disable flag in operand makes it NOP
+        assert!(evaluate_timelock(tx.clone(), op, Sequence::DISABLE_FLAG as i64 | 100).unwrap());
+        assert!(matches!(evaluate_timelock(tx.clone(), op, -1), Err(Error::NegativeLocktime)));
+
+        // version 1 does not enforce BIP68
+        tx.version = Version::new(1);
+        assert!(matches!(evaluate_timelock(tx, op, 5), Err(Error::UnsatisfiedLocktime)));
+    }
+
+    #[test]
+    fn operator_checksequenceverify_disabled_input() {
+        use crate::transaction::{Sequence, Version};
+
+        let mut tx = tx_for_timelock();
+        tx.version = Version::new(2);
+        tx.inputs[0].sequence = Sequence::new(Sequence::DISABLE_FLAG | 10);
+        let result = evaluate_timelock(tx, Opcode::OpChecksequenceverify, 5);
+        assert!(matches!(result, Err(Error::UnsatisfiedLocktime)));
+    }
 
     #[test]
     fn operator_verify_transaction() {
@@ -318,7 +1244,7 @@ mod tests {
         let prevout_bytes = hex::decode("0046c323000000001600141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap();
         let prevout = TxOut::parse(&prevout_bytes).unwrap();
 
-        assert_eq!(super::verify_tx_input(&tx, 1, Some(prevout)).unwrap(), true);
+        assert_eq!(super::verify_tx_input(&tx, 1, Some(prevout), VerifyFlags::consensus()).unwrap(), true);
     }
 
     #[test]
@@ -329,7 +1255,7 @@ mod tests {
         let prevout_bytes = hex::decode("0046c323000000001600141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap();
         let prevout = TxOut::parse(&prevout_bytes).unwrap();
 
-        assert_eq!(super::verify_tx_input(&tx, 1, Some(prevout)).unwrap(), false);
+        assert_eq!(super::verify_tx_input(&tx, 1, Some(prevout), VerifyFlags::consensus()).unwrap(), false);
     }
 
     #[test]
@@ -339,7 +1265,7 @@ mod tests {
 
         let prevout = TxOut::new(1000000000u64, hex::decode("a9144733f37cf4db86fbc2efed2500b4f4e49f31202387").unwrap());
 
-        assert_eq!(super::verify_tx_input(&tx, 0, Some(prevout)).unwrap(), true);
+        assert_eq!(super::verify_tx_input(&tx, 0, Some(prevout), VerifyFlags::consensus()).unwrap(), true);
     }
 
     #[test]
@@ -349,7 +1275,7 @@ mod tests {
 
         let prevout = TxOut::new(1000000000u64, hex::decode("a9144733f37cf4db86fbc2efed2500b4f4e49f31202387").unwrap());
 
-        assert_eq!(super::verify_tx_input(&tx, 0, Some(prevout)).unwrap(), false);
+        assert_eq!(super::verify_tx_input(&tx, 0, Some(prevout), VerifyFlags::consensus()).unwrap(), false);
     }
 
     #[test]
@@ -359,6 +1285,302 @@ mod tests {
 
         let prevout = TxOut::new(4900000000u64, hex::decode("00205d1b56b63d714eebe542309525f484b7e9d6f686b3781b6f61ef925d66d6f6a0").unwrap());
 
-        assert_eq!(super::verify_tx_input(&tx, 1, Some(prevout)).unwrap(), true);
+        assert_eq!(super::verify_tx_input(&tx, 1, Some(prevout), VerifyFlags::consensus()).unwrap(), true);
+    }
+
+    // spend <pk> OP_CHECKSIG nested in p2sh-p2wsh by the BIP143 P2SH-P2WSH transaction
+    fn verify_p2sh_p2wsh(sighash: crate::transaction::SigHash, tamper: impl Fn(&mut Transaction)) -> Result<bool, Error> {
+        use crate::script::{Script, ScriptBuilder};
+        use crate::secp256k1::PrivateKey;
+
+        let bytes = hex::decode("010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000").unwrap();
+        let mut tx = Transaction::parse(&bytes).unwrap();
+        let key = PrivateKey::new(7u32.into()).unwrap();
+        let witness_script = ScriptBuilder::p2pk(key.pk_point()).unwrap();
+        let redeem_script = ScriptBuilder::p2wsh(&witness_script).unwrap();
+        let prevout = TxOut::new(987_654_321, ScriptBuilder::p2sh(&redeem_script).unwrap().raw_serialize().unwrap());
+
+        let witness_script = witness_script.raw_serialize().unwrap();
+        let z = tx.signature_hash_segwit_v0(0, &witness_script, prevout.amount(), sighash.into()).unwrap();
+        let sig = hex::decode(key.sign_deterministic(U256::from_big_endian(&z)).unwrap().der()).unwrap();
+        tx.inputs[0].script = Script::new(vec![redeem_script.raw_serialize().unwrap().into()]).raw_serialize().unwrap();
+        tx.inputs[0].witness = vec![[sig, vec![sighash.value()]].concat(), witness_script];
+        tamper(&mut tx);
+
+        super::verify_tx_input(&tx, 0, Some(prevout), VerifyFlags::standard())
+    }
+
+    #[test]
+    fn operator_verify_transaction_input_p2sh_p2wsh() {
+        use crate::transaction::SigHash;
+
+        assert!(verify_p2sh_p2wsh(SigHash::All, |_| {}).unwrap());
+        // the other outputs are not signed by SIGHASH_SINGLE
+        assert!(verify_p2sh_p2wsh(SigHash::Single, |tx| tx.outputs[1] = TxOut::new(1, vec![0x51])).unwrap());
+        assert!(matches!(verify_p2sh_p2wsh(SigHash::All, |tx| tx.outputs[1] = TxOut::new(1, vec![0x51])), Err(Error::SigNullFail)));
+        // other inputs can be added to SIGHASH_NONE|ANYONECANPAY
+        assert!(verify_p2sh_p2wsh(SigHash::NoneAnyoneCanpay, |tx| {
+            let input = tx.inputs[0].clone();
+            tx.inputs.push(input);
+            tx.outputs.clear();
+        }).unwrap());
+        // script_sig must be exactly the push of the nested witness program
+        let result = verify_p2sh_p2wsh(SigHash::All, |tx| tx.inputs[0].script.insert(0, 0x51));
+        assert!(matches!(result, Err(Error::WitnessMalleatedP2sh)));
+    }
+
+    // spend <pk> OP_CHECKSIG directly or by p2wsh, signed with any hash type byte
+    fn verify_p2pk_hash_type(hash_type: u8, witness: bool, flags: VerifyFlags) -> Result<bool, Error> {
+        use crate::script::{Script, ScriptBuilder};
+        use crate::secp256k1::PrivateKey;
+
+        let bytes = hex::decode("010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000").unwrap();
+        let mut tx = Transaction::parse(&bytes).unwrap();
+        let key = PrivateKey::new(7u32.into()).unwrap();
+        let script = ScriptBuilder::p2pk(key.pk_point()).unwrap();
+        let (prevout, z) = if witness {
+            let prevout = TxOut::new(987_654_321, ScriptBuilder::p2wsh(&script).unwrap().raw_serialize().unwrap());
+            let z = tx.signature_hash_segwit_v0(0, &script.raw_serialize().unwrap(), prevout.amount(), hash_type as u32).unwrap();
+            (prevout, z)
+        } else {
+            let prevout = TxOut::new(987_654_321, script.raw_serialize().unwrap());
+            (prevout, tx.signature_hash_legacy(0, &script.raw_serialize().unwrap(), hash_type as u32).unwrap())
+        };
+        let sig = hex::decode(key.sign_deterministic(U256::from_big_endian(&z)).unwrap().der()).unwrap();
+        let sig = [sig, vec![hash_type]].concat();
+        if witness {
+            tx.inputs[0].witness = vec![sig, script.raw_serialize().unwrap()];
+        } else {
+            tx.inputs[0].script = Script::new(vec![sig.into()]).raw_serialize().unwrap();
+        }
+
+        super::verify_tx_input(&tx, 0, Some(prevout), flags)
+    }
+
+    #[test]
+    fn operator_verify_transaction_input_undefined_hash_type() {
+        // an undefined hash type is signed as it is, only STRICTENC rejects it
+        for hash_type in [0x00, 0x04, 0x84] {
+            for witness in [false, true] {
+                assert!(verify_p2pk_hash_type(hash_type, witness, VerifyFlags::consensus()).unwrap());
+                let result = verify_p2pk_hash_type(hash_type, witness, VerifyFlags::consensus() | VerifyFlags::STRICTENC);
+                assert!(matches!(result, Err(Error::SigHashType)));
+            }
+        }
+    }
+
+    #[test]
+    fn operator_verify_transaction_input_p2sh_once() {
+        use crate::script::{Script, ScriptBuilder};
+
+        let bytes = hex::decode("010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000").unwrap();
+        let mut tx = Transaction::parse(&bytes).unwrap();
+        // the redeem script looks like p2sh itself, <00> is only hashed by it rather than run as OP_0
+        let redeem_script = Script::new(vec![Opcode::OpEqual.into(), hash::hash160(&[0x00]).to_vec().into(), Opcode::OpHash160.into()]);
+        let prevout = TxOut::new(987_654_321, ScriptBuilder::p2sh(&redeem_script).unwrap().raw_serialize().unwrap());
+        tx.inputs[0].script = Script::new(vec![redeem_script.raw_serialize().unwrap().into(), vec![0x00].into()]).raw_serialize().unwrap();
+        assert!(super::verify_tx_input(&tx, 0, Some(prevout), VerifyFlags::consensus()).unwrap());
+    }
+
+    #[test]
+    fn operator_verify_transaction_input_witness_unexpected() {
+        use crate::script::Script;
+
+        let bytes = hex::decode("010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000").unwrap();
+        let mut tx = Transaction::parse(&bytes).unwrap();
+        let prevout = TxOut::new(987_654_321, Script::new(vec![Opcode::Op1.into()]).raw_serialize().unwrap());
+        assert!(super::verify_tx_input(&tx, 0, Some(prevout.clone()), VerifyFlags::consensus()).unwrap());
+        // a witness is only allowed to spend a witness program
+        tx.inputs[0].witness = vec![vec![0x01, 0x02, 0x03]];
+        let result = super::verify_tx_input(&tx, 0, Some(prevout.clone()), VerifyFlags::consensus());
+        assert!(matches!(result, Err(Error::WitnessUnexpected)));
+        assert!(super::verify_tx_input(&tx, 0, Some(prevout), VerifyFlags::P2SH).unwrap());
+    }
+
+    #[test]
+    fn operator_verify_transaction_input_witness_malleated() {
+        let (mut tx, prevout) = tx_for_policy();
+        tx.inputs[1].script = vec![0x51];
+        assert!(matches!(super::verify_tx_input(&tx, 1, Some(prevout.clone()), VerifyFlags::consensus()), Err(Error::WitnessMalleated)));
+
+        let (mut tx, _) = tx_for_policy();
+        tx.inputs[1].witness.pop();
+        assert!(matches!(super::verify_tx_input(&tx, 1, Some(prevout), VerifyFlags::consensus()), Err(Error::WitnessProgramMismatch)));
+        // OP_0 <21 bytes>
+        let prevout = TxOut::new(1000u64, [vec![0x00, 0x15], vec![0x11; 21]].concat());
+        assert!(matches!(super::verify_tx_input(&tx, 1, Some(prevout), VerifyFlags::consensus()), Err(Error::WitnessProgramWrongLength)));
+    }
+
+    fn tx_for_policy() -> (Transaction, TxOut) {
+        let bytes = hex::decode("01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000").unwrap();
+        let tx = Transaction::parse(&bytes).unwrap();
+        let prevout_bytes = hex::decode("0046c323000000001600141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap();
+        let prevout = TxOut::parse(&prevout_bytes).unwrap();
+        (tx, prevout)
+    }
+
+    #[test]
+    fn operator_check_transaction_input_valid() {
+        let (tx, prevout) = tx_for_policy();
+        assert!(matches!(super::check_tx_input(&tx, 1, Some(prevout)).unwrap(), super::InputVerdict::Valid));
+    }
+
+    #[test]
+    fn operator_check_transaction_input_non_standard() {
+        use crate::secp256k1::{S256Curve, Signature};
+
+        // malleate the witness signature into high s, it is still valid in a block
+        let (mut tx, prevout) = tx_for_policy();
+        let sig_raw = tx.inputs[1].witness[0].clone();
+        let (sig, _) = Signature::parse_der(&sig_raw).unwrap();
+        let high_s = Signature::new(sig.r(), S256Curve::n() - sig.s());
+        let mut sig_high_s = hex::decode(high_s.der()).unwrap();
+        sig_high_s.push(*sig_raw.last().unwrap());
+        tx.inputs[1].witness[0] = sig_high_s;
+
+        let verdict = super::check_tx_input(&tx, 1, Some(prevout)).unwrap();
+        assert!(matches!(verdict, super::InputVerdict::NonStandard(Error::SigHighS)));
+    }
+
+    #[test]
+    fn operator_check_transaction_input_invalid() {
+        let (mut tx, prevout) = tx_for_policy();
+        tx.inputs[1].witness[0][10] ^= 1;
+
+        let verdict = super::check_tx_input(&tx, 1, Some(prevout)).unwrap();
+        assert!(matches!(verdict, super::InputVerdict::Invalid(Error::EvalFalse)));
+    }
+
+    #[test]
+    fn operator_verify_transaction_input_upgradable_witness_program() {
+        let (tx, _) = tx_for_policy();
+        // OP_1 <2 bytes>
+        let prevout = TxOut::new(1000u64, hex::decode("5102abcd").unwrap());
+        assert!(super::verify_tx_input(&tx, 1, Some(prevout.clone()), VerifyFlags::consensus()).unwrap());
+        let result = super::verify_tx_input(&tx, 1, Some(prevout), VerifyFlags::standard());
+        assert!(matches!(result, Err(Error::DiscourageUpgradableWitnessProgram)));
+    }
+
+    // BIP341 test vectors of key path spending, the outputs spent by the inputs
+    fn taproot_prevouts() -> Vec<TxOut> {
+        vec![
+            ("512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343", 420000000),
+            ("5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3", 462000000),
+            ("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac", 294000000),
+            ("5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e", 504000000),
+            ("512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605", 630000000),
+            ("00147dd65592d0ab2fe0d0257d571abf032cd9db93dc", 378000000),
+            ("512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831", 672000000),
+            ("5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5", 546000000),
+            ("512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220", 588000000),
+        ].into_iter().map(|(script, amount)| TxOut::new(amount, hex::decode(script).unwrap())).collect()
+    }
+
+    fn taproot_tx() -> Transaction {
+        let bytes = hex::decode("020000000001097de20cbff686da83a54981d2b9bab3586f4ca7e48f57f5b55963115f3b334e9c010000000000000000d7b7cab57b1393ace2d064f4d4a2cb8af6def61273e127517d44759b6dafdd990000000000fffffffff8e1f583384333689228c5d28eac13366be082dc57441760d957275419a41842000000006b4830450221008f3b8f8f0537c420654d2283673a761b7ee2ea3c130753103e08ce79201cf32a022079e7ab904a1980ef1c5890b648c8783f4d10103dd62f740d13daa79e298d50c201210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798fffffffff0689180aa63b30cb162a73c6d2a38b7eeda2a83ece74310fda0843ad604853b0100000000feffffffaa5202bdf6d8ccd2ee0f0202afbbb7461d9264a25e5bfd3c5a52ee1239e0ba6c0000000000feffffff956149bdc66faa968eb2be2d2faa29718acbfe3941215893a2a3446d32acd050000000000000000000e664b9773b88c09c32cb70a2a3e4da0ced63b7ba3b22f848531bbb1d5d5f4c94010000000000000000e9aa6b8e6c9de67619e6a3924ae25696bb7b694bb677a632a74ef7eadfd4eabf0000000000ffffffffa778eb6a263dc090464cd125c466b5a99667720b1c110468831d058aa1b82af10100000000ffffffff0200ca9a3b000000001976a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac807840cb0000000020ac9a87f5594be208f8532db38cff670c450ed2fea8fcdefcc9a663f78bab962b0141ed7c1647cb97379e76892be0cacff57ec4a7102aa24296ca39af7541246d8ff14d38958d4cc1e2e478e4d4a764bbfd835b16d4e314b72937b29833060b87276c030141052aedffc554b41f52b521071793a6b88d6dbca9dba94cf34c83696de0c1ec35ca9c5ed4ab28059bd606a4f3a657eec0bb96661d42921b5f50a95ad33675b54f83000141ff45f742a876139946a149ab4d9185574b98dc919d2eb6754f8abaa59d18b025637a3aa043b91817739554f4ed2026cf8022dbd83e351ce1fabc272841d2510a010140b4010dd48a617db09926f729e79c33ae0b4e94b79f04a1ae93ede6315eb3669de185a17d2b0ac9ee09fd4c64b678a0b61a0a86fa888a273c8511be83bfd6810f0247304402202b795e4de72646d76eab3f0ab27dfa30b810e856ff3a46c9a702df53bb0d8cc302203ccc4d822edab5f35caddb10af1be93583526ccfbade4b4ead350781e2f8adcd012102f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f90141a3785919a2ce3c4ce26f298c3d51619bc474ae24014bcdd31328cd8cfbab2eff3395fa0a16fe5f486d12f22a9cedded5ae74feb4bbe5351346508c5405bcfee0020141ea0c6ba90763c2d3a296ad82ba45881abb4f426b3f87af162dd24d5109edc1cdd11915095ba47c3a9963dc1e6c432939872bc49212fe34c632cd3ab9fed429c4820141bbc9584a11074e83bc8c6759ec55401f0ae7b03ef290c3139814f545b58a9f8127258000874f44bc46db7646322107d4d86aec8e73b8719a61fff761d75b5dd9810065cd1d").unwrap();
+        Transaction::parse(&bytes).unwrap()
+    }
+
+    #[test]
+    fn operator_verify_taproot_key_path() {
+        let tx = taproot_tx();
+        let prevouts = taproot_prevouts();
+        // SIGHASH_SINGLE, SIGHASH_DEFAULT and SIGHASH_ALL|ANYONECANPAY
+        for index in [0, 4, 8] {
+            assert!(super::verify_tx_input_with_prevouts(&tx, index, &prevouts, VerifyFlags::standard()).unwrap(), "input {}", index);
+        }
+        // the p2wpkh input is verified as before
+        assert!(super::verify_tx_input_with_prevouts(&tx, 5, &prevouts, VerifyFlags::standard()).unwrap());
+
+        let mut tampered = tx.clone();
+        tampered.inputs[4].witness[0][10] ^= 1;
+        let result = super::verify_tx_input_with_prevouts(&tampered, 4, &prevouts, VerifyFlags::consensus());
+        assert!(matches!(result, Err(Error::SchnorrSig)));
+        // 65 bytes signature must not use an explicit SIGHASH_DEFAULT
+        let mut tampered = tx.clone();
+        tampered.inputs[4].witness[0].push(0x00);
+        let result = super::verify_tx_input_with_prevouts(&tampered, 4, &prevouts, VerifyFlags::consensus());
+        assert!(matches!(result, Err(Error::SchnorrSigHashType)));
+        // another amount is signed
+        let mut prevouts = prevouts;
+        prevouts[2] = TxOut::new(1, prevouts[2].script().clone());
+        let result = super::verify_tx_input_with_prevouts(&tx, 4, &prevouts, VerifyFlags::consensus());
+        assert!(matches!(result, Err(Error::SchnorrSig)));
+        // without TAPROOT it is still anyone-can-spend
+        let mut flags = VerifyFlags::consensus();
+        flags.remove(VerifyFlags::TAPROOT);
+        assert!(super::verify_tx_input_with_prevouts(&tampered, 4, &prevouts, flags).unwrap());
+    }
+
+    // spend input 0 of the BIP341 transaction by a single leaf of script, the internal key is from the vectors
+    fn verify_taproot_script_path(leaf_version: u8, script: &str, mut stack: Vec<Vec<u8>>, flags: VerifyFlags) -> Result<bool, Error> {
+        use crate::script::{taproot, ScriptBuilder};
+
+        let script = hex::decode(script).unwrap();
+        let internal_key = hex::decode("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27").unwrap();
+        let leaf_hash = taproot::tap_leaf_hash(leaf_version, &script);
+        let (output_key, parity) = taproot::tap_tweak(&internal_key, Some(&leaf_hash)).unwrap();
+
+        let mut tx = taproot_tx();
+        let mut prevouts = taproot_prevouts();
+        prevouts[0] = TxOut::new(prevouts[0].amount(), ScriptBuilder::p2tr(&output_key).raw_serialize().unwrap());
+        stack.push(script);
+        stack.push([vec![leaf_version | parity as u8], internal_key].concat());
+        tx.inputs[0].witness = stack;
+
+        super::verify_tx_input_with_prevouts(&tx, 0, &prevouts, flags)
+    }
+
+    #[test]
+    fn operator_verify_taproot_script_path() {
+        let flags = VerifyFlags::standard();
+        // OP_2 OP_EQUAL
+        assert!(verify_taproot_script_path(0xc0, "5287", vec![vec![2]], flags).unwrap());
+        assert!(!verify_taproot_script_path(0xc0, "5287", vec![vec![3]], flags).unwrap());
+        // the stack must be clean
+        assert!(matches!(verify_taproot_script_path(0xc0, "5287", vec![vec![2], vec![2]], flags), Err(Error::CleanStack)));
+        // OP_IF requires exactly empty or 0x01
+        assert!(matches!(verify_taproot_script_path(0xc0, "635168", vec![vec![2]], VerifyFlags::consensus()), Err(Error::MinimalIf)));
+        // OP_CHECKMULTISIG is disabled
+        assert!(matches!(verify_taproot_script_path(0xc0, "0000ae", vec![], flags), Err(Error::TapscriptCheckmultisig)));
+        // an empty signature fails OP_CHECKSIG without error, an unknown public key type succeeds
+        assert!(verify_taproot_script_path(0xc0, &format!("20{}ac0087", "11".repeat(32)), vec![vec![]], flags).unwrap());
+        assert!(verify_taproot_script_path(0xc0, "021111ac", vec![vec![1]], VerifyFlags::consensus()).unwrap());
+        assert!(matches!(verify_taproot_script_path(0xc0, "021111ac", vec![vec![1]], flags), Err(Error::DiscourageUpgradablePubKeyType)));
+    }
+
+    #[test]
+    fn operator_verify_taproot_upgradable() {
+        let flags = VerifyFlags::consensus();
+        // OP_SUCCESS80 makes the script succeed even after an OP_RETURN
+        assert!(verify_taproot_script_path(0xc0, "6a50", vec![], flags).unwrap());
+        assert!(matches!(verify_taproot_script_path(0xc0, "6a50", vec![], VerifyFlags::standard()), Err(Error::DiscourageOpSuccess)));
+        // unknown leaf version
+        assert!(verify_taproot_script_path(0xc2, "6a", vec![], flags).unwrap());
+        let result = verify_taproot_script_path(0xc2, "6a", vec![], VerifyFlags::standard());
+        assert!(matches!(result, Err(Error::DiscourageUpgradableTaprootVersion)));
+    }
+
+    #[test]
+    fn operator_checksigadd() {
+        let pk = vec![0x11; 32];
+        let mut context = ExecutionContext::from(Stack::from(vec![vec![], vec![5], pk.clone()]));
+        let script_context = mocker(SigVersion::Tapscript, VerifyFlags::NONE);
+        let op = CommandElement::Op(Opcode::OpChecksigadd);
+        assert!(super::evaluate_command(op.clone(), &mut context, &script_context).unwrap());
+        assert_eq!(context.stack.inner(), &vec![vec![5]]);
+
+        // a 64 bytes signature which is not valid
+        let mut context = ExecutionContext::from(Stack::from(vec![vec![1; 64], vec![5], pk.clone()]));
+        context.validation_weight_left = 100;
+        let result = super::evaluate_command(op.clone(), &mut context, &script_context);
+        assert!(matches!(result, Err(Error::SchnorrSig)));
+        // out of validation weight
+        let mut context = ExecutionContext::from(Stack::from(vec![vec![1; 64], vec![5], pk]));
+        let result = super::evaluate_command(op.clone(), &mut context, &script_context);
+        assert!(matches!(result, Err(Error::TapscriptValidationWeight)));
+
+        let result = evaluate_op(vec![vec![], vec![], vec![]], Opcode::OpChecksigadd);
+        assert!(matches!(result, Err(Error::BadOpcode(Opcode::OpChecksigadd))));
     }
 }
@@ -1,8 +1,11 @@
 use std::ops::Add;
-use super::{CommandElement, operator, Stack, Error, Opcode, ZProvider};
+use std::fmt;
+use super::{CommandElement, Interpreter, Error, Num, Opcode, ScriptExecutionContext, ScriptType};
 use crate::util::{varint, Reader};
 
-#[derive(Debug, Clone)]
+pub const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+
+#[derive(Debug, Clone, Default)]
 pub struct Script {
     cmds: Vec<CommandElement>
 }
@@ -18,6 +21,13 @@ impl Add for Script {
     }
 }
 
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let asm: Vec<String> = self.cmds.iter().rev().map(|cmd| cmd.to_string()).collect();
+        write!(f, "{}", asm.join(" "))
+    }
+}
+
 impl Script {
 
     pub fn new(cmds: Vec<CommandElement>) -> Self {
@@ -43,6 +53,21 @@ impl Script {
         Self::parse_raw_reader(&mut reader)
     }
 
+    // commands before the first invalid push, as GetOp of bitcoin core stops there
+    pub fn parse_raw_lossy(bytes: &[u8]) -> Self {
+        let mut reader = Reader::new(bytes);
+        let mut cmds = Vec::new();
+        while !reader.is_empty() {
+            match CommandElement::parse_reader(&mut reader) {
+                Ok(cmd) => cmds.push(cmd),
+                Err(_) => break,
+            }
+        }
+        cmds.reverse();
+
+        Self { cmds }
+    }
+
     pub fn parse_raw_reader(reader: &mut Reader) -> Result<Self, Error> {
         let mut cmds = Vec::new();
         while !reader.is_empty() {
@@ -53,6 +78,17 @@ impl Script {
         Ok(Self { cmds })
     }
 
+    // e.g. OP_DUP OP_HASH160 <hash160> OP_EQUALVERIFY OP_CHECKSIG
+    pub fn from_asm(asm: &str) -> Result<Self, Error> {
+        let mut cmds = Vec::new();
+        for token in asm.split_whitespace() {
+            cmds.push(CommandElement::parse_asm(token)?);
+        }
+        cmds.reverse();
+
+        Ok(Self { cmds })
+    }
+
     // parse witness
     pub fn parse_witness(bytes_vec: &Vec<Vec<u8>>) -> Result<Self, Error> {
         let mut cmds = Vec::new();
@@ -87,23 +123,21 @@ impl Script {
         Ok(result)
     }
 
-    // @param index: index of input in inputs
-    pub fn evaluate(&self, index: usize, z_provider: &mut Box<dyn ZProvider>) -> Result<bool, Error> {
-        let mut cmds = self.cmds.clone();
-        let mut stack = Stack::new();
-        while let Some(cmd) = cmds.pop() {
-            if operator::evaluate_command(cmd, &mut stack, index, z_provider)? == false {
-                return Ok(false);
-            }
-            if let Some(hash160) = Self::get_hash160_if_p2sh(&cmds) {
-                if operator::evaluate_p2sh(&mut cmds, &mut stack, &hash160)? == false {
-                    return Ok(false)
-                }
-            }
+    pub fn evaluate(&self, context: &ScriptExecutionContext) -> Result<bool, Error> {
+        Interpreter::new(self, context).finish()
+    }
+
+    // FindAndDelete of bitcoin core, every minimal push of data is removed
+    pub fn find_and_delete(&self, data: &[u8]) -> Self {
+        if data.is_empty() {
+            return self.clone();
         }
+        let cmds = self.cmds.iter()
+            .filter(|cmd| !matches!(cmd, CommandElement::Data(ele) if ele == data))
+            .cloned()
+            .collect();
 
-        let ele = stack.pop()?;
-        Ok(ele.len() > 0) // 0 is empty vec in stack
+        Self { cmds }
     }
 
     pub fn get_hash160_if_p2sh(cmds: &Vec<CommandElement>) -> Option<Vec<u8>> {
@@ -112,7 +146,7 @@ impl Script {
         }
         match (&cmds[0], &cmds[1], &cmds[2]) {
             (CommandElement::Op(ops0), CommandElement::Data(data), CommandElement::Op(ops1)) => {
-                if *ops0 == Opcode::OpEqual && *ops1 == Opcode::OpHash160 {
+                if *ops0 == Opcode::OpEqual && data.len() == 20 && *ops1 == Opcode::OpHash160 {
                     Some(data.clone())
                 } else {
                     None
@@ -150,6 +184,11 @@ impl Script {
         Ok(height)
     }
 
+    // size of raw_serialize
+    pub fn size(&self) -> usize {
+        self.cmds.iter().map(|cmd| cmd.size()).sum()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.cmds.len() == 0
     }
@@ -159,17 +198,166 @@ impl Script {
     }
 
     pub fn get_index_as_data(&self, i: usize) -> Option<Vec<u8>> {
-        let element = match self.cmds.get(i) {
-            Some(ele) => ele,
-            None => return None,
+        self.cmds.get(i).and_then(|ele| ele.data().cloned())
+    }
+
+    // only constants and data pushes
+    pub fn is_push_only(&self) -> bool {
+        self.cmds.iter().all(|cmd| match cmd {
+            CommandElement::Data(_) | CommandElement::NonMinimalData(..) => true,
+            CommandElement::Op(op) => op.value() <= Opcode::Op16.value(),
+            CommandElement::Unknown(_) => false,
+        })
+    }
+
+    // type mark
+
+    // @return (version, program) if it is a witness program of BIP141
+    pub fn witness_program(&self) -> Option<(u8, Vec<u8>)> {
+        let cmds = self.cmds();
+        if cmds.len() != 2 {
+            return None;
+        }
+        let version = match &cmds[1] {
+            CommandElement::Op(Opcode::Op0) => 0,
+            CommandElement::Op(op) => op.small_int().filter(|v| (1..=16).contains(v))? as u8,
+            _ => return None,
         };
-        match element {
-            CommandElement::Data(data) => Some(data.clone()),
+        match &cmds[0] {
+            CommandElement::Data(program) if (2..=40).contains(&program.len()) => Some((version, program.clone())),
             _ => None,
         }
     }
 
-    // type mark
+    // legacy sigops, OP_CHECKMULTISIG counts as MAX_PUBKEYS_PER_MULTISIG
+    // unless accurate and the number of keys is pushed by OP_1..OP_16 right before it
+    pub fn sigop_count(&self, accurate: bool) -> usize {
+        let mut count = 0;
+        let mut last_cmd: Option<&CommandElement> = None;
+        for cmd in self.cmds.iter().rev() {
+            match cmd {
+                CommandElement::Op(Opcode::OpChecksig) | CommandElement::Op(Opcode::OpChecksigverify) => count += 1,
+                CommandElement::Op(Opcode::OpCheckmultisig) | CommandElement::Op(Opcode::OpCheckmultisigverify) => {
+                    count += match last_cmd {
+                        Some(CommandElement::Op(op)) if accurate => Self::multisig_count(op).unwrap_or(MAX_PUBKEYS_PER_MULTISIG),
+                        _ => MAX_PUBKEYS_PER_MULTISIG,
+                    }
+                }
+                _ => (),
+            }
+            last_cmd = Some(cmd);
+        }
+        count
+    }
+
+    // sigops of the redeem script if self is p2sh, the redeem script is the last push of script_sig
+    pub fn p2sh_sigop_count(&self, script_sig: &Script) -> usize {
+        if !self.is_p2sh_pubkey() {
+            return self.sigop_count(true);
+        }
+        if !script_sig.is_push_only() {
+            return 0;
+        }
+        Self::parse_raw_lossy(&script_sig.last_push_data()).sigop_count(true)
+    }
+
+    // BIP141, sigops of the witness program in self or in the redeem script of p2sh
+    pub fn witness_sigop_count(&self, script_sig: &Script, witness: &[Vec<u8>]) -> usize {
+        let program = match self.witness_program() {
+            Some(program) => Some(program),
+            None if self.is_p2sh_pubkey() && script_sig.is_push_only() => {
+                Self::parse_raw_lossy(&script_sig.last_push_data()).witness_program()
+            }
+            None => None,
+        };
+        match program {
+            Some((0, program)) if program.len() == 20 => 1,
+            Some((0, program)) if program.len() == 32 => match witness.last() {
+                Some(witness_script) => Self::parse_raw_lossy(witness_script).sigop_count(true),
+                None => 0,
+            },
+            _ => 0,
+        }
+    }
+
+    // OP_0 and OP_1..OP_16 push no data
+    fn last_push_data(&self) -> Vec<u8> {
+        self.get_bottom_as_data().unwrap_or_default()
+    }
+
+    // the solver of bitcoin core, pushes are compared by their data only
+    pub fn classify(&self) -> ScriptType {
+        if let Some((version, program)) = self.witness_program() {
+            return match (version, program.len()) {
+                (0, 20) => ScriptType::WitnessV0KeyHash(program),
+                (0, 32) => ScriptType::WitnessV0ScriptHash(program),
+                (0, _) => ScriptType::NonStandard,
+                (1, 32) => ScriptType::WitnessV1Taproot(program),
+                _ => ScriptType::WitnessUnknown { version, program },
+            };
+        }
+
+        let cmds: Vec<&CommandElement> = self.cmds.iter().rev().collect();
+        match cmds.as_slice() {
+            [CommandElement::Op(Opcode::OpReturn), rest @ ..] => {
+                let data: Option<Vec<Vec<u8>>> = rest.iter().map(|cmd| Self::push_data(cmd)).collect();
+                data.map(ScriptType::NullData).unwrap_or(ScriptType::NonStandard)
+            }
+            [CommandElement::Data(key), CommandElement::Op(Opcode::OpChecksig)] if Self::is_valid_pubkey_size(key) => {
+                ScriptType::PubKey(key.clone())
+            }
+            [
+                CommandElement::Op(Opcode::OpDup),
+                CommandElement::Op(Opcode::OpHash160),
+                CommandElement::Data(hash),
+                CommandElement::Op(Opcode::OpEqualverify),
+                CommandElement::Op(Opcode::OpChecksig),
+            ] if hash.len() == 20 => ScriptType::PubKeyHash(hash.clone()),
+            [CommandElement::Op(Opcode::OpHash160), CommandElement::Data(hash), CommandElement::Op(Opcode::OpEqual)] if hash.len() == 20 => {
+                ScriptType::ScriptHash(hash.clone())
+            }
+            [CommandElement::Op(m), keys @ .., CommandElement::Op(n), CommandElement::Op(Opcode::OpCheckmultisig)] => {
+                let (m, n) = match (Self::multisig_count(m), Self::multisig_count(n)) {
+                    (Some(m), Some(n)) if m <= n => (m, n),
+                    _ => return ScriptType::NonStandard,
+                };
+                let keys: Vec<Vec<u8>> = keys.iter()
+                    .filter_map(|cmd| match cmd {
+                        CommandElement::Data(key) if Self::is_valid_pubkey_size(key) => Some(key.clone()),
+                        _ => None,
+                    })
+                    .collect();
+                if keys.len() != n {
+                    return ScriptType::NonStandard;
+                }
+                ScriptType::Multisig { required: m, keys }
+            }
+            _ => ScriptType::NonStandard,
+        }
+    }
+
+    // data of the push, the constants OP_0, OP_1NEGATE and OP_1 to OP_16 included
+    fn push_data(cmd: &CommandElement) -> Option<Vec<u8>> {
+        match cmd {
+            CommandElement::Data(data) | CommandElement::NonMinimalData(_, data) => Some(data.clone()),
+            CommandElement::Op(op) => op.small_int().map(|n| Num::from(n).encode()),
+            CommandElement::Unknown(_) => None,
+        }
+    }
+
+    // OP_1 to OP_16
+    fn multisig_count(op: &Opcode) -> Option<usize> {
+        op.small_int().filter(|n| (1..=16).contains(n)).map(|n| n as usize)
+    }
+
+    // the size should match the prefix of sec
+    fn is_valid_pubkey_size(key: &[u8]) -> bool {
+        match key.first() {
+            Some(0x02) | Some(0x03) => key.len() == 33,
+            Some(0x04) | Some(0x06) | Some(0x07) => key.len() == 65,
+            _ => false,
+        }
+    }
 
     pub fn is_p2sh_pubkey(&self) -> bool {
         let cmds = self.cmds();
@@ -209,11 +397,16 @@ impl Script {
 
 #[cfg(test)]
 mod tests {
-    use crate::script::{CommandElement, Opcode, Script, ZProvider, ZProviderMocker, TransactionLegacyZProvider};
-    use crate::transaction::{Transaction, SigHash};
+    use crate::script::{CommandElement, Error, Interpreter, Opcode, Script, ScriptBuilder, ScriptExecutionContext, ScriptType, SigVersion, SignatureCheckerMocker, VerifyFlags};
     use primitive_types::U256;
     use crate::util::hash;
 
+    // signatures sign z, there is no transaction
+    fn mocker(z: U256) -> ScriptExecutionContext<'static> {
+        let flags = VerifyFlags::P2SH | VerifyFlags::CHECKLOCKTIMEVERIFY | VerifyFlags::CHECKSEQUENCEVERIFY;
+        ScriptExecutionContext::new(SigVersion::Base, flags).with_checker(Box::new(SignatureCheckerMocker(z)))
+    }
+
     #[test]
     fn script_evaluate_p2pk_success() {
         let sec = CommandElement::Data(hex::decode("04887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34").unwrap());
@@ -226,8 +419,8 @@ mod tests {
         let combined_script = script_pubkey + script_sig;
 
         let z = U256::from_big_endian(&hex::decode("7c076ff316692a3d7eb3c3bb0f8b1488cf72e1afcd929e29307032997a838a3d").unwrap());
-        let mut z = Box::new(ZProviderMocker(z)) as Box<dyn ZProvider>;
-        let result = combined_script.evaluate(0, &mut z).unwrap();
+        let z = mocker(z);
+        let result = combined_script.evaluate(&z).unwrap();
         assert!(result);
     }
 
@@ -242,8 +435,8 @@ mod tests {
 
         let combined_script = script_pubkey + script_sig;
         let z = U256::from_big_endian(&hex::decode("7c076ff316692a3d7eb3c3bb0f8b1488cf72e1afcd929e29307032997a838a3e").unwrap());
-        let mut z = Box::new(ZProviderMocker(z)) as Box<dyn ZProvider>;
-        let result = combined_script.evaluate(0, &mut z).unwrap();
+        let z = mocker(z);
+        let result = combined_script.evaluate(&z).unwrap();
         assert!(!result);
     }
 
@@ -257,8 +450,8 @@ mod tests {
 
         let combined_script = script_pubkey + script_sig;
         let z = U256::from_big_endian(&hex::decode("7c076ff316692a3d7eb3c3bb0f8b1488cf72e1afcd929e29307032997a838a3d").unwrap());
-        let mut z = Box::new(ZProviderMocker(z)) as Box<dyn ZProvider>;
-        let result = combined_script.evaluate(0, &mut z).unwrap();
+        let z = mocker(z);
+        let result = combined_script.evaluate(&z).unwrap();
         assert!(result);
     }
 
@@ -271,12 +464,12 @@ mod tests {
         let script_sig = Script::parse_raw(&script_sig).unwrap();
 
         let combined_script = script_pubkey + script_sig;
-        let mut z = Box::new(ZProviderMocker(U256::zero())) as Box<dyn ZProvider>;
-        let result = combined_script.evaluate(0, &mut z).unwrap();
+        let z = mocker(U256::zero());
+        let result = combined_script.evaluate(&z).unwrap();
         assert!(result);
     }
 
-    fn script_pubkey_for_check_multisig_and_z() -> (Script, Box<dyn ZProvider>) {
+    fn script_pubkey_for_check_multisig_and_z() -> (Script, ScriptExecutionContext<'static>) {
         // new key for test
         use crate::secp256k1::PrivateKey;
         let sk = PrivateKey::new(1.into()).unwrap();
@@ -289,7 +482,7 @@ mod tests {
         let script_pubkey = Script::new(vec![op_checkmultisig, op_2, sec0, sec1, op_1]);
 
         let z = U256::from_big_endian(&hex::decode("7c076ff316692a3d7eb3c3bb0f8b1488cf72e1afcd929e29307032997a838a3d").unwrap());
-        let z = Box::new(ZProviderMocker(z)) as Box<dyn ZProvider>;
+        let z = mocker(z);
 
         (script_pubkey, z)
     }
@@ -300,10 +493,10 @@ mod tests {
         let op_0 = CommandElement::Op(Opcode::Op0); // for satoshi bug
         let script_sig = Script::new(vec![sig, op_0]);
 
-        let (script_pubkey, mut z) = script_pubkey_for_check_multisig_and_z();
+        let (script_pubkey, z) = script_pubkey_for_check_multisig_and_z();
         let combined_script = script_pubkey + script_sig;
 
-        let result = combined_script.evaluate(0, &mut z).unwrap();
+        let result = combined_script.evaluate(&z).unwrap();
         assert!(result);
     }
 
@@ -313,25 +506,91 @@ mod tests {
         let op_0 = CommandElement::Op(Opcode::Op0); // for satoshi bug
         let script_sig = Script::new(vec![sig, op_0]);
 
-        let (script_pubkey, mut z) = script_pubkey_for_check_multisig_and_z();
+        let (script_pubkey, z) = script_pubkey_for_check_multisig_and_z();
         let combined_script = script_pubkey + script_sig;
 
-        let result = combined_script.evaluate(0, &mut z).unwrap();
+        let result = combined_script.evaluate(&z).unwrap();
         assert!(!result);
     }
 
+    fn evaluate_hex(script_hex: &str, sig_version: SigVersion) -> Result<bool, Error> {
+        evaluate_hex_with_flags(script_hex, sig_version, VerifyFlags::consensus())
+    }
+
+    fn evaluate_hex_with_flags(script_hex: &str, sig_version: SigVersion, flags: VerifyFlags) -> Result<bool, Error> {
+        let script = Script::parse_raw(&hex::decode(script_hex).unwrap()).unwrap();
+        let context = ScriptExecutionContext::new(sig_version, flags).with_checker(Box::new(SignatureCheckerMocker(U256::zero())));
+        script.evaluate(&context)
+    }
+
+    #[test]
+    fn script_evaluate_if_else() {
+        // OP_1 OP_IF OP_2 OP_ELSE OP_3 OP_ENDIF OP_2 OP_EQUAL
+        assert!(evaluate_hex("5163526753685287", SigVersion::Base).unwrap());
+        // OP_0 OP_IF OP_2 OP_ELSE OP_3 OP_ENDIF OP_3 OP_EQUAL
+        assert!(evaluate_hex("0063526753685387", SigVersion::Base).unwrap());
+        // OP_0 OP_NOTIF OP_2 OP_ENDIF OP_2 OP_EQUAL
+        assert!(evaluate_hex("006452685287", SigVersion::Base).unwrap());
+        // OP_1 OP_IF OP_ELSE OP_ELSE OP_0 OP_ENDIF: multiple OP_ELSE toggle the branch
+        assert!(!evaluate_hex("516367670068", SigVersion::Base).unwrap());
+    }
+
+    #[test]
+    fn script_evaluate_if_nested() {
+        // OP_1 OP_IF OP_0 OP_IF <unknown 0xba> OP_ELSE OP_2 OP_ENDIF OP_ENDIF
+        assert!(evaluate_hex("51630063ba67526868", SigVersion::Base).unwrap());
+        // OP_0 OP_IF OP_1 OP_IF OP_RETURN OP_ENDIF OP_ENDIF OP_1
+        assert!(evaluate_hex("006351636a686851", SigVersion::Base).unwrap());
+    }
+
+    #[test]
+    fn script_evaluate_if_unbalanced() {
+        assert!(matches!(evaluate_hex("516351", SigVersion::Base), Err(Error::UnbalancedConditional)));
+        assert!(matches!(evaluate_hex("5168", SigVersion::Base), Err(Error::UnbalancedConditional)));
+        assert!(matches!(evaluate_hex("5167", SigVersion::Base), Err(Error::UnbalancedConditional)));
+        assert!(matches!(evaluate_hex("63", SigVersion::Base), Err(Error::UnbalancedConditional))); // no condition on stack
+    }
+
+    #[test]
+    fn script_evaluate_if_disabled_in_unexecuted_branch() {
+        // OP_0 OP_IF OP_CAT OP_ENDIF OP_1
+        assert!(matches!(evaluate_hex("00637e6851", SigVersion::Base), Err(Error::DisabledOpcode(Opcode::OpCat))));
+        // OP_0 OP_IF OP_VERIF OP_ENDIF OP_1
+        assert!(matches!(evaluate_hex("0063656851", SigVersion::Base), Err(Error::BadOpcode(Opcode::OpVerif))));
+        // OP_0 OP_IF OP_VER OP_ENDIF OP_1: OP_VER only fails when executed
+        assert!(evaluate_hex("0063626851", SigVersion::Base).unwrap());
+    }
+
+    #[test]
+    fn script_evaluate_if_minimal() {
+        // <02> OP_IF OP_1 OP_ENDIF
+        assert!(evaluate_hex("0102635168", SigVersion::Base).unwrap());
+        assert!(evaluate_hex("0102635168", SigVersion::WitnessV0).unwrap()); // MINIMALIF is policy only
+        let flags = VerifyFlags::consensus() | VerifyFlags::MINIMALIF;
+        assert!(evaluate_hex_with_flags("0102635168", SigVersion::Base, flags).unwrap());
+        assert!(matches!(evaluate_hex_with_flags("0102635168", SigVersion::WitnessV0, flags), Err(Error::MinimalIf)));
+        // <0x00> OP_IF OP_0 OP_ELSE OP_1 OP_ENDIF
+        assert!(evaluate_hex("01006300675168", SigVersion::Base).unwrap());
+        assert!(matches!(evaluate_hex_with_flags("01006300675168", SigVersion::WitnessV0, flags), Err(Error::MinimalIf)));
+        // OP_1 OP_IF OP_1 OP_ENDIF
+        assert!(evaluate_hex("51635168", SigVersion::WitnessV0).unwrap());
+    }
+
     #[test]
     fn script_get_hash160_if_p2sh_true() {
-        let cmds = vec![Opcode::OpEqual.into(), vec![0u8].into(), Opcode::OpHash160.into()];
+        let cmds = vec![Opcode::OpEqual.into(), vec![0u8; 20].into(), Opcode::OpHash160.into()];
         let hash160 = Script::get_hash160_if_p2sh(&cmds);
-        assert_eq!(hash160, Some(vec![0u8]));
+        assert_eq!(hash160, Some(vec![0u8; 20]));
     }
 
     #[test]
     fn script_get_hash160_if_p2sh_false() {
-        let cmds = vec![Opcode::OpEqualverify.into(), vec![0u8].into(), Opcode::OpHash160.into()];
+        let cmds = vec![Opcode::OpEqualverify.into(), vec![0u8; 20].into(), Opcode::OpHash160.into()];
         let hash160 = Script::get_hash160_if_p2sh(&cmds);
         assert!(hash160.is_none());
+        // the hash of p2sh is pushed in exactly 20 bytes
+        let cmds = vec![Opcode::OpEqual.into(), vec![0u8].into(), Opcode::OpHash160.into()];
+        assert!(Script::get_hash160_if_p2sh(&cmds).is_none());
     }
 
     #[test]
@@ -339,7 +598,7 @@ mod tests {
         use crate::secp256k1::PrivateKey;
         // test data
         let z_raw = U256::one();
-        let z = ZProviderMocker(z_raw);
+        let z = mocker(z_raw);
 
         let sk1 = PrivateKey::new(911.into()).unwrap();
         let sk1_pk = sk1.pk_point().clone();
@@ -359,7 +618,7 @@ mod tests {
             ]
         );
 
-        let script_redeem_hash = hash::hash160(&script_redeem.serialize().unwrap());
+        let script_redeem_hash = hash::hash160(&script_redeem.raw_serialize().unwrap());
         let script_pubkey = Script::new(
             vec![
                 Opcode::OpEqual.into(),
@@ -370,16 +629,14 @@ mod tests {
 
         let script_sig = Script::new(
             vec![
-                script_redeem.serialize().unwrap().into(),
+                script_redeem.raw_serialize().unwrap().into(),
                 [hex::decode(sk1_sig.der()).unwrap(), vec![1u8]].concat().into(), // 1u8 for SigHash::All
                 [hex::decode(sk2_sig.der()).unwrap(), vec![1u8]].concat().into(),
                 Opcode::Op0.into()
             ]
         );
 
-        let combined_script = script_pubkey + script_sig;
-        let mut z = Box::new(z) as Box<dyn ZProvider>;
-        assert!(combined_script.evaluate(0, &mut z).unwrap());
+        assert!(Interpreter::new(&script_pubkey, &z).with_script_sig(&script_sig).finish().unwrap());
     }
 
     #[test]
@@ -387,7 +644,7 @@ mod tests {
         use crate::secp256k1::PrivateKey;
         // test data
         let z_raw = U256::one();
-        let z = ZProviderMocker(z_raw);
+        let z = mocker(z_raw);
 
         let sk1 = PrivateKey::new(911.into()).unwrap();
         let sk1_pk = sk1.pk_point().clone();
@@ -407,7 +664,7 @@ mod tests {
             ]
         );
 
-        let script_redeem_hash = hash::hash160(&script_redeem.serialize().unwrap());
+        let script_redeem_hash = hash::hash160(&script_redeem.raw_serialize().unwrap());
         let script_pubkey = Script::new(
             vec![
                 Opcode::OpEqual.into(),
@@ -418,16 +675,14 @@ mod tests {
 
         let script_sig = Script::new(
             vec![
-                script_redeem.serialize().unwrap().into(),
+                script_redeem.raw_serialize().unwrap().into(),
                 [hex::decode(sk1_sig.der()).unwrap(), vec![1u8]].concat().into(), // 1u8 for SigHash::All
                 [hex::decode(sk2_sig.der()).unwrap(), vec![1u8]].concat().into(),
                 Opcode::Op0.into()
             ]
         );
 
-        let combined_script = script_pubkey + script_sig;
-        let mut z = Box::new(z) as Box<dyn ZProvider>;
-        assert!(!combined_script.evaluate(0, &mut z).unwrap());
+        assert!(!Interpreter::new(&script_pubkey, &z).with_script_sig(&script_sig).finish().unwrap());
     }
 
     #[test]
@@ -450,17 +705,289 @@ mod tests {
     }
 
     #[test]
-    fn script_transaction_z_sighash_all() {
-        let bytes = hex::decode("0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf830\
-            3c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccf\
-            cf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8\
-            e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278\
-            afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88a\
-            c99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600").unwrap();
-        let tx = Transaction::parse(&bytes).unwrap();
+    fn script_evaluate_flags_upgradable_nops() {
+        // OP_NOP1 OP_1
+        assert!(evaluate_hex("b051", SigVersion::Base).unwrap());
+        let flags = VerifyFlags::consensus() | VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS;
+        assert!(matches!(evaluate_hex_with_flags("b051", SigVersion::Base, flags), Err(Error::DiscourageUpgradableNops)));
+        // OP_1 OP_CHECKLOCKTIMEVERIFY: OP_NOP2 without its flag, no transaction is needed
+        assert!(evaluate_hex_with_flags("51b1", SigVersion::Base, VerifyFlags::P2SH).unwrap());
+        assert!(matches!(evaluate_hex("51b1", SigVersion::Base), Err(Error::MissingTransaction)));
+        let flags = VerifyFlags::P2SH | VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS;
+        assert!(matches!(evaluate_hex_with_flags("51b1", SigVersion::Base, flags), Err(Error::DiscourageUpgradableNops)));
+    }
+
+    #[test]
+    fn script_evaluate_flags_minimal_data() {
+        // <01> OP_1 OP_EQUAL: 0x01 should be pushed by OP_1
+        assert!(evaluate_hex("01015187", SigVersion::Base).unwrap());
+        let flags = VerifyFlags::consensus() | VerifyFlags::MINIMALDATA;
+        assert!(matches!(evaluate_hex_with_flags("01015187", SigVersion::Base, flags), Err(Error::MinimalData)));
+        // <0x81> should be pushed by OP_1NEGATE
+        assert!(matches!(evaluate_hex_with_flags("0181", SigVersion::Base, flags), Err(Error::MinimalData)));
+        assert!(evaluate_hex_with_flags("0111", SigVersion::Base, flags).unwrap());
+        // OP_PUSHDATA1 for a single byte
+        assert!(evaluate_hex("4c0111", SigVersion::Base).unwrap());
+        assert!(matches!(evaluate_hex_with_flags("4c0111", SigVersion::Base, flags), Err(Error::MinimalData)));
+    }
+
+    #[test]
+    fn script_evaluate_flags_minimal_num() {
+        // <0100> OP_1ADD, 1 with a zero padding
+        assert!(evaluate_hex("0201008b", SigVersion::Base).unwrap());
+        let flags = VerifyFlags::consensus() | VerifyFlags::MINIMALDATA;
+        assert!(matches!(evaluate_hex_with_flags("0201008b", SigVersion::Base, flags), Err(Error::NumNotMinimal)));
+        // <80> OP_1ADD, negative zero
+        assert!(evaluate_hex("01808b", SigVersion::Base).unwrap());
+        assert!(matches!(evaluate_hex_with_flags("01808b", SigVersion::Base, flags), Err(Error::NumNotMinimal)));
+        // <ff00> OP_1ADD, 0x00 is needed for the sign
+        assert!(evaluate_hex_with_flags("02ff008b", SigVersion::Base, flags).unwrap());
+    }
+
+    #[test]
+    fn script_raw_serialize_large_push() {
+        let mut bytes = vec![0x4d, 0x2c, 0x01];
+        bytes.extend_from_slice(&[7u8; 300]);
+        bytes.push(0x75);
+        let script = Script::parse_raw(&bytes).unwrap();
+        assert_eq!(script.cmds()[1], CommandElement::Data(vec![7u8; 300]));
+        assert_eq!(script.size(), bytes.len());
+        assert_eq!(script.raw_serialize().unwrap(), bytes);
+        // larger than 520 bytes is parsed, but fails in the interpreter
+        let mut bytes = vec![0x4d, 0x09, 0x02];
+        bytes.extend_from_slice(&[7u8; 521]);
+        assert_eq!(Script::parse_raw(&bytes).unwrap().raw_serialize().unwrap(), bytes);
+        assert!(matches!(evaluate_hex(&hex::encode(&bytes), SigVersion::Base), Err(Error::PushSize)));
+    }
 
-        let mut provider = TransactionLegacyZProvider::from(tx);
-        let z = provider.z(0, SigHash::All, None, None).unwrap();
-        assert_eq!("27e0c5994dec7824e56dec6b2fcb342eb7cdb0d0957c2fce9882f715e85d81a6", hex::encode(z));
+    #[test]
+    fn script_evaluate_flags_clean_stack() {
+        // OP_1 OP_1
+        assert!(evaluate_hex("5151", SigVersion::Base).unwrap());
+        let flags = VerifyFlags::consensus() | VerifyFlags::CLEANSTACK;
+        assert!(matches!(evaluate_hex_with_flags("5151", SigVersion::Base, flags), Err(Error::CleanStack)));
+        // witness script always requires clean stack
+        assert!(matches!(evaluate_hex("5151", SigVersion::WitnessV0), Err(Error::CleanStack)));
+        // OP_1 OP_0 is false, not an error
+        assert!(!evaluate_hex_with_flags("5100", SigVersion::Base, flags).unwrap());
+    }
+
+    #[test]
+    fn script_evaluate_flags_const_script_code() {
+        // OP_0 OP_IF OP_CODESEPARATOR OP_ENDIF OP_1
+        assert!(evaluate_hex("0063ab6851", SigVersion::Base).unwrap());
+        let flags = VerifyFlags::consensus() | VerifyFlags::CONST_SCRIPTCODE;
+        assert!(matches!(evaluate_hex_with_flags("0063ab6851", SigVersion::Base, flags), Err(Error::OpCodeSeparator)));
+        assert!(evaluate_hex_with_flags("0063ab6851", SigVersion::WitnessV0, flags).unwrap());
+    }
+
+    #[test]
+    fn script_evaluate_flags_p2sh() {
+        // OP_HASH160 <hash160(redeem)> OP_EQUAL spent by <redeem>
+        let evaluate = |redeem: u8, flags: VerifyFlags| {
+            let script_sig = Script::new(vec![vec![redeem].into()]);
+            let script = Script::parse_raw(&hex::decode(format!("a914{}87", hex::encode(hash::hash160(&[redeem])))).unwrap()).unwrap();
+            let context = ScriptExecutionContext::new(SigVersion::Base, flags).with_checker(Box::new(SignatureCheckerMocker(U256::zero())));
+            Interpreter::new(&script, &context).with_script_sig(&script_sig).finish()
+        };
+        assert!(evaluate(0x51, VerifyFlags::consensus()).unwrap());
+        // <OP_0> is not evaluated as redeem script without P2SH
+        assert!(!evaluate(0x00, VerifyFlags::consensus()).unwrap());
+        assert!(evaluate(0x00, VerifyFlags::NONE).unwrap());
+    }
+
+    #[test]
+    fn script_is_push_only() {
+        assert!(Script::parse_raw(&hex::decode("00014f5160").unwrap()).unwrap().is_push_only());
+        assert!(!Script::parse_raw(&hex::decode("0061").unwrap()).unwrap().is_push_only());
+    }
+
+    #[test]
+    fn script_witness_program() {
+        let script = Script::parse_raw(&hex::decode("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap()).unwrap();
+        assert_eq!(script.witness_program(), Some((0, hex::decode("1d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap())));
+        let script = Script::parse_raw(&hex::decode("5102abcd").unwrap()).unwrap();
+        assert_eq!(script.witness_program(), Some((1, vec![0xab, 0xcd])));
+        let script = Script::parse_raw(&hex::decode("5101ab").unwrap()).unwrap();
+        assert_eq!(script.witness_program(), None);
+    }
+
+    // (hex, asm of decodescript)
+    fn asm_vectors() -> Vec<(&'static str, &'static str)> {
+        vec![
+            (
+                "76a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac",
+                "OP_DUP OP_HASH160 bc3b654dca7e56b04dca18f2566cdaf02e8d9ada OP_EQUALVERIFY OP_CHECKSIG",
+            ),
+            (
+                "a9144733f37cf4db86fbc2efed2500b4f4e49f31202387",
+                "OP_HASH160 4733f37cf4db86fbc2efed2500b4f4e49f312023 OP_EQUAL",
+            ),
+            (
+                "00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1",
+                "0 1d0f172a0ecb48aee1be1f2687d2963ae33f71a1",
+            ),
+            (
+                "21026dccc749adc2a9d0d89497ac511f760f45c47dc5ed9cf352a58ac706453880aeadab210255a9626aebf5e29c0e6538428ba0d1dcf6ca98ffdf086aa8ced5e0d0215ea465ac",
+                "026dccc749adc2a9d0d89497ac511f760f45c47dc5ed9cf352a58ac706453880ae OP_CHECKSIGVERIFY OP_CODESEPARATOR 0255a9626aebf5e29c0e6538428ba0d1dcf6ca98ffdf086aa8ced5e0d0215ea465 OP_CHECKSIG",
+            ),
+            (
+                "5221022626e955ea6ea6d98850c994f9107b036b1334f18ca8830bfff1295d21cfdb702103b287eaf122eea69030a0e9feed096bed8045c8b98bec453e1ffac7fbdbd4bb7152ae",
+                "2 022626e955ea6ea6d98850c994f9107b036b1334f18ca8830bfff1295d21cfdb70 03b287eaf122eea69030a0e9feed096bed8045c8b98bec453e1ffac7fbdbd4bb71 2 OP_CHECKMULTISIG",
+            ),
+            (
+                "03c0e70ab17521026dccc749adc2a9d0d89497ac511f760f45c47dc5ed9cf352a58ac706453880aeac",
+                "714688 OP_CHECKLOCKTIMEVERIFY OP_DROP 026dccc749adc2a9d0d89497ac511f760f45c47dc5ed9cf352a58ac706453880ae OP_CHECKSIG",
+            ),
+            (
+                "6a0b68656c6c6f20776f726c64",
+                "OP_RETURN 68656c6c6f20776f726c64",
+            ),
+            (
+                "4f01810180b0",
+                "-1 -1 0 OP_NOP1",
+            ),
+        ]
+    }
+
+    #[test]
+    fn script_display_asm() {
+        for (script_hex, asm) in asm_vectors() {
+            let script = Script::parse_raw(&hex::decode(script_hex).unwrap()).unwrap();
+            assert_eq!(script.to_string(), asm);
+        }
+        let script = Script::parse_raw(&hex::decode("51bb").unwrap()).unwrap();
+        assert_eq!(script.to_string(), "1 OP_UNKNOWN");
+        let script = Script::parse_raw(&hex::decode("51ba").unwrap()).unwrap();
+        assert_eq!(script.to_string(), "1 OP_CHECKSIGADD");
+    }
+
+    #[test]
+    fn script_from_asm_round_trip() {
+        // the last one is not minimal encoded, it cannot be restored from asm
+        for (script_hex, asm) in asm_vectors().into_iter().take(7) {
+            let script = Script::from_asm(asm).unwrap();
+            assert_eq!(hex::encode(script.raw_serialize().unwrap()), script_hex);
+            assert_eq!(script.to_string(), asm);
+        }
+    }
+
+    #[test]
+    fn script_from_asm() {
+        let script = Script::from_asm("OP_DUP HASH160 <bc3b654dca7e56b04dca18f2566cdaf02e8d9ada> OP_EQUALVERIFY OP_CHECKSIG").unwrap();
+        assert_eq!(hex::encode(script.raw_serialize().unwrap()), "76a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac");
+        // explicit data push of small number
+        let script = Script::from_asm("<05> 5 -129 OP_TRUE").unwrap();
+        assert_eq!(hex::encode(script.raw_serialize().unwrap()), "01055502818051");
+        assert!(matches!(Script::from_asm("OP_DUP OP_FOO"), Err(Error::InvalidAsmToken(token)) if token == "OP_FOO"));
+        assert!(Script::from_asm("").unwrap().is_empty());
+    }
+
+    fn classify_hex(hex_str: &str) -> ScriptType {
+        Script::parse_raw(&hex::decode(hex_str).unwrap()).unwrap().classify()
+    }
+
+    #[test]
+    fn script_classify_standard() {
+        // genesis coinbase output
+        let key = "04678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f";
+        assert_eq!(classify_hex(&format!("41{}ac", key)), ScriptType::PubKey(hex::decode(key).unwrap()));
+
+        let hash = "62e907b15cbf27d5425399ebf6f0fb50ebb88f18";
+        assert_eq!(classify_hex(&format!("76a914{}88ac", hash)), ScriptType::PubKeyHash(hex::decode(hash).unwrap()));
+        assert_eq!(classify_hex(&format!("a914{}87", hash)), ScriptType::ScriptHash(hex::decode(hash).unwrap()));
+
+        let hash = "751e76e8199196d454941c45d1b3a323f1433bd6";
+        assert_eq!(classify_hex(&format!("0014{}", hash)), ScriptType::WitnessV0KeyHash(hex::decode(hash).unwrap()));
+        let hash = "1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262";
+        assert_eq!(classify_hex(&format!("0020{}", hash)), ScriptType::WitnessV0ScriptHash(hex::decode(hash).unwrap()));
+        let key = "a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c";
+        assert_eq!(classify_hex(&format!("5120{}", key)), ScriptType::WitnessV1Taproot(hex::decode(key).unwrap()));
+        assert_eq!(classify_hex("52020001"), ScriptType::WitnessUnknown { version: 2, program: vec![0, 1] });
+    }
+
+    #[test]
+    fn script_classify_multisig() {
+        let key1 = "022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e";
+        let key2 = "03a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c0";
+        let script_type = classify_hex(&format!("5121{}21{}52ae", key1, key2));
+        assert_eq!(script_type, ScriptType::Multisig { required: 1, keys: vec![hex::decode(key1).unwrap(), hex::decode(key2).unwrap()] });
+
+        // m > n, n does not match the keys, invalid key size
+        assert_eq!(classify_hex(&format!("5321{}21{}52ae", key1, key2)), ScriptType::NonStandard);
+        assert_eq!(classify_hex(&format!("5121{}21{}53ae", key1, key2)), ScriptType::NonStandard);
+        assert_eq!(classify_hex(&format!("5121{}2104{}52ae", key1, &key2[2..])), ScriptType::NonStandard);
+    }
+
+    #[test]
+    fn script_classify_null_data() {
+        assert_eq!(classify_hex("6a04deadbeef"), ScriptType::NullData(vec![vec![0xde, 0xad, 0xbe, 0xef]]));
+        assert_eq!(classify_hex("6a"), ScriptType::NullData(vec![]));
+        assert_eq!(classify_hex("6a0051"), ScriptType::NullData(vec![vec![], vec![1]]));
+        assert_eq!(classify_hex("6a04deadbeef75"), ScriptType::NonStandard);
+    }
+
+    #[test]
+    fn script_classify_non_standard() {
+        // version 0 with a program of neither 20 nor 32 bytes
+        assert_eq!(classify_hex("0015000000000000000000000000000000000000000000"), ScriptType::NonStandard);
+        assert_eq!(classify_hex("51"), ScriptType::NonStandard);
+        assert_eq!(classify_hex("0500000000ac"), ScriptType::NonStandard);
+        assert_eq!(classify_hex("").name(), "nonstandard");
+    }
+
+    fn script_from_hex(hex_str: &str) -> Script {
+        Script::parse_raw(&hex::decode(hex_str).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn script_sigop_count() {
+        assert_eq!(script_from_hex("").sigop_count(true), 0);
+
+        // OP_1 <key> <key> OP_2 OP_CHECKMULTISIG
+        let key = "02".to_owned() + &"00".repeat(32);
+        let multisig = format!("5121{}21{}52ae", key, key);
+        assert_eq!(script_from_hex(&multisig).sigop_count(true), 2);
+        assert_eq!(script_from_hex(&multisig).sigop_count(false), 20);
+
+        // ... OP_IF OP_CHECKSIG OP_ENDIF
+        let script = script_from_hex(&format!("{}63ac68", multisig));
+        assert_eq!(script.sigop_count(true), 3);
+        assert_eq!(script.sigop_count(false), 21);
+
+        // the number of keys is not pushed by OP_n
+        assert_eq!(script_from_hex(&format!("5121{}21{}0102af", key, key)).sigop_count(true), 20);
+        // OP_0 OP_CHECKMULTISIG
+        assert_eq!(script_from_hex("00ae").sigop_count(true), 20);
+        // OP_CHECKSIGVERIFY then a broken push
+        assert_eq!(Script::parse_raw_lossy(&hex::decode("ad4c").unwrap()).sigop_count(true), 1);
+    }
+
+    #[test]
+    fn script_p2sh_sigop_count() {
+        let key = "02".to_owned() + &"00".repeat(32);
+        let redeem_hex = format!("5121{}21{}52ae63ac68", key, key);
+        let redeem_bytes = hex::decode(&redeem_hex).unwrap();
+        let p2sh = ScriptBuilder::p2sh(&script_from_hex(&redeem_hex)).unwrap();
+        assert_eq!(p2sh.sigop_count(true), 0);
+
+        // OP_0 <redeem script>
+        let script_sig = script_from_hex(&format!("004c{:02x}{}", redeem_bytes.len(), redeem_hex));
+        assert_eq!(p2sh.p2sh_sigop_count(&script_sig), 3);
+        // not push only
+        let script_sig = script_from_hex(&format!("4c{:02x}{}75", redeem_bytes.len(), redeem_hex));
+        assert_eq!(p2sh.p2sh_sigop_count(&script_sig), 0);
+        // not p2sh
+        assert_eq!(script_from_hex(&redeem_hex).p2sh_sigop_count(&script_sig), 3);
+    }
+
+    #[test]
+    fn script_find_and_delete() {
+        let script = Script::from_asm("<0102> OP_DROP <0102> <010203> OP_CHECKSIG").unwrap();
+        assert_eq!(script.find_and_delete(&[1, 2]).to_string(), "OP_DROP 197121 OP_CHECKSIG");
+        // a non-minimal push is not the same bytes
+        let script = Script::parse_raw(&hex::decode("4c020102ac").unwrap()).unwrap();
+        assert_eq!(script.find_and_delete(&[1, 2]).raw_serialize().unwrap(), hex::decode("4c020102ac").unwrap());
+        assert_eq!(script.find_and_delete(&[]).raw_serialize().unwrap(), hex::decode("4c020102ac").unwrap());
     }
 }
@@ -0,0 +1,140 @@
+use crate::util::hash::Hash256Value;
+use crate::transaction::{Transaction, TxOut};
+use super::{operator, Error, Script, SigVersion, VerifyFlags, SignatureChecker, TransactionSignatureChecker};
+
+// what a script is executed against besides its stacks: the spending transaction and the rules
+pub struct ScriptExecutionContext<'a> {
+    pub tx: Option<&'a Transaction>, // None for a script executed alone
+    pub prevouts: Vec<Option<TxOut>>, // the output spent by each input, if known
+    pub input_index: usize,
+    pub sig_version: SigVersion,
+    pub flags: VerifyFlags,
+    pub annex: Option<Vec<u8>>, // only for taproot
+    pub leaf_hash: Option<Hash256Value>, // only for tapscript
+    checker: Box<dyn SignatureChecker>,
+}
+
+impl<'a> ScriptExecutionContext<'a> {
+    // without transaction, signatures are checked by the given checker and timelocks always fail
+    pub fn new(sig_version: SigVersion, flags: VerifyFlags) -> Self {
+        Self {
+            tx: None,
+            prevouts: Vec::new(),
+            input_index: 0,
+            sig_version,
+            flags,
+            annex: None,
+            leaf_hash: None,
+            checker: Box::new(TransactionSignatureChecker),
+        }
+    }
+
+    // @param input_index: index of input in inputs
+    pub fn for_input(tx: &'a Transaction, input_index: usize, sig_version: SigVersion, flags: VerifyFlags) -> Self {
+        Self {
+            tx: Some(tx),
+            prevouts: vec![None; tx.inputs.len()],
+            input_index,
+            ..Self::new(sig_version, flags)
+        }
+    }
+
+    // the output spent by this input, enough before taproot
+    pub fn with_prevout(mut self, prevout: TxOut) -> Self {
+        if self.input_index >= self.prevouts.len() {
+            self.prevouts.resize(self.input_index + 1, None);
+        }
+        self.prevouts[self.input_index] = Some(prevout);
+        self
+    }
+
+    pub fn with_prevouts(mut self, prevouts: &[TxOut]) -> Self {
+        self.prevouts = prevouts.iter().cloned().map(Some).collect();
+        self
+    }
+
+    pub fn with_checker(mut self, checker: Box<dyn SignatureChecker>) -> Self {
+        self.checker = checker;
+        self
+    }
+
+    pub fn prevout(&self) -> Option<&TxOut> {
+        self.prevouts.get(self.input_index)?.as_ref()
+    }
+
+    // all spent outputs, None if any of them is unknown
+    pub fn prevouts(&self) -> Option<Vec<TxOut>> {
+        if self.tx.map(|tx| tx.inputs.len()) != Some(self.prevouts.len()) {
+            return None;
+        }
+        self.prevouts.iter().cloned().collect()
+    }
+
+    pub fn signature_hash(&self, script_code: &Script, hash_type: u32) -> Result<Hash256Value, Error> {
+        self.checker.signature_hash(self, script_code, hash_type)
+    }
+
+    pub fn signature_hash_taproot(&self, hash_type: u8, code_separator_pos: Option<usize>) -> Result<Hash256Value, Error> {
+        self.checker.signature_hash_taproot(self, hash_type, code_separator_pos)
+    }
+
+    // BIP65
+    pub fn check_lock_time(&self, lock_time: i64) -> Result<bool, Error> {
+        let tx = self.tx.ok_or(Error::MissingTransaction)?;
+        Ok(operator::check_lock_time(tx, self.input_index, lock_time))
+    }
+
+    // BIP112
+    pub fn check_sequence(&self, sequence: i64) -> Result<bool, Error> {
+        let tx = self.tx.ok_or(Error::MissingTransaction)?;
+        Ok(operator::check_sequence(tx, self.input_index, sequence))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::script::{Error, Script, SigVersion, VerifyFlags};
+    use crate::transaction::{Transaction, TxOut, SigHash};
+    use super::ScriptExecutionContext;
+
+    fn tx() -> Transaction {
+        let bytes = hex::decode("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000").unwrap();
+        Transaction::parse(&bytes).unwrap()
+    }
+
+    #[test]
+    fn script_execution_context_witness_v0() {
+        let tx = tx();
+        let script_code = Script::parse_raw(&hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap()).unwrap();
+        let prevout = TxOut::new(600_000_000, hex::decode("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap());
+
+        // BIP143 signs the amount, so the prevout is required
+        let context = ScriptExecutionContext::for_input(&tx, 1, SigVersion::WitnessV0, VerifyFlags::consensus());
+        assert!(matches!(context.signature_hash(&script_code, SigHash::All.into()), Err(Error::MissingTransaction)));
+        let context = context.with_prevout(prevout);
+        let z = context.signature_hash(&script_code, SigHash::All.into()).unwrap();
+        assert_eq!(hex::encode(z), "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670");
+        // taproot needs all the prevouts
+        assert!(context.prevouts().is_none());
+        assert!(context.signature_hash_taproot(0x00, None).is_err());
+    }
+
+    #[test]
+    fn script_execution_context_legacy() {
+        let bytes = hex::decode("0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600").unwrap();
+        let tx = Transaction::parse(&bytes).unwrap();
+        let script_code = Script::parse_raw(&hex::decode("76a914a802fc56c704ce87c42d7c92eb75e7896bdc41ae88ac").unwrap()).unwrap();
+
+        let context = ScriptExecutionContext::for_input(&tx, 0, SigVersion::Base, VerifyFlags::consensus());
+        let z = context.signature_hash(&script_code, SigHash::All.into()).unwrap();
+        assert_eq!(hex::encode(z), "27e0c5994dec7824e56dec6b2fcb342eb7cdb0d0957c2fce9882f715e85d81a6");
+    }
+
+    #[test]
+    fn script_execution_context_without_transaction() {
+        let context = ScriptExecutionContext::new(SigVersion::Base, VerifyFlags::consensus());
+        assert!(matches!(context.check_lock_time(0), Err(Error::MissingTransaction)));
+        assert!(matches!(context.check_sequence(0), Err(Error::MissingTransaction)));
+        assert!(matches!(context.signature_hash(&Script::new(vec![]), SigHash::All.into()), Err(Error::MissingTransaction)));
+    }
+}
@@ -0,0 +1,49 @@
+// standard templates of script_pubkey, with the data extracted from them
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptType {
+    PubKey(Vec<u8>),         // <pubkey> OP_CHECKSIG
+    PubKeyHash(Vec<u8>),     // OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+    ScriptHash(Vec<u8>),     // OP_HASH160 <20 bytes> OP_EQUAL
+    Multisig { required: usize, keys: Vec<Vec<u8>> }, // OP_m <pubkey>... OP_n OP_CHECKMULTISIG
+    NullData(Vec<Vec<u8>>),  // OP_RETURN <data>...
+    WitnessV0KeyHash(Vec<u8>),
+    WitnessV0ScriptHash(Vec<u8>),
+    WitnessV1Taproot(Vec<u8>),
+    WitnessUnknown { version: u8, program: Vec<u8> },
+    NonStandard,
+}
+
+impl ScriptType {
+    // the same names as the "type" of decodescript in bitcoin core
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PubKey(_) => "pubkey",
+            Self::PubKeyHash(_) => "pubkeyhash",
+            Self::ScriptHash(_) => "scripthash",
+            Self::Multisig { .. } => "multisig",
+            Self::NullData(_) => "nulldata",
+            Self::WitnessV0KeyHash(_) => "witness_v0_keyhash",
+            Self::WitnessV0ScriptHash(_) => "witness_v0_scripthash",
+            Self::WitnessV1Taproot(_) => "witness_v1_taproot",
+            Self::WitnessUnknown { .. } => "witness_unknown",
+            Self::NonStandard => "nonstandard",
+        }
+    }
+
+    pub fn is_standard(&self) -> bool {
+        *self != Self::NonStandard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScriptType;
+
+    #[test]
+    fn script_type_name() {
+        assert_eq!(ScriptType::PubKeyHash(vec![0; 20]).name(), "pubkeyhash");
+        assert_eq!(ScriptType::WitnessUnknown { version: 2, program: vec![0; 2] }.name(), "witness_unknown");
+        assert!(ScriptType::NullData(vec![]).is_standard());
+        assert!(!ScriptType::NonStandard.is_standard());
+    }
+}
@@ -0,0 +1,7 @@
+// which rules the script is executed with
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SigVersion {
+    Base,
+    WitnessV0,
+    Tapscript, // BIP342, leaf version 0xc0 of taproot script path
+}
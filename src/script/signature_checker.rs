@@ -0,0 +1,67 @@
+use crate::util::hash::Hash256Value;
+use primitive_types::U256;
+use super::{Error, Script, ScriptExecutionContext, SigVersion};
+
+// the message signed by a signature of the input, mocked in tests without a transaction
+pub trait SignatureChecker {
+    // legacy or BIP143 signature message, chosen by the sig_version of context
+    // @param script_code: the executed script after the last OP_CODESEPARATOR
+    // @param hash_type: the last byte of signature, the undefined ones are signed as well
+    fn signature_hash(&self, context: &ScriptExecutionContext, script_code: &Script, hash_type: u32) -> Result<Hash256Value, Error>;
+
+    // BIP341 signature message, hash_type 0x00 is SIGHASH_DEFAULT
+    // @param code_separator_pos: opcode position of the last executed OP_CODESEPARATOR in tapscript
+    fn signature_hash_taproot(&self, context: &ScriptExecutionContext, hash_type: u8, code_separator_pos: Option<usize>) -> Result<Hash256Value, Error>;
+}
+
+// hash the transaction of context, as bitcoin core does
+pub struct TransactionSignatureChecker;
+
+impl SignatureChecker for TransactionSignatureChecker {
+    fn signature_hash(&self, context: &ScriptExecutionContext, script_code: &Script, hash_type: u32) -> Result<Hash256Value, Error> {
+        let tx = context.tx.ok_or(Error::MissingTransaction)?;
+        let script_code = script_code.raw_serialize()?;
+        let z = match context.sig_version {
+            SigVersion::Base => tx.signature_hash_legacy(context.input_index, &script_code, hash_type)?,
+            SigVersion::WitnessV0 => {
+                // BIP143 signs the amount of the spent output
+                let prevout = context.prevout().ok_or(Error::MissingTransaction)?;
+                tx.signature_hash_segwit_v0(context.input_index, &script_code, prevout.amount(), hash_type)?
+            },
+            // legacy and BIP143 signatures are not allowed in tapscript
+            SigVersion::Tapscript => return Err(Error::NotImpl),
+        };
+        Ok(z)
+    }
+
+    fn signature_hash_taproot(&self, context: &ScriptExecutionContext, hash_type: u8, code_separator_pos: Option<usize>) -> Result<Hash256Value, Error> {
+        let tx = context.tx.ok_or(Error::MissingTransaction)?;
+        // BIP341 signs the amounts and script_pubkeys of all spent outputs
+        let prevouts = context.prevouts().ok_or(Error::MissingTransaction)?;
+        let leaf = context.leaf_hash.map(|leaf_hash| {
+            let pos = code_separator_pos.map(|pos| pos as u32).unwrap_or(0xffff_ffff);
+            (leaf_hash, pos)
+        });
+        let z = tx.signature_hash_taproot(context.input_index, &prevouts, hash_type, context.annex.as_deref(), leaf)?;
+        Ok(z)
+    }
+}
+
+// mock
+pub struct SignatureCheckerMocker(pub U256);
+
+impl SignatureChecker for SignatureCheckerMocker {
+    fn signature_hash(&self, _: &ScriptExecutionContext, _: &Script, _: u32) -> Result<Hash256Value, Error> {
+        let mut result = [0u8; 32];
+        self.0.to_big_endian(&mut result);
+
+        Ok(result)
+    }
+
+    fn signature_hash_taproot(&self, _: &ScriptExecutionContext, _: u8, _: Option<usize>) -> Result<Hash256Value, Error> {
+        let mut result = [0u8; 32];
+        self.0.to_big_endian(&mut result);
+
+        Ok(result)
+    }
+}
@@ -1,6 +1,7 @@
 use super::Error;
 use std::fmt;
 
+#[derive(Default)]
 pub struct Stack(Vec<Vec<u8>>);
 
 impl Stack {
@@ -15,6 +16,52 @@ impl Stack {
     pub fn pop(&mut self) -> Result<Vec<u8>, Error> {
         self.0.pop().ok_or(Error::EmptyStack)
     }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn inner(&self) -> &Vec<Vec<u8>> {
+        &self.0
+    }
+
+    // depth 0 is the top of stack
+    pub fn peek(&self, depth: usize) -> Result<&Vec<u8>, Error> {
+        let i = self.index_of_depth(depth)?;
+        Ok(&self.0[i])
+    }
+
+    pub fn remove(&mut self, depth: usize) -> Result<Vec<u8>, Error> {
+        let i = self.index_of_depth(depth)?;
+        Ok(self.0.remove(i))
+    }
+
+    // after insert, ele will be at `depth`
+    pub fn insert(&mut self, depth: usize, ele: Vec<u8>) -> Result<(), Error> {
+        if depth > self.0.len() {
+            return Err(Error::InvalidStackOperation);
+        }
+        let i = self.0.len() - depth;
+        self.0.insert(i, ele);
+        Ok(())
+    }
+
+    fn index_of_depth(&self, depth: usize) -> Result<usize, Error> {
+        if depth >= self.0.len() {
+            return Err(Error::InvalidStackOperation);
+        }
+        Ok(self.0.len() - 1 - depth)
+    }
+}
+
+impl From<Vec<Vec<u8>>> for Stack {
+    fn from(elements: Vec<Vec<u8>>) -> Self {
+        Self(elements)
+    }
 }
 
 impl fmt::Debug for Stack {
@@ -31,3 +78,20 @@ impl fmt::Debug for Stack {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Stack;
+
+    #[test]
+    fn stack_peek_remove_insert() {
+        let mut stack = Stack::from(vec![vec![1], vec![2], vec![3]]);
+        assert_eq!(stack.peek(0).unwrap(), &vec![3]);
+        assert_eq!(stack.peek(2).unwrap(), &vec![1]);
+        assert!(stack.peek(3).is_err());
+
+        assert_eq!(stack.remove(1).unwrap(), vec![2]);
+        stack.insert(1, vec![4]).unwrap();
+        assert_eq!(stack.inner(), &vec![vec![1], vec![4], vec![3]]);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::secp256k1::{S256Curve, S256Point};
+use crate::transaction::{Transaction, TxOut};
+use crate::util::{hash::{self, Hash256Value}, varint, Reader};
+use primitive_types::U256;
+use super::{operator, CommandElement, Error, Interpreter, Script, ScriptExecutionContext, SigVersion, Stack, VerifyFlags, MAX_SCRIPT_ELEMENT_SIZE, MAX_STACK_SIZE, VALIDATION_WEIGHT_OFFSET};
+
+pub const TAPROOT_LEAF_MASK: u8 = 0xfe;
+pub const TAPROOT_LEAF_TAPSCRIPT: u8 = 0xc0;
+pub const TAPROOT_CONTROL_BASE_SIZE: usize = 33;
+pub const TAPROOT_CONTROL_NODE_SIZE: usize = 32;
+pub const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+// the last witness element starting with it is the annex
+pub const ANNEX_TAG: u8 = 0x50;
+
+// the last witness element of script path spending
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlBlock {
+    leaf_version: u8,
+    output_key_parity: u8, // 1 if y of the output key is odd
+    internal_key: [u8; 32],
+    path: Vec<Hash256Value>, // from the leaf up to the root
+}
+
+impl ControlBlock {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let len = bytes.len();
+        if len < TAPROOT_CONTROL_BASE_SIZE
+            || !(len - TAPROOT_CONTROL_BASE_SIZE).is_multiple_of(TAPROOT_CONTROL_NODE_SIZE)
+            || (len - TAPROOT_CONTROL_BASE_SIZE) / TAPROOT_CONTROL_NODE_SIZE > TAPROOT_CONTROL_MAX_NODE_COUNT {
+            return Err(Error::TaprootWrongControlSize);
+        }
+        let mut internal_key = [0u8; 32];
+        internal_key.copy_from_slice(&bytes[1..TAPROOT_CONTROL_BASE_SIZE]);
+        let path = bytes[TAPROOT_CONTROL_BASE_SIZE..]
+            .chunks(TAPROOT_CONTROL_NODE_SIZE)
+            .map(hash::convert_slice_into_hash256)
+            .collect();
+
+        Ok(Self {
+            leaf_version: bytes[0] & TAPROOT_LEAF_MASK,
+            output_key_parity: bytes[0] & 1,
+            internal_key,
+            path,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![self.leaf_version | self.output_key_parity];
+        result.extend_from_slice(&self.internal_key);
+        for node in &self.path {
+            result.extend_from_slice(node);
+        }
+        result
+    }
+
+    pub fn leaf_version(&self) -> u8 {
+        self.leaf_version
+    }
+
+    pub fn internal_key(&self) -> &[u8; 32] {
+        &self.internal_key
+    }
+
+    pub fn path(&self) -> &Vec<Hash256Value> {
+        &self.path
+    }
+
+    pub fn merkle_root(&self, leaf_hash: &Hash256Value) -> Hash256Value {
+        self.path.iter().fold(*leaf_hash, |node, sibling| tap_branch_hash(&node, sibling))
+    }
+
+    // the output key must be the internal key tweaked by the merkle root
+    pub fn verify_commitment(&self, output_key: &[u8], leaf_hash: &Hash256Value) -> bool {
+        let merkle_root = self.merkle_root(leaf_hash);
+        match tap_tweak(&self.internal_key, Some(&merkle_root)) {
+            Ok((key, parity)) => key[..] == *output_key && parity == (self.output_key_parity == 1),
+            Err(_) => false,
+        }
+    }
+}
+
+pub fn tap_leaf_hash(leaf_version: u8, script: &[u8]) -> Hash256Value {
+    let mut msg = vec![leaf_version];
+    msg.append(&mut varint::encode(script.len() as u64));
+    msg.extend_from_slice(script);
+    hash::tagged_hash("TapLeaf", &msg)
+}
+
+// children are sorted, so the path does not need to record the direction
+pub fn tap_branch_hash(a: &Hash256Value, b: &Hash256Value) -> Hash256Value {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    hash::tagged_hash("TapBranch", &[&left[..], &right[..]].concat())
+}
+
+// Q = P + hash(P || merkle_root)G
+// @return (x-only output key, y of the output key is odd)
+pub fn tap_tweak(internal_key: &[u8], merkle_root: Option<&Hash256Value>) -> Result<([u8; 32], bool), Error> {
+    let point = S256Point::lift_x(internal_key).map_err(|_| Error::InvalidPublicKey)?;
+    let mut msg = internal_key.to_vec();
+    if let Some(root) = merkle_root {
+        msg.extend_from_slice(root);
+    }
+    let tweak = U256::from_big_endian(&hash::tagged_hash("TapTweak", &msg));
+    if tweak >= S256Curve::n() {
+        return Err(Error::InvalidPublicKey);
+    }
+    let output = point + S256Point::g() * tweak;
+    let key = output.x_only().ok_or(Error::InvalidPublicKey)?;
+
+    Ok((key, !output.has_even_y()))
+}
+
+// BIP342, these opcodes make the script succeed once they appear, even unexecuted
+pub fn is_op_success(byte: u8) -> bool {
+    matches!(byte, 80 | 98 | 126..=129 | 131..=134 | 137..=138 | 141..=142 | 149..=153 | 187..=254)
+}
+
+// BIP341 verification of a segwit v1 output with a 32 bytes program, which is not wrapped in p2sh
+pub fn verify_taproot(tx: &Transaction, input_index: usize, prevouts: &[TxOut], program: &[u8], flags: VerifyFlags) -> Result<bool, Error> {
+    let witness = &tx.inputs[input_index].witness;
+    if witness.is_empty() {
+        return Err(Error::WitnessProgramWitnessEmpty);
+    }
+    let mut stack = witness.clone();
+    let mut context = ScriptExecutionContext::for_input(tx, input_index, SigVersion::Tapscript, flags).with_prevouts(prevouts);
+    if stack.len() >= 2 && stack.last().is_some_and(|ele| ele.first() == Some(&ANNEX_TAG)) {
+        context.annex = stack.pop();
+    }
+
+    // key path spending
+    if stack.len() == 1 {
+        operator::check_schnorr_signature(&stack[0], program, &context, None)?;
+        return Ok(true);
+    }
+
+    // script path spending
+    let control = ControlBlock::parse(&stack.pop().expect("at least 2 elements"))?;
+    let script = stack.pop().expect("at least 2 elements");
+    let leaf_hash = tap_leaf_hash(control.leaf_version(), &script);
+    if !control.verify_commitment(program, &leaf_hash) {
+        return Err(Error::WitnessProgramMismatch);
+    }
+    if control.leaf_version() != TAPROOT_LEAF_TAPSCRIPT {
+        if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION) {
+            return Err(Error::DiscourageUpgradableTaprootVersion);
+        }
+        return Ok(true);
+    }
+
+    // OP_SUCCESS is checked before any other rule, the rest of the script does not need to be parsable
+    let mut reader = Reader::new(&script);
+    while !reader.is_empty() {
+        let byte = match CommandElement::parse_reader(&mut reader)? {
+            CommandElement::Op(op) => op.value(),
+            CommandElement::Unknown(byte) => byte,
+            _ => continue,
+        };
+        if is_op_success(byte) {
+            if flags.contains(VerifyFlags::DISCOURAGE_OP_SUCCESS) {
+                return Err(Error::DiscourageOpSuccess);
+            }
+            return Ok(true);
+        }
+    }
+
+    if stack.len() > MAX_STACK_SIZE {
+        return Err(Error::StackSize);
+    }
+    if stack.iter().any(|ele| ele.len() > MAX_SCRIPT_ELEMENT_SIZE) {
+        return Err(Error::PushSize);
+    }
+    let validation_weight = witness_size(witness) as i64 + VALIDATION_WEIGHT_OFFSET;
+
+    context.leaf_hash = Some(leaf_hash);
+    let script = Script::parse_raw(&script)?;
+    Interpreter::new(&script, &context)
+        .with_stack(Stack::from(stack))
+        .with_validation_weight(validation_weight)
+        .finish()
+}
+
+// serialized size of the witness, including the count of elements
+fn witness_size(witness: &[Vec<u8>]) -> usize {
+    let mut size = varint::encode(witness.len() as u64).len();
+    for ele in witness {
+        size += varint::encode(ele.len() as u64).len() + ele.len();
+    }
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::hash;
+    use super::{tap_leaf_hash, tap_tweak, is_op_success, ControlBlock};
+
+    #[test]
+    fn taproot_tweak_key_path_only() {
+        // BIP341 test vector of scriptPubKey 0
+        let internal_key = hex::decode("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d").unwrap();
+        let (key, _) = tap_tweak(&internal_key, None).unwrap();
+        assert_eq!(hex::encode(key), "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343");
+    }
+
+    #[test]
+    fn taproot_control_block_single_leaf() {
+        // BIP341 test vector of scriptPubKey 1
+        let script = hex::decode("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac").unwrap();
+        let leaf_hash = tap_leaf_hash(0xc0, &script);
+        assert_eq!(hex::encode(leaf_hash), "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21");
+
+        let bytes = hex::decode("c1187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27").unwrap();
+        let control = ControlBlock::parse(&bytes).unwrap();
+        assert_eq!(control.leaf_version(), 0xc0);
+        assert!(control.path().is_empty());
+        assert_eq!(control.serialize(), bytes);
+        assert_eq!(control.merkle_root(&leaf_hash), leaf_hash);
+
+        let output_key = hex::decode("147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3").unwrap();
+        assert!(control.verify_commitment(&output_key, &leaf_hash));
+        // wrong parity
+        let mut bytes = bytes;
+        bytes[0] = 0xc0;
+        assert!(!ControlBlock::parse(&bytes).unwrap().verify_commitment(&output_key, &leaf_hash));
+    }
+
+    #[test]
+    fn taproot_control_block_two_leaves() {
+        // BIP341 test vector of scriptPubKey 3
+        let output_key = hex::decode("712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5").unwrap();
+        let script_1 = hex::decode("06424950333431").unwrap();
+        let leaf_hash = tap_leaf_hash(250, &script_1);
+        assert_eq!(hex::encode(leaf_hash), "f224a923cd0021ab202ab139cc56802ddb92dcfc172b9212261a539df79a112a");
+
+        let bytes = hex::decode("faee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf37865928ad69ec7cf41c2a4001fd1f738bf1e505ce2277acdcaa63fe4765192497f47a7").unwrap();
+        let control = ControlBlock::parse(&bytes).unwrap();
+        assert_eq!(control.leaf_version(), 250);
+        assert_eq!(control.path().len(), 1);
+        assert_eq!(hex::encode(control.merkle_root(&leaf_hash)), "6c2dc106ab816b73f9d07e3cd1ef2c8c1256f519748e0813e4edd2405d277bef");
+        assert!(control.verify_commitment(&output_key, &leaf_hash));
+        assert!(!control.verify_commitment(&output_key, &hash::sha256(&script_1)));
+    }
+
+    #[test]
+    fn taproot_control_block_size() {
+        assert!(ControlBlock::parse(&[0xc0; 32]).is_err());
+        assert!(ControlBlock::parse(&[0xc0; 33 + 31]).is_err());
+        assert!(ControlBlock::parse(&[0xc0; 33 + 32 * 128]).is_ok());
+        assert!(ControlBlock::parse(&[0xc0; 33 + 32 * 129]).is_err());
+    }
+
+    #[test]
+    fn taproot_op_success() {
+        assert!(is_op_success(0x50));
+        assert!(is_op_success(0x7e)); // OP_CAT
+        assert!(is_op_success(0xbb));
+        assert!(!is_op_success(0xba)); // OP_CHECKSIGADD
+        assert!(!is_op_success(0xff));
+        assert!(!is_op_success(0xac));
+    }
+}
@@ -0,0 +1,355 @@
+// run the test vectors of bitcoin core: script_tests.json, tx_valid.json and tx_invalid.json
+// TODO vendor the unmodified upstream files of src/test/data, the ones in test_data are written in their format
+use crate::transaction::{Transaction, TxOut};
+use crate::util::json::Json;
+use std::collections::HashSet;
+use super::{operator, CommandElement, Error, Num, Opcode, VerifyFlags};
+
+const MAX_MONEY: u64 = 21_000_000 * 100_000_000;
+
+// vectors which are run but expected to fail, by file and index, each with a comment of the reason
+// vectors are never removed from the files, and a listed one which passes fails the test
+const KNOWN_FAILURES: &[(&str, usize)] = &[];
+
+// ParseScript of bitcoin core: decimal numbers, 0x raw bytes, 'string' pushes and opcode names with or without OP_
+fn parse_short_asm(asm: &str) -> Result<Vec<u8>, String> {
+    let mut result = Vec::new();
+    for token in asm.split_whitespace() {
+        let digits = token.strip_prefix('-').unwrap_or(token);
+        if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+            let n: i64 = token.parse().map_err(|_| format!("invalid number {}", token))?;
+            match Opcode::from_small_int(n) {
+                Some(op) => result.push(op.value()),
+                None => push(Num::from(n).encode(), &mut result)?,
+            }
+        } else if let Some(raw) = token.strip_prefix("0x").filter(|raw| !raw.is_empty()) {
+            result.extend(hex::decode(raw).map_err(|_| format!("invalid hex {}", token))?);
+        } else if let Some(s) = token.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+            push(s.as_bytes().to_vec(), &mut result)?;
+        } else {
+            let op = Opcode::from_name(token).ok_or(format!("unknown opcode {}", token))?;
+            result.push(op.value());
+        }
+    }
+    Ok(result)
+}
+
+fn push(data: Vec<u8>, result: &mut Vec<u8>) -> Result<(), String> {
+    CommandElement::Data(data).serialize(result).map_err(|e| format!("{:?}", e))
+}
+
+// ScriptError of bitcoin core, None for the errors which are not raised by script verification
+fn error_name(e: &Error) -> Option<&'static str> {
+    let name = match e {
+        Error::EvalFalse => "EVAL_FALSE",
+        Error::Verify => "VERIFY",
+        Error::EqualVerify => "EQUALVERIFY",
+        Error::NumEqualVerify => "NUMEQUALVERIFY",
+        Error::CheckSigVerify => "CHECKSIGVERIFY",
+        Error::CheckMultisigVerify => "CHECKMULTISIGVERIFY",
+        Error::OpReturn => "OP_RETURN",
+        Error::ScriptSize => "SCRIPT_SIZE",
+        Error::PushSize => "PUSH_SIZE",
+        Error::OpCount => "OP_COUNT",
+        Error::StackSize => "STACK_SIZE",
+        Error::SigCount => "SIG_COUNT",
+        Error::PublicKeyIsTooMuchForCheckMultisig => "PUBKEY_COUNT",
+        Error::BadOpcode(_) | Error::UnknownByteInScript(_) => "BAD_OPCODE",
+        // GetOp fails on a push running past the end of script
+        Error::InvalidBytes => "BAD_OPCODE",
+        Error::DisabledOpcode(_) => "DISABLED_OPCODE",
+        Error::EmptyStack | Error::InvalidStackOperation => "INVALID_STACK_OPERATION",
+        Error::InvalidAltStackOperation => "INVALID_ALTSTACK_OPERATION",
+        Error::UnbalancedConditional => "UNBALANCED_CONDITIONAL",
+        Error::NegativeLocktime => "NEGATIVE_LOCKTIME",
+        Error::UnsatisfiedLocktime => "UNSATISFIED_LOCKTIME",
+        Error::SigHashType => "SIG_HASHTYPE",
+        Error::SigDer => "SIG_DER",
+        Error::MinimalData => "MINIMALDATA",
+        Error::SigPushOnly => "SIG_PUSHONLY",
+        Error::SigHighS => "SIG_HIGH_S",
+        Error::SigNullDummy => "SIG_NULLDUMMY",
+        Error::PubKeyType => "PUBKEYTYPE",
+        Error::CleanStack => "CLEANSTACK",
+        Error::MinimalIf => "MINIMALIF",
+        Error::SigNullFail => "NULLFAIL",
+        Error::DiscourageUpgradableNops => "DISCOURAGE_UPGRADABLE_NOPS",
+        Error::DiscourageUpgradableWitnessProgram => "DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM",
+        Error::DiscourageUpgradableTaprootVersion => "DISCOURAGE_UPGRADABLE_TAPROOT_VERSION",
+        Error::DiscourageUpgradablePubKeyType => "DISCOURAGE_UPGRADABLE_PUBKEYTYPE",
+        Error::DiscourageOpSuccess => "DISCOURAGE_OP_SUCCESS",
+        Error::WitnessProgramWrongLength => "WITNESS_PROGRAM_WRONG_LENGTH",
+        Error::WitnessProgramWitnessEmpty => "WITNESS_PROGRAM_WITNESS_EMPTY",
+        Error::WitnessProgramMismatch | Error::InvalidWitnessRedeemScript => "WITNESS_PROGRAM_MISMATCH",
+        Error::WitnessMalleated => "WITNESS_MALLEATED",
+        Error::WitnessMalleatedP2sh => "WITNESS_MALLEATED_P2SH",
+        Error::WitnessUnexpected => "WITNESS_UNEXPECTED",
+        Error::WitnessPubKeyType => "WITNESS_PUBKEYTYPE",
+        Error::OpCodeSeparator => "OP_CODESEPARATOR",
+        Error::SchnorrSig => "SCHNORR_SIG",
+        Error::SchnorrSigSize => "SCHNORR_SIG_SIZE",
+        Error::SchnorrSigHashType => "SCHNORR_SIG_HASHTYPE",
+        Error::TaprootWrongControlSize => "TAPROOT_WRONG_CONTROL_SIZE",
+        Error::TapscriptValidationWeight => "TAPSCRIPT_VALIDATION_WEIGHT",
+        Error::TapscriptCheckmultisig => "TAPSCRIPT_CHECKMULTISIGVERIFY",
+        // the hash of redeem script is checked by OP_EQUAL of the p2sh script_pubkey
+        Error::InvalidRedeemScript => "EVAL_FALSE",
+        // CScriptNum throws scriptnum_error, which is caught as an unknown error
+        Error::NumDecodeOverflow | Error::NumSize | Error::NumNotMinimal => "UNKNOWN_ERROR",
+        Error::EmptyBytes |
+        Error::TooLongBytes |
+        Error::InvalidOpcode |
+        Error::InvalidTxFee |
+        Error::InvalidWitnessElement |
+        Error::SigHashIsNotTheSame |
+        Error::MissingTransaction |
+        Error::InvalidAsmToken(_) |
+        Error::EmptyScript |
+        Error::InvalidBlockHeightInCoinbase |
+        Error::InvalidPublicKey |
+        Error::InvalidSignature |
+        Error::Unknown(_) |
+        Error::Transaction(_) |
+        Error::NotImpl => return None,
+    };
+    Some(name)
+}
+
+// an error without a name of bitcoin core never matches the expected one
+fn result_name(result: &Result<bool, Error>) -> String {
+    match result {
+        Ok(true) => "OK".into(),
+        Ok(false) => "EVAL_FALSE".into(),
+        Err(e) => error_name(e).map_or_else(|| format!("{:?}", e), String::from),
+    }
+}
+
+fn load(name: &str) -> Vec<Json> {
+    let path = format!("{}/test_data/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let content = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    match Json::parse(&content).unwrap() {
+        Json::Array(vectors) => vectors,
+        _ => panic!("{} is not an array", name),
+    }
+}
+
+// run every vector, the ones with a single string are comments and the known failures must fail
+// @return descriptions of the failed vectors
+fn run_vectors(name: &str, run: impl Fn(&[Json]) -> Result<(), String>) -> Vec<String> {
+    let mut failures = Vec::new();
+    let vectors = load(name);
+    for (i, vector) in vectors.iter().enumerate() {
+        let items = vector.as_array().unwrap();
+        if items.len() == 1 && items[0].as_str().is_some() {
+            continue;
+        }
+        match (run(items), KNOWN_FAILURES.contains(&(name, i))) {
+            (Err(message), false) => failures.push(format!("{} #{}: {}", name, i, message)),
+            (Ok(()), true) => failures.push(format!("{} #{}: passes but is in KNOWN_FAILURES", name, i)),
+            _ => {},
+        }
+    }
+    failures
+}
+
+// the coinbase of bitcoin core test, whose only output is spent by the script to be tested
+fn crediting_tx(script_pubkey: &[u8], amount: u64) -> Transaction {
+    let mut bytes = hex::decode("0100000001").unwrap();
+    bytes.extend([0u8; 32]);
+    bytes.extend(hex::decode("ffffffff020000ffffffff01").unwrap());
+    bytes.extend(TxOut::new(amount, script_pubkey.to_vec()).serialize().unwrap());
+    bytes.extend([0u8; 4]);
+    Transaction::parse(&bytes).unwrap()
+}
+
+fn spending_tx(credit: &Transaction, script_sig: Vec<u8>, witness: Vec<Vec<u8>>) -> Transaction {
+    let mut bytes = hex::decode("0100000001").unwrap();
+    bytes.extend([0u8; 32]);
+    bytes.extend(hex::decode("0000000000ffffffff01").unwrap());
+    bytes.extend(TxOut::new(credit.outputs[0].amount(), Vec::new()).serialize().unwrap());
+    bytes.extend([0u8; 4]);
+    let mut tx = Transaction::parse(&bytes).unwrap();
+    tx.inputs[0].prev_tx = credit.id().unwrap();
+    tx.inputs[0].script = script_sig;
+    tx.inputs[0].set_witness(witness);
+    tx
+}
+
+// [[wit..., amount]?, scriptSig, scriptPubKey, flags, expected_scripterror, comments...]
+fn run_script_test(items: &[Json]) -> Result<(), String> {
+    let (witness, amount, items) = match items[0].as_array() {
+        Some(wit) => {
+            let (amount, wit) = wit.split_last().ok_or("empty witness")?;
+            let witness = wit.iter()
+                .map(|ele| ele.as_str().and_then(|ele| hex::decode(ele).ok()).ok_or("invalid witness"))
+                .collect::<Result<Vec<_>, _>>()?;
+            (witness, btc_to_satoshi(amount)?, &items[1..])
+        },
+        None => (Vec::new(), 0, items),
+    };
+    let field = |i: usize| items.get(i).and_then(Json::as_str).ok_or(format!("missing field {}", i));
+    let script_sig = parse_short_asm(field(0)?)?;
+    let script_pubkey = parse_short_asm(field(1)?)?;
+    let flags = VerifyFlags::from_names(field(2)?).ok_or(format!("unknown flags {}", field(2)?))?;
+    let expected = field(3)?;
+
+    let credit = crediting_tx(&script_pubkey, amount);
+    let spend = spending_tx(&credit, script_sig, witness);
+    let result = operator::verify_tx_input_with_prevouts(&spend, 0, &credit.outputs, flags);
+    if result_name(&result) != expected {
+        return Err(format!("{:?} {:?} [{}]: expected {} but {:?}", field(0)?, field(1)?, field(2)?, expected, result));
+    }
+    Ok(())
+}
+
+fn btc_to_satoshi(amount: &Json) -> Result<u64, String> {
+    match amount {
+        Json::Number(n) => Ok(*n as u64 * 100_000_000),
+        Json::Float(f) => Ok((f * 100_000_000.0).round() as u64),
+        _ => Err("invalid amount".into()),
+    }
+}
+
+// the rules of CheckTransaction which do not depend on the chain
+fn check_transaction(tx: &Transaction) -> Result<(), String> {
+    if tx.inputs.is_empty() || tx.outputs.is_empty() {
+        return Err("no input or output".into());
+    }
+    let mut total = 0u64;
+    for output in &tx.outputs {
+        total = total.saturating_add(output.amount());
+        if output.amount() > MAX_MONEY || total > MAX_MONEY {
+            return Err("output out of range".into());
+        }
+    }
+    let mut outpoints = HashSet::new();
+    for input in &tx.inputs {
+        if !outpoints.insert((input.prev_tx, input.prev_index.value())) {
+            return Err("duplicate inputs".into());
+        }
+    }
+    if tx.is_coinbase() {
+        if !(2..=100).contains(&tx.inputs[0].script.len()) {
+            return Err("bad coinbase length".into());
+        }
+    } else if tx.inputs.iter().any(|input| input.is_coinbase()) {
+        return Err("null prevout".into());
+    }
+    Ok(())
+}
+
+// [[[prevout hash, prevout index, prevout scriptPubKey, amount?], ...], serializedTransaction, flags]
+// the transaction is deserialized alone, tx_invalid.json expects some of them to fail
+fn parse_tx_bytes(items: &[Json]) -> Result<Vec<u8>, String> {
+    let tx_hex = items.get(1).and_then(Json::as_str).ok_or("missing transaction")?;
+    hex::decode(tx_hex).map_err(|e| e.to_string())
+}
+
+fn parse_flags(items: &[Json]) -> Result<&str, String> {
+    Ok(items.get(2).and_then(Json::as_str).ok_or("missing flags")?)
+}
+
+// @return outputs spent by each input of tx and flags
+fn parse_tx_test(items: &[Json], tx: &Transaction) -> Result<(Vec<TxOut>, VerifyFlags), String> {
+    let flags_str = parse_flags(items)?;
+    let flags = VerifyFlags::from_names(flags_str).ok_or(format!("unknown flags {}", flags_str))?;
+
+    let mut spent = Vec::new();
+    for prevout in items[0].as_array().ok_or("missing prevouts")? {
+        let prevout = prevout.as_array().ok_or("invalid prevout")?;
+        let hash = prevout.first().and_then(Json::as_str).and_then(|h| hex::decode(h).ok()).ok_or("invalid prevout hash")?;
+        let index = prevout.get(1).and_then(Json::as_i64).ok_or("invalid prevout index")?;
+        let script = parse_short_asm(prevout.get(2).and_then(Json::as_str).ok_or("invalid prevout script")?)?;
+        let amount = prevout.get(3).and_then(Json::as_i64).unwrap_or(0);
+        spent.push((hash, index as u32, TxOut::new(amount as u64, script))); // -1 is the index of coinbase
+    }
+    let prevouts = tx.inputs.iter()
+        .map(|input| {
+            spent.iter()
+                .find(|(hash, index, _)| hash[..] == input.prev_tx[..] && *index == input.prev_index.value())
+                .map(|(_, _, prevout)| prevout.clone())
+                .ok_or(format!("unknown prevout of input {}", hex::encode(input.prev_tx)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((prevouts, flags))
+}
+
+// flags depending on others are removed as TrimFlags of bitcoin core
+fn trim_flags(mut flags: VerifyFlags) -> VerifyFlags {
+    if !flags.contains(VerifyFlags::P2SH) {
+        flags.remove(VerifyFlags::WITNESS);
+    }
+    if !flags.contains(VerifyFlags::WITNESS) {
+        flags.remove(VerifyFlags::CLEANSTACK);
+    }
+    flags
+}
+
+// the flags of tx_valid.json are excluded from all flags
+fn run_tx_valid(items: &[Json]) -> Result<(), String> {
+    let tx = Transaction::parse(&parse_tx_bytes(items)?).map_err(|e| format!("{:?}", e))?;
+    check_transaction(&tx)?;
+    let (prevouts, excluded) = parse_tx_test(items, &tx)?;
+    let mut flags = VerifyFlags::standard() | VerifyFlags::SIGPUSHONLY;
+    flags.remove(excluded);
+    let flags = trim_flags(flags);
+    for i in 0..tx.inputs.len() {
+        let result = operator::verify_tx_input_with_prevouts(&tx, i, &prevouts, flags);
+        if !matches!(result, Ok(true)) {
+            return Err(format!("input {} of {}: {:?}", i, hex::encode(tx.id().unwrap()), result));
+        }
+    }
+    Ok(())
+}
+
+// the transaction of tx_invalid.json is invalid under its flags,
+// BADTX is the flags of the ones failing deserialization or CheckTransaction
+fn run_tx_invalid(items: &[Json]) -> Result<(), String> {
+    let bad_tx = parse_flags(items)? == "BADTX";
+    let tx = match Transaction::parse(&parse_tx_bytes(items)?) {
+        Ok(tx) => tx,
+        Err(_) if bad_tx => return Ok(()),
+        Err(e) => return Err(format!("not deserialized: {:?}", e)),
+    };
+    match check_transaction(&tx) {
+        Err(_) if bad_tx => return Ok(()),
+        Err(reason) => return Err(format!("{} without BADTX", reason)),
+        Ok(()) if bad_tx => return Err(format!("{} is not a bad transaction", hex::encode(tx.id().unwrap()))),
+        Ok(()) => {},
+    }
+    let (prevouts, flags) = parse_tx_test(items, &tx)?;
+    let flags = trim_flags(flags);
+    let valid = (0..tx.inputs.len()).all(|i| matches!(operator::verify_tx_input_with_prevouts(&tx, i, &prevouts, flags), Ok(true)));
+    if valid {
+        return Err(format!("{} is valid", hex::encode(tx.id().unwrap())));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_vectors_short_asm() {
+    assert_eq!(hex::encode(parse_short_asm("0 1 -1 16 17 1000 -129").unwrap()), "00514f60011102e803028180");
+    assert_eq!(hex::encode(parse_short_asm("0x4c 0x01 0x07 'Az' ''").unwrap()), "4c010702417a00");
+    assert_eq!(hex::encode(parse_short_asm("DUP OP_HASH160 NOP2 OP_CHECKSIG").unwrap()), "76a9b1ac");
+    assert!(parse_short_asm("OP_UNKNOWN").is_err());
+    assert!(parse_short_asm("0x4").is_err());
+}
+
+#[test]
+fn test_vectors_script_tests() {
+    let failures = run_vectors("script_tests.json", run_script_test);
+    assert!(failures.is_empty(), "{} failed:\n{}", failures.len(), failures.join("\n"));
+}
+
+#[test]
+fn test_vectors_tx_valid() {
+    let failures = run_vectors("tx_valid.json", run_tx_valid);
+    assert!(failures.is_empty(), "{} failed:\n{}", failures.len(), failures.join("\n"));
+}
+
+#[test]
+fn test_vectors_tx_invalid() {
+    let failures = run_vectors("tx_invalid.json", run_tx_invalid);
+    assert!(failures.is_empty(), "{} failed:\n{}", failures.len(), failures.join("\n"));
+}
@@ -0,0 +1,168 @@
+use std::ops::{BitOr, BitOrAssign};
+
+// script verification flags, the bits are the same as SCRIPT_VERIFY_* of bitcoin core
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VerifyFlags(u32);
+
+impl VerifyFlags {
+    pub const NONE: Self = Self(0);
+    pub const P2SH: Self = Self(1 << 0); // BIP16
+    pub const STRICTENC: Self = Self(1 << 1);
+    pub const DERSIG: Self = Self(1 << 2); // BIP66
+    pub const LOW_S: Self = Self(1 << 3);
+    pub const NULLDUMMY: Self = Self(1 << 4); // BIP147
+    pub const SIGPUSHONLY: Self = Self(1 << 5);
+    pub const MINIMALDATA: Self = Self(1 << 6);
+    pub const DISCOURAGE_UPGRADABLE_NOPS: Self = Self(1 << 7);
+    pub const CLEANSTACK: Self = Self(1 << 8);
+    pub const CHECKLOCKTIMEVERIFY: Self = Self(1 << 9); // BIP65
+    pub const CHECKSEQUENCEVERIFY: Self = Self(1 << 10); // BIP112
+    pub const WITNESS: Self = Self(1 << 11); // BIP141
+    pub const DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM: Self = Self(1 << 12);
+    pub const MINIMALIF: Self = Self(1 << 13);
+    pub const NULLFAIL: Self = Self(1 << 14);
+    pub const WITNESS_PUBKEYTYPE: Self = Self(1 << 15);
+    pub const CONST_SCRIPTCODE: Self = Self(1 << 16);
+    pub const TAPROOT: Self = Self(1 << 17); // BIP341 and BIP342
+    pub const DISCOURAGE_UPGRADABLE_TAPROOT_VERSION: Self = Self(1 << 18);
+    pub const DISCOURAGE_OP_SUCCESS: Self = Self(1 << 19);
+    pub const DISCOURAGE_UPGRADABLE_PUBKEYTYPE: Self = Self(1 << 20);
+
+    // rules enforced by blocks, failing them makes the transaction invalid
+    pub fn consensus() -> Self {
+        Self::P2SH |
+        Self::DERSIG |
+        Self::NULLDUMMY |
+        Self::CHECKLOCKTIMEVERIFY |
+        Self::CHECKSEQUENCEVERIFY |
+        Self::WITNESS |
+        Self::TAPROOT
+    }
+
+    // rules enforced by relay policy, failing them only makes the transaction non-standard
+    pub fn standard() -> Self {
+        Self::consensus() |
+        Self::STRICTENC |
+        Self::LOW_S |
+        Self::MINIMALDATA |
+        Self::DISCOURAGE_UPGRADABLE_NOPS |
+        Self::CLEANSTACK |
+        Self::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM |
+        Self::MINIMALIF |
+        Self::NULLFAIL |
+        Self::WITNESS_PUBKEYTYPE |
+        Self::CONST_SCRIPTCODE |
+        Self::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION |
+        Self::DISCOURAGE_OP_SUCCESS |
+        Self::DISCOURAGE_UPGRADABLE_PUBKEYTYPE
+    }
+
+    // comma separated names used by the test vectors of bitcoin core, such as "P2SH,STRICTENC"
+    // NONE or an empty string means no flag
+    pub fn from_names(names: &str) -> Option<Self> {
+        let mut flags = Self::NONE;
+        for name in names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            flags |= Self::from_name(name)?;
+        }
+        Some(flags)
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let flag = match name {
+            "NONE" => Self::NONE,
+            "P2SH" => Self::P2SH,
+            "STRICTENC" => Self::STRICTENC,
+            "DERSIG" => Self::DERSIG,
+            "LOW_S" => Self::LOW_S,
+            "NULLDUMMY" => Self::NULLDUMMY,
+            "SIGPUSHONLY" => Self::SIGPUSHONLY,
+            "MINIMALDATA" => Self::MINIMALDATA,
+            "DISCOURAGE_UPGRADABLE_NOPS" => Self::DISCOURAGE_UPGRADABLE_NOPS,
+            "CLEANSTACK" => Self::CLEANSTACK,
+            "CHECKLOCKTIMEVERIFY" => Self::CHECKLOCKTIMEVERIFY,
+            "CHECKSEQUENCEVERIFY" => Self::CHECKSEQUENCEVERIFY,
+            "WITNESS" => Self::WITNESS,
+            "DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM" => Self::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM,
+            "MINIMALIF" => Self::MINIMALIF,
+            "NULLFAIL" => Self::NULLFAIL,
+            "WITNESS_PUBKEYTYPE" => Self::WITNESS_PUBKEYTYPE,
+            "CONST_SCRIPTCODE" => Self::CONST_SCRIPTCODE,
+            "TAPROOT" => Self::TAPROOT,
+            "DISCOURAGE_UPGRADABLE_TAPROOT_VERSION" => Self::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION,
+            "DISCOURAGE_OP_SUCCESS" => Self::DISCOURAGE_OP_SUCCESS,
+            "DISCOURAGE_UPGRADABLE_PUBKEYTYPE" => Self::DISCOURAGE_UPGRADABLE_PUBKEYTYPE,
+            _ => return None,
+        };
+        Some(flag)
+    }
+
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersects(&self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for VerifyFlags {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for VerifyFlags {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VerifyFlags;
+
+    #[test]
+    fn verify_flags_contains() {
+        let flags = VerifyFlags::P2SH | VerifyFlags::WITNESS;
+        assert!(flags.contains(VerifyFlags::P2SH));
+        assert!(flags.contains(VerifyFlags::P2SH | VerifyFlags::WITNESS));
+        assert!(!flags.contains(VerifyFlags::DERSIG));
+        assert!(flags.intersects(VerifyFlags::P2SH | VerifyFlags::DERSIG));
+        assert!(!flags.intersects(VerifyFlags::DERSIG));
+        assert!(flags.contains(VerifyFlags::NONE));
+        assert_eq!(flags.bits(), 0x801);
+    }
+
+    #[test]
+    fn verify_flags_standard_is_superset_of_consensus() {
+        assert!(VerifyFlags::standard().contains(VerifyFlags::consensus()));
+        assert!(!VerifyFlags::consensus().contains(VerifyFlags::CLEANSTACK));
+
+        let mut flags = VerifyFlags::standard();
+        flags.remove(VerifyFlags::CLEANSTACK);
+        assert!(!flags.contains(VerifyFlags::CLEANSTACK));
+        assert!(flags.contains(VerifyFlags::consensus()));
+    }
+
+    #[test]
+    fn verify_flags_from_names() {
+        assert_eq!(VerifyFlags::from_names("P2SH,STRICTENC"), Some(VerifyFlags::P2SH | VerifyFlags::STRICTENC));
+        assert_eq!(VerifyFlags::from_names("NONE"), Some(VerifyFlags::NONE));
+        assert_eq!(VerifyFlags::from_names(""), Some(VerifyFlags::NONE));
+        assert_eq!(VerifyFlags::from_names("P2SH,UNKNOWN"), None);
+    }
+}
@@ -2,6 +2,7 @@ use crate::field::{FieldElement, FieldElementCreator, Prime};
 use super::S256Curve;
 use primitive_types::U256;
 use std::ops::{Add, Sub, Mul, Div};
+use zeroize::Zeroize;
 
 // creator
 
@@ -39,6 +40,12 @@ impl S256FieldElementN {
     }
 }
 
+impl Zeroize for S256FieldElementN {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
 impl PartialEq for S256FieldElementN {
     fn eq(&self, other: &Self) -> bool {
         self.inner() == other.inner()
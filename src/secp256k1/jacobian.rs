@@ -0,0 +1,463 @@
+use primitive_types::{U256, U512};
+use std::ops::{Add, Mul, Neg};
+use std::sync::OnceLock;
+use super::S256Curve;
+
+// p = 2^256 - 0x1000003d1, so 2^256 = 0x1000003d1 (mod p)
+const P: U256 = U256([0xfffffffefffffc2f, u64::MAX, u64::MAX, u64::MAX]);
+const P_COMPLEMENT: u64 = 0x1000003d1;
+const G_X: U256 = U256([0x59f2815b16f81798, 0x029bfcdb2dce28d9, 0x55a06295ce870b07, 0x79be667ef9dcbbac]);
+const G_Y: U256 = U256([0x9c47d08ffb10d4b8, 0xfd17b448a6855419, 0x5da4fbfc0e1108a8, 0x483ada7726a3c465]);
+// width of wNAF, the odd multiples P, 3P, ..., (2^(w-1)-1)P are precomputed
+const WNAF_WIDTH: u32 = 5;
+// G is multiplied by every verification, its table of 64 affine points is built once
+const G_WNAF_WIDTH: u32 = 8;
+
+static G_TABLE: OnceLock<Vec<JacobianPoint>> = OnceLock::new();
+
+// the field arithmetic of secp256k1 without FieldElement, only to_affine needs an inversion
+// the reductions select by masks instead of branches, so the ladder of secrets runs in constant time
+
+// a if flag else b
+fn select(flag: bool, a: U256, b: U256) -> U256 {
+    let mask = 0u64.wrapping_sub(flag as u64);
+    let mut result = b;
+    for i in 0..4 {
+        result.0[i] ^= mask & (a.0[i] ^ b.0[i]);
+    }
+    result
+}
+
+// U256::is_zero stops at the first non-zero limb
+fn is_zero(a: U256) -> bool {
+    a.0[0] | a.0[1] | a.0[2] | a.0[3] == 0
+}
+
+fn add_mod(a: U256, b: U256) -> U256 {
+    let (sum, overflow) = a.overflowing_add(b);
+    // sum + 2^256 - p wraps to sum + 0x1000003d1
+    let (reduced, borrow) = sum.overflowing_sub(P);
+    select(overflow | !borrow, reduced, sum)
+}
+
+fn sub_mod(a: U256, b: U256) -> U256 {
+    let (diff, borrow) = a.overflowing_sub(b);
+    diff.overflowing_add(select(borrow, P, U256::zero())).0
+}
+
+fn split(v: U512) -> (U256, U256) {
+    let w = v.0;
+    (U256([w[4], w[5], w[6], w[7]]), U256([w[0], w[1], w[2], w[3]]))
+}
+
+// hi*2^256 + lo = hi*0x1000003d1 + lo (mod p), folded twice to fit into 256 bits
+fn reduce(v: U512) -> U256 {
+    let (hi, lo) = split(v);
+    let (hi, lo) = split(hi.full_mul(P_COMPLEMENT.into()) + U512::from(lo));
+    // hi < 2^34 now
+    let (result, overflow) = lo.overflowing_add(U256::from(hi.low_u64() as u128 * P_COMPLEMENT as u128));
+    let result = result.overflowing_add(select(overflow, P_COMPLEMENT.into(), U256::zero())).0;
+    let (reduced, borrow) = result.overflowing_sub(P);
+    select(borrow, result, reduced)
+}
+
+fn mul_mod(a: U256, b: U256) -> U256 {
+    reduce(a.full_mul(b))
+}
+
+fn sqr_mod(a: U256) -> U256 {
+    mul_mod(a, a)
+}
+
+// a^-1 = a^(p-2)
+fn inv_mod(a: U256) -> U256 {
+    let exponent = P - 2;
+    let mut result = U256::one();
+    for i in (0..256).rev() {
+        result = sqr_mod(result);
+        if exponent.bit(i) {
+            result = mul_mod(result, a);
+        }
+    }
+    result
+}
+
+// point of secp256k1 in jacobian coordinates: (x/z^2, y/z^3), z = 0 for infinity
+// add and double need no inversion, which is the cost of the affine FieldEccPoint
+#[derive(Debug, Copy, Clone)]
+pub struct JacobianPoint {
+    x: U256,
+    y: U256,
+    z: U256,
+}
+
+impl JacobianPoint {
+    pub fn infinity() -> Self {
+        Self { x: U256::one(), y: U256::one(), z: U256::zero() }
+    }
+
+    // x and y must be on the curve, S256Point checks them
+    pub fn from_affine(x: U256, y: U256) -> Self {
+        Self { x, y, z: U256::one() }
+    }
+
+    pub fn is_infinity(&self) -> bool {
+        self.z.is_zero()
+    }
+
+    fn is_g(&self) -> bool {
+        self.z == U256::one() && self.x == G_X && self.y == G_Y
+    }
+
+    // the same point with z = 1, which makes the additions with it cheaper
+    fn normalize(&self) -> Self {
+        match self.to_affine() {
+            Some((x, y)) => Self::from_affine(x, y),
+            None => Self::infinity(),
+        }
+    }
+
+    // (x, y) or None if infinity
+    pub fn to_affine(&self) -> Option<(U256, U256)> {
+        if self.is_infinity() {
+            return None;
+        }
+        let z_inv = inv_mod(self.z);
+        let z_inv2 = sqr_mod(z_inv);
+        let z_inv3 = mul_mod(z_inv2, z_inv);
+        Some((mul_mod(self.x, z_inv2), mul_mod(self.y, z_inv3)))
+    }
+
+    // dbl-2009-l of a = 0
+    // infinity stays so by z3 = 2*y*z, and no point of secp256k1 has y = 0, so it never branches
+    pub fn double(&self) -> Self {
+        let a = sqr_mod(self.x);
+        let b = sqr_mod(self.y);
+        let c = sqr_mod(b);
+        let x_b = add_mod(self.x, b);
+        let d = sub_mod(sub_mod(sqr_mod(x_b), a), c);
+        let d = add_mod(d, d);
+        let e = add_mod(add_mod(a, a), a);
+        let f = sqr_mod(e);
+
+        let x3 = sub_mod(f, add_mod(d, d));
+        let c8 = add_mod(c, c);
+        let c8 = add_mod(c8, c8);
+        let c8 = add_mod(c8, c8);
+        let y3 = sub_mod(mul_mod(e, sub_mod(d, x3)), c8);
+        let y_z = mul_mod(self.y, self.z);
+        let z3 = add_mod(y_z, y_z);
+
+        Self { x: x3, y: y3, z: z3 }
+    }
+
+    // the digits of k in wNAF, from the least significant, every non-zero digit is odd and followed by w-1 zeros
+    pub fn wnaf(mut k: U256, width: u32) -> Vec<i8> {
+        let window = 1u64 << width;
+        let mut digits = Vec::with_capacity(257);
+        while !k.is_zero() {
+            let mut digit = 0i64;
+            if k.bit(0) {
+                digit = (k.low_u64() % window) as i64;
+                if digit >= (window / 2) as i64 {
+                    digit -= window as i64;
+                }
+                // k < n, adding at most 2^(w-1) cannot overflow
+                k = if digit >= 0 { k - digit as u64 } else { k + (-digit) as u64 };
+            }
+            digits.push(digit as i8);
+            k >>= 1;
+        }
+        digits
+    }
+
+    // P, 3P, 5P, ..., (2^(w-1)-1)P
+    fn odd_multiples(&self, width: u32) -> Vec<Self> {
+        let table_size = 1usize << (width - 2);
+        let double = self.double();
+        let mut table = Vec::with_capacity(table_size);
+        table.push(*self);
+        for i in 1..table_size {
+            table.push(table[i - 1] + double);
+        }
+        table
+    }
+
+    fn g_table() -> &'static [Self] {
+        G_TABLE.get_or_init(|| {
+            Self::from_affine(G_X, G_Y).odd_multiples(G_WNAF_WIDTH).iter().map(Self::normalize).collect()
+        })
+    }
+
+    fn add_digit(self, table: &[Self], digit: i8) -> Self {
+        match digit {
+            0 => self,
+            d if d > 0 => self + table[(d / 2) as usize],
+            d => self + -table[(-d / 2) as usize],
+        }
+    }
+
+    // Strauss: the scalars share one chain of doublings, every wNAF digit adds a point of its table
+    fn mul_wnaf(scalars: &[(U256, &[Self], u32)]) -> Self {
+        let digits: Vec<Vec<i8>> = scalars.iter().map(|(k, _, width)| Self::wnaf(*k, *width)).collect();
+        let len = digits.iter().map(Vec::len).max().unwrap_or(0);
+
+        let mut result = Self::infinity();
+        for i in (0..len).rev() {
+            result = result.double();
+            for (digits, (_, table, _)) in digits.iter().zip(scalars) {
+                if let Some(digit) = digits.get(i) {
+                    result = result.add_digit(table, *digit);
+                }
+            }
+        }
+        result
+    }
+
+    // other if flag else self, without a branch
+    fn select(self, flag: bool, other: Self) -> Self {
+        Self { x: select(flag, other.x, self.x), y: select(flag, other.y, self.y), z: select(flag, other.z, self.z) }
+    }
+
+    // swap self and other if flag without a branch
+    fn conditional_swap(&mut self, flag: bool, other: &mut Self) {
+        let (a, b) = (*self, *other);
+        *self = a.select(flag, b);
+        *other = b.select(flag, a);
+    }
+
+    // Montgomery ladder for secret scalars: every bit costs one addition and one doubling
+    // k + n or k + 2n has the bit 256 set, so the ladder always runs 256 steps from (P, 2P)
+    pub fn mul_ladder(&self, k: U256) -> Self {
+        let n = S256Curve::n();
+        let (reduced, borrow) = k.overflowing_sub(n);
+        let k = select(borrow, k, reduced);
+        let (k_n, carry) = k.overflowing_add(n);
+        // 2n > 2^256, the carry of k + 2n is always set when the one of k + n is not
+        let (k_2n, _) = k_n.overflowing_add(n);
+        let scalar = select(carry, k_n, k_2n);
+
+        let mut r0 = *self;
+        let mut r1 = self.double();
+        for i in (0..256).rev() {
+            let bit = scalar.bit(i);
+            r0.conditional_swap(bit, &mut r1);
+            r1 = r0.add_complete(r1);
+            r0 = r0.double();
+            r0.conditional_swap(bit, &mut r1);
+        }
+        r0
+    }
+
+    // u*G + v*self, which is what ECDSA and Schnorr verify
+    pub fn mul_add_g(&self, u: U256, v: U256) -> Self {
+        let n = S256Curve::n();
+        let (u, v) = (u % n, v % n);
+        if self.is_infinity() || v.is_zero() {
+            return Self::from_affine(G_X, G_Y) * u;
+        }
+        let table = self.odd_multiples(WNAF_WIDTH);
+        Self::mul_wnaf(&[(u, Self::g_table(), G_WNAF_WIDTH), (v, &table, WNAF_WIDTH)])
+    }
+}
+
+impl PartialEq for JacobianPoint {
+    // x1*z2^2 = x2*z1^2 and y1*z2^3 = y2*z1^3
+    fn eq(&self, other: &Self) -> bool {
+        if self.is_infinity() || other.is_infinity() {
+            return self.is_infinity() && other.is_infinity();
+        }
+        let z1_2 = sqr_mod(self.z);
+        let z2_2 = sqr_mod(other.z);
+        mul_mod(self.x, z2_2) == mul_mod(other.x, z1_2)
+            && mul_mod(self.y, mul_mod(z2_2, other.z)) == mul_mod(other.y, mul_mod(z1_2, self.z))
+    }
+}
+
+impl JacobianPoint {
+    // add-1998-cmo-2 of Cohen, Miyaji and Ono, other_affine specializes it to z2 = 1 and skips the multiplications by z2
+    // @return the sum, h and r, the sum is wrong if either is infinity or they have the same x
+    fn add_unchecked(self, other: Self, other_affine: bool) -> (Self, U256, U256) {
+        let z1_2 = sqr_mod(self.z);
+        let u2 = mul_mod(other.x, z1_2);
+        let s2 = mul_mod(other.y, mul_mod(self.z, z1_2));
+        let (u1, s1) = if other_affine {
+            (self.x, self.y)
+        } else {
+            let z2_2 = sqr_mod(other.z);
+            (mul_mod(self.x, z2_2), mul_mod(self.y, mul_mod(other.z, z2_2)))
+        };
+        let h = sub_mod(u2, u1);
+        let r = sub_mod(s2, s1);
+
+        let hh = sqr_mod(h);
+        let hhh = mul_mod(h, hh);
+        let v = mul_mod(u1, hh);
+        let x3 = sub_mod(sub_mod(sqr_mod(r), hhh), add_mod(v, v));
+        let y3 = sub_mod(mul_mod(r, sub_mod(v, x3)), mul_mod(s1, hhh));
+        let z3 = if other_affine { mul_mod(self.z, h) } else { mul_mod(mul_mod(self.z, other.z), h) };
+
+        (Self { x: x3, y: y3, z: z3 }, h, r)
+    }
+
+    fn add_with(self, other: Self, other_affine: bool) -> Self {
+        if self.is_infinity() {
+            return other;
+        }
+        if other.is_infinity() {
+            return self;
+        }
+        let (sum, h, r) = self.add_unchecked(other, other_affine);
+        if h.is_zero() {
+            // the same x: P + P or P + (-P)
+            return if r.is_zero() { self.double() } else { Self::infinity() };
+        }
+        sum
+    }
+
+    // the exceptions of add_with are selected by masks, so the ladder does not branch on the secret points
+    // P + (-P) needs no selection, z3 = z1*z2*h is already 0
+    fn add_complete(self, other: Self) -> Self {
+        let (sum, h, r) = self.add_unchecked(other, false);
+        let doubled = self.double();
+        let mut result = sum.select(is_zero(h) & is_zero(r), doubled);
+        result = result.select(is_zero(other.z), self);
+        result.select(is_zero(self.z), other)
+    }
+}
+
+impl Add for JacobianPoint {
+    type Output = Self;
+
+    // z2 = 1 for the points of the tables
+    fn add(self, other: Self) -> Self {
+        self.add_with(other, other.z == U256::one())
+    }
+}
+
+impl Neg for JacobianPoint {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { x: self.x, y: sub_mod(U256::zero(), self.y), z: self.z }
+    }
+}
+
+impl Mul<U256> for JacobianPoint {
+    type Output = Self;
+
+    fn mul(self, coefficient: U256) -> Self {
+        let coefficient = coefficient % S256Curve::n();
+        if self.is_infinity() || coefficient.is_zero() {
+            return Self::infinity();
+        }
+        if self.is_g() {
+            return Self::mul_wnaf(&[(coefficient, Self::g_table(), G_WNAF_WIDTH)]);
+        }
+        let table = self.odd_multiples(WNAF_WIDTH);
+        Self::mul_wnaf(&[(coefficient, &table, WNAF_WIDTH)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JacobianPoint, inv_mod, mul_mod, P};
+    use super::super::{S256Curve, S256Point};
+    use primitive_types::U256;
+
+    fn g() -> JacobianPoint {
+        S256Point::g().to_jacobian()
+    }
+
+    // the generic affine multiplication of FieldEccPoint
+    fn affine_mul(k: U256) -> Option<(U256, U256)> {
+        (S256Point::g().into_inner() * k).into_field_point().map(|p| (p.x().num(), p.y().num()))
+    }
+
+    #[test]
+    fn jacobian_field_arithmetic() {
+        let a = P - 1;
+        assert_eq!(mul_mod(a, a), U256::one());
+        let b = U256::from_str_radix("c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5", 16).unwrap();
+        assert_eq!(mul_mod(b, inv_mod(b)), U256::one());
+    }
+
+    #[test]
+    fn jacobian_point_add_double() {
+        let g = g();
+        assert_eq!(g + g, g.double());
+        assert_eq!((g.double() + g).to_affine(), affine_mul(3.into()));
+        assert!((g + -g).is_infinity());
+        assert_eq!(g + JacobianPoint::infinity(), g);
+        assert!(JacobianPoint::infinity().double().is_infinity());
+        assert!(JacobianPoint::infinity().to_affine().is_none());
+    }
+
+    #[test]
+    fn jacobian_point_add_complete() {
+        let g = g();
+        let g3 = g.double() + g;
+        assert_eq!(g.double().add_complete(g), g3);
+        assert_eq!(g.add_complete(g), g.double());
+        assert!(g.add_complete(-g).is_infinity());
+        assert_eq!(g.add_complete(JacobianPoint::infinity()), g);
+        assert_eq!(JacobianPoint::infinity().add_complete(g3), g3);
+        assert!(JacobianPoint::infinity().add_complete(JacobianPoint::infinity()).is_infinity());
+    }
+
+    #[test]
+    fn jacobian_point_wnaf() {
+        for k in [1u64, 7, 15, 16, 31, 0xdeadbeef, u64::MAX] {
+            for (width, max) in [(5, 16), (8, 128)] {
+                let digits = JacobianPoint::wnaf(k.into(), width);
+                let value = digits.iter().rev().fold(0i128, |acc, d| acc * 2 + *d as i128);
+                assert_eq!(value, k as i128);
+                assert!(digits.iter().all(|d| *d == 0 || (d % 2 != 0 && (*d as i32).abs() < max)));
+            }
+        }
+    }
+
+    #[test]
+    fn jacobian_point_mul() {
+        let n = S256Curve::n();
+        for k in [U256::one(), U256::from(2), U256::from(5003), U256::from_str_radix("54321deadbeef0123456789abcdef", 16).unwrap(), n - 1] {
+            assert_eq!((g() * k).to_affine(), affine_mul(k));
+        }
+        assert!((g() * n).is_infinity());
+        assert!((g() * U256::zero()).is_infinity());
+        assert_eq!(g() * (n - 1), -g());
+        // not G, with the table of 5
+        let g2 = g().double();
+        assert_eq!((g2 * U256::from(5003)).to_affine(), affine_mul(10006.into()));
+    }
+
+    #[test]
+    fn jacobian_point_mul_ladder() {
+        let n = S256Curve::n();
+        let k = U256::from_str_radix("8f8a276c19f4149656b280621e358cce24f5f52542772691ee69063b74f15d15", 16).unwrap();
+        for k in [U256::one(), U256::from(2), U256::from(5003), k, n - 1, n + 5, U256::MAX] {
+            assert_eq!(g().mul_ladder(k), g() * k);
+        }
+        let p = g() * U256::from(7);
+        assert_eq!(p.mul_ladder(k), p * k);
+        assert!(g().mul_ladder(U256::zero()).is_infinity());
+        assert!(g().mul_ladder(n).is_infinity());
+        assert!(JacobianPoint::infinity().mul_ladder(k).is_infinity());
+    }
+
+    #[test]
+    fn jacobian_point_mul_add_g() {
+        let n = S256Curve::n();
+        let p = g() * U256::from(7);
+        let u = U256::from_str_radix("8f8a276c19f4149656b280621e358cce24f5f52542772691ee69063b74f15d15", 16).unwrap();
+        let v = U256::from_str_radix("54321deadbeef0123456789abcdef", 16).unwrap();
+        assert_eq!(p.mul_add_g(u, v), g() * u + p * v);
+        // u*G + v*P = (u + 7v)*G
+        assert_eq!(p.mul_add_g(u, v).to_affine(), affine_mul((u + v * 7) % n));
+        assert_eq!(p.mul_add_g(u, U256::zero()), g() * u);
+        assert_eq!(p.mul_add_g(U256::zero(), v), p * v);
+        // u*G + (-u/7)*P = infinity
+        assert!(p.mul_add_g(U256::from(7), n - 1).is_infinity());
+        assert_eq!(JacobianPoint::infinity().mul_add_g(u, v), g() * u);
+    }
+}
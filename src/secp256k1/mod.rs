@@ -4,15 +4,21 @@ use crate::field::Prime;
 
 pub use element_n::{S256FieldElementN, S256FieldElementNCreator};
 pub use element_p::{S256FieldElementP, S256FieldElementPCreator};
+pub use jacobian::JacobianPoint;
 pub use point::S256Point;
 pub use private_key::PrivateKey;
-pub use signature::Signature;
+pub use recoverable::RecoverableSignature;
+pub use signature::{DerError, Signature};
+pub use schnorr::SchnorrSignature;
 
 mod element_n;
 mod element_p;
+mod jacobian;
 mod point;
 mod signature;
 mod private_key;
+mod recoverable;
+mod schnorr;
 
 pub struct S256Curve;
 
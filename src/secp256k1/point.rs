@@ -2,7 +2,7 @@ use crate::field_ecc::{FieldEccPoint, FieldEccPointCreator, FieldPoint, FieldPoi
 use crate::util::{base58, hash};
 use primitive_types::U256;
 use std::ops::{Add, Mul};
-use super::{S256Curve, S256FieldElementP, S256FieldElementPCreator};
+use super::{JacobianPoint, S256Curve, S256FieldElementP, S256FieldElementPCreator, SchnorrSignature};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct S256Point(FieldEccPoint);
@@ -38,6 +38,41 @@ impl S256Point {
         return Err("invalid sec".into());
     }
 
+    // BIP340, the point with x and an even y
+    pub fn lift_x(x: &[u8]) -> Result<Self, String> {
+        if x.len() != 32 {
+            return Err("invalid len of x-only public key".into());
+        }
+        if U256::from_big_endian(x) >= S256Curve::prime().0 {
+            return Err("x is out of range of the field".into());
+        }
+        Self::parse(&[&[2u8], x].concat())
+    }
+
+    // BIP340, 32 bytes of x
+    pub fn x_only(&self) -> Option<[u8; 32]> {
+        self.0.field_point().as_ref().map(|point| {
+            let mut x_bytes = [0u8; 32];
+            point.x().num().to_big_endian(&mut x_bytes);
+            x_bytes
+        })
+    }
+
+    pub fn has_even_y(&self) -> bool {
+        match self.0.field_point() {
+            Some(point) => (point.y().num() % 2).is_zero(),
+            None => false,
+        }
+    }
+
+    // BIP340 verifies with the x-only key, the parity of y is ignored
+    pub fn verify_schnorr(&self, msg: &[u8], sig: &SchnorrSignature) -> bool {
+        match self.x_only() {
+            Some(pk) => sig.verify(msg, &pk),
+            None => false,
+        }
+    }
+
     pub fn from_s256_field_element(x: S256FieldElementP, y: S256FieldElementP) -> Result<Self, String> {
         let field_point = FieldPointCreator::from_field_element(x.into_inner(), y.into_inner()).expect("prime of s256_field_element is different");
         Self::from_field_point(field_point)
@@ -46,14 +81,38 @@ impl S256Point {
     pub fn from_field_point(point: FieldPoint) -> Result<Self, String> {
         let p = S256Curve::prime();
         let field_ecc_point_creator = FieldEccPointCreator::new(p, S256Curve::a(), S256Curve::b());
+        // the cofactor of secp256k1 is 1, every point on the curve has the order n
         let ecc_point = field_ecc_point_creator.with_field_point(point)?;
 
-        let n = S256Curve::n();
-        if !( ecc_point.clone() * n ).is_infinity() {
-            return Err(format!("invalid n({:x}) for ecc_point({:?})", n, ecc_point));
+        Ok(Self(ecc_point))
+    }
+
+    pub fn to_jacobian(&self) -> JacobianPoint {
+        match self.0.field_point() {
+            Some(point) => JacobianPoint::from_affine(point.x().num(), point.y().num()),
+            None => JacobianPoint::infinity(),
         }
+    }
 
-        Ok(Self(ecc_point))
+    // the Montgomery ladder for a secret coefficient, Mul is faster for public ones
+    pub fn mul_secret(&self, coefficient: U256) -> Self {
+        Self::from_jacobian(self.to_jacobian().mul_ladder(coefficient))
+    }
+
+    // u*G + v*point in one pass, G uses the precomputed table
+    pub fn mul_add_g(&self, u: U256, v: U256) -> Self {
+        Self::from_jacobian(self.to_jacobian().mul_add_g(u, v))
+    }
+
+    pub fn from_jacobian(point: JacobianPoint) -> Self {
+        let field_ecc_point_creator = FieldEccPointCreator::new(S256Curve::prime(), S256Curve::a(), S256Curve::b());
+        match point.to_affine() {
+            Some((x, y)) => {
+                let field_point = FieldPointCreator::new(S256Curve::prime()).from_u256(x, y);
+                Self(field_ecc_point_creator.with_field_point(field_point).expect("jacobian point is on the curve"))
+            },
+            None => Self(field_ecc_point_creator.infinity()),
+        }
     }
 
     pub fn into_inner(self) -> FieldEccPoint {
@@ -100,7 +159,8 @@ impl S256Point {
                 let mut x_bytes = [0u8; 32];
                 point.x().num().to_big_endian(&mut x_bytes);
 
-                let mut result = if (point.x().num() % 2).is_zero() { vec![2u8] } else { vec![3u8] };
+                // the prefix is the parity of y
+                let mut result = if (point.y().num() % 2).is_zero() { vec![2u8] } else { vec![3u8] };
                 result.append(&mut x_bytes.to_vec());
 
                 Some(result)
@@ -131,8 +191,7 @@ impl Add<Self> for S256Point {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self {
-        let ecc_point = self.inner().clone() + rhs.inner().clone();
-        Self(ecc_point)
+        Self::from_jacobian(self.to_jacobian() + rhs.to_jacobian())
     }
 }
     
@@ -140,12 +199,9 @@ impl Add<Self> for S256Point {
 impl Mul<U256> for S256Point {
     type Output = Self;
 
+    // wNAF in jacobian coordinates, the result is converted back to affine with one inversion
     fn mul(self, coefficient: U256) -> Self {
-        let n = S256Curve::n();
-        let coefficient = coefficient % n;
-        let ecc_point_result = self.0 * coefficient;
-        
-        Self(ecc_point_result)
+        Self::from_jacobian(self.to_jacobian() * coefficient)
     }
 }
 
@@ -185,7 +241,9 @@ mod tests {
     #[test]
     fn s256_point_sec_compressed_2() {
         let g_sec = (S256Point::g() * 2.into()).sec_compressed().unwrap();
-        assert_eq!(hex::encode(g_sec), "03c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5");
+        assert_eq!(hex::encode(g_sec), "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5");
+        let g_sec = (S256Point::g() * 3.into()).sec_compressed().unwrap();
+        assert_eq!(hex::encode(g_sec), "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9");
     }
 
     #[test]
@@ -245,4 +303,24 @@ mod tests {
         let address = pk.address(true, false);
         assert_eq!(address, "1F1Pn2y6pDb68E5nYJJeba4TLg2U7B6KF1");
     }
+
+    #[test]
+    fn s256_point_lift_x() {
+        let g = S256Point::g();
+        let x = g.x_only().unwrap();
+        assert_eq!(hex::encode(x), "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+        assert_eq!(S256Point::lift_x(&x).unwrap(), g);
+        assert!(g.has_even_y());
+
+        // 6G has an odd y, lift_x returns its negation
+        let g6 = S256Point::g() * 6.into();
+        assert!(!g6.has_even_y());
+        let lifted = S256Point::lift_x(&g6.x_only().unwrap()).unwrap();
+        assert!(lifted.has_even_y());
+        assert_eq!(lifted, S256Point::g() * (S256Curve::n() - 6));
+
+        // no point with x = 5, and x >= p
+        assert!(S256Point::lift_x(&{ let mut x = [0u8; 32]; x[31] = 5; x }).is_err());
+        assert!(S256Point::lift_x(&[0xff; 32]).is_err());
+    }
 }
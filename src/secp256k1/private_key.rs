@@ -1,6 +1,10 @@
-use super::{S256Curve, S256FieldElementN, S256FieldElementNCreator, S256Point, Signature};
+use super::{RecoverableSignature, S256Curve, S256FieldElementN, S256FieldElementNCreator, S256Point, SchnorrSignature, Signature};
 use primitive_types::U256;
 use crate::util::{base58, hash};
+use std::fmt;
+use zeroize::Zeroize;
+
+// the secret is zeroed on drop, and only scalar multiplications in constant time are done with it
 pub struct PrivateKey {
     secret: S256FieldElementN,
     point: S256Point,
@@ -9,8 +13,7 @@ pub struct PrivateKey {
 impl PrivateKey {
     pub fn new(secret: U256) -> Result<Self, &'static str> {
         let secret = S256FieldElementNCreator::from_u256(secret);
-        let g = S256Point::g();
-        let point = g * secret.num();
+        let point = S256Point::g().mul_secret(secret.num());
         if point.inner().field_point().is_none() {
             return Err("invalid key because of s*G=infinity");
         }
@@ -22,29 +25,117 @@ impl PrivateKey {
         &self.point
     }
 
-    // sig = (z + r*e)/k
+    // sig = (z + r*e)/k, always low s as the standard of bitcoin
     pub fn sign(&self, z: U256, k: U256) -> Result<Signature, &'static str> {
-        let g = S256Point::g();
-        let k = S256FieldElementNCreator::from_u256(k);
-        let k_pk = g * k.num();
-        let r = match k_pk.into_inner().into_field_point() {
-            Some(p) => p.x(),
-            None => return Err("failed to sign because of k*G=infinity"),
+        self.sign_recoverable(z, k).map(|signature| signature.signature().clone())
+    }
+
+    pub fn sign_deterministic(&self, z: U256) -> Result<Signature, &'static str> {
+        let k = self.deterministic_k(z);
+        self.sign(z, k.num())
+    }
+
+    // the recovery id comes from R, negating s for low s negates R as well
+    pub fn sign_recoverable(&self, z: U256, k: U256) -> Result<RecoverableSignature, &'static str> {
+        let mut k = S256FieldElementNCreator::from_u256(k);
+        let k_pk = S256Point::g().mul_secret(k.num());
+        let (r, y_is_odd) = match k_pk.into_inner().into_field_point() {
+            Some(p) => (p.x().num(), !(p.y().num() % 2).is_zero()),
+            None => {
+                k.zeroize();
+                return Err("failed to sign because of k*G=infinity");
+            },
         };
-        let r = S256FieldElementNCreator::from_u256(r.num());
+        let mut recovery_id = if y_is_odd { 1u8 } else { 0u8 };
+        if r >= S256Curve::n() {
+            recovery_id |= 2;
+        }
+        let r = S256FieldElementNCreator::from_u256(r);
         let z = S256FieldElementNCreator::from_u256(z);
-        
-        let signature = (z + r * self.secret) / k;
-        if signature.num() > S256Curve::n()/2 {
-            return Err("invalid signature because of the signature of BTC should less than N/2"); // the feature is not for secp256k1, just for Bitcoin
+
+        let s = (z + r * self.secret) / k;
+        k.zeroize();
+        let mut signature = Signature::new(r.num(), s.num());
+        if !signature.is_low_s() {
+            signature.normalize_s();
+            recovery_id ^= 1;
         }
 
-        Ok(Signature::new(r.num(), signature.num()))
+        RecoverableSignature::new(signature, recovery_id).map_err(|_| "invalid recovery id")
     }
 
-    pub fn sign_deterministic(&self, z: U256) -> Result<Signature, &'static str> {
+    pub fn sign_recoverable_deterministic(&self, z: U256) -> Result<RecoverableSignature, &'static str> {
         let k = self.deterministic_k(z);
-        self.sign(z, k.num())
+        self.sign_recoverable(z, k.num())
+    }
+
+    // BIP340, the 32 bytes of x of the public key
+    pub fn x_only_public_key(&self) -> [u8; 32] {
+        self.point.x_only().expect("public key of a valid secret is not infinity")
+    }
+
+    // BIP340, the secret and the nonce are negated when their points have an odd y
+    // sig = R || (k + e*d), e = hash(R || P || msg)
+    pub fn sign_schnorr(&self, msg: &[u8], aux_rand: &[u8; 32]) -> Result<SchnorrSignature, &'static str> {
+        let n = S256Curve::n();
+        let mut d = if self.point.has_even_y() { self.secret.num() } else { n - self.secret.num() };
+        let mut d_bytes = [0u8; 32];
+        d.to_big_endian(&mut d_bytes);
+        let pk = self.x_only_public_key();
+
+        // the aux randomness masks the secret to protect the nonce against side channels
+        let aux_hash = hash::tagged_hash("BIP0340/aux", aux_rand);
+        let mut nonce_msg: Vec<u8> = d_bytes.iter().zip(aux_hash.iter()).map(|(d, a)| d ^ a).collect();
+        d_bytes.zeroize();
+        nonce_msg.extend_from_slice(&pk);
+        nonce_msg.extend_from_slice(msg);
+        let mut nonce = hash::tagged_hash("BIP0340/nonce", &nonce_msg);
+        nonce_msg.zeroize();
+        let mut k = U256::from_big_endian(&nonce) % n;
+        nonce.zeroize();
+        let big_r = S256Point::g().mul_secret(k);
+        let r = match big_r.x_only() {
+            Some(r) if !k.is_zero() => r,
+            _ => {
+                k.0.zeroize();
+                d.0.zeroize();
+                return Err("failed to sign because of k=0");
+            },
+        };
+        if !big_r.has_even_y() {
+            k = n - k;
+        }
+
+        let e = SchnorrSignature::challenge(&r, &pk, msg);
+        let s = S256FieldElementNCreator::from_u256(k) + S256FieldElementNCreator::from_u256(e) * S256FieldElementNCreator::from_u256(d);
+        k.0.zeroize();
+        d.0.zeroize();
+        let signature = SchnorrSignature::new(r, s.num());
+        if !signature.verify(msg, &pk) {
+            return Err("failed to verify the schnorr signature");
+        }
+
+        Ok(signature)
+    }
+
+    // BIP341, the secret of the output key Q = P + hash(P || merkle_root)G, P has an even y
+    pub fn tap_tweak(&self, merkle_root: Option<&[u8; 32]>) -> Result<Self, &'static str> {
+        let n = S256Curve::n();
+        let mut msg = self.x_only_public_key().to_vec();
+        if let Some(root) = merkle_root {
+            msg.extend_from_slice(root);
+        }
+        let tweak = U256::from_big_endian(&hash::tagged_hash("TapTweak", &msg));
+        if tweak >= n {
+            return Err("invalid key because of tweak>=n");
+        }
+
+        let mut d = if self.point.has_even_y() { self.secret } else { S256FieldElementNCreator::from_u256(n - self.secret.num()) };
+        let mut secret = d + S256FieldElementNCreator::from_u256(tweak);
+        d.zeroize();
+        let result = Self::new(secret.num());
+        secret.zeroize();
+        result
     }
 
     pub fn wif(&self, compressed: bool, test_net: bool) -> String {
@@ -55,14 +146,15 @@ impl PrivateKey {
         let suffix = if compressed { Some(1u8) } else { None };
 
         let mut result = [prefix].to_vec();
-        println!("result of wif: {:?}", hex::encode(&result));
-        result.append(&mut secret_bytes.to_vec());
+        result.extend_from_slice(&secret_bytes);
+        secret_bytes.zeroize();
         if let Some(suffix) = suffix {
             result.push(suffix);
         }
-        println!("result of wif: {:?}", hex::encode(&result));
 
-        base58::encode_bytes_checksum(&result)
+        let wif = base58::encode_bytes_checksum(&result);
+        result.zeroize();
+        wif
     }
 
     pub fn deterministic_k(&self, z: U256) -> S256FieldElementN {
@@ -75,19 +167,25 @@ impl PrivateKey {
         let mut secret_bytes = [0u8; 32];
         self.secret.num().to_big_endian(&mut secret_bytes);
 
-        let message = [v.to_vec(), vec![0u8], secret_bytes.to_vec(), z_bytes.to_vec()].concat();
+        let mut message = [&v[..], &[0u8], &secret_bytes, &z_bytes].concat();
         k = hash::hmac256(&k, &message);
+        message.zeroize();
         v = hash::hmac256(&k, &v);
 
-        let message = [v.to_vec(), vec![1u8], secret_bytes.to_vec(), z_bytes.to_vec()].concat();
+        let mut message = [&v[..], &[1u8], &secret_bytes, &z_bytes].concat();
         k = hash::hmac256(&k, &message);
+        message.zeroize();
         v = hash::hmac256(&k, &v);
 
+        secret_bytes.zeroize();
+
         let n_prime = S256Curve::n();
         loop {
             v = hash::hmac256(&k, &v);
             let candidate = U256::from_big_endian(&v);
             if !candidate.is_zero() && candidate < n_prime {
+                k.zeroize();
+                v.zeroize();
                 return S256FieldElementNCreator::from_u256(candidate);
             }
             // update k
@@ -98,6 +196,20 @@ impl PrivateKey {
     }
 }
 
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+// only the public key is shown
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sec = self.point.sec_compressed().map(hex::encode).unwrap_or_default();
+        write!(f, "PrivateKey({})", sec)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::PrivateKey;
@@ -139,13 +251,15 @@ mod tests {
     }
 
     #[test]
-    fn priv_key_sign_failed_2() {
+    fn priv_key_sign_low_s() {
         let priv_key = PrivateKey::new(100u32.into()).unwrap();
 
+        // s is higher than N/2 with this k, it is replaced by N - s
         let msg_hash = U256::from(200);
         let k = U256::from(300);
-        let signature = priv_key.sign(msg_hash, k);
-        assert_eq!(signature.err(), Some("invalid signature because of the signature of BTC should less than N/2"));
+        let signature = priv_key.sign(msg_hash, k).unwrap();
+        assert!(signature.is_low_s());
+        assert!(signature.verify(msg_hash, priv_key.pk_point().clone()));
     }
 
     #[test]
@@ -172,6 +286,24 @@ mod tests {
         assert_eq!(wif, "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgiuQJv1h8Ytr2S53a");
     }
 
+    #[test]
+    fn priv_key_debug_hides_secret() {
+        let sk = PrivateKey::new(U256::from_str_radix("54321deadbeef", 16).unwrap()).unwrap();
+        let debug = format!("{:?}", sk);
+        assert!(!debug.contains("54321deadbeef"));
+        assert_eq!(debug, format!("PrivateKey({})", hex::encode(sk.pk_point().sec_compressed().unwrap())));
+    }
+
+    // run Drop in place and read the memory it leaves, ManuallyDrop keeps the memory and does not drop it twice
+    #[test]
+    fn priv_key_secret_zeroize_on_drop() {
+        use std::mem::ManuallyDrop;
+        let mut sk = ManuallyDrop::new(PrivateKey::new(5003.into()).unwrap());
+        assert_eq!(sk.secret.num(), 5003.into());
+        unsafe { std::ptr::drop_in_place(&mut *sk) };
+        assert!(sk.secret.num().is_zero());
+    }
+
     #[test]
     fn priv_key_deterministic_k() {
         let sk = PrivateKey::new(1.into()).unwrap();
@@ -0,0 +1,128 @@
+use primitive_types::U256;
+use super::{S256Curve, S256FieldElementNCreator, S256Point, Signature};
+
+// the recovery id picks R out of the four points whose x mod n is r
+// bit 0: y of R is odd, bit 1: x of R is r + n
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoverableSignature {
+    signature: Signature,
+    recovery_id: u8,
+}
+
+impl RecoverableSignature {
+    pub fn new(signature: Signature, recovery_id: u8) -> Result<Self, String> {
+        if recovery_id > 3 {
+            return Err(format!("invalid recovery id {}", recovery_id));
+        }
+        Ok(Self { signature, recovery_id })
+    }
+
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    pub fn recovery_id(&self) -> u8 {
+        self.recovery_id
+    }
+
+    // [27 + recovery_id (+4 if compressed)] [r] [s], the header of signmessage
+    pub fn serialize_compact(&self, compressed: bool) -> [u8; 65] {
+        let mut result = [0u8; 65];
+        result[0] = 27 + self.recovery_id + if compressed { 4 } else { 0 };
+        self.signature.r().to_big_endian(&mut result[1..33]);
+        self.signature.s().to_big_endian(&mut result[33..65]);
+        result
+    }
+
+    // @return (Self, the public key is compressed)
+    pub fn parse_compact(bytes: &[u8]) -> Result<(Self, bool), String> {
+        if bytes.len() != 65 {
+            return Err("invalid len of compact signature".into());
+        }
+        if !(27..=34).contains(&bytes[0]) {
+            return Err(format!("invalid header byte {} of compact signature", bytes[0]));
+        }
+        let header = bytes[0] - 27;
+        let signature = Signature::new(U256::from_big_endian(&bytes[1..33]), U256::from_big_endian(&bytes[33..65]));
+
+        Ok((Self::new(signature, header & 3)?, header & 4 != 0))
+    }
+
+    // Q = (s*R - z*G)/r
+    pub fn recover(&self, z: U256) -> Result<S256Point, String> {
+        let n = S256Curve::n();
+        let (r, s) = (self.signature.r(), self.signature.s());
+        if r.is_zero() || r >= n || s.is_zero() || s >= n {
+            return Err("r or s is out of range".into());
+        }
+
+        let mut x = r;
+        if self.recovery_id & 2 != 0 {
+            if r >= S256Curve::prime().0 - n {
+                return Err("x of R is out of range of the field".into());
+            }
+            x = r + n;
+        }
+        let mut sec = [0u8; 33];
+        sec[0] = 2 + (self.recovery_id & 1);
+        x.to_big_endian(&mut sec[1..]);
+        let big_r = S256Point::parse(&sec)?;
+
+        let r_inv = S256FieldElementNCreator::from_u256(U256::one()) / S256FieldElementNCreator::from_u256(r);
+        let z = S256FieldElementNCreator::from_u256(z);
+        let u = S256FieldElementNCreator::from_u256(U256::zero()) - z * r_inv;
+        let v = S256FieldElementNCreator::from_u256(s) * r_inv;
+        let point = big_r.mul_add_g(u.num(), v.num());
+        if point.inner().field_point().is_none() {
+            return Err("recovered public key is infinity".into());
+        }
+
+        Ok(point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use primitive_types::U256;
+    use super::RecoverableSignature;
+    use crate::secp256k1::{PrivateKey, Signature};
+
+    #[test]
+    fn recoverable_signature_recover() {
+        let priv_key = PrivateKey::new(U256::from(12345)).unwrap();
+        for z in [U256::from(1), U256::from(0xdeadbeefu64), U256::MAX] {
+            let signature = priv_key.sign_recoverable_deterministic(z).unwrap();
+            assert_eq!(&signature.recover(z).unwrap(), priv_key.pk_point());
+            assert!(signature.signature().verify(z, priv_key.pk_point().clone()));
+
+            let mut other = signature.clone();
+            other.recovery_id ^= 1;
+            assert_ne!(&other.recover(z).unwrap(), priv_key.pk_point());
+        }
+    }
+
+    #[test]
+    fn recoverable_signature_compact() {
+        let priv_key = PrivateKey::new(U256::from(12345)).unwrap();
+        let signature = priv_key.sign_recoverable_deterministic(U256::from(7)).unwrap();
+        for compressed in [false, true] {
+            let bytes = signature.serialize_compact(compressed);
+            assert_eq!(bytes[0], 27 + signature.recovery_id() + if compressed { 4 } else { 0 });
+            assert_eq!(RecoverableSignature::parse_compact(&bytes).unwrap(), (signature.clone(), compressed));
+        }
+
+        assert!(RecoverableSignature::parse_compact(&[27u8; 64]).is_err());
+        assert!(RecoverableSignature::parse_compact(&[35u8; 65]).is_err());
+        assert!(RecoverableSignature::new(Signature::new(U256::one(), U256::one()), 4).is_err());
+    }
+
+    #[test]
+    fn recoverable_signature_recover_invalid() {
+        let z = U256::from(7);
+        let zero = RecoverableSignature::new(Signature::new(U256::zero(), U256::one()), 0).unwrap();
+        assert!(zero.recover(z).is_err());
+        // r + n is beyond p for almost every r
+        let high = RecoverableSignature::new(Signature::new(U256::MAX >> 1, U256::one()), 2).unwrap();
+        assert!(high.recover(z).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use primitive_types::U256;
+use crate::util::hash;
+use super::{S256Curve, S256Point};
+
+// BIP340, r is the x of R which has an even y
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchnorrSignature {
+    r: [u8; 32],
+    s: U256,
+}
+
+impl SchnorrSignature {
+    pub fn new(r: [u8; 32], s: U256) -> Self {
+        Self { r, s }
+    }
+
+    pub fn r(&self) -> &[u8; 32] {
+        &self.r
+    }
+
+    pub fn s(&self) -> U256 {
+        self.s
+    }
+
+    // 64 bytes of r || s, the range is checked by verify
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() != 64 {
+            return Err("invalid len of bytes in SchnorrSignature::parse".into());
+        }
+        let mut r = [0u8; 32];
+        r.copy_from_slice(&bytes[..32]);
+        let s = U256::from_big_endian(&bytes[32..]);
+
+        Ok(Self { r, s })
+    }
+
+    pub fn serialize(&self) -> [u8; 64] {
+        let mut result = [0u8; 64];
+        result[..32].copy_from_slice(&self.r);
+        self.s.to_big_endian(&mut result[32..]);
+        result
+    }
+
+    // e = hash(r || P || msg), R = s*G - e*P
+    // @param pk: 32 bytes x-only public key
+    pub fn verify(&self, msg: &[u8], pk: &[u8]) -> bool {
+        let pk_point = match S256Point::lift_x(pk) {
+            Ok(point) => point,
+            Err(_) => return false,
+        };
+        let n = S256Curve::n();
+        if U256::from_big_endian(&self.r) >= S256Curve::prime().0 || self.s >= n {
+            return false;
+        }
+        let e = Self::challenge(&self.r, pk, msg);
+
+        // -e*P = (n-e)*P
+        let big_r = pk_point.mul_add_g(self.s, n - e);
+        big_r.has_even_y() && big_r.x_only() == Some(self.r)
+    }
+
+    pub(super) fn challenge(r: &[u8; 32], pk: &[u8], msg: &[u8]) -> U256 {
+        let e = hash::tagged_hash("BIP0340/challenge", &[r, pk, msg].concat());
+        U256::from_big_endian(&e) % S256Curve::n()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SchnorrSignature;
+    use super::super::PrivateKey;
+    use std::convert::TryInto;
+    use primitive_types::U256;
+
+    fn verify_hex(pk: &str, msg: &str, sig: &str) -> bool {
+        let sig = SchnorrSignature::parse(&hex::decode(sig).unwrap()).unwrap();
+        sig.verify(&hex::decode(msg).unwrap(), &hex::decode(pk).unwrap())
+    }
+
+    #[test]
+    fn schnorr_signature_bip340_vectors() {
+        // BIP340 test vectors with a secret key: secret, public key, aux_rand, msg, sig
+        let sign_vectors = [
+            ("0000000000000000000000000000000000000000000000000000000000000003", "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9", "0000000000000000000000000000000000000000000000000000000000000000", "0000000000000000000000000000000000000000000000000000000000000000", "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca821525f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0"),
+            ("b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef", "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "0000000000000000000000000000000000000000000000000000000000000001", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89", "6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de33418906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a"),
+            ("c90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74020bbea63b14e5c9", "dd308afec5777e13121fa72b9cc1b7cc0139715309b086c960e18fd969774eb8", "c87aa53824b4d7ae2eb035a2b5bbbccc080e76cdc6d1692c4b0b62d798e6d906", "7e2d58d8b3bcdf1abadec7829054f90dda9805aab56c77333024b9d0a508b75c", "5831aaeed7b44bb74e5eab94ba9d4294c49bcf2a60728d8b4c200f50dd313c1bab745879a5ad954a72c45a91c3a51d3c7adea98d82f8481e0e1e03674a6f3fb7"),
+            ("0b432b2677937381aef05bb02a66ecd012773062cf3fa2549e44f58ed2401710", "25d1dff95105f5253c4022f628a996ad3a0d95fbf21d468a1b33f8c160d8f517", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "7eb0509757e246f19449885651611cb965ecc1a187dd51b64fda1edc9637d5ec97582b9cb13db3933705b32ba982af5af25fd78881ebb32771fc5922efc66ea3"),
+            ("0340034003400340034003400340034003400340034003400340034003400340", "778caa53b4393ac467774d09497a87224bf9fab6f6e68b23086497324d6fd117", "0000000000000000000000000000000000000000000000000000000000000000", "", "71535db165ecd9fbbc046e5ffaea61186bb6ad436732fccc25291a55895464cf6069ce26bf03466228f19a3a62db8a649f2d560fac652827d1af0574e427ab63"),
+            ("0340034003400340034003400340034003400340034003400340034003400340", "778caa53b4393ac467774d09497a87224bf9fab6f6e68b23086497324d6fd117", "0000000000000000000000000000000000000000000000000000000000000000", "11", "08a20a0afef64124649232e0693c583ab1b9934ae63b4c3511f3ae1134c6a303ea3173bfea6683bd101fa5aa5dbc1996fe7cacfc5a577d33ec14564cec2bacbf"),
+            ("0340034003400340034003400340034003400340034003400340034003400340", "778caa53b4393ac467774d09497a87224bf9fab6f6e68b23086497324d6fd117", "0000000000000000000000000000000000000000000000000000000000000000", "0102030405060708090a0b0c0d0e0f1011", "5130f39a4059b43bc7cac09a19ece52b5d8699d1a71e3c52da9afdb6b50ac370c4a482b77bf960f8681540e25b6771ece1e5a37fd80e5a51897c5566a97ea5a5"),
+        ];
+        for (secret, pk, aux_rand, msg, sig) in sign_vectors {
+            let sk = PrivateKey::new(U256::from_str_radix(secret, 16).unwrap()).unwrap();
+            assert_eq!(hex::encode(sk.x_only_public_key()), pk);
+            let aux_rand: [u8; 32] = hex::decode(aux_rand).unwrap().try_into().unwrap();
+            let signature = sk.sign_schnorr(&hex::decode(msg).unwrap(), &aux_rand).unwrap();
+            assert_eq!(hex::encode(signature.serialize()), sig);
+            assert!(sk.pk_point().verify_schnorr(&hex::decode(msg).unwrap(), &signature));
+        }
+        // message of 100 bytes, vector 18
+        let sk = PrivateKey::new(U256::from_str_radix("0340034003400340034003400340034003400340034003400340034003400340", 16).unwrap()).unwrap();
+        let signature = sk.sign_schnorr(&[0x99; 100], &[0u8; 32]).unwrap();
+        assert_eq!(hex::encode(signature.serialize()), "403b12b0d8555a344175ea7ec746566303321e5dbfa8be6f091635163eca79a8585ed3e3170807e7c03b720fc54c7b23897fcba0e9d0b4a06894cfd249f22367");
+
+        // BIP340 test vectors without a secret key: public key, msg, sig, result
+        let verify_vectors = [
+            // 4: r with leading zero bytes
+            ("d69c3509bb99e412e68b0fe8544e72837dfa30746d8be2aa65975f29d22dc7b9", "4df3c3f68fcc83b27e9d42c90431a72499f17875c81a599b566c9889b9696703", "00000000000000000000003b78ce563f89a0ed9414f5aa28ad0d96d6795f9c6376afb1548af603b3eb45c9f8207dee1060cb71c04e80f593060b07d28308d7f4", true),
+            // 5: public key not on the curve
+            ("eefdea4cdb677750a420fee807eacf21eb9898ae79b9768766e4faa04a2d4a34", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89", "6cff5c3ba86c69ea4b7376f31a9bcb4f74c1976089b2d9963da2e5543e17776969e89b4c5564d00349106b8497785dd7d1d713a8ae82b32fa79d5f7fc407d39b", false),
+            // 6: R has an odd y
+            ("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89", "fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a14602975563cc27944640ac607cd107ae10923d9ef7a73c643e166be5ebeafa34b1ac553e2", false),
+            // 7: negated message
+            ("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89", "1fa62e331edbc21c394792d2ab1100a7b432b013df3f6ff4f99fcb33e0e1515f28890b3edb6e7189b630448b515ce4f8622a954cfe545735aaea5134fccdb2bd", false),
+            // 8: negated s
+            ("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89", "6cff5c3ba86c69ea4b7376f31a9bcb4f74c1976089b2d9963da2e5543e177769961764b3aa9b2ffcb6ef947b6887a226e8d7c93e00c5ed0c1834ff0d0c2e6da6", false),
+            // 9: s*G - e*P is infinity
+            ("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89", "0000000000000000000000000000000000000000000000000000000000000000123dda8328af9c23a94c1feecfd123ba4fb73476f0d594dcb65c6425bd186051", false),
+            // 10: s*G - e*P is infinity
+            ("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89", "00000000000000000000000000000000000000000000000000000000000000017615fbaf5ae28864013c099742deadb4dba87f11ac6754f93780d5a1837cf197", false),
+            // 11: r is not the x of a point
+            ("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89", "4a298dacae57395a15d0795ddbfd1dcb564da82b0f269bc70a74f8220429ba1d69e89b4c5564d00349106b8497785dd7d1d713a8ae82b32fa79d5f7fc407d39b", false),
+            // 12: r is equal to the field size
+            ("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89", "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f69e89b4c5564d00349106b8497785dd7d1d713a8ae82b32fa79d5f7fc407d39b", false),
+            // 13: s is equal to the curve order
+            ("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89", "6cff5c3ba86c69ea4b7376f31a9bcb4f74c1976089b2d9963da2e5543e177769fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141", false),
+            // 14: public key exceeds the field size
+            ("fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc30", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89", "6cff5c3ba86c69ea4b7376f31a9bcb4f74c1976089b2d9963da2e5543e17776969e89b4c5564d00349106b8497785dd7d1d713a8ae82b32fa79d5f7fc407d39b", false),
+        ];
+        for (pk, msg, sig, result) in verify_vectors {
+            assert_eq!(verify_hex(pk, msg, sig), result, "{}", sig);
+        }
+    }
+
+    #[test]
+    fn schnorr_signature_serialize() {
+        let bytes = hex::decode("6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de33418906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a").unwrap();
+        let sig = SchnorrSignature::parse(&bytes).unwrap();
+        assert_eq!(sig.serialize().to_vec(), bytes);
+        assert!(SchnorrSignature::parse(&bytes[1..]).is_err());
+    }
+}
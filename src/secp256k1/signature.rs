@@ -1,9 +1,8 @@
 use primitive_types::U256;
 use crate::secp256k1::point::S256Point;
-use super::{S256FieldElementN, S256FieldElementNCreator};
-use crate::util::math;
+use super::{S256Curve, S256FieldElementN, S256FieldElementNCreator};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Signature {
     r: S256FieldElementN,
     s: S256FieldElementN,
@@ -17,11 +16,21 @@ impl Signature {
         Self { r, s }
     }
 
+    pub fn r(&self) -> U256 {
+        self.r.num()
+    }
+
+    pub fn s(&self) -> U256 {
+        self.s.num()
+    }
+
+    // high s is accepted as consensus does, see is_low_s for policy
     // sig = (z + r*e)/k
     pub fn verify(&self, z: U256, s256_pk_point: S256Point) -> bool {
         let z = S256FieldElementNCreator::from_u256(z);
-        let g = S256Point::g();
-        let cal_pk_point = g * (z/self.s).num() + s256_pk_point * (self.r/self.s).num();
+        // one inversion for both z/s and r/s
+        let s_inv = S256FieldElementNCreator::from_u256(U256::one()) / self.s;
+        let cal_pk_point = s256_pk_point.mul_add_g((z * s_inv).num(), (self.r * s_inv).num());
         let cal_x = match cal_pk_point.into_inner().into_field_point() {
             None => return false,
             Some(p) => p.x(),
@@ -35,21 +44,80 @@ impl Signature {
         let s_der = Self::der_parse_u256(&self.s.num());
         let sig = format!("02{:02x}{}02{:02x}{}", r_der.len()/2, r_der, s_der.len()/2, s_der);
 
-        format!("30{:x}{}", sig.len()/2, sig)
+        format!("30{:02x}{}", sig.len()/2, sig)
     }
 
+    // big endian without leading zero, prefix 00 if the highest bit is set
     pub fn der_parse_u256(num: &U256) -> String {
-        let mut prefix = "";
-        if num.byte(31) >= 0x80 { // small order
-            prefix = "00";
+        let mut bytes = [0u8; 32];
+        num.to_big_endian(&mut bytes);
+        let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(31);
+        let mut result = bytes[start..].to_vec();
+        if result[0] >= 0x80 {
+            result.insert(0, 0);
+        }
+        hex::encode(result)
+    }
+
+    // BIP62, s of a valid signature can always be replaced by n - s, the lower one is standard
+    pub fn is_low_s(&self) -> bool {
+        self.s.num() <= S256Curve::n() / 2
+    }
+
+    pub fn normalize_s(&mut self) {
+        if !self.is_low_s() {
+            self.s = S256FieldElementNCreator::from_u256(S256Curve::n() - self.s.num());
+        }
+    }
+
+    // BIP66, the bytes end with the sighash byte
+    // 0x30 [total-length] 0x02 [R-length] [R] 0x02 [S-length] [S] [sighash]
+    pub fn check_strict_der(bytes: &[u8]) -> Result<(), DerError> {
+        if bytes.len() < 9 || bytes.len() > 73 {
+            return Err(DerError::InvalidSize);
         }
-        format!("{}{:x}", prefix, num)
+        if bytes[0] != 0x30 {
+            return Err(DerError::InvalidSequenceMark);
+        }
+        if bytes[1] as usize != bytes.len() - 3 {
+            return Err(DerError::InvalidTotalLength);
+        }
+        let len_r = bytes[3] as usize;
+        if 5 + len_r >= bytes.len() {
+            return Err(DerError::InvalidRLength);
+        }
+        let len_s = bytes[5 + len_r] as usize;
+        if len_r + len_s + 7 != bytes.len() {
+            return Err(DerError::InvalidSLength);
+        }
+
+        Self::check_strict_der_integer(&bytes[2..], len_r)?;
+        Self::check_strict_der_integer(&bytes[(len_r + 4)..], len_s)
     }
 
+    // 0x02 [length] [integer]
+    fn check_strict_der_integer(bytes: &[u8], len: usize) -> Result<(), DerError> {
+        if bytes[0] != 0x02 {
+            return Err(DerError::InvalidIntegerMark);
+        }
+        if len == 0 {
+            return Err(DerError::ZeroLengthInteger);
+        }
+        if bytes[2] & 0x80 != 0 {
+            return Err(DerError::NegativeInteger);
+        }
+        // 0x00 is only allowed to keep the integer positive
+        if len > 1 && bytes[2] == 0x00 && bytes[3] & 0x80 == 0 {
+            return Err(DerError::ExcessivePadding);
+        }
+        Ok(())
+    }
+
+    // lax parsing, any integer within 33 bytes is accepted
     // @return (Self, bytes_used)
     pub fn parse_der(bytes: &[u8]) -> Result<(Self, usize), String> {
-        if bytes.is_empty() {
-            return Err("cannot convert empty bytes into Signature in Signature::parse_der".into());
+        if bytes.len() < 2 {
+            return Err("bytes.len() is too short in Signature::parse_der".into());
         }
         if bytes[0] != 0x30 {
             return Err("bytes[0] != 0x30 for Signature::parse_der".into());
@@ -69,45 +137,50 @@ impl Signature {
 
     // @return (U256, byte_used)
     fn parse_der_element(bytes: &[u8]) -> Result<(U256, usize), String> {
-        if bytes.is_empty() {
+        if bytes.len() < 2 {
             return Err("cannot convert empty bytes into U256 in Signature::parse_element".into());
         }
-
-        let mut i = 0;
-        let len = bytes.len();
-        if bytes[i] != 0x02 {
+        if bytes[0] != 0x02 {
             return Err("invalid mark byte in Signature::parse_element".into());
         }
 
-        i = math::check_range_add(i, 1, len)?;
-        let num_bytes = bytes[i];
-        let result = match num_bytes {
-            0x21 => {
-                i = math::check_range_add(i, 1, len)?;
-                if bytes[i] != 0x00 {
-                    return Err("invalid prefix-00 in Signature::parse_element".into());
-                }
-                let fst = math::check_range_add(i, 1, len)?; // skip 0x00 byte
-                let lst = math::check_range_add(i, 0x20, len)?;
-                (U256::from_big_endian(&bytes[fst..(lst+1)]), 0x23)
-            },
-            0x20 => {
-                let fst = math::check_range_add(i, 1, len)?;
-                let lst = math::check_range_add(i, 0x20, len)?;
-                (U256::from_big_endian(&bytes[fst..(lst+1)]), 0x22)
-            },
-            _ => return Err("invalid len of bytes in Signature::parse_element".into()),
+        let num_bytes = bytes[1] as usize;
+        if num_bytes == 0 || num_bytes > 33 {
+            return Err("invalid len of bytes in Signature::parse_element".into());
+        }
+        let num = match bytes.get(2..(2 + num_bytes)) {
+            Some(num) => num,
+            None => return Err("bytes.len() is too short in Signature::parse_element".into()),
         };
+        let start = num.iter().position(|byte| *byte != 0).unwrap_or(num.len());
+        if num.len() - start > 32 {
+            return Err("integer is out of range in Signature::parse_element".into());
+        }
 
-        Ok(result)
+        Ok((U256::from_big_endian(&num[start..]), 2 + num_bytes))
     }
 }
 
+// why an encoding is not strict DER of BIP66
+#[derive(Debug, PartialEq)]
+pub enum DerError {
+    InvalidSize,
+    InvalidSequenceMark,
+    InvalidTotalLength,
+    InvalidRLength,
+    InvalidSLength,
+    InvalidIntegerMark,
+    ZeroLengthInteger,
+    NegativeInteger,
+    ExcessivePadding,
+}
+
 #[cfg(test)]
 mod tests {
     use crate::secp256k1::{point::S256Point, S256FieldElementPCreator};
     use primitive_types::U256;
-    use super::Signature;
+    use super::{DerError, Signature};
+    use crate::secp256k1::S256Curve;
 
     #[test]
     fn signature_verify_success() {
@@ -179,4 +252,64 @@ mod tests {
         assert_eq!(signature, signature_2);
         assert_eq!(bytes_used, 71);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn signature_der_short_r() {
+        let signature = Signature::new(U256::from(0xabc), U256::from(0x80));
+        assert_eq!(signature.der(), "300802020abc02020080");
+
+        let der = hex::decode(signature.der()).unwrap();
+        let (signature_2, bytes_used) = Signature::parse_der(&der).unwrap();
+        assert_eq!(signature, signature_2);
+        assert_eq!(bytes_used, 10);
+    }
+
+    #[test]
+    fn signature_parse_der_lax() {
+        // 33-byte r with unnecessary padding is decodable, but not strict
+        let der = hex::decode("30260221000000000000000000000000000000000000000000000000000000000000000001020101").unwrap();
+        let (signature, _) = Signature::parse_der(&der).unwrap();
+        assert_eq!(signature, Signature::new(U256::one(), U256::one()));
+        assert!(Signature::parse_der(&hex::decode("3006020100020100").unwrap()).is_ok());
+        assert!(Signature::parse_der(&hex::decode("300602010002").unwrap()).is_err());
+    }
+
+    #[test]
+    fn signature_check_strict_der() {
+        let valid = "3045022037206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c60221008ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec01";
+        assert_eq!(Signature::check_strict_der(&hex::decode(valid).unwrap()), Ok(()));
+        assert_eq!(Signature::check_strict_der(&hex::decode("300602010102010101").unwrap()), Ok(()));
+
+        let cases = vec![
+            ("3006020101020101", DerError::InvalidSize),
+            ("310602010102010101", DerError::InvalidSequenceMark),
+            ("300702010102010101", DerError::InvalidTotalLength),
+            ("300602050102010101", DerError::InvalidRLength),
+            ("300602010102020101", DerError::InvalidSLength),
+            ("300603010102010101", DerError::InvalidIntegerMark),
+            ("300602010103010101", DerError::InvalidIntegerMark),
+            ("300602000202010101", DerError::ZeroLengthInteger),
+            ("300602018102010101", DerError::NegativeInteger),
+            ("30070202000102010101", DerError::ExcessivePadding),
+            ("30070201010202000101", DerError::ExcessivePadding),
+        ];
+        for (der, reason) in cases {
+            assert_eq!(Signature::check_strict_der(&hex::decode(der).unwrap()), Err(reason), "{}", der);
+        }
+    }
+
+    #[test]
+    fn signature_low_s() {
+        let r = U256::from(1);
+        let high_s = S256Curve::n() - 1;
+        let mut signature = Signature::new(r, high_s);
+        assert!(!signature.is_low_s());
+
+        signature.normalize_s();
+        assert!(signature.is_low_s());
+        assert_eq!(signature.s(), U256::one());
+        signature.normalize_s();
+        assert_eq!(signature.s(), U256::one());
+        assert!(Signature::new(r, S256Curve::n() / 2).is_low_s());
+    }
+}
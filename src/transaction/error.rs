@@ -6,6 +6,7 @@ use hex::FromHexError;
 pub enum Error {
     InvalidVersion,
     InvalidTxIn,
+    InvalidSequence,
     InvalidTxFee,
     InvalidSigHash,
     InvalidSegwitTx,
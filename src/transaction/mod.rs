@@ -1,9 +1,10 @@
 pub use error::Error;
 pub use version::Version;
-pub use tx_in::{TxIn, Sequence, PrevIndex};
+pub use tx_in::{TxIn, PrevIndex};
+pub use sequence::{Sequence, SequenceLocks, RelativeLockTime};
 pub use tx_out::TxOut;
 pub use tx_fetcher::TxFetcher;
-pub use transaction::Transaction;
+pub use transaction::{Transaction, WITNESS_SCALE_FACTOR, MAX_BLOCK_SIGOPS_COST};
 pub use sighash::SigHash;
 
 pub type LockTime = Version;
@@ -15,3 +16,4 @@ mod tx_out;
 mod tx_fetcher;
 mod transaction;
 mod sighash;
+mod sequence;
@@ -0,0 +1,119 @@
+use super::Error;
+
+// BIP68
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000ffff;
+const SEQUENCE_LOCKTIME_GRANULARITY: u32 = 9; // time-based lock is in units of 512 seconds
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelativeLockTime {
+    Blocks(u16),
+    Time(u16), // in units of 512 seconds
+}
+
+impl RelativeLockTime {
+    pub fn seconds(&self) -> Option<u32> {
+        match self {
+            Self::Blocks(_) => None,
+            Self::Time(units) => Some((*units as u32) << SEQUENCE_LOCKTIME_GRANULARITY),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sequence(u32);
+
+impl Sequence {
+    pub const FINAL: u32 = 0xffffffff;
+    pub const DISABLE_FLAG: u32 = SEQUENCE_LOCKTIME_DISABLE_FLAG;
+    pub const TYPE_FLAG: u32 = SEQUENCE_LOCKTIME_TYPE_FLAG;
+    pub const LOCKTIME_MASK: u32 = SEQUENCE_LOCKTIME_MASK;
+
+    pub fn new(v: u32) -> Self {
+        Self(v)
+    }
+
+    pub fn from_relative_lock_time(lock_time: RelativeLockTime) -> Self {
+        match lock_time {
+            RelativeLockTime::Blocks(blocks) => Self(blocks as u32),
+            RelativeLockTime::Time(units) => Self(SEQUENCE_LOCKTIME_TYPE_FLAG | units as u32),
+        }
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 4 {
+            return Err(Error::InvalidSequence)
+        }
+
+        let mut param: [u8; 4] = Default::default();
+        param.copy_from_slice(&bytes[0..4]);
+        Ok(Self(u32::from_le_bytes(param)))
+    }
+
+    pub fn serialize(&self) -> [u8; 4] {
+        self.0.to_le_bytes()
+    }
+
+    pub fn value(&self) -> u32 {
+        self.0
+    }
+
+    pub fn is_final(&self) -> bool {
+        self.0 == Self::FINAL
+    }
+
+    // None if relative lock-time is disabled for the input
+    pub fn relative_lock_time(&self) -> Option<RelativeLockTime> {
+        if self.0 & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return None;
+        }
+        let value = (self.0 & SEQUENCE_LOCKTIME_MASK) as u16;
+        if self.0 & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            Some(RelativeLockTime::Time(value))
+        } else {
+            Some(RelativeLockTime::Blocks(value))
+        }
+    }
+}
+
+// the last invalid height and time of the transaction, -1 means no constraint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SequenceLocks {
+    pub min_height: i64,
+    pub min_time: i64,
+}
+
+impl SequenceLocks {
+    // @param block_height: height of the block which includes the transaction
+    // @param prev_block_mtp: median-time-past of the block before it
+    pub fn is_satisfied(&self, block_height: u32, prev_block_mtp: u32) -> bool {
+        self.min_height < block_height as i64 && self.min_time < prev_block_mtp as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RelativeLockTime, Sequence};
+
+    #[test]
+    fn sequence_parse_and_serialize() {
+        let bytes = [0xfeu8, 0xff, 0xff, 0xff];
+        let sequence = Sequence::parse(&bytes).unwrap();
+        assert_eq!(sequence.value(), 0xfffffffe);
+        assert_eq!(sequence.serialize(), bytes);
+        assert!(!sequence.is_final());
+    }
+
+    #[test]
+    fn sequence_relative_lock_time() {
+        assert_eq!(Sequence::new(144).relative_lock_time(), Some(RelativeLockTime::Blocks(144)));
+        assert_eq!(Sequence::new((1 << 22) | 3).relative_lock_time(), Some(RelativeLockTime::Time(3)));
+        assert_eq!(Sequence::new(Sequence::FINAL).relative_lock_time(), None);
+        assert_eq!(Sequence::new((1 << 31) | 144).relative_lock_time(), None);
+        assert_eq!(RelativeLockTime::Time(3).seconds(), Some(1536));
+
+        let sequence = Sequence::from_relative_lock_time(RelativeLockTime::Time(3));
+        assert_eq!(sequence.value(), 0x00400003);
+    }
+}
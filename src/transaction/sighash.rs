@@ -51,3 +51,10 @@ impl SigHash {
         }
     }
 }
+
+// the hash_type of signature messages, which also takes the undefined types
+impl From<SigHash> for u32 {
+    fn from(sighash: SigHash) -> Self {
+        sighash.value() as u32
+    }
+}
@@ -1,5 +1,5 @@
 use std::convert::TryFrom;
-use super::{Error, TxIn, TxOut, Version, LockTime, SigHash};
+use super::{Error, TxIn, TxOut, Version, LockTime, Sequence, SequenceLocks, RelativeLockTime};
 use crate::util::{
     hash::{self, Hash256Value},
     varint,
@@ -8,6 +8,9 @@ use crate::util::{
 };
 use crate::script::Script;
 
+pub const WITNESS_SCALE_FACTOR: usize = 4;
+pub const MAX_BLOCK_SIGOPS_COST: usize = 80_000;
+
 #[derive(Debug, Clone)]
 pub struct SegwitField {
     pub marker: u8,
@@ -185,6 +188,35 @@ impl Transaction {
         self.inputs.len() == 1 && self.inputs[0].is_coinbase()
     }
 
+    // sigops of scriptSig and scriptPubKey, without executing them
+    pub fn legacy_sigop_count(&self) -> usize {
+        let inputs = self.inputs.iter().map(|input| Script::parse_raw_lossy(&input.script).sigop_count(false));
+        let outputs = self.outputs.iter().map(|output| Script::parse_raw_lossy(output.script()).sigop_count(false));
+        inputs.chain(outputs).sum()
+    }
+
+    // BIP141, legacy and p2sh sigops are scaled by WITNESS_SCALE_FACTOR, it should be <= MAX_BLOCK_SIGOPS_COST in a block
+    // p2sh and witness are assumed to be active
+    // @param prevouts: the output spent by each input
+    pub fn sigop_cost(&self, prevouts: &[TxOut]) -> Result<usize, Error> {
+        let mut cost = self.legacy_sigop_count() * WITNESS_SCALE_FACTOR;
+        if self.is_coinbase() {
+            return Ok(cost);
+        }
+        if prevouts.len() != self.inputs.len() {
+            return Err(Error::InvalidTxIn);
+        }
+        for (input, prevout) in self.inputs.iter().zip(prevouts) {
+            let script_sig = Script::parse_raw_lossy(&input.script);
+            let script_pubkey = Script::parse_raw_lossy(prevout.script());
+            if script_pubkey.is_p2sh_pubkey() {
+                cost += script_pubkey.p2sh_sigop_count(&script_sig) * WITNESS_SCALE_FACTOR;
+            }
+            cost += script_pubkey.witness_sigop_count(&script_sig, &input.witness);
+        }
+        Ok(cost)
+    }
+
     // TODO move to intermediator
     pub fn get_block_height_from_coinbase(&self) -> Result<u32, Error> {
         if !self.is_coinbase() {
@@ -196,22 +228,126 @@ impl Transaction {
         Ok(height)
     }
 
-    pub fn hash_prevouts(&self, sighash: SigHash) -> Result<Hash256Value, Error> {
-        if sighash.is_anyone_can_pay() {
+    // BIP68, the result should be checked with SequenceLocks::is_satisfied
+    // @param coin_heights: height of block which includes the prevout of each input
+    // @param coin_mtps: median-time-past of the block before coin_heights[i]
+    pub fn sequence_locks(&self, coin_heights: &[u32], coin_mtps: &[u32]) -> Result<SequenceLocks, Error> {
+        if coin_heights.len() != self.inputs.len() || coin_mtps.len() != self.inputs.len() {
+            return Err(Error::InvalidTxIn);
+        }
+        let mut locks = SequenceLocks { min_height: -1, min_time: -1 };
+        if (self.version.value() as i32) < 2 {
+            return Ok(locks);
+        }
+        for (i, input) in self.inputs.iter().enumerate() {
+            match input.sequence.relative_lock_time() {
+                None => continue,
+                Some(RelativeLockTime::Blocks(blocks)) => {
+                    let min_height = coin_heights[i] as i64 + blocks as i64 - 1;
+                    locks.min_height = locks.min_height.max(min_height);
+                },
+                Some(lock_time @ RelativeLockTime::Time(_)) => {
+                    let seconds = lock_time.seconds().expect("time-based lock");
+                    let min_time = coin_mtps[i] as i64 + seconds as i64 - 1;
+                    locks.min_time = locks.min_time.max(min_time);
+                },
+            }
+        }
+        Ok(locks)
+    }
+
+    // BIP341, hash_type 0x00 is SIGHASH_DEFAULT which signs as SIGHASH_ALL
+    // @param prevouts: the output spent by each input
+    // @param leaf: (tapleaf hash, opcode position of the last executed OP_CODESEPARATOR) for script path spending
+    pub fn signature_hash_taproot(&self, input_index: usize, prevouts: &[TxOut], hash_type: u8, annex: Option<&[u8]>, leaf: Option<(Hash256Value, u32)>) -> Result<Hash256Value, Error> {
+        if !matches!(hash_type, 0x00..=0x03 | 0x81..=0x83) {
             return Err(Error::InvalidSigHash);
         }
+        if input_index >= self.inputs.len() || prevouts.len() != self.inputs.len() {
+            return Err(Error::InvalidTxIn);
+        }
+        let output_type = if hash_type == 0x00 { 0x01 } else { hash_type & 0x03 };
+        let anyone_can_pay = hash_type & 0x80 != 0;
+
+        let mut msg = vec![0x00, hash_type]; // epoch
+        msg.extend_from_slice(&self.version.serialize());
+        msg.extend_from_slice(&self.locktime.serialize());
+        if !anyone_can_pay {
+            let mut outpoints = Vec::new();
+            let mut amounts = Vec::new();
+            let mut script_pubkeys = Vec::new();
+            let mut sequences = Vec::new();
+            for (input, prevout) in self.inputs.iter().zip(prevouts) {
+                outpoints.append(&mut Self::serialize_outpoint(input));
+                amounts.extend_from_slice(&prevout.amount().to_le_bytes());
+                script_pubkeys.append(&mut varint::encode(prevout.script().len() as u64));
+                script_pubkeys.extend_from_slice(prevout.script());
+                sequences.extend_from_slice(&input.sequence.serialize());
+            }
+            msg.extend_from_slice(&hash::sha256(&outpoints));
+            msg.extend_from_slice(&hash::sha256(&amounts));
+            msg.extend_from_slice(&hash::sha256(&script_pubkeys));
+            msg.extend_from_slice(&hash::sha256(&sequences));
+        }
+        if output_type == 0x01 {
+            let mut outputs = Vec::new();
+            for output in &self.outputs {
+                outputs.append(&mut output.serialize()?);
+            }
+            msg.extend_from_slice(&hash::sha256(&outputs));
+        }
+
+        let spend_type = if leaf.is_some() { 2 } else { 0 } + if annex.is_some() { 1 } else { 0 };
+        msg.push(spend_type);
+        let input = &self.inputs[input_index];
+        if anyone_can_pay {
+            let prevout = &prevouts[input_index];
+            msg.append(&mut Self::serialize_outpoint(input));
+            msg.append(&mut prevout.serialize()?);
+            msg.extend_from_slice(&input.sequence.serialize());
+        } else {
+            msg.extend_from_slice(&(input_index as u32).to_le_bytes());
+        }
+        if let Some(annex) = annex {
+            let annex = [varint::encode(annex.len() as u64), annex.to_vec()].concat();
+            msg.extend_from_slice(&hash::sha256(&annex));
+        }
+        if output_type == 0x03 {
+            let output = self.outputs.get(input_index).ok_or(Error::InvalidSigHash)?;
+            msg.extend_from_slice(&hash::sha256(&output.serialize()?));
+        }
+        if let Some((leaf_hash, code_separator_pos)) = leaf {
+            msg.extend_from_slice(&leaf_hash);
+            msg.push(0x00); // key_version
+            msg.extend_from_slice(&code_separator_pos.to_le_bytes());
+        }
+
+        Ok(hash::tagged_hash("TapSighash", &msg))
+    }
+
+    fn serialize_outpoint(input: &TxIn) -> Vec<u8> {
+        let mut prev_tx = input.prev_tx.to_vec();
+        prev_tx.reverse();
+        prev_tx.extend_from_slice(&input.prev_index.serialize());
+        prev_tx
+    }
+
+    // BIP143, zero if the other inputs are not signed
+    // the hash types are tested by bits as bitcoin core does, any hash_type is allowed by consensus
+    pub fn hash_prevouts(&self, hash_type: u32) -> Result<Hash256Value, Error> {
+        if hash_type & 0x80 != 0 {
+            return Ok([0u8; 32]);
+        }
         let mut result = Vec::new();
         for input in &self.inputs {
-            let mut prev_tx = input.prev_tx.to_vec();
-            prev_tx.reverse();
-            result.append(&mut prev_tx);
-            result.append(&mut input.prev_index.serialize().to_vec());
+            result.append(&mut Self::serialize_outpoint(input));
         }
         Ok(hash::hash256(&result))
     }
 
-    pub fn hash_sequence(&self, sighash: SigHash) -> Result<Hash256Value, Error> {
-        if sighash != SigHash::All {
+    pub fn hash_sequence(&self, hash_type: u32) -> Result<Hash256Value, Error> {
+        let base_type = hash_type & 0x1f;
+        if hash_type & 0x80 != 0 || base_type == 0x02 || base_type == 0x03 {
             return Ok([0u8; 32]);
         }
         let mut result = Vec::new();
@@ -221,29 +357,117 @@ impl Transaction {
         Ok(hash::hash256(&result))
     }
 
-    pub fn hash_outputs(&self, input_index: usize, sighash: SigHash) -> Result<Hash256Value, Error> {
-        match sighash {
-            SigHash::All => {
+    pub fn hash_outputs(&self, input_index: usize, hash_type: u32) -> Result<Hash256Value, Error> {
+        match hash_type & 0x1f {
+            0x02 => Ok([0u8; 32]),
+            0x03 if input_index < self.outputs.len() => Ok(hash::hash256(&self.outputs[input_index].serialize()?)),
+            0x03 => Ok([0u8; 32]),
+            _ => {
                 let mut result = Vec::new();
                 for output in &self.outputs {
                     result.append(&mut output.serialize()?);
                 }
                 Ok(hash::hash256(&result))
             },
-            SigHash::Single if input_index < self.outputs.len() => {
-                let mut result = Vec::new();
-                result.append(&mut self.outputs[input_index].serialize()?);
-                Ok(hash::hash256(&result))
+        }
+    }
+
+    // BIP143, the signature message of segwit v0
+    // @param script_code: the witness script, or the p2pkh script of p2wpkh, without the length prefix
+    // @param amount: the amount of the spent output
+    pub fn signature_hash_segwit_v0(&self, input_index: usize, script_code: &[u8], amount: u64, hash_type: u32) -> Result<Hash256Value, Error> {
+        let input = self.inputs.get(input_index).ok_or(Error::InvalidTxIn)?;
+
+        let mut msg = Vec::new();
+        msg.extend_from_slice(&self.version.serialize());
+        msg.extend_from_slice(&self.hash_prevouts(hash_type)?);
+        msg.extend_from_slice(&self.hash_sequence(hash_type)?);
+        msg.append(&mut Self::serialize_outpoint(input));
+        msg.append(&mut varint::encode(script_code.len() as u64));
+        msg.extend_from_slice(script_code);
+        msg.extend_from_slice(&amount.to_le_bytes());
+        msg.extend_from_slice(&input.sequence.serialize());
+        msg.extend_from_slice(&self.hash_outputs(input_index, hash_type)?);
+        msg.extend_from_slice(&self.locktime.serialize());
+        msg.extend_from_slice(&hash_type.to_le_bytes());
+
+        Ok(hash::hash256(&msg))
+    }
+
+    // the original signature message, any hash_type is allowed by consensus
+    // @param script_code: the script_pubkey or the redeem script, OP_CODESEPARATOR is removed here
+    pub fn signature_hash_legacy(&self, input_index: usize, script_code: &[u8], hash_type: u32) -> Result<Hash256Value, Error> {
+        if input_index >= self.inputs.len() {
+            return Err(Error::InvalidTxIn);
+        }
+        let base_type = hash_type & 0x1f;
+        let anyone_can_pay = hash_type & 0x80 != 0;
+        // the bug of SIGHASH_SINGLE signs the number one
+        if base_type == 0x03 && input_index >= self.outputs.len() {
+            let mut one = [0u8; 32];
+            one[0] = 1;
+            return Ok(one);
+        }
+
+        let mut tx = self.clone();
+        let script_code = Self::remove_code_separators(script_code);
+        for (i, input) in tx.inputs.iter_mut().enumerate() {
+            input.script = if i == input_index { script_code.clone() } else { vec![] };
+            input.witness = vec![];
+            // SIGHASH_NONE and SIGHASH_SINGLE let the others update their inputs
+            if i != input_index && (base_type == 0x02 || base_type == 0x03) {
+                input.sequence = Sequence::new(0);
+            }
+        }
+        if anyone_can_pay {
+            tx.inputs = vec![tx.inputs.swap_remove(input_index)];
+        }
+        match base_type {
+            0x02 => tx.outputs.clear(),
+            0x03 => {
+                tx.outputs.truncate(input_index + 1);
+                for output in tx.outputs.iter_mut().take(input_index) {
+                    *output = TxOut::new(u64::MAX, vec![]);
+                }
             },
-            SigHash::Single => Ok([0u8; 32]),
-            _ => Err(Error::InvalidSigHash) // impl in the future
+            _ => {},
         }
+
+        let mut msg = tx.serialize_legacy()?;
+        msg.extend_from_slice(&hash_type.to_le_bytes());
+        Ok(hash::hash256(&msg))
+    }
+
+    // walk the raw script by opcode, so data of a truncated push is kept as is
+    fn remove_code_separators(script: &[u8]) -> Vec<u8> {
+        let mut result = Vec::new();
+        let mut i = 0;
+        while i < script.len() {
+            let op = script[i];
+            let (header_len, data_len) = match op {
+                0x01..=0x4b => (1, op as usize),
+                0x4c if i + 1 < script.len() => (2, script[i + 1] as usize),
+                0x4d if i + 2 < script.len() => (3, u16::from_le_bytes([script[i + 1], script[i + 2]]) as usize),
+                0x4e if i + 4 < script.len() => (5, u32::from_le_bytes([script[i + 1], script[i + 2], script[i + 3], script[i + 4]]) as usize),
+                0x4c..=0x4e => (script.len() - i, 0),
+                _ => (1, 0),
+            };
+            let end = script.len().min(i + header_len + data_len);
+            if op != 0xab { // OP_CODESEPARATOR
+                result.extend_from_slice(&script[i..end]);
+            }
+            i = end;
+        }
+        result
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::transaction::{TxFetcher, Transaction};
+    use crate::transaction::{TxFetcher, TxOut, Transaction};
+    use crate::script::{Script, ScriptBuilder};
+    use crate::secp256k1::{PrivateKey, S256Point};
+    use crate::util::hash;
 
     #[test]
     fn transaction_parse_legacy() {
@@ -290,6 +514,33 @@ mod tests {
         Transaction::parse(&bytes).unwrap()
     }
 
+    #[test]
+    fn transaction_sequence_locks() {
+        use crate::transaction::{Sequence, Version, RelativeLockTime};
+
+        let mut tx = get_tx_from_parsed();
+        tx.inputs[0].sequence = Sequence::from_relative_lock_time(RelativeLockTime::Blocks(10));
+        // version 1 disables BIP68
+        let locks = tx.sequence_locks(&[100], &[0]).unwrap();
+        assert_eq!((locks.min_height, locks.min_time), (-1, -1));
+
+        tx.version = Version::new(2);
+        let locks = tx.sequence_locks(&[100], &[0]).unwrap();
+        assert_eq!((locks.min_height, locks.min_time), (109, -1));
+        assert!(!locks.is_satisfied(109, 0));
+        assert!(locks.is_satisfied(110, 0));
+
+        tx.inputs[0].sequence = Sequence::from_relative_lock_time(RelativeLockTime::Time(2));
+        let locks = tx.sequence_locks(&[100], &[1_000_000]).unwrap();
+        assert_eq!((locks.min_height, locks.min_time), (-1, 1_000_000 + 1024 - 1));
+        assert!(locks.is_satisfied(101, 1_000_000 + 1024));
+
+        tx.inputs[0].sequence = Sequence::new(Sequence::DISABLE_FLAG | 10);
+        let locks = tx.sequence_locks(&[100], &[0]).unwrap();
+        assert_eq!((locks.min_height, locks.min_time), (-1, -1));
+        assert!(tx.sequence_locks(&[], &[]).is_err());
+    }
+
     #[test]
     fn transaction_fee_2() {
         let tx = get_tx_from_parsed();
@@ -297,4 +548,191 @@ mod tests {
 
         assert!(fee > 0);
     }
+
+    fn get_segwit_tx() -> Transaction {
+        let bytes = hex::decode("0100000000010115e180dc28a2327e687facc33f10f2a20da717e5548406f7ae8b4c811072f8560100000000ffffffff0100b4f505000000001976a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac02483045022100df7b7e5cda14ddf91290e02ea10786e03eb11ee36ec02dd862fe9a326bbcb7fd02203f5b4496b667e6e281cc654a2da9e4f08660c620a1051337fa8965f727eb19190121038262a6c6cec93c2d3ecd6c6072efea86d02ff8e3328bbd0242b20af3425990ac00000000").unwrap();
+        Transaction::parse(&bytes).unwrap()
+    }
+
+    fn get_multisig_2_of_3() -> Script {
+        let pks: Vec<S256Point> = (1u32..=3).map(|secret| PrivateKey::new(secret.into()).unwrap().pk_point().clone()).collect();
+        ScriptBuilder::multisig(2, &pks).unwrap()
+    }
+
+    #[test]
+    fn transaction_sigop_cost_p2wpkh() {
+        let tx = get_segwit_tx();
+        // the p2pkh output
+        assert_eq!(tx.legacy_sigop_count(), 1);
+
+        let pkh = hash::hash160(&tx.inputs[0].witness[1]);
+        let prevout = TxOut::new(600_000_000, ScriptBuilder::p2wpkh(&pkh).raw_serialize().unwrap());
+        assert_eq!(tx.sigop_cost(&[prevout]).unwrap(), 4 + 1);
+
+        // nested in p2sh
+        let mut tx = tx;
+        let redeem_script = ScriptBuilder::p2wpkh(&pkh).raw_serialize().unwrap();
+        tx.inputs[0].script = [vec![redeem_script.len() as u8], redeem_script].concat();
+        let prevout = TxOut::new(600_000_000, ScriptBuilder::p2sh_p2wpkh(&pkh).unwrap().raw_serialize().unwrap());
+        assert_eq!(tx.sigop_cost(&[prevout]).unwrap(), 4 + 1);
+        assert!(tx.sigop_cost(&[]).is_err());
+    }
+
+    #[test]
+    fn transaction_sigop_cost_multisig() {
+        let multisig = get_multisig_2_of_3();
+        let multisig_bytes = multisig.raw_serialize().unwrap();
+
+        // p2sh: OP_0 <sig> <sig> <redeem script>, accurate count of the redeem script is scaled
+        let mut tx = get_segwit_tx();
+        tx.inputs[0].witness = vec![];
+        tx.inputs[0].script = [vec![0x00, 0x01, 0x01, 0x01, 0x01, 0x4c, multisig_bytes.len() as u8], multisig_bytes.clone()].concat();
+        let prevout = TxOut::new(600_000_000, ScriptBuilder::p2sh(&multisig).unwrap().raw_serialize().unwrap());
+        assert_eq!(tx.sigop_cost(&[prevout]).unwrap(), 4 + 3 * 4);
+
+        // p2wsh: the witness script is counted accurately without scaling
+        let mut tx = get_segwit_tx();
+        tx.inputs[0].witness = vec![vec![], vec![1], vec![1], multisig_bytes.clone()];
+        let prevout = TxOut::new(600_000_000, ScriptBuilder::p2wsh(&multisig).unwrap().raw_serialize().unwrap());
+        assert_eq!(tx.sigop_cost(&[prevout]).unwrap(), 4 + 3);
+
+        // bare multisig is counted as legacy sigops of the output, 20 for OP_CHECKMULTISIG
+        let mut tx = get_segwit_tx();
+        tx.outputs[0] = TxOut::new(600_000_000, multisig_bytes);
+        let prevout = TxOut::new(600_000_000, vec![0x51]);
+        assert_eq!(tx.sigop_cost(&[prevout]).unwrap(), 20 * 4);
+    }
+
+    // BIP341 test vectors of key path spending
+    fn get_taproot_prevouts() -> Vec<TxOut> {
+        vec![
+            ("512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343", 420000000),
+            ("5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3", 462000000),
+            ("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac", 294000000),
+            ("5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e", 504000000),
+            ("512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605", 630000000),
+            ("00147dd65592d0ab2fe0d0257d571abf032cd9db93dc", 378000000),
+            ("512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831", 672000000),
+            ("5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5", 546000000),
+            ("512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220", 588000000),
+        ].into_iter().map(|(script, amount)| TxOut::new(amount, hex::decode(script).unwrap())).collect()
+    }
+
+    #[test]
+    fn transaction_signature_hash_taproot() {
+        let bytes = hex::decode("02000000097de20cbff686da83a54981d2b9bab3586f4ca7e48f57f5b55963115f3b334e9c010000000000000000d7b7cab57b1393ace2d064f4d4a2cb8af6def61273e127517d44759b6dafdd990000000000fffffffff8e1f583384333689228c5d28eac13366be082dc57441760d957275419a418420000000000fffffffff0689180aa63b30cb162a73c6d2a38b7eeda2a83ece74310fda0843ad604853b0100000000feffffffaa5202bdf6d8ccd2ee0f0202afbbb7461d9264a25e5bfd3c5a52ee1239e0ba6c0000000000feffffff956149bdc66faa968eb2be2d2faa29718acbfe3941215893a2a3446d32acd050000000000000000000e664b9773b88c09c32cb70a2a3e4da0ced63b7ba3b22f848531bbb1d5d5f4c94010000000000000000e9aa6b8e6c9de67619e6a3924ae25696bb7b694bb677a632a74ef7eadfd4eabf0000000000ffffffffa778eb6a263dc090464cd125c466b5a99667720b1c110468831d058aa1b82af10100000000ffffffff0200ca9a3b000000001976a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac807840cb0000000020ac9a87f5594be208f8532db38cff670c450ed2fea8fcdefcc9a663f78bab962b0065cd1d").unwrap();
+        let tx = Transaction::parse(&bytes).unwrap();
+        let prevouts = get_taproot_prevouts();
+
+        let cases = vec![
+            (0, 0x03, "2514a6272f85cfa0f45eb907fcb0d121b808ed37c6ea160a5a9046ed5526d555"),
+            (1, 0x83, "325a644af47e8a5a2591cda0ab0723978537318f10e6a63d4eed783b96a71a4d"),
+            (3, 0x01, "bf013ea93474aa67815b1b6cc441d23b64fa310911d991e713cd34c7f5d46669"),
+            (4, 0x00, "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef"),
+            (6, 0x02, "15f25c298eb5cdc7eb1d638dd2d45c97c4c59dcaec6679cfc16ad84f30876b85"),
+            (7, 0x82, "cd292de50313804dabe4685e83f923d2969577191a3e1d2882220dca88cbeb10"),
+            (8, 0x81, "cccb739eca6c13a8a89e6e5cd317ffe55669bbda23f2fd37b0f18755e008edd2"),
+        ];
+        for (index, hash_type, expected) in cases {
+            let sighash = tx.signature_hash_taproot(index, &prevouts, hash_type, None, None).unwrap();
+            assert_eq!(hex::encode(sighash), expected, "input {}", index);
+        }
+
+        // no output for SIGHASH_SINGLE, undefined hash type, missing prevouts
+        assert!(tx.signature_hash_taproot(2, &prevouts, 0x03, None, None).is_err());
+        assert!(tx.signature_hash_taproot(0, &prevouts, 0x04, None, None).is_err());
+        assert!(tx.signature_hash_taproot(0, &prevouts[1..], 0x00, None, None).is_err());
+    }
+
+    #[test]
+    fn transaction_signature_hash_segwit_v0() {
+        use crate::transaction::SigHash;
+
+        // BIP143, native P2WPKH
+        let bytes = hex::decode("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000").unwrap();
+        let tx = Transaction::parse(&bytes).unwrap();
+        assert_eq!(hex::encode(tx.hash_prevouts(SigHash::All.into()).unwrap()), "96b827c8483d4e9b96712b6713a7b68d6e8003a781feba36c31143470b4efd37");
+        assert_eq!(hex::encode(tx.hash_sequence(SigHash::All.into()).unwrap()), "52b0a642eea2fb7ae638c36f6252b6750293dbe574a806984b8e4d8548339a3b");
+        assert_eq!(hex::encode(tx.hash_outputs(1, SigHash::All.into()).unwrap()), "863ef3e1a92afbfdb97f31ad0fc7683ee943e9abcf2501590ff8f6551f47e5e5");
+        let script_code = hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap();
+        let sighash = tx.signature_hash_segwit_v0(1, &script_code, 600_000_000, SigHash::All.into()).unwrap();
+        assert_eq!(hex::encode(sighash), "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670");
+
+        // BIP143, P2SH-P2WPKH
+        let bytes = hex::decode("0100000001db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a54770100000000feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac92040000").unwrap();
+        let tx = Transaction::parse(&bytes).unwrap();
+        let script_code = hex::decode("76a91479091972186c449eb1ded22b78e40d009bdf008988ac").unwrap();
+        let sighash = tx.signature_hash_segwit_v0(0, &script_code, 1_000_000_000, SigHash::All.into()).unwrap();
+        assert_eq!(hex::encode(sighash), "64f3b0f4dd2bb3aa1ce8566d220cc74dda9df97d8490cc81d89d735c92e59fb6");
+
+        // BIP143, P2SH-P2WSH of 6-of-6 multisig signed by every sighash type
+        let bytes = hex::decode("010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000").unwrap();
+        let tx = Transaction::parse(&bytes).unwrap();
+        let witness_script = hex::decode("56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae").unwrap();
+        let cases = [
+            (SigHash::All, "185c0be5263dce5b4bb50a047973c1b6272bfbd0103a89444597dc40b248ee7c"),
+            (SigHash::None, "e9733bc60ea13c95c6527066bb975a2ff29a925e80aa14c213f686cbae5d2f36"),
+            (SigHash::Single, "1e1f1c303dc025bd664acb72e583e933fae4cff9148bf78c157d1e8f78530aea"),
+            (SigHash::AllAnyoneCanpay, "2a67f03e63a6a422125878b40b82da593be8d4efaafe88ee528af6e5a9955c6e"),
+            (SigHash::NoneAnyoneCanpay, "781ba15f3779d5542ce8ecb5c18716733a5ee42a6f51488ec96154934e2c890a"),
+            (SigHash::SingleAnyoneCanpay, "511e8e52ed574121fc1b654970395502128263f62662e076dc6baf05c2e6a99b"),
+        ];
+        for (sighash_type, expected) in cases {
+            let sighash = tx.signature_hash_segwit_v0(0, &witness_script, 987_654_321, sighash_type.into()).unwrap();
+            assert_eq!(hex::encode(sighash), expected, "{:?}", sighash_type);
+        }
+        assert!(tx.signature_hash_segwit_v0(1, &witness_script, 987_654_321, SigHash::All.into()).is_err());
+    }
+
+    // the vectors of Bitcoin Core sighash.json carried by libbtc, which leaves out the scripts with OP_CODESEPARATOR,
+    // those are checked by transaction_signature_hash_legacy_code_separator
+    #[test]
+    fn transaction_signature_hash_legacy() {
+        use crate::util::json::Json;
+
+        let vectors = Json::parse(include_str!("../../test_data/sighash.json")).unwrap();
+        let vectors = vectors.as_array().unwrap();
+        assert!(vectors.len() > 1);
+        for vector in vectors.iter().skip(1) { // the first one is the header
+            let vector = vector.as_array().unwrap();
+            let tx = Transaction::parse(&hex::decode(vector[0].as_str().unwrap()).unwrap()).unwrap();
+            let script_code = hex::decode(vector[1].as_str().unwrap()).unwrap();
+            let input_index = vector[2].as_i64().unwrap() as usize;
+            let hash_type = vector[3].as_i64().unwrap() as u32;
+            let mut sighash = tx.signature_hash_legacy(input_index, &script_code, hash_type).unwrap();
+            sighash.reverse(); // uint256 of bitcoin core is shown in reversed order
+            assert_eq!(hex::encode(sighash), vector[4].as_str().unwrap(), "{:?}", vector);
+        }
+    }
+
+    #[test]
+    fn transaction_signature_hash_legacy_code_separator() {
+        let tx = get_tx_from_parsed();
+        // OP_CODESEPARATOR is removed, but not the same byte in data
+        let with_separator = tx.signature_hash_legacy(0, &hex::decode("ab51ab02abab").unwrap(), 1).unwrap();
+        let without_separator = tx.signature_hash_legacy(0, &hex::decode("5102abab").unwrap(), 1).unwrap();
+        assert_eq!(with_separator, without_separator);
+        // random scripts of the opcodes used by sighash.json, hashed by SignatureHashOld of Bitcoin Core
+        let vectors = [
+            ("c33028b301d5093e1e8397270d75a0b009b2a6509a01861061ab022ca122a6ba935b8513320200000000ffffffff013bcf5a0500000000015200000000", "636551ab6a65", 0, -1105342539, "ce4144c48f06f0d72de1950bb9f2f041bf331e705590f0b5d6822f564fb86869"),
+            ("d3b7421e011f4de0f1cea9ba7458bf3486bee722519efab711a963fa8c100970cf7488b7bb0200000003525352dcd61b300148be5d05000000000000000000", "ab65525265", 0, 815197173, "36da60d97a870adb5dda207a64376bfd108dd359a99a289b0b82d429cdff2a66"),
+            ("e3cdbfb4014d90ae6a4401e85f7ac717adc2c035858bf6ff48979dd399d155bce1f150daea0300000002ac51a67a0d39017f6c71040000000005535200535200000000", "63535100ab5365", 0, 2115850587, "81b09e15c64aa878d350d8132df1463893cef2eda5ca472f646e8c86b69cccd6"),
+            ("4db591ab018adcef5f4f3f2060e41f7829ce3a07ea41d681e8cb70a0e37685561e4767ac3b0000000005000052acabd280e63601ae6ef20000000000036a636326c908f7", "6aab0052516aab", 0, -2093054211, "d5b5f4f92a9c6d287f16ae7a05930c2960a3785de852d5294c49200f48193672"),
+            ("f2b539a401e4e8402869d5e1502dbc3156dbce93583f516a4947b333260d5af1a34810c6a00200000003525363ffffffff01d305e2000000000005acab535200a265fe77", "abab0052", 0, 1701894298, "58e98bde0f4ba2a8a17359cc3ddee1e7d350a53e1f10d6f34e3253b5b1534f30"),
+            ("af1c4ab301ec462f76ee69ba419b1b2557b7ded639f3442a3522d4f9170b2d6859765c3df402000000016affffffff01a5ca6c000000000008ab52536aab00005300000000", "00ab51ab", 0, 922689046, "39fc64877b75873a8386139126aaea0d3be5e1f58367be3def5fb39ea2382a51"),
+        ];
+        for (tx_hex, script_hex, input_index, hash_type, expected) in vectors {
+            let tx = Transaction::parse(&hex::decode(tx_hex).unwrap()).unwrap();
+            let mut sighash = tx.signature_hash_legacy(input_index, &hex::decode(script_hex).unwrap(), hash_type as u32).unwrap();
+            sighash.reverse();
+            assert_eq!(hex::encode(sighash), expected, "{}", script_hex);
+        }
+        // the bug of SIGHASH_SINGLE without the output of the same index
+        let mut tx = get_tx_from_parsed();
+        tx.outputs.clear();
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        assert_eq!(tx.signature_hash_legacy(0, &[], 3).unwrap(), one);
+        assert!(tx.signature_hash_legacy(1, &[], 1).is_err());
+    }
 }
@@ -3,7 +3,7 @@ use crate::util::{
     varint,
     Reader,
 };
-use super::{Error, Version, TxFetcher, TxOut};
+use super::{Error, Version, TxFetcher, TxOut, Sequence};
 use std::convert::{TryFrom, TryInto};
 use std::fmt;
 
@@ -92,7 +92,6 @@ impl fmt::Debug for TxIn {
     }
 }
 
-pub type Sequence = Version;
 pub type PrevIndex = Version;
 
 #[cfg(test)]
@@ -74,9 +74,20 @@ pub fn decode_btc_addr(characters: &str) -> Result<Vec<u8>, &'static str> {
     Ok(result)
 }
 
+// payload without the 4 bytes of checksum
+pub fn decode_checksum(characters: &str) -> Result<Vec<u8>, &'static str> {
+    let mut result = decode_btc_addr(characters)?;
+    result.truncate(result.len() - 4);
+
+    Ok(result)
+}
+
 pub fn decode(characters: &str) -> Result<Vec<u8>, &'static str> {
     let mut result = Vec::new();
-    for character in characters.trim_start_matches("0").chars() {
+    // each leading '1' is a leading zero byte
+    let digits = characters.trim_start_matches('1');
+    let zeros = characters.len() - digits.len();
+    for character in digits.chars() {
         let mut carry = match BASE58_ALPHABET.iter().position(|&b| character == b as char) {
             None => return Err("invalid char of base58"),
             Some(p) => p,
@@ -91,6 +102,7 @@ pub fn decode(characters: &str) -> Result<Vec<u8>, &'static str> {
             carry /= BASE256;
         }
     }
+    result.extend(std::iter::repeat_n(0, zeros));
     result.reverse();
 
     Ok(result)
@@ -141,4 +153,13 @@ mod tests {
         assert_eq!(super::encode_bytes(&bytes), base58);
         assert_eq!(super::decode(base58).unwrap(), bytes);
     }
+
+    #[test]
+    fn base58_decode_leading_zeros() {
+        // p2pkh address of the main net, whose first byte is 0x00
+        let bytes = super::decode_checksum("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH").unwrap();
+        assert_eq!(hex::encode(&bytes), "00751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(super::encode_bytes_checksum(&bytes), "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH");
+        assert!(super::decode_checksum("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMh").is_err());
+    }
 }
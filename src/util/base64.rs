@@ -0,0 +1,85 @@
+// RFC 4648 with padding, signed messages are exchanged in this encoding
+const BASE64_ALPHABET: [u8; 64] = *b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const PADDING: u8 = b'=';
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut result = Vec::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        // 3 bytes as 4 chars of 6 bits, the missing bytes become padding
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize]);
+            } else {
+                result.push(PADDING);
+            }
+        }
+    }
+
+    String::from_utf8(result).unwrap()
+}
+
+pub fn decode(characters: &str) -> Result<Vec<u8>, &'static str> {
+    let characters = characters.as_bytes();
+    if !characters.len().is_multiple_of(4) {
+        return Err("invalid len of base64");
+    }
+
+    let mut result = Vec::with_capacity(characters.len() / 4 * 3);
+    let chunks = characters.len() / 4;
+    for (index, chunk) in characters.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|&&c| c == PADDING).count();
+        if padding > 2 || (padding > 0 && index + 1 != chunks) {
+            return Err("invalid padding of base64");
+        }
+
+        let mut group = 0u32;
+        for character in &chunk[..(4 - padding)] {
+            let value = match BASE64_ALPHABET.iter().position(|b| b == character) {
+                None => return Err("invalid char of base64"),
+                Some(p) => p as u32,
+            };
+            group = group << 6 | value;
+        }
+        group <<= 6 * padding as u32;
+        // the bits under the padding must be zero to keep the encoding unique
+        if group & ((1 << (8 * padding)) - 1) != 0 {
+            return Err("invalid padding of base64");
+        }
+        result.extend_from_slice(&group.to_be_bytes()[1..(4 - padding)]);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn base64_rfc4648() {
+        let cases = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (bytes, characters) in cases {
+            assert_eq!(super::encode(bytes.as_bytes()), characters);
+            assert_eq!(super::decode(characters).unwrap(), bytes.as_bytes());
+        }
+        assert_eq!(super::decode("+/8=").unwrap(), vec![0xfb, 0xff]);
+    }
+
+    #[test]
+    fn base64_decode_invalid() {
+        assert!(super::decode("Zg=").is_err());
+        assert!(super::decode("Z===").is_err());
+        assert!(super::decode("Zg==Zm8=").is_err());
+        assert!(super::decode("Zh==").is_err());
+        assert!(super::decode("Zm9*").is_err());
+    }
+}
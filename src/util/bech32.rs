@@ -0,0 +1,180 @@
+// BIP173 and BIP350, addresses of witness programs
+const CHARSET: [u8; 32] = *b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2bc830a3;
+const MAX_LEN: usize = 90;
+
+// bech32 for witness v0, bech32m for v1 and later
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Variant {
+    Bech32,
+    Bech32m,
+}
+
+impl Variant {
+    fn constant(&self) -> u32 {
+        match self {
+            Self::Bech32 => BECH32_CONST,
+            Self::Bech32m => BECH32M_CONST,
+        }
+    }
+}
+
+fn polymod(values: &[u8]) -> u32 {
+    let mut chk = 1u32;
+    for value in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ffffff) << 5) ^ *value as u32;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut result: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    result.push(0);
+    result.extend(hrp.bytes().map(|b| b & 31));
+    result
+}
+
+fn create_checksum(hrp: &str, data: &[u8], variant: Variant) -> Vec<u8> {
+    let values = [hrp_expand(hrp), data.to_vec(), vec![0; 6]].concat();
+    let chk = polymod(&values) ^ variant.constant();
+    (0..6).map(|i| ((chk >> (5 * (5 - i))) & 31) as u8).collect()
+}
+
+// @param data: 5-bit values
+pub fn encode(hrp: &str, data: &[u8], variant: Variant) -> String {
+    let checksum = create_checksum(hrp, data, variant);
+    let mut result = format!("{}1", hrp);
+    result.extend(data.iter().chain(checksum.iter()).map(|v| CHARSET[*v as usize] as char));
+    result
+}
+
+// @return (hrp in lower case, 5-bit values without checksum, variant)
+pub fn decode(s: &str) -> Result<(String, Vec<u8>, Variant), &'static str> {
+    if s.len() > MAX_LEN {
+        return Err("bech32 string is too long");
+    }
+    if s.bytes().any(|b| !(33..=126).contains(&b)) {
+        return Err("invalid char of bech32");
+    }
+    if s.bytes().any(|b| b.is_ascii_lowercase()) && s.bytes().any(|b| b.is_ascii_uppercase()) {
+        return Err("mixed case of bech32");
+    }
+    let s = s.to_ascii_lowercase();
+    let separator = s.rfind('1').ok_or("missing separator of bech32")?;
+    if separator == 0 || separator + 7 > s.len() {
+        return Err("invalid position of the separator of bech32");
+    }
+    let hrp = &s[..separator];
+    let data = s[separator + 1..].bytes()
+        .map(|b| CHARSET.iter().position(|c| *c == b).map(|p| p as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or("invalid char of bech32")?;
+    let variant = match polymod(&[hrp_expand(hrp), data.clone()].concat()) {
+        BECH32_CONST => Variant::Bech32,
+        BECH32M_CONST => Variant::Bech32m,
+        _ => return Err("invalid checksum of bech32"),
+    };
+
+    Ok((hrp.to_string(), data[..data.len() - 6].to_vec(), variant))
+}
+
+// regroup bits, such as 8-bit bytes into 5-bit values
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, &'static str> {
+    let mut acc = 0u32;
+    let mut bits = 0u32;
+    let max = (1u32 << to) - 1;
+    let mut result = Vec::new();
+    for value in data {
+        if (*value as u32) >> from != 0 {
+            return Err("invalid value to convert bits");
+        }
+        acc = (acc << from) | *value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            result.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            result.push(((acc << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || ((acc << (to - bits)) & max) != 0 {
+        return Err("invalid padding of bech32");
+    }
+    Ok(result)
+}
+
+// such as bc1q... for main net and tb1q... for test net
+pub fn encode_segwit_address(hrp: &str, version: u8, program: &[u8]) -> Result<String, &'static str> {
+    if version > 16 || program.len() < 2 || program.len() > 40 || (version == 0 && program.len() != 20 && program.len() != 32) {
+        return Err("invalid witness program");
+    }
+    let variant = if version == 0 { Variant::Bech32 } else { Variant::Bech32m };
+    let mut data = vec![version];
+    data.append(&mut convert_bits(program, 8, 5, true)?);
+
+    Ok(encode(hrp, &data, variant))
+}
+
+// @return (witness version, witness program)
+pub fn decode_segwit_address(hrp: &str, address: &str) -> Result<(u8, Vec<u8>), &'static str> {
+    let (address_hrp, data, variant) = decode(address)?;
+    if address_hrp != hrp {
+        return Err("invalid hrp of the address");
+    }
+    let (version, program) = data.split_first().ok_or("missing witness version")?;
+    let program = convert_bits(program, 5, 8, false)?;
+    if *version > 16 || program.len() < 2 || program.len() > 40 || (*version == 0 && program.len() != 20 && program.len() != 32) {
+        return Err("invalid witness program");
+    }
+    let expected = if *version == 0 { Variant::Bech32 } else { Variant::Bech32m };
+    if variant != expected {
+        return Err("invalid variant of bech32 for the witness version");
+    }
+
+    Ok((*version, program))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_segwit_address, encode_segwit_address};
+
+    #[test]
+    fn bech32_segwit_address() {
+        let cases = [
+            ("bc", "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", 0, "751e76e8199196d454941c45d1b3a323f1433bd6"),
+            ("tb", "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7", 0, "1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262"),
+            ("bc", "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0", 1, "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"),
+        ];
+        for (hrp, address, version, program) in cases {
+            let program = hex::decode(program).unwrap();
+            assert_eq!(decode_segwit_address(hrp, address).unwrap(), (version, program.clone()));
+            assert_eq!(encode_segwit_address(hrp, version, &program).unwrap(), address.to_lowercase());
+        }
+    }
+
+    #[test]
+    fn bech32_segwit_address_invalid() {
+        // wrong checksum
+        assert!(decode_segwit_address("bc", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5").is_err());
+        // mixed case
+        assert!(decode_segwit_address("bc", "bc1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4").is_err());
+        // wrong hrp
+        assert!(decode_segwit_address("tb", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").is_err());
+        // witness v1 with the checksum of bech32 instead of bech32m
+        let data = [vec![1u8], super::convert_bits(&[0x79; 32], 8, 5, true).unwrap()].concat();
+        let address = super::encode("bc", &data, super::Variant::Bech32);
+        assert!(decode_segwit_address("bc", &address).is_err());
+        // witness v0 must be 20 or 32 bytes
+        assert!(encode_segwit_address("bc", 0, &[0; 21]).is_err());
+    }
+}
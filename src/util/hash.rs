@@ -1,4 +1,5 @@
-use sha2::{Sha256, Digest as Sha256Digest};
+use sha2::{Sha256, Sha512, Digest as Sha256Digest};
+use sha1::Sha1;
 use ripemd160::{Ripemd160, Digest as Rip160Digest};
 use hmac::{Hmac, Mac};
 
@@ -37,6 +38,12 @@ pub fn sha256(bytes: &[u8]) -> [u8; 32] {
     hasher.finalize().into()
 }
 
+pub fn sha1(bytes: &[u8]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
+
 pub fn ripemd160(bytes: &[u8]) -> [u8; 20] {
     let mut hasher = Ripemd160::new();
     hasher.update(bytes);
@@ -51,6 +58,16 @@ pub fn hash256(bytes: &[u8]) -> [u8; 32] {
     sha256(&sha256(bytes))
 }
 
+// BIP340, sha256(sha256(tag) || sha256(tag) || msg)
+pub fn tagged_hash(tag: &str, msg: &[u8]) -> [u8; 32] {
+    let tag_hash = sha256(tag.as_bytes());
+    let mut hasher = Sha256::new();
+    hasher.update(tag_hash);
+    hasher.update(tag_hash);
+    hasher.update(msg);
+    hasher.finalize().into()
+}
+
 // Create alias for HMAC-SHA256
 pub type HmacSha256 = Hmac<Sha256>;
 
@@ -60,6 +77,14 @@ pub fn hmac256(k: &[u8], m: &[u8]) -> [u8; 32] {
     mac.finalize().into_bytes().into()
 }
 
+pub type HmacSha512 = Hmac<Sha512>;
+
+pub fn hmac512(k: &[u8], m: &[u8]) -> [u8; 64] {
+    let mut mac = HmacSha512::new_from_slice(k).expect("HMAC can take key of any size");
+    mac.update(m);
+    mac.finalize().into_bytes().into()
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -68,4 +93,17 @@ mod tests {
         let hash = super::hash160(&pk);
         assert_eq!(hex::encode(hash), "f54a5851e9372b87810a8e60cdd2e7cfd80b6e31");
     }
+
+    #[test]
+    fn test_sha1() {
+        let hash = super::sha1("abc".as_bytes());
+        assert_eq!(hex::encode(hash), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+
+    #[test]
+    fn test_hmac512() {
+        // RFC 4231, test case 2
+        let mac = super::hmac512(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(hex::encode(mac), "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737");
+    }
 }
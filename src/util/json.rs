@@ -0,0 +1,186 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+// minimal JSON, enough to read the test vectors of bitcoin core
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    Float(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        let mut chars = s.chars().peekable();
+        let value = Self::parse_value(&mut chars)?;
+        Self::skip_whitespace(&mut chars);
+        if chars.next().is_some() {
+            return Err("unexpected characters after json value");
+        }
+        Ok(value)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        match self {
+            Self::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Self::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn skip_whitespace(chars: &mut Peekable<Chars>) {
+        while matches!(chars.peek(), Some(c) if c.is_whitespace()) {
+            chars.next();
+        }
+    }
+
+    fn expect_word(chars: &mut Peekable<Chars>, word: &str) -> Result<(), &'static str> {
+        for expected in word.chars() {
+            if chars.next() != Some(expected) {
+                return Err("invalid json literal");
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_value(chars: &mut Peekable<Chars>) -> Result<Self, &'static str> {
+        Self::skip_whitespace(chars);
+        match chars.peek().ok_or("unexpected end of json")? {
+            'n' => Self::expect_word(chars, "null").map(|_| Self::Null),
+            't' => Self::expect_word(chars, "true").map(|_| Self::Bool(true)),
+            'f' => Self::expect_word(chars, "false").map(|_| Self::Bool(false)),
+            '"' => Self::parse_string(chars).map(Self::Str),
+            '[' => {
+                chars.next();
+                let mut items = Vec::new();
+                Self::parse_sequence(chars, ']', |chars| {
+                    items.push(Self::parse_value(chars)?);
+                    Ok(())
+                })?;
+                Ok(Self::Array(items))
+            },
+            '{' => {
+                chars.next();
+                let mut members = Vec::new();
+                Self::parse_sequence(chars, '}', |chars| {
+                    Self::skip_whitespace(chars);
+                    let key = Self::parse_string(chars)?;
+                    Self::skip_whitespace(chars);
+                    if chars.next() != Some(':') {
+                        return Err("expect ':' in json object");
+                    }
+                    members.push((key, Self::parse_value(chars)?));
+                    Ok(())
+                })?;
+                Ok(Self::Object(members))
+            },
+            _ => Self::parse_number(chars),
+        }
+    }
+
+    // items separated by ',' until the close bracket, the open bracket is consumed
+    fn parse_sequence<F>(chars: &mut Peekable<Chars>, close: char, mut parse_item: F) -> Result<(), &'static str>
+        where F: FnMut(&mut Peekable<Chars>) -> Result<(), &'static str> {
+        Self::skip_whitespace(chars);
+        if chars.peek() == Some(&close) {
+            chars.next();
+            return Ok(());
+        }
+        loop {
+            parse_item(chars)?;
+            Self::skip_whitespace(chars);
+            match chars.next() {
+                Some(',') => continue,
+                Some(c) if c == close => return Ok(()),
+                _ => return Err("expect ',' or close bracket in json"),
+            }
+        }
+    }
+
+    fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, &'static str> {
+        if chars.next() != Some('"') {
+            return Err("expect '\"' of json string");
+        }
+        let mut result = String::new();
+        loop {
+            match chars.next().ok_or("unterminated json string")? {
+                '"' => return Ok(result),
+                '\\' => {
+                    let escaped = match chars.next().ok_or("unterminated json string")? {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'u' => {
+                            let code: String = (0..4).filter_map(|_| chars.next()).collect();
+                            let code = u32::from_str_radix(&code, 16).map_err(|_| "invalid json unicode escape")?;
+                            char::from_u32(code).ok_or("invalid json unicode escape")?
+                        },
+                        c => c, // '"', '\\' and '/'
+                    };
+                    result.push(escaped);
+                },
+                c => result.push(c),
+            }
+        }
+    }
+
+    fn parse_number(chars: &mut Peekable<Chars>) -> Result<Self, &'static str> {
+        let mut s = String::new();
+        while let Some(&c) = chars.peek() {
+            if !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+                break;
+            }
+            s.push(c);
+            chars.next();
+        }
+        if let Ok(n) = s.parse::<i64>() {
+            return Ok(Self::Number(n));
+        }
+        s.parse::<f64>().map(Self::Float).map_err(|_| "invalid json value")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Json;
+
+    #[test]
+    fn json_parse() {
+        let json = Json::parse(r#" [["0100", "HASH160 0x14", -1, 1.5e2], {"k": [true, null]}, "a\"bA"] "#).unwrap();
+        let items = json.as_array().unwrap();
+        assert_eq!(items[0], Json::Array(vec![Json::Str("0100".into()), Json::Str("HASH160 0x14".into()), Json::Number(-1), Json::Float(150.0)]));
+        assert_eq!(items[1].get("k"), Some(&Json::Array(vec![Json::Bool(true), Json::Null])));
+        assert_eq!(items[2].as_str(), Some("a\"bA"));
+        assert_eq!(Json::parse("[]").unwrap(), Json::Array(vec![]));
+
+        assert!(Json::parse("[1,]").is_err());
+        assert!(Json::parse("[1] 2").is_err());
+        assert!(Json::parse("\"abc").is_err());
+    }
+}
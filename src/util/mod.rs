@@ -1,9 +1,12 @@
 pub use reader::Reader;
 
 pub mod base58;
+pub mod base64;
+pub mod bech32;
 pub mod converter;
 pub mod hash;
 pub mod io;
+pub mod json;
 pub mod varint;
 pub mod math;
 mod reader;
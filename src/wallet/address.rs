@@ -0,0 +1,89 @@
+use crate::script::{Opcode, Script, ScriptBuilder, ScriptType};
+use crate::util::{base58, bech32, hash::{self, Hash160Value}};
+use super::Error;
+
+const P2PKH_MAIN: u8 = 0x00;
+const P2PKH_TEST: u8 = 0x6f;
+const P2SH_MAIN: u8 = 0x05;
+const P2SH_TEST: u8 = 0xc4;
+const HRP_MAIN: &str = "bc";
+const HRP_TEST: &str = "tb";
+
+// address of the script_pubkey, only p2pkh, p2sh and witness programs have one
+pub fn from_script(script: &Script, test_net: bool) -> Result<String, Error> {
+    let hrp = if test_net { HRP_TEST } else { HRP_MAIN };
+    let base58_address = |prefix: u8, h: &[u8]| base58::encode_bytes_checksum(&[&[prefix], h].concat());
+    let address = match script.classify() {
+        ScriptType::PubKeyHash(h) => base58_address(if test_net { P2PKH_TEST } else { P2PKH_MAIN }, &h),
+        ScriptType::ScriptHash(h) => base58_address(if test_net { P2SH_TEST } else { P2SH_MAIN }, &h),
+        ScriptType::WitnessV0KeyHash(program) | ScriptType::WitnessV0ScriptHash(program) => {
+            bech32::encode_segwit_address(hrp, 0, &program).map_err(Error::Bech32)?
+        },
+        ScriptType::WitnessV1Taproot(program) => bech32::encode_segwit_address(hrp, 1, &program).map_err(Error::Bech32)?,
+        ScriptType::WitnessUnknown { version, program } => bech32::encode_segwit_address(hrp, version, &program).map_err(Error::Bech32)?,
+        _ => return Err(Error::UnsupportedScript),
+    };
+
+    Ok(address)
+}
+
+// OP_HASH160 <script hash> OP_EQUAL, the redeem script is unknown
+fn p2sh_of_hash(h: &Hash160Value) -> Script {
+    Script::new(vec![Opcode::OpEqual.into(), h.to_vec().into(), Opcode::OpHash160.into()])
+}
+
+// @return (script_pubkey, the address is of test net)
+pub fn to_script(address: &str) -> Result<(Script, bool), Error> {
+    let lower = address.to_ascii_lowercase();
+    for (hrp, test_net) in [(HRP_MAIN, false), (HRP_TEST, true)] {
+        if lower.starts_with(&format!("{}1", hrp)) {
+            let (version, program) = bech32::decode_segwit_address(hrp, address).map_err(Error::Bech32)?;
+            return Ok((ScriptBuilder::witness_program(version as usize, program), test_net));
+        }
+    }
+
+    let bytes = base58::decode_checksum(address).map_err(Error::Base58)?;
+    if bytes.len() != 21 {
+        return Err(Error::InvalidAddress(address.into()));
+    }
+    let h = hash::convert_slice_into_hash160(&bytes[1..]);
+    match bytes[0] {
+        P2PKH_MAIN => Ok((ScriptBuilder::p2pkh(&h), false)),
+        P2PKH_TEST => Ok((ScriptBuilder::p2pkh(&h), true)),
+        P2SH_MAIN => Ok((p2sh_of_hash(&h), false)),
+        P2SH_TEST => Ok((p2sh_of_hash(&h), true)),
+        _ => Err(Error::InvalidAddress(address.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::script::Script;
+    use super::{from_script, to_script};
+    use super::super::Error;
+
+    #[test]
+    fn address_round_trip() {
+        let cases = [
+            ("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH", "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac", false),
+            ("mzx5YhAH9kNHtcN481u6WkjeHjYtVeKVh2", "76a914d52ad7ca9b3d096a38e752c2018e6fbc40cdf26f88ac", true),
+            ("3CK4fEwbMP7heJarmU4eqA3sMbVJyEnU3V", "a914748284390f9e263a4b766a75d0633c50426eb87587", false),
+            ("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", "0014751e76e8199196d454941c45d1b3a323f1433bd6", false),
+            ("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0", "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798", false),
+        ];
+        for (address, script_hex, test_net) in cases {
+            let (script, is_test_net) = to_script(address).unwrap();
+            assert_eq!(hex::encode(script.raw_serialize().unwrap()), script_hex);
+            assert_eq!(is_test_net, test_net);
+            assert_eq!(from_script(&script, test_net).unwrap(), address);
+        }
+    }
+
+    #[test]
+    fn address_invalid() {
+        assert!(to_script("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5").is_err());
+        assert!(to_script("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMh").is_err());
+        let op_return = Script::parse_raw(&hex::decode("6a0100").unwrap()).unwrap();
+        assert!(matches!(from_script(&op_return, false), Err(Error::UnsupportedScript)));
+    }
+}
@@ -0,0 +1,363 @@
+use crate::secp256k1::{S256Curve, S256FieldElementNCreator, S256Point};
+use crate::util::{base58, hash};
+use primitive_types::U256;
+use super::Error;
+use std::fmt;
+use zeroize::Zeroize;
+
+// child numbers from it are hardened
+pub const HARDENED: u32 = 0x8000_0000;
+const EXTENDED_KEY_LEN: usize = 78;
+
+const XPUB: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const XPRV: [u8; 4] = [0x04, 0x88, 0xad, 0xe4];
+const TPUB: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+const TPRV: [u8; 4] = [0x04, 0x35, 0x83, 0x94];
+
+// BIP32 extended public key, xpub of main net and tpub of test net
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedPubKey {
+    pub test_net: bool,
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+    pub chain_code: [u8; 32],
+    pub public_key: S256Point,
+}
+
+// BIP32 extended private key, xprv of main net and tprv of test net
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedPrivKey {
+    pub test_net: bool,
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+    pub chain_code: [u8; 32],
+    secret: U256,
+}
+
+// such as 0'/1h/2, with or without the leading m
+pub fn parse_path(path: &str) -> Result<Vec<u32>, Error> {
+    let path = path.strip_prefix('m').unwrap_or(path);
+    if path.is_empty() {
+        return Ok(vec![]);
+    }
+    let path = path.strip_prefix('/').ok_or_else(|| Error::InvalidPath(path.into()))?;
+    path.split('/').map(parse_child_number).collect()
+}
+
+// such as 1, 1' or 1h
+pub fn parse_child_number(s: &str) -> Result<u32, Error> {
+    let invalid = || Error::InvalidPath(s.into());
+    let (digits, hardened) = match s.strip_suffix('\'').or_else(|| s.strip_suffix('h')) {
+        Some(digits) => (digits, true),
+        None => (s, false),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let index: u32 = digits.parse().map_err(|_| invalid())?;
+    if index >= HARDENED {
+        return Err(invalid());
+    }
+    Ok(if hardened { index | HARDENED } else { index })
+}
+
+// such as /0'/1, hardened children are marked by '
+pub fn format_path(path: &[u32]) -> String {
+    path.iter().map(|index| match index & HARDENED {
+        0 => format!("/{}", index),
+        _ => format!("/{}'", index & !HARDENED),
+    }).collect()
+}
+
+fn fingerprint(public_key: &S256Point) -> [u8; 4] {
+    let mut result = [0u8; 4];
+    result.copy_from_slice(&public_key.hash160(true)[..4]);
+    result
+}
+
+fn serialize(version: [u8; 4], depth: u8, parent_fingerprint: &[u8; 4], child_number: u32, chain_code: &[u8; 32], key: &[u8]) -> String {
+    let mut bytes = version.to_vec();
+    bytes.push(depth);
+    bytes.extend_from_slice(parent_fingerprint);
+    bytes.extend_from_slice(&child_number.to_be_bytes());
+    bytes.extend_from_slice(chain_code);
+    bytes.extend_from_slice(key);
+    base58::encode_bytes_checksum(&bytes)
+}
+
+// the fields shared by extended public and private keys
+struct RawExtendedKey {
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: [u8; 32],
+    key: Vec<u8>, // 0x00 and the secret, or the compressed public key
+}
+
+fn deserialize(s: &str) -> Result<RawExtendedKey, Error> {
+    let bytes = base58::decode_checksum(s).map_err(Error::Base58)?;
+    if bytes.len() != EXTENDED_KEY_LEN {
+        return Err(Error::InvalidExtendedKeyLength(bytes.len()));
+    }
+    let mut version = [0u8; 4];
+    version.copy_from_slice(&bytes[0..4]);
+    let mut parent_fingerprint = [0u8; 4];
+    parent_fingerprint.copy_from_slice(&bytes[5..9]);
+    let mut child_number = [0u8; 4];
+    child_number.copy_from_slice(&bytes[9..13]);
+    let mut chain_code = [0u8; 32];
+    chain_code.copy_from_slice(&bytes[13..45]);
+
+    Ok(RawExtendedKey {
+        version,
+        depth: bytes[4],
+        parent_fingerprint,
+        child_number: u32::from_be_bytes(child_number),
+        chain_code,
+        key: bytes[45..].to_vec(),
+    })
+}
+
+// I = HMAC-SHA512(chain code, data), IL is the tweak and IR is the chain code of the child
+fn hmac_split(chain_code: &[u8; 32], data: &[u8]) -> (U256, [u8; 32]) {
+    let i = hash::hmac512(chain_code, data);
+    let mut chain_code = [0u8; 32];
+    chain_code.copy_from_slice(&i[32..]);
+    (U256::from_big_endian(&i[..32]), chain_code)
+}
+
+impl ExtendedPrivKey {
+    pub fn new_master(seed: &[u8], test_net: bool) -> Result<Self, Error> {
+        let i = hash::hmac512(b"Bitcoin seed", seed);
+        let secret = U256::from_big_endian(&i[..32]);
+        if secret.is_zero() || secret >= S256Curve::n() {
+            return Err(Error::InvalidPrivateKey);
+        }
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&i[32..]);
+
+        Ok(Self { test_net, depth: 0, parent_fingerprint: [0; 4], child_number: 0, chain_code, secret })
+    }
+
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let raw = deserialize(s)?;
+        let test_net = match raw.version {
+            XPRV => false,
+            TPRV => true,
+            _ => return Err(Error::UnknownVersion(raw.version)),
+        };
+        if raw.key[0] != 0 {
+            return Err(Error::InvalidPrivateKey);
+        }
+        let secret = U256::from_big_endian(&raw.key[1..]);
+        if secret.is_zero() || secret >= S256Curve::n() {
+            return Err(Error::InvalidPrivateKey);
+        }
+
+        Ok(Self {
+            test_net,
+            depth: raw.depth,
+            parent_fingerprint: raw.parent_fingerprint,
+            child_number: raw.child_number,
+            chain_code: raw.chain_code,
+            secret,
+        })
+    }
+
+    pub fn secret(&self) -> U256 {
+        self.secret
+    }
+
+    pub fn public_key(&self) -> S256Point {
+        S256Point::g().mul_secret(self.secret)
+    }
+
+    pub fn fingerprint(&self) -> [u8; 4] {
+        fingerprint(&self.public_key())
+    }
+
+    pub fn extended_pub_key(&self) -> ExtendedPubKey {
+        ExtendedPubKey {
+            test_net: self.test_net,
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+            chain_code: self.chain_code,
+            public_key: self.public_key(),
+        }
+    }
+
+    pub fn derive_child(&self, index: u32) -> Result<Self, Error> {
+        let public_key = self.public_key();
+        let mut data = if index & HARDENED != 0 {
+            let mut secret = [0u8; 33];
+            self.secret.to_big_endian(&mut secret[1..]);
+            secret.to_vec()
+        } else {
+            public_key.sec_compressed().ok_or(Error::InvalidPublicKey)?
+        };
+        data.extend_from_slice(&index.to_be_bytes());
+        let (tweak, chain_code) = hmac_split(&self.chain_code, &data);
+        data.zeroize();
+        if tweak >= S256Curve::n() {
+            return Err(Error::InvalidChild(index));
+        }
+        let secret = (S256FieldElementNCreator::from_u256(tweak) + S256FieldElementNCreator::from_u256(self.secret)).num();
+        if secret.is_zero() {
+            return Err(Error::InvalidChild(index));
+        }
+
+        Ok(Self {
+            test_net: self.test_net,
+            depth: self.depth.wrapping_add(1),
+            parent_fingerprint: fingerprint(&public_key),
+            child_number: index,
+            chain_code,
+            secret,
+        })
+    }
+
+    pub fn derive_path(&self, path: &[u32]) -> Result<Self, Error> {
+        path.iter().try_fold(self.clone(), |key, index| key.derive_child(*index))
+    }
+}
+
+impl fmt::Display for ExtendedPrivKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = if self.test_net { TPRV } else { XPRV };
+        let mut key = [0u8; 33];
+        self.secret.to_big_endian(&mut key[1..]);
+        let result = write!(f, "{}", serialize(version, self.depth, &self.parent_fingerprint, self.child_number, &self.chain_code, &key));
+        key.zeroize();
+        result
+    }
+}
+
+impl Drop for ExtendedPrivKey {
+    fn drop(&mut self) {
+        self.secret.0.zeroize();
+    }
+}
+
+impl ExtendedPubKey {
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let raw = deserialize(s)?;
+        let test_net = match raw.version {
+            XPUB => false,
+            TPUB => true,
+            _ => return Err(Error::UnknownVersion(raw.version)),
+        };
+        if raw.key[0] != 2 && raw.key[0] != 3 {
+            return Err(Error::InvalidPublicKey);
+        }
+        let public_key = S256Point::parse(&raw.key).map_err(|_| Error::InvalidPublicKey)?;
+
+        Ok(Self {
+            test_net,
+            depth: raw.depth,
+            parent_fingerprint: raw.parent_fingerprint,
+            child_number: raw.child_number,
+            chain_code: raw.chain_code,
+            public_key,
+        })
+    }
+
+    pub fn fingerprint(&self) -> [u8; 4] {
+        fingerprint(&self.public_key)
+    }
+
+    // only unhardened children can be derived
+    pub fn derive_child(&self, index: u32) -> Result<Self, Error> {
+        if index & HARDENED != 0 {
+            return Err(Error::HardenedFromPublic(index));
+        }
+        let mut data = self.public_key.sec_compressed().ok_or(Error::InvalidPublicKey)?;
+        data.extend_from_slice(&index.to_be_bytes());
+        let (tweak, chain_code) = hmac_split(&self.chain_code, &data);
+        if tweak >= S256Curve::n() {
+            return Err(Error::InvalidChild(index));
+        }
+        let public_key = S256Point::g() * tweak + self.public_key.clone();
+        if public_key.inner().is_infinity() {
+            return Err(Error::InvalidChild(index));
+        }
+
+        Ok(Self {
+            test_net: self.test_net,
+            depth: self.depth.wrapping_add(1),
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+            chain_code,
+            public_key,
+        })
+    }
+
+    pub fn derive_path(&self, path: &[u32]) -> Result<Self, Error> {
+        path.iter().try_fold(self.clone(), |key, index| key.derive_child(*index))
+    }
+}
+
+impl fmt::Display for ExtendedPubKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = if self.test_net { TPUB } else { XPUB };
+        let key = self.public_key.sec_compressed().ok_or(fmt::Error)?;
+        write!(f, "{}", serialize(version, self.depth, &self.parent_fingerprint, self.child_number, &self.chain_code, &key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_path, format_path, ExtendedPrivKey, ExtendedPubKey, HARDENED};
+    use super::super::Error;
+
+    #[test]
+    fn bip32_vector_1() {
+        // BIP32 test vector 1
+        let master = ExtendedPrivKey::new_master(&hex::decode("000102030405060708090a0b0c0d0e0f").unwrap(), false).unwrap();
+        assert_eq!(master.to_string(), "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi");
+        assert_eq!(master.extended_pub_key().to_string(), "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8");
+
+        let child = master.derive_path(&parse_path("m/0h/1").unwrap()).unwrap();
+        assert_eq!(child.to_string(), "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs");
+        assert_eq!(child.extended_pub_key().to_string(), "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ");
+
+        // the public derivation of an unhardened child is the same as the private one
+        let parent = ExtendedPubKey::parse("xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw").unwrap();
+        assert_eq!(parent.derive_child(1).unwrap(), child.extended_pub_key());
+        assert!(matches!(parent.derive_child(HARDENED), Err(Error::HardenedFromPublic(_))));
+    }
+
+    #[test]
+    fn bip32_parse() {
+        let s = "xprv9s21ZrQH143K25QhxbucbDDuQ4naNntJRi4KUfWT7xo4EKsHt2QJDu7KXp1A3u7Bi1j8ph3EGsZ9Xvz9dGuVrtHHs7pXeTzjuxBrCmmhgC6";
+        let key = ExtendedPrivKey::parse(s).unwrap();
+        assert_eq!(key.to_string(), s);
+        assert_eq!(key.extended_pub_key().to_string(), "xpub661MyMwAqRbcEZVB4dScxMAdx6d4nFc9nvyvH3v4gJL378CSRZiYmhRoP7mBy6gSPSCYk6SzXPTf3ND1cZAceL7SfJ1Z3GC8vBgp2epUt13");
+        // a private key is not a public key
+        assert!(matches!(ExtendedPubKey::parse(s), Err(Error::UnknownVersion(_))));
+        assert!(ExtendedPrivKey::parse(&s[..s.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn bip32_path() {
+        assert_eq!(parse_path("m/0'/1h/2").unwrap(), vec![HARDENED, 1 | HARDENED, 2]);
+        assert_eq!(parse_path("m").unwrap(), vec![]);
+        assert_eq!(format_path(&[HARDENED, 1]), "/0'/1");
+        assert!(parse_path("m/").is_err());
+        assert!(parse_path("m/2147483648").is_err());
+        assert!(parse_path("m/-1").is_err());
+    }
+
+    // run Drop in place and read the memory it leaves, ManuallyDrop keeps the memory and does not drop it twice
+    #[test]
+    fn bip32_secret_zeroize_on_drop() {
+        use std::mem::ManuallyDrop;
+        let mut key = ManuallyDrop::new(ExtendedPrivKey::new_master(&hex::decode("000102030405060708090a0b0c0d0e0f").unwrap(), false).unwrap());
+        assert!(!key.secret().is_zero());
+        unsafe { std::ptr::drop_in_place(&mut *key) };
+        assert!(key.secret().is_zero());
+    }
+}
@@ -0,0 +1,42 @@
+use crate::script::Error as ScriptError;
+use crate::transaction::Error as TransactionError;
+
+#[derive(Debug)]
+pub enum Error {
+    // encoding
+    Base58(&'static str),
+    Bech32(&'static str),
+    Base64(&'static str),
+
+    // bip32
+    InvalidExtendedKeyLength(usize),
+    UnknownVersion([u8; 4]),
+    InvalidPrivateKey,
+    InvalidPublicKey,
+    InvalidChild(u32), // the child is invalid, the next index should be used instead
+    HardenedFromPublic(u32), // hardened child cannot be derived from a public key
+    InvalidPath(String),
+
+    // address
+    InvalidAddress(String),
+    UnsupportedScript, // no address for the script_pubkey
+
+    // signed message
+    InvalidSignature(String),
+    KeyMismatch, // the key is not the one of the address
+
+    Script(ScriptError),
+    Transaction(TransactionError),
+}
+
+impl From<ScriptError> for Error {
+    fn from(e: ScriptError) -> Self {
+        Self::Script(e)
+    }
+}
+
+impl From<TransactionError> for Error {
+    fn from(e: TransactionError) -> Self {
+        Self::Transaction(e)
+    }
+}
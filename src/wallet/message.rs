@@ -0,0 +1,330 @@
+use primitive_types::U256;
+use crate::script::{operator, Script, ScriptBuilder, ScriptType, VerifyFlags};
+use crate::secp256k1::{PrivateKey, RecoverableSignature, S256Point};
+use crate::transaction::{LockTime, PrevIndex, Sequence, SigHash, Transaction, TxIn, TxOut, Version};
+use crate::util::{base64, hash::{self, Hash256Value}, varint, Reader};
+use super::{address, Error};
+
+const MESSAGE_MAGIC: &[u8] = b"Bitcoin Signed Message:\n";
+const BIP322_TAG: &str = "BIP0322-signed-message";
+const SIGHASH_DEFAULT: u8 = 0x00;
+
+// BIP137, the header byte tells which address of the recovered public key signed
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MessageAddressType {
+    P2pkhUncompressed,
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+}
+
+impl MessageAddressType {
+    // the header is base + recovery id
+    fn header_base(&self) -> u8 {
+        match self {
+            Self::P2pkhUncompressed => 27,
+            Self::P2pkh => 31,
+            Self::P2shP2wpkh => 35,
+            Self::P2wpkh => 39,
+        }
+    }
+
+    fn from_header(header: u8) -> Option<Self> {
+        match header {
+            27..=30 => Some(Self::P2pkhUncompressed),
+            31..=34 => Some(Self::P2pkh),
+            35..=38 => Some(Self::P2shP2wpkh),
+            39..=42 => Some(Self::P2wpkh),
+            _ => None,
+        }
+    }
+
+    pub fn script_pubkey(&self, pk: &S256Point) -> Result<Script, Error> {
+        let script = match self {
+            Self::P2pkhUncompressed => ScriptBuilder::p2pkh(&pk.hash160(false)),
+            Self::P2pkh => ScriptBuilder::p2pkh(&pk.hash160(true)),
+            Self::P2shP2wpkh => ScriptBuilder::p2sh_p2wpkh(&pk.hash160(true))?,
+            Self::P2wpkh => ScriptBuilder::p2wpkh(&pk.hash160(true)),
+        };
+        Ok(script)
+    }
+}
+
+// hash256 of the magic and the message, both with a varint length prefix
+pub fn message_hash(message: &str) -> Hash256Value {
+    let mut msg = varint::encode(MESSAGE_MAGIC.len() as u64);
+    msg.extend_from_slice(MESSAGE_MAGIC);
+    msg.append(&mut varint::encode(message.len() as u64));
+    msg.extend_from_slice(message.as_bytes());
+
+    hash::hash256(&msg)
+}
+
+// signmessage of bitcoin core, @return base64 of the 65 bytes compact signature
+pub fn sign_message(key: &PrivateKey, message: &str, address_type: MessageAddressType) -> Result<String, Error> {
+    let z = U256::from_big_endian(&message_hash(message));
+    let signature = key.sign_recoverable_deterministic(z).map_err(|e| Error::InvalidSignature(e.into()))?;
+    let mut bytes = signature.serialize_compact(false);
+    bytes[0] = address_type.header_base() + signature.recovery_id();
+
+    Ok(base64::encode(&bytes))
+}
+
+// @return (public key of the signer, address type from the header)
+pub fn recover_message_signer(signature: &str, message: &str) -> Result<(S256Point, MessageAddressType), Error> {
+    let mut bytes = base64::decode(signature).map_err(Error::Base64)?;
+    if bytes.len() != 65 {
+        return Err(Error::InvalidSignature("invalid len of compact signature".into()));
+    }
+    let address_type = MessageAddressType::from_header(bytes[0]).ok_or_else(|| Error::InvalidSignature(format!("invalid header byte {}", bytes[0])))?;
+    bytes[0] -= address_type.header_base() - 27;
+    let (signature, _) = RecoverableSignature::parse_compact(&bytes).map_err(Error::InvalidSignature)?;
+    let pk = signature.recover(U256::from_big_endian(&message_hash(message))).map_err(Error::InvalidSignature)?;
+
+    Ok((pk, address_type))
+}
+
+// verifymessage, BIP137 compact signatures and BIP322 simple signatures are both accepted
+pub fn verify_message(address: &str, signature: &str, message: &str) -> Result<bool, Error> {
+    let (script_pubkey, _) = address::to_script(address)?;
+    let bytes = base64::decode(signature).map_err(Error::Base64)?;
+    if bytes.len() != 65 || MessageAddressType::from_header(bytes[0]).is_none() {
+        return verify_message_bip322(address, signature, message);
+    }
+
+    let (pk, address_type) = match recover_message_signer(signature, message) {
+        Ok(signer) => signer,
+        Err(_) => return Ok(false),
+    };
+    let signer_script = address_type.script_pubkey(&pk)?;
+
+    Ok(signer_script.raw_serialize()? == script_pubkey.raw_serialize()?)
+}
+
+// BIP322, tagged hash of the message
+pub fn bip322_message_hash(message: &str) -> Hash256Value {
+    hash::tagged_hash(BIP322_TAG, message.as_bytes())
+}
+
+// BIP322, the virtual transaction paying to the address, its input commits to the message
+pub fn bip322_to_spend(script_pubkey: &Script, message: &str) -> Result<Transaction, Error> {
+    // OP_0 PUSH32 <message hash>
+    let mut script_sig = vec![0x00, 0x20];
+    script_sig.extend_from_slice(&bip322_message_hash(message));
+    let input = TxIn {
+        prev_tx: [0u8; 32],
+        prev_index: PrevIndex::new(0xffffffff),
+        script: script_sig,
+        sequence: Sequence::new(0),
+        witness: Vec::new(),
+    };
+    let output = TxOut::new(0, script_pubkey.raw_serialize()?);
+
+    Ok(Transaction { version: Version::new(0), inputs: vec![input], outputs: vec![output], locktime: LockTime::new(0), segwit: None })
+}
+
+// BIP322, the virtual transaction spending to_spend with the signature as witness
+pub fn bip322_to_sign(to_spend: &Transaction, witness: Vec<Vec<u8>>) -> Result<Transaction, Error> {
+    let input = TxIn {
+        prev_tx: to_spend.id()?,
+        prev_index: PrevIndex::new(0),
+        script: Vec::new(),
+        sequence: Sequence::new(0),
+        witness,
+    };
+    // OP_RETURN
+    let output = TxOut::new(0, vec![0x6a]);
+
+    Ok(Transaction { version: Version::new(0), inputs: vec![input], outputs: vec![output], locktime: LockTime::new(0), segwit: None })
+}
+
+// BIP322 simple signature, only p2wpkh and the key path of p2tr without script tree are signed
+// @return base64 of the witness of to_sign
+pub fn sign_message_bip322(key: &PrivateKey, message: &str, address: &str) -> Result<String, Error> {
+    let (script_pubkey, _) = address::to_script(address)?;
+    let to_spend = bip322_to_spend(&script_pubkey, message)?;
+    let to_sign = bip322_to_sign(&to_spend, Vec::new())?;
+
+    let witness = match script_pubkey.classify() {
+        ScriptType::WitnessV0KeyHash(program) => {
+            let h = key.pk_point().hash160(true);
+            if program != h {
+                return Err(Error::KeyMismatch);
+            }
+            let script_code = ScriptBuilder::p2pkh(&h).raw_serialize()?;
+            let z = to_sign.signature_hash_segwit_v0(0, &script_code, 0, SigHash::All.into())?;
+            let signature = key.sign_deterministic(U256::from_big_endian(&z)).map_err(|e| Error::InvalidSignature(e.into()))?;
+            let mut sig = hex::decode(signature.der()).expect("der is hex");
+            sig.push(SigHash::All.value());
+            let sec = key.pk_point().sec_compressed().ok_or(Error::InvalidPublicKey)?;
+            vec![sig, sec]
+        },
+        ScriptType::WitnessV1Taproot(program) => {
+            let tweaked = key.tap_tweak(None).map_err(|_| Error::InvalidPrivateKey)?;
+            if program != tweaked.x_only_public_key() {
+                return Err(Error::KeyMismatch);
+            }
+            let sighash = to_sign.signature_hash_taproot(0, &to_spend.outputs, SIGHASH_DEFAULT, None, None)?;
+            let signature = tweaked.sign_schnorr(&sighash, &[0u8; 32]).map_err(|e| Error::InvalidSignature(e.into()))?;
+            vec![signature.serialize().to_vec()]
+        },
+        _ => return Err(Error::UnsupportedScript),
+    };
+
+    Ok(base64::encode(&serialize_witness(&witness)))
+}
+
+// BIP322 simple signature, to_sign is verified with the standard flags
+pub fn verify_message_bip322(address: &str, signature: &str, message: &str) -> Result<bool, Error> {
+    let (script_pubkey, _) = address::to_script(address)?;
+    let witness = parse_witness(&base64::decode(signature).map_err(Error::Base64)?)?;
+    let to_spend = bip322_to_spend(&script_pubkey, message)?;
+    let to_sign = bip322_to_sign(&to_spend, witness)?;
+
+    let result = operator::verify_tx_input_with_prevouts(&to_sign, 0, &to_spend.outputs, VerifyFlags::standard());
+    Ok(matches!(result, Ok(true)))
+}
+
+// the count of items, then each item with a varint length prefix
+fn serialize_witness(witness: &[Vec<u8>]) -> Vec<u8> {
+    let mut result = varint::encode(witness.len() as u64);
+    for item in witness {
+        result.append(&mut varint::encode(item.len() as u64));
+        result.extend_from_slice(item);
+    }
+    result
+}
+
+fn parse_witness(bytes: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let mut reader = Reader::new(bytes);
+    let invalid = |e: &'static str| Error::InvalidSignature(e.into());
+    let count = varint::decode_with_reader(&mut reader).map_err(invalid)?;
+    let mut witness = Vec::new();
+    for _ in 0..count {
+        let len = varint::decode_with_reader(&mut reader).map_err(invalid)?;
+        witness.push(reader.more(len).map_err(invalid)?.to_vec());
+    }
+    if !reader.is_empty() {
+        return Err(invalid("extra bytes after the witness"));
+    }
+    Ok(witness)
+}
+
+#[cfg(test)]
+mod tests {
+    use primitive_types::U256;
+    use super::{
+        bip322_message_hash, bip322_to_sign, bip322_to_spend, message_hash, recover_message_signer,
+        sign_message, sign_message_bip322, verify_message, MessageAddressType,
+    };
+    use crate::secp256k1::PrivateKey;
+    use crate::util::base58;
+    use crate::wallet::{address, Error};
+
+    fn priv_key_of_wif(wif: &str) -> PrivateKey {
+        let bytes = base58::decode_checksum(wif).unwrap();
+        PrivateKey::new(U256::from_big_endian(&bytes[1..33])).unwrap()
+    }
+
+    #[test]
+    fn message_sign_and_verify_core() {
+        // rpc_signmessage.py of bitcoin core
+        let priv_key = priv_key_of_wif("cUeKHd5orzT3mz8P9pxyREHfsWtVfgsfDjiZZBcjUBAaGk1BTj7N");
+        let address = "mpLQjfK79b7CCV4VMJWEWAj5Mpx8Up5zxB";
+        let message = "This is just a test message";
+        let expected = "INbVnW4e6PeRmsv2Qgu8NuopvrVjkcxob+sX8OcZG0SALhWybUjzMLPdAsXI46YZGb0KQTRii+wWIQzRpG/U+S0=";
+
+        let signature = sign_message(&priv_key, message, MessageAddressType::P2pkh).unwrap();
+        assert_eq!(signature, expected);
+        assert!(verify_message(address, &signature, message).unwrap());
+        assert!(!verify_message(address, &signature, "This is just a test message.").unwrap());
+        assert!(!verify_message("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH", &signature, message).unwrap());
+
+        let (pk, address_type) = recover_message_signer(&signature, message).unwrap();
+        assert_eq!(&pk, priv_key.pk_point());
+        assert_eq!(address_type, MessageAddressType::P2pkh);
+    }
+
+    #[test]
+    fn message_sign_and_verify_bip137() {
+        let priv_key = PrivateKey::new(U256::from(8675309)).unwrap();
+        let message = "Hello World";
+        let cases = [
+            (MessageAddressType::P2pkhUncompressed, 27),
+            (MessageAddressType::P2pkh, 31),
+            (MessageAddressType::P2shP2wpkh, 35),
+            (MessageAddressType::P2wpkh, 39),
+        ];
+        for (address_type, header_base) in cases {
+            let script_pubkey = address_type.script_pubkey(priv_key.pk_point()).unwrap();
+            let address = address::from_script(&script_pubkey, false).unwrap();
+            let signature = sign_message(&priv_key, message, address_type).unwrap();
+            let header = crate::util::base64::decode(&signature).unwrap()[0];
+            assert!((header_base..header_base + 4).contains(&header));
+            assert!(verify_message(&address, &signature, message).unwrap(), "{:?}", address_type);
+        }
+
+        // the header of another address type recovers the same key for a different address
+        let signature = sign_message(&priv_key, message, MessageAddressType::P2wpkh).unwrap();
+        let address = address::from_script(&MessageAddressType::P2pkh.script_pubkey(priv_key.pk_point()).unwrap(), false).unwrap();
+        assert!(!verify_message(&address, &signature, message).unwrap());
+    }
+
+    #[test]
+    fn message_hash_magic() {
+        assert_eq!(hex::encode(message_hash("")), "80e795d4a4caadd7047af389d9f7f220562feb6196032e2131e10563352c4bcc");
+    }
+
+    #[test]
+    fn message_bip322_to_spend() {
+        assert_eq!(hex::encode(bip322_message_hash("")), "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1");
+        assert_eq!(hex::encode(bip322_message_hash("Hello World")), "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a");
+
+        let (script_pubkey, _) = address::to_script("bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l").unwrap();
+        let cases = [
+            ("", "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7", "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6"),
+            ("Hello World", "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b", "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf"),
+        ];
+        for (message, to_spend_id, to_sign_id) in cases {
+            let to_spend = bip322_to_spend(&script_pubkey, message).unwrap();
+            assert_eq!(hex::encode(to_spend.id().unwrap()), to_spend_id);
+            let to_sign = bip322_to_sign(&to_spend, Vec::new()).unwrap();
+            assert_eq!(hex::encode(to_sign.id().unwrap()), to_sign_id);
+        }
+    }
+
+    #[test]
+    fn message_verify_bip322() {
+        let address = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+        let empty = "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        let hello = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        assert!(verify_message(address, empty, "").unwrap());
+        assert!(verify_message(address, hello, "Hello World").unwrap());
+        assert!(!verify_message(address, empty, "Hello World").unwrap());
+        assert!(!verify_message(address, hello, "").unwrap());
+        assert!(!verify_message("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", hello, "Hello World").unwrap());
+
+        let taproot = "bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3";
+        let signature = "AUHd69PrJQEv+oKTfZ8l+WROBHuy9HKrbFCJu7U1iK2iiEy1vMU5EfMtjc+VSHM7aU0SDbak5IUZRVno2P5mjSafAQ==";
+        assert!(verify_message(taproot, signature, "Hello World").unwrap());
+        assert!(!verify_message(taproot, signature, "").unwrap());
+
+        assert!(matches!(verify_message(address, "AkcwRAIg", ""), Err(Error::InvalidSignature(_))));
+        assert!(matches!(verify_message(address, "not base64", ""), Err(Error::Base64(_))));
+    }
+
+    #[test]
+    fn message_sign_bip322() {
+        let priv_key = priv_key_of_wif("L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k");
+        for address in ["bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l", "bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3"] {
+            for message in ["", "Hello World"] {
+                let signature = sign_message_bip322(&priv_key, message, address).unwrap();
+                assert!(verify_message(address, &signature, message).unwrap(), "{} {}", address, message);
+            }
+        }
+
+        let other = PrivateKey::new(U256::from(8675309)).unwrap();
+        assert!(matches!(sign_message_bip322(&other, "", "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l"), Err(Error::KeyMismatch)));
+        assert!(matches!(sign_message_bip322(&priv_key, "", "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"), Err(Error::UnsupportedScript)));
+    }
+}
@@ -1,4 +1,11 @@
-//TODO impl bip39, bip32 and so on
+//TODO impl bip39 and so on
+pub use error::Error;
+pub use bip32::{ExtendedPrivKey, ExtendedPubKey, HARDENED};
+
+pub mod address;
+pub mod bip32;
+pub mod message;
+mod error;
 
 #[cfg(test)]
 mod test {